@@ -0,0 +1,56 @@
+//! Casting one 640-column frame's worth of rays, one at a time and batched.
+
+use backrooms::{
+    camera::{raycast, raycast_batch},
+    tile::Tile,
+    world::ArrayWorld,
+};
+use cgmath::{vec2, Vector2};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ndarray::Array2;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+const COLUMNS: usize = 640;
+
+/// A walled-in 256 by 256 world with a wall on about one tile in fifty.
+fn world() -> ArrayWorld {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let map = Array2::from_shape_fn((256, 256), |(y, x)| {
+        let edge = x == 0 || y == 0 || x == 255 || y == 255;
+        match edge || rng.gen_bool(0.02) {
+            true => Tile::Wall,
+            false => Tile::Floor,
+        }
+    });
+    ArrayWorld::from(map)
+}
+
+/// One ray per column of a camera with a 90 degree field of view.
+fn rays(dir: Vector2<f32>) -> Vec<Vector2<f32>> {
+    let plane = vec2(-dir.y, dir.x);
+    (0..COLUMNS)
+        .map(|i| dir + plane * (2.0 * i as f32 / COLUMNS as f32 - 1.0))
+        .collect()
+}
+
+fn frame(c: &mut Criterion) {
+    let world = world();
+    let pos = vec2(128.5, 128.5);
+    let rays = rays(vec2(0.6, 0.8));
+
+    let mut group = c.benchmark_group("640 columns");
+    group.bench_function("raycast", |b| {
+        b.iter(|| {
+            rays.iter()
+                .map(|ray| raycast(&world, black_box(pos), *ray, 1000.0))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("raycast_batch", |b| {
+        b.iter(|| raycast_batch(&world, black_box(pos), &rays, 1000.0))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, frame);
+criterion_main!(benches);
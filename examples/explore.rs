@@ -0,0 +1,66 @@
+//! Walk around a generated level drawn as text, such as over SSH.
+//!
+//! Run with `cargo run --example explore --features terminal -- [preset] [seed]`.
+
+use backrooms::{
+    camera::CameraParams,
+    render::FACE_LIGHT,
+    terminal::{run, TerminalParams},
+    util::Rectangle,
+    world::ArrayWorld,
+    worldgen::{pipeline::WorldGenerator, presets::Preset},
+};
+use cgmath::vec2;
+use image::Rgb;
+use rand::{rngs::SmallRng, SeedableRng};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let preset = args
+        .next()
+        .map(|name| Preset::from_name(&name).expect("unknown preset"))
+        .unwrap_or(Preset::Level0);
+    let seed = args
+        .next()
+        .map(|s| s.parse().expect("seed must be an integer"))
+        .unwrap_or_else(rand::random);
+
+    let bounds = Rectangle {
+        x: 0,
+        y: 0,
+        w: 128,
+        h: 128,
+    };
+    let world = ArrayWorld::from(preset.generate(&mut SmallRng::seed_from_u64(seed), bounds));
+    let (y, x) = world
+        .map()
+        .indexed_iter()
+        .find(|(_, t)| t.is_passable())
+        .map(|(pos, _)| pos)
+        .expect("level has no floor");
+
+    let camera = CameraParams {
+        pos: vec2(x as f32 + 0.5, y as f32 + 0.5),
+        facing_unit: vec2(1.0, 0.0),
+        n_rays: 0,
+        max_dist: 64.0,
+        projection_plane_width: 1.0,
+        pitch: 0.0,
+    }
+    .with_fov_degrees(75.0);
+    let params = TerminalParams {
+        ceiling: Rgb([200, 195, 160]),
+        floor: Rgb([130, 110, 60]),
+        door: Rgb([150, 90, 40]),
+        eye_height: 0.5,
+        face_light: FACE_LIGHT,
+        fog_distance: 24.0,
+        cell_aspect: 2.0,
+    };
+
+    if let Err(e) = run(&world, camera, &params) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    println!("{} seed: {seed}", preset.name());
+}
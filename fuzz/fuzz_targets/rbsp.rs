@@ -0,0 +1,79 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use backrooms::{
+    util::Rectangle,
+    worldgen::hallways::{rbsp, KeepWeight, OffsetDistribution, PerimeterRing, RbspParams},
+};
+use libfuzzer_sys::fuzz_target;
+use rand::{rngs::SmallRng, SeedableRng};
+
+/// Raw fuzzer input. Sizes are kept small so that each run stays fast.
+#[derive(Debug, Arbitrary)]
+struct Input {
+    seed: u64,
+    x: i16,
+    y: i16,
+    w: u8,
+    h: u8,
+    min_room_len: u8,
+    max_room_len: u8,
+    p_keep_rooms: f32,
+    k_deoblongification: f32,
+    hallway_width: u8,
+    offset_distribution: u8,
+    jitter: f32,
+    max_aspect_ratio: f32,
+    perimeter_ring: u8,
+    ring_min_len: u8,
+}
+
+fuzz_target!(|input: Input| {
+    let bounds = Rectangle {
+        x: input.x as isize,
+        y: input.y as isize,
+        w: input.w as usize,
+        h: input.h as usize,
+    };
+    let params = RbspParams {
+        // Zero-length rooms make the tree explode in size without exercising
+        // anything new.
+        min_room_len: (input.min_room_len as usize).max(1),
+        max_room_len: input.max_room_len as usize,
+        p_keep_rooms: input.p_keep_rooms,
+        k_deoblongification: input.k_deoblongification,
+        hallway_width: (input.hallway_width % 8) as usize,
+        offset_distribution: match input.offset_distribution % 3 {
+            0 => OffsetDistribution::Uniform,
+            1 => OffsetDistribution::Triangular,
+            _ => OffsetDistribution::GoldenRatio {
+                jitter: input.jitter,
+            },
+        },
+        max_aspect_ratio: input.max_aspect_ratio,
+        keep_weight: KeepWeight::Constant,
+        perimeter_ring: match input.perimeter_ring % 3 {
+            0 => PerimeterRing::None,
+            1 => PerimeterRing::Outer,
+            _ => PerimeterRing::Subtrees {
+                min_len: input.ring_min_len as usize,
+            },
+        },
+    };
+
+    let tree = rbsp(
+        &mut SmallRng::seed_from_u64(input.seed),
+        bounds.clone(),
+        params,
+    );
+
+    for r in tree.rooms() {
+        assert!(
+            r.x >= bounds.x
+                && r.y >= bounds.y
+                && r.x_end() <= bounds.x_end()
+                && r.y_end() <= bounds.y_end(),
+            "{r:?} is outside {bounds:?}"
+        );
+    }
+});
@@ -1,12 +1,23 @@
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
 use auto_impl::auto_impl;
-use cgmath::{vec2, MetricSpace, Vector2};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use wide::{f32x8, CmpLt};
 
-use crate::util::Direction;
+use crate::{
+    math::{self, vec2, InnerSpace, MetricSpace, Vector2},
+    tile::MaterialId,
+    util::{Axis, Corner, Direction},
+};
 
 #[derive(Debug, Clone)]
 pub struct CameraParams {
     pub pos: Vector2<f32>,
 
+    /// The direction the camera faces. Doesn't need to be a unit vector, but
+    /// nothing is hit if it is zero.
     pub facing_unit: Vector2<f32>,
 
     pub n_rays: usize,
@@ -14,85 +25,828 @@ pub struct CameraParams {
     pub max_dist: f32,
 
     /// The projection plane is 1 unit away from the camera. Adjusting this value
-    /// allows you to adjust the FOV.
+    /// allows you to adjust the FOV; see [`CameraParams::with_fov_degrees`].
     pub projection_plane_width: f32,
+
+    /// How far the camera looks up, in radians, or down if negative. Rays are
+    /// cast level regardless; renderers shear the view vertically instead.
+    pub pitch: f32,
+}
+
+impl CameraParams {
+    /// How far in front of the camera a hit is, measured along the facing
+    /// direction rather than along the ray.
+    ///
+    /// Wall heights should be scaled by this rather than by the distance to
+    /// the hit, or walls bow outwards towards the edges of the view.
+    pub fn perpendicular_distance(&self, hit: &RaycastHit) -> f32 {
+        (hit.hit_pos - self.pos).dot(self.facing())
+    }
+
+    /// The facing direction as a unit vector.
+    pub fn facing(&self) -> Vector2<f32> {
+        self.facing_unit.normalize()
+    }
+
+    /// The horizontal field of view, in radians, from one edge of the
+    /// projection plane to the other.
+    pub fn fov(&self) -> f32 {
+        2.0 * math::atan(self.projection_plane_width / 2.0)
+    }
+
+    pub fn fov_degrees(&self) -> f32 {
+        self.fov().to_degrees()
+    }
+
+    /// Set the projection plane width to give a horizontal field of view, in
+    /// radians. Only fields of view between 0 and pi make sense.
+    pub fn with_fov(self, fov: f32) -> Self {
+        Self {
+            projection_plane_width: 2.0 * math::tan(fov / 2.0),
+            ..self
+        }
+    }
+
+    pub fn with_fov_degrees(self, fov: f32) -> Self {
+        self.with_fov(fov.to_radians())
+    }
 }
 
 #[auto_impl(&, Box, Arc)]
 pub trait RaycastableWorld {
+    /// Everything a ray needs to know about a grid coordinate, or `None` if
+    /// there is nothing there at all, such as off the edge of the world.
+    fn cell(&self, pos: (isize, isize)) -> Option<CellInfo>;
+
     /// Given a grid coordinate, return if there is an object there or not.
-    fn exists(&self, pos: (isize, isize)) -> bool;
+    fn exists(&self, pos: (isize, isize)) -> bool {
+        self.cell(pos).is_some_and(|c| c.solid)
+    }
+
+    /// A dense view of the world for [`raycast_batch`] to step through, if
+    /// the world keeps one. Without one, every cell a batched ray enters is
+    /// looked up with [`RaycastableWorld::cell`].
+    fn grid(&self) -> Option<CellGrid<'_>> {
+        None
+    }
 }
 
-#[derive(Debug, Clone)]
+/// One byte per cell of a [`RaycastableWorld`], row by row, saying whether
+/// rays can pass straight through it.
+#[derive(Debug, Clone, Copy)]
+pub struct CellGrid<'a> {
+    /// For the cell at `(x, y)`, `cells[y * width + x]` is 0 if the cell is
+    /// empty, meaning it isn't solid, see-through, diagonal or thin, and
+    /// anything else if it has to be looked up.
+    pub cells: &'a [u8],
+
+    pub width: usize,
+}
+
+impl CellGrid<'_> {
+    /// True if rays pass straight through the cell. Cells off the grid have to
+    /// be looked up.
+    fn is_empty(&self, (x, y): (isize, isize)) -> bool {
+        if x < 0 || y < 0 || x as usize >= self.width {
+            return false;
+        }
+        let i = (y as usize).checked_mul(self.width).map(|i| i + x as usize);
+        i.and_then(|i| self.cells.get(i)) == Some(&0)
+    }
+}
+
+/// What is in one cell of a [`RaycastableWorld`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellInfo {
+    /// True if there is an object in the cell that stops rays.
+    pub solid: bool,
+
+    /// What the object is made of, for picking its texture.
+    pub material: MaterialId,
+
+    /// How tall the object is, in wall units.
+    pub height: f32,
+
+    /// If the object only fills half of its cell, split along the diagonal,
+    /// the corner it fills.
+    pub diagonal: Option<Corner>,
+
+    /// A thin surface standing somewhere inside the cell, such as a closed
+    /// door. The cell does not need to be solid for it to be hit.
+    pub thin: Option<ThinWall>,
+
+    /// True if the cell is filled with something that rays pass through but
+    /// that is still drawn, such as glass. Only used if the cell isn't solid.
+    pub see_through: bool,
+}
+
+/// A wall with no thickness, spanning a cell along one axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThinWall {
+    /// The axis the wall runs along.
+    pub axis: Axis,
+
+    /// How far across the cell the wall stands, in [0, 1]. Doors set into
+    /// the middle of a doorway, with a frame on either side, stand at 0.5.
+    pub offset: f32,
+
+    /// True if this is a closed door rather than a wall.
+    pub door: bool,
+}
+
+impl Default for CellInfo {
+    /// An empty cell, one unit tall.
+    fn default() -> Self {
+        Self {
+            solid: false,
+            material: MaterialId::default(),
+            height: 1.0,
+            diagonal: None,
+            thin: None,
+            see_through: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RaycastHit {
     pub hit_pos: Vector2<f32>,
     pub wall: Vector2<usize>,
     pub wall_side: Direction,
+
+    /// How far the ray travelled from where it was cast to `hit_pos`.
+    pub distance: f32,
+
+    /// The corner filled by the wall, if the ray hit the diagonal face of a
+    /// half-cell wall rather than one of the sides of its cell.
+    pub diagonal: Option<Corner>,
+
+    /// How tall the wall is, in wall units.
+    pub height: f32,
+
+    /// What the wall is made of.
+    pub material: MaterialId,
+
+    /// True if the ray hit a closed door rather than a wall.
+    pub door: bool,
+
+    /// True if the ray hit a [`ThinWall`] standing inside the cell rather
+    /// than the edge of the cell.
+    pub thin: bool,
+
+    /// True if the ray went on through the wall. See [`raycast_all`].
+    pub see_through: bool,
+
+    /// How far along the face of the wall the ray hit, in [0, 1], for picking
+    /// a column of its texture. Increases from left to right when looking at
+    /// the face.
+    pub u: f32,
 }
 
-/// Raycast along a plane.
-///
-/// Facing must be a unit vector.
-pub fn raycast_camera(
+/// One of the rays cast by [`raycast_camera`], and what it hit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraRay {
+    /// Which ray this is, counting from the left edge of the view.
+    pub index: usize,
+
+    /// The direction of the ray, reaching the projection plane one unit in
+    /// front of the camera.
+    pub ray: Vector2<f32>,
+
+    /// The wall the ray stopped at, if any within the camera's `max_dist`.
+    pub hit: Option<RaycastHit>,
+}
+
+/// Raycast along a plane, from left to right.
+pub fn raycast_camera(world: impl RaycastableWorld, params: &CameraParams) -> Vec<CameraRay> {
+    let rays = gen_rays(
+        params.facing_unit,
+        params.projection_plane_width,
+        params.n_rays,
+    );
+
+    rays.enumerate()
+        .map(|(index, ray)| CameraRay {
+            index,
+            ray,
+            hit: raycast(&world, params.pos, ray, params.max_dist),
+        })
+        .collect()
+}
+
+/// Raycast along a plane as [`raycast_camera`] does, splitting the rays
+/// between threads.
+#[cfg(feature = "parallel")]
+pub fn raycast_camera_par(
+    world: impl RaycastableWorld + Sync,
+    params: &CameraParams,
+) -> Vec<CameraRay> {
+    let rays = gen_rays(
+        params.facing_unit,
+        params.projection_plane_width,
+        params.n_rays,
+    )
+    .collect::<Vec<_>>();
+
+    rays.into_par_iter()
+        .enumerate()
+        .map(|(index, ray)| CameraRay {
+            index,
+            ray,
+            hit: raycast(&world, params.pos, ray, params.max_dist),
+        })
+        .collect()
+}
+
+/// Raycast along a plane as [`raycast_camera`] does, but returning every hit
+/// along each ray, as [`raycast_all`] does.
+pub fn raycast_camera_all(
     world: impl RaycastableWorld,
     params: &CameraParams,
-) -> Vec<Option<RaycastHit>> {
+    see_over: f32,
+) -> Vec<Vec<RaycastHit>> {
     let rays = gen_rays(
         params.facing_unit,
         params.projection_plane_width,
         params.n_rays,
     );
 
-    rays.map(|ray| raycast(&world, params.pos, ray, params.max_dist))
+    rays.map(|ray| raycast_all(&world, params.pos, ray, params.max_dist, see_over))
         .collect()
 }
 
+/// Something other than a wall that [`pick`] can hit, such as an entity drawn
+/// as a sprite. Picked as a circle around `pos`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickTarget {
+    pub pos: Vector2<f32>,
+    pub radius: f32,
+}
+
+/// What the middle of the view is on, as found by [`pick`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickResult {
+    /// Where the center ray hit.
+    pub pos: Vector2<f32>,
+
+    /// The cell that was hit, or the cell the target stands in if a target
+    /// was hit.
+    pub cell: (isize, isize),
+
+    /// Which of the targets was hit, by its index, if one was hit before any
+    /// wall.
+    pub entity: Option<usize>,
+
+    /// The side of the wall that was hit, if a wall was hit.
+    pub face: Option<Direction>,
+
+    /// How far the hit is from the camera.
+    pub distance: f32,
+}
+
+/// Find what is in the middle of the view, by casting a ray from the camera
+/// straight along its facing direction out to its `max_dist`, for things like
+/// opening the door the player is looking at.
+///
+/// Any of `targets` in front of the first wall the ray hits are hit instead,
+/// the nearest first.
+pub fn pick(
+    world: impl RaycastableWorld,
+    params: &CameraParams,
+    targets: &[PickTarget],
+) -> Option<PickResult> {
+    let facing = params.facing_unit.normalize();
+    let wall = raycast(world, params.pos, facing, params.max_dist);
+    let wall_dist = wall.as_ref().map_or(params.max_dist, |hit| hit.distance);
+
+    let target = targets
+        .iter()
+        .enumerate()
+        .filter_map(|(i, target)| {
+            // Where the ray passes nearest the middle of the target, and how
+            // far short of there it first touches the target.
+            let to = target.pos - params.pos;
+            let along = to.dot(facing);
+            let across2 = to.magnitude2() - along * along;
+            let r2 = target.radius * target.radius;
+            let dist = (along - math::sqrt((r2 - across2).max(0.0))).max(0.0);
+            (along >= 0.0 && across2 <= r2 && dist <= wall_dist).then_some((i, dist))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+
+    match (target, wall) {
+        (Some((i, distance)), _) => {
+            let pos = targets[i].pos;
+            Some(PickResult {
+                pos: params.pos + facing * distance,
+                cell: (math::floor(pos.x) as isize, math::floor(pos.y) as isize),
+                entity: Some(i),
+                face: None,
+                distance,
+            })
+        }
+        (None, Some(hit)) => Some(PickResult {
+            pos: hit.hit_pos,
+            cell: (hit.wall.x as isize, hit.wall.y as isize),
+            entity: None,
+            face: Some(hit.wall_side),
+            distance: hit.distance,
+        }),
+        (None, None) => None,
+    }
+}
+
+/// Why a ray couldn't be cast by [`try_raycast`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaycastError {
+    /// The ray has no direction, so would never leave its cell.
+    ZeroRay,
+    /// The ray has an infinite or NaN component.
+    NonFiniteRay,
+    /// The position has an infinite or NaN component.
+    NonFinitePosition,
+    /// The maximum distance is NaN.
+    NanMaxDist,
+    /// The ray started or hit a wall in a cell whose coordinates don't fit in
+    /// a [`RaycastHit`], such as one with a negative coordinate.
+    OutOfRange,
+}
+
+impl fmt::Display for RaycastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RaycastError::ZeroRay => write!(f, "ray has no direction"),
+            RaycastError::NonFiniteRay => write!(f, "ray is not finite"),
+            RaycastError::NonFinitePosition => {
+                write!(f, "ray starts at a position that is not finite")
+            }
+            RaycastError::NanMaxDist => write!(f, "maximum ray distance is NaN"),
+            RaycastError::OutOfRange => write!(f, "ray reached a cell out of range"),
+        }
+    }
+}
+
+impl core::error::Error for RaycastError {}
+
 /// Perform a single raycast from the given position along the given ray.
+/// See-through cells are passed through as if they were empty.
+///
+/// Rays that can't be cast, as described by [`try_raycast`], hit nothing.
 pub fn raycast(
     world: impl RaycastableWorld,
     pos: Vector2<f32>,
     ray: Vector2<f32>,
     max_dist: f32,
 ) -> Option<RaycastHit> {
-    let max_dist_2 = max_dist * max_dist;
+    try_raycast(world, pos, ray, max_dist).ok().flatten()
+}
+
+/// Perform a single raycast as [`raycast`] does, but with an error for a ray
+/// with no direction, a ray or position that isn't finite, a NaN `max_dist`,
+/// or a ray that reaches a wall in a cell with a negative coordinate.
+pub fn try_raycast(
+    world: impl RaycastableWorld,
+    pos: Vector2<f32>,
+    ray: Vector2<f32>,
+    max_dist: f32,
+) -> Result<Option<RaycastHit>, RaycastError> {
+    march(world, pos, ray, max_dist, 0.0, |_| {})
+}
 
-    let mut march_pos = pos;
-    let mut this_grid = march_pos.map(|x| x.floor()).cast::<isize>().unwrap();
+/// Perform a single raycast from the given position along the given ray,
+/// returning every hit in order from nearest to farthest: one for each stretch
+/// of see-through cells the ray enters, and one for each wall lower than
+/// `see_over` it passes over, followed by whatever stopped it, if anything did.
+///
+/// Pass the camera's eye height as `see_over` to see over walls below eye
+/// level, such as counters, or 0 to stop at the first wall.
+pub fn raycast_all(
+    world: impl RaycastableWorld,
+    pos: Vector2<f32>,
+    ray: Vector2<f32>,
+    max_dist: f32,
+    see_over: f32,
+) -> Vec<RaycastHit> {
+    let mut hits = vec![];
+    let last = march(world, pos, ray, max_dist, see_over, |hit| hits.push(hit));
+    hits.extend(last.ok().flatten());
+    hits
+}
 
-    loop {
-        if march_pos.distance2(pos) > max_dist_2 {
-            return None;
+/// How many rays [`raycast_batch`] steps through the grid together.
+const LANES: usize = 8;
+
+/// Cast many rays from the same position, giving the same hits as calling
+/// [`raycast`] for each of them.
+///
+/// Rays are stepped through the grid [`LANES`] at a time in vector registers,
+/// reading the world's [`CellGrid`] to skip empty cells without looking them
+/// up. Any ray that runs into a diagonal or thin wall is finished off by
+/// [`raycast`].
+pub fn raycast_batch(
+    world: impl RaycastableWorld,
+    pos: Vector2<f32>,
+    rays: &[Vector2<f32>],
+    max_dist: f32,
+) -> Vec<Option<RaycastHit>> {
+    let start = match start_cell(pos) {
+        Ok(start) if !max_dist.is_nan() => start,
+        _ => return vec![None; rays.len()],
+    };
+    let start_info = world.cell(start.into()).unwrap_or_default();
+    if start_info.diagonal.is_some() || start_info.thin.is_some() {
+        return rays
+            .iter()
+            .map(|ray| raycast(&world, pos, *ray, max_dist))
+            .collect();
+    }
+
+    let mut hits = Vec::with_capacity(rays.len());
+    for group in rays.chunks(LANES) {
+        hits.extend(raycast_lanes(&world, pos, start, group, max_dist));
+    }
+    hits
+}
+
+/// Step up to [`LANES`] rays through the grid together.
+fn raycast_lanes(
+    world: &impl RaycastableWorld,
+    pos: Vector2<f32>,
+    start: Vector2<isize>,
+    rays: &[Vector2<f32>],
+    max_dist: f32,
+) -> Vec<Option<RaycastHit>> {
+    let grid = world.grid();
+    let mut hits = vec![None; rays.len()];
+    let mut active = [false; LANES];
+    let (mut next_x, mut next_y) = ([f32::INFINITY; LANES], [f32::INFINITY; LANES]);
+    let (mut delta_x, mut delta_y) = ([f32::INFINITY; LANES], [f32::INFINITY; LANES]);
+    let (mut dir_x, mut dir_y) = ([Direction::East; LANES], [Direction::North; LANES]);
+    let mut max_t = [0.0; LANES];
+    for (i, ray) in rays.iter().enumerate() {
+        if !(ray.x.is_finite() && ray.y.is_finite()) || *ray == vec2(0.0, 0.0) {
+            continue;
+        }
+        active[i] = true;
+        (dir_x[i], next_x[i], delta_x[i]) = grid_steps(pos.x, ray.x, Direction::East);
+        (dir_y[i], next_y[i], delta_y[i]) = grid_steps(pos.y, ray.y, Direction::North);
+        max_t[i] = max_dist / ray.magnitude();
+    }
+    let (mut next_x, mut next_y) = (f32x8::new(next_x), f32x8::new(next_y));
+    let (delta_x, delta_y) = (f32x8::new(delta_x), f32x8::new(delta_y));
+    let (mut cell_x, mut cell_y) = ([start.x; LANES], [start.y; LANES]);
+
+    while active.contains(&true) {
+        // Step every lane at once, whether or not it is still going.
+        let step_x = next_x.cmp_lt(next_y);
+        let t = step_x.blend(next_x, next_y).to_array();
+        next_x = step_x.blend(next_x + delta_x, next_x);
+        next_y = step_x.blend(next_y, next_y + delta_y);
+        let step_x = step_x.move_mask();
+
+        for (i, ray) in rays.iter().enumerate() {
+            if !active[i] {
+                continue;
+            }
+            let dir = match step_x & (1 << i) {
+                0 => dir_y[i],
+                _ => dir_x[i],
+            };
+            let step = Vector2::<isize>::from(dir);
+            (cell_x[i], cell_y[i]) = (cell_x[i] + step.x, cell_y[i] + step.y);
+            if t[i] > max_t[i] {
+                active[i] = false;
+                continue;
+            }
+            if grid.is_some_and(|g| g.is_empty((cell_x[i], cell_y[i]))) {
+                continue;
+            }
+
+            let cell = vec2(cell_x[i], cell_y[i]);
+            let info = world.cell(cell.into()).unwrap_or_default();
+            if info.thin.is_some() || (info.solid && info.diagonal.is_some()) {
+                hits[i] = raycast(world, pos, *ray, max_dist);
+                active[i] = false;
+            } else if info.solid {
+                let hit_pos = snap_to_edge(pos + *ray * t[i], cell, dir);
+                hits[i] = edge_hit(cell, &info, pos, hit_pos, -dir).ok();
+                active[i] = false;
+            }
+        }
+    }
+    hits
+}
+
+/// A hit on the side of a cell facing `side`, by a ray cast from `from`.
+fn edge_hit(
+    cell: Vector2<isize>,
+    info: &CellInfo,
+    from: Vector2<f32>,
+    hit_pos: Vector2<f32>,
+    side: Direction,
+) -> Result<RaycastHit, RaycastError> {
+    Ok(RaycastHit {
+        hit_pos,
+        wall: wall_cell(cell)?,
+        wall_side: side,
+        distance: hit_pos.distance(from),
+        diagonal: None,
+        height: info.height,
+        material: info.material,
+        door: false,
+        thin: false,
+        see_through: info.see_through && !info.solid,
+        u: face_u(hit_pos - cell.cast().unwrap(), side),
+    })
+}
+
+/// The cell a position is in.
+fn start_cell(pos: Vector2<f32>) -> Result<Vector2<isize>, RaycastError> {
+    if !(pos.x.is_finite() && pos.y.is_finite()) {
+        return Err(RaycastError::NonFinitePosition);
+    }
+    pos.map(math::floor).cast().ok_or(RaycastError::OutOfRange)
+}
+
+/// A cell as it's given in a [`RaycastHit`].
+fn wall_cell(cell: Vector2<isize>) -> Result<Vector2<usize>, RaycastError> {
+    cell.cast().ok_or(RaycastError::OutOfRange)
+}
+
+/// March along a ray until it stops, passing the face of each stretch of
+/// see-through cells it enters, and of each wall lower than `see_over` it hits,
+/// to `passed`.
+fn march(
+    world: impl RaycastableWorld,
+    pos: Vector2<f32>,
+    ray: Vector2<f32>,
+    max_dist: f32,
+    see_over: f32,
+    mut passed: impl FnMut(RaycastHit),
+) -> Result<Option<RaycastHit>, RaycastError> {
+    if !(ray.x.is_finite() && ray.y.is_finite()) {
+        return Err(RaycastError::NonFiniteRay);
+    }
+    if ray == vec2(0.0, 0.0) {
+        return Err(RaycastError::ZeroRay);
+    }
+    if max_dist.is_nan() {
+        return Err(RaycastError::NanMaxDist);
+    }
+    let max_t = max_dist / ray.magnitude();
+
+    let this_grid = start_cell(pos)?;
+    let start = world.cell(this_grid.into()).unwrap_or_default();
+    let mut in_see_through = start.see_through && !start.solid;
+
+    // The ray may start in the open half of a diagonal wall.
+    if let Some(corner) = start.diagonal {
+        let box_offset = this_grid.cast().unwrap();
+        if let Some(hit) = raycast_diagonal(corner, pos - box_offset, ray) {
+            return Ok(Some(RaycastHit {
+                hit_pos: hit + box_offset,
+                wall: wall_cell(this_grid)?,
+                wall_side: diagonal_side(corner, ray),
+                distance: (hit + box_offset).distance(pos),
+                diagonal: Some(corner),
+                height: start.height,
+                material: start.material,
+                door: false,
+                thin: false,
+                see_through: false,
+                u: face_u(hit, diagonal_side(corner, ray)),
+            }));
         }
+    }
 
+    // Or in front of a thin wall, such as in a doorway in front of the door.
+    if let Some(thin) = start.thin {
         let box_offset = this_grid.cast().unwrap();
-        let box_pos = march_pos - box_offset;
-        let (box_hit_pos, outgoing_dir) = raycast_in_box(box_pos, ray);
-        let hit_pos = box_hit_pos + box_offset;
+        if let Some((hit, side)) = raycast_thin(thin, pos - box_offset, ray) {
+            return Ok(Some(RaycastHit {
+                hit_pos: hit + box_offset,
+                wall: wall_cell(this_grid)?,
+                wall_side: side,
+                distance: (hit + box_offset).distance(pos),
+                diagonal: None,
+                height: start.height,
+                material: start.material,
+                door: thin.door,
+                thin: true,
+                see_through: false,
+                u: face_u(hit, side),
+            }));
+        }
+    }
 
-        let probe_cell = this_grid + Vector2::<isize>::from(outgoing_dir);
+    // Step from cell to cell along the grid lines the ray crosses. `next_t` is
+    // how many rays along the next line across each axis is, and `delta_t`
+    // how many rays apart those lines are.
+    let (dir_x, next_x, delta_x) = grid_steps(pos.x, ray.x, Direction::East);
+    let (dir_y, next_y, delta_y) = grid_steps(pos.y, ray.y, Direction::North);
+    let (mut next_t, delta_t) = (vec2(next_x, next_y), vec2(delta_x, delta_y));
+    let mut probe_cell = this_grid;
 
-        if world.exists(probe_cell.into()) {
-            return Some(RaycastHit {
-                hit_pos,
-                wall: probe_cell.cast().unwrap(),
-                wall_side: -outgoing_dir,
-            });
+    loop {
+        // Ties step along y, so rays through a corner pass it vertically.
+        let (t, outgoing_dir) = if next_t.x < next_t.y {
+            let t = next_t.x;
+            next_t.x += delta_t.x;
+            (t, dir_x)
         } else {
-            march_pos = hit_pos;
-            this_grid = probe_cell;
+            let t = next_t.y;
+            next_t.y += delta_t.y;
+            (t, dir_y)
+        };
+        if t > max_t {
+            return Ok(None);
+        }
+        probe_cell += Vector2::<isize>::from(outgoing_dir);
+
+        let hit_pos = snap_to_edge(pos + ray * t, probe_cell, outgoing_dir);
+        let probe = world.cell(probe_cell.into()).unwrap_or_default();
+
+        // Thin walls are further into the cell than its edge, so rays that
+        // miss carry on through the cell.
+        if let Some(thin) = probe.thin {
+            let probe_offset = probe_cell.cast().unwrap();
+            if let Some((hit, side)) = raycast_thin(thin, hit_pos - probe_offset, ray) {
+                return Ok(Some(RaycastHit {
+                    hit_pos: hit + probe_offset,
+                    wall: wall_cell(probe_cell)?,
+                    wall_side: side,
+                    distance: (hit + probe_offset).distance(pos),
+                    diagonal: None,
+                    height: probe.height,
+                    material: probe.material,
+                    door: thin.door,
+                    thin: true,
+                    see_through: false,
+                    u: face_u(hit, side),
+                }));
+            }
+        }
+
+        if probe.solid {
+            let hit = match probe.diagonal {
+                None => Some((hit_pos, None)),
+                Some(corner) => {
+                    let probe_offset = probe_cell.cast().unwrap();
+                    let entry = hit_pos - probe_offset;
+                    if corner.contains(entry) {
+                        Some((hit_pos, None))
+                    } else {
+                        raycast_diagonal(corner, entry, ray)
+                            .map(|hit| (hit + probe_offset, Some(corner)))
+                    }
+                }
+            };
+            if let Some((hit_pos, diagonal)) = hit {
+                let wall_side = match diagonal {
+                    Some(corner) => diagonal_side(corner, ray),
+                    None => -outgoing_dir,
+                };
+                let probe_offset = probe_cell.cast().unwrap();
+                let hit = RaycastHit {
+                    hit_pos,
+                    wall: wall_cell(probe_cell)?,
+                    wall_side,
+                    distance: hit_pos.distance(pos),
+                    diagonal,
+                    height: probe.height,
+                    material: probe.material,
+                    door: false,
+                    thin: false,
+                    see_through: false,
+                    u: face_u(hit_pos - probe_offset, wall_side),
+                };
+                if probe.height >= see_over {
+                    return Ok(Some(hit));
+                }
+                passed(hit);
+            }
+        }
+
+        // Only the face between an open cell and a see-through one is drawn,
+        // not the faces between neighboring panes.
+        let probe_see_through = probe.see_through && !probe.solid;
+        if probe_see_through && !in_see_through {
+            passed(edge_hit(probe_cell, &probe, pos, hit_pos, -outgoing_dir)?);
         }
+        in_see_through = probe_see_through;
     }
 }
 
+/// Snap a point where a ray entered a cell, travelling in direction `dir`, onto
+/// the edge of the cell it crossed, so the error in the point only ever shows
+/// along the edge.
+fn snap_to_edge(mut pos: Vector2<f32>, cell: Vector2<isize>, dir: Direction) -> Vector2<f32> {
+    match dir {
+        Direction::East => pos.x = cell.x as f32,
+        Direction::West => pos.x = (cell.x + 1) as f32,
+        Direction::North => pos.y = cell.y as f32,
+        Direction::South => pos.y = (cell.y + 1) as f32,
+    }
+    pos
+}
+
+/// For a ray starting at `pos` along one axis with component `ray`, the
+/// direction it steps in along that axis, how many rays along it first crosses
+/// a grid line, and how many rays apart the lines are. `positive` is the
+/// direction of the axis. Rays that never cross a line cross it at infinity.
+fn grid_steps(pos: f32, ray: f32, positive: Direction) -> (Direction, f32, f32) {
+    let cell = math::floor(pos);
+    if ray > 0.0 {
+        (positive, (cell + 1.0 - pos) / ray, 1.0 / ray)
+    } else if ray < 0.0 {
+        (-positive, (cell - pos) / ray, -1.0 / ray)
+    } else {
+        (-positive, f32::INFINITY, f32::INFINITY)
+    }
+}
+
+/// How far along a face of a unit cell a point on it is, from the left end of
+/// the face as seen from outside. `pos` is relative to the cell.
+fn face_u(pos: Vector2<f32>, side: Direction) -> f32 {
+    let u = match side {
+        Direction::West => pos.y,
+        Direction::East => 1.0 - pos.y,
+        Direction::South => 1.0 - pos.x,
+        Direction::North => pos.x,
+    };
+    u.clamp(0.0, 1.0)
+}
+
+/// The side of a cell facing a ray that hits the diagonal cutting off the given
+/// corner. Diagonals face two sides at once, so this is whichever one the ray
+/// is travelling more directly towards.
+fn diagonal_side(corner: Corner, ray: Vector2<f32>) -> Direction {
+    let (ew, ns) = corner.sides();
+    if ray.x.abs() >= ray.y.abs() {
+        -ew
+    } else {
+        -ns
+    }
+}
+
+/// Raycast from a point in the open half of a unit cell to the diagonal that
+/// cuts off the given corner, returning where the ray hits it, if it does
+/// before leaving the cell.
+fn raycast_diagonal(corner: Corner, pos: Vector2<f32>, ray: Vector2<f32>) -> Option<Vector2<f32>> {
+    let (normal, c) = corner.diagonal();
+    let towards = normal.dot(ray);
+    if towards <= 0.0 {
+        return None;
+    }
+    let hit = pos + ray * ((c - normal.dot(pos)) / towards);
+    let in_cell = |v: f32| (-f32::EPSILON..=1.0 + f32::EPSILON).contains(&v);
+    (in_cell(hit.x) && in_cell(hit.y)).then_some(hit)
+}
+
+/// Raycast from a point in a unit cell to a thin wall in the cell, returning
+/// where the ray hits it and the side of the wall it hits, if it does before
+/// leaving the cell.
+fn raycast_thin(
+    thin: ThinWall,
+    pos: Vector2<f32>,
+    ray: Vector2<f32>,
+) -> Option<(Vector2<f32>, Direction)> {
+    // Swap coordinates so the wall always runs along y, at x = offset.
+    let swap = |v: Vector2<f32>| match thin.axis {
+        Axis::Vertical => v,
+        Axis::Horizontal => vec2(v.y, v.x),
+    };
+    let (p, r) = (swap(pos), swap(ray));
+    if r.x == 0.0 {
+        return None;
+    }
+    let t = (thin.offset - p.x) / r.x;
+    if t < 0.0 {
+        return None;
+    }
+    let hit = p + r * t;
+    if !(-f32::EPSILON..=1.0 + f32::EPSILON).contains(&hit.y) {
+        return None;
+    }
+    let side = match (thin.axis, r.x > 0.0) {
+        (Axis::Vertical, true) => Direction::West,
+        (Axis::Vertical, false) => Direction::East,
+        (Axis::Horizontal, true) => Direction::South,
+        (Axis::Horizontal, false) => Direction::North,
+    };
+    Some((swap(hit), side))
+}
+
 /// Generates a number of rays, for projection plane distance of 1.
 ///
-/// Facing must be a unit vector.
+/// Facing is normalized, so doesn't need to be a unit vector.
 fn gen_rays(
-    facing_unit: Vector2<f32>,
+    facing: Vector2<f32>,
     projection_plane_width: f32,
     n_rays: usize,
 ) -> impl Iterator<Item = Vector2<f32>> {
+    let facing_unit = facing.normalize();
+
     // Calculate the perpendicular of the unit vector, to the left.
     let facing_left_unit = vec2(facing_unit.y, -facing_unit.x);
 
@@ -104,53 +858,13 @@ fn gen_rays(
     })
 }
 
-/// Raycast to the edge of the box bounded by points (0, 0) and (1, 1).
-fn raycast_in_box(pos: Vector2<f32>, ray: Vector2<f32>) -> (Vector2<f32>, Direction) {
-    use Direction::*;
-
-    /// This is restricted to the case where both components of ray_unit
-    /// are less than or equal to zero.
-    #[inline(always)]
-    fn towards_origin(pos: Vector2<f32>, ray: Vector2<f32>) -> (Vector2<f32>, Direction) {
-        let xdir = if ray.x > 0.0 { East } else { West };
-        let ydir = if ray.y > 0.0 { North } else { South };
-
-        match (ray.x == 0.0, ray.y == 0.0) {
-            (true, true) => panic!("Cannot raycast with zero-valued ray"),
-            (true, false) => return (vec2(pos.x, 0.0), ydir),
-            (false, true) => return (vec2(0.0, pos.y), xdir),
-            (false, false) => (),
-        }
-
-        let x_int = pos.x - (ray.x / ray.y) * pos.y;
-        let y_int = pos.y - (ray.y / ray.x) * pos.x;
-
-        if x_int < 0.0 {
-            (vec2(0.0, y_int), xdir)
-        } else {
-            (vec2(x_int, 0.0), ydir)
-        }
-    }
-
-    if ray.x > 0.0 {
-        let (o, d) = raycast_in_box(vec2(1.0 - pos.x, pos.y), vec2(-ray.x, ray.y));
-        return (vec2(1.0 - o.x, o.y), d.reflect_lr());
-    }
-    if ray.y > 0.0 {
-        let (o, d) = towards_origin(vec2(pos.x, 1.0 - pos.y), vec2(ray.x, -ray.y));
-        return (vec2(o.x, 1.0 - o.y), d.reflect_ud());
-    }
-
-    towards_origin(pos, ray)
-}
-
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use crate::world::ArrayWorld;
+    use crate::{tile::Tile, world::ArrayWorld, worldgen::doors::Door};
 
     use super::*;
-    use cgmath::{assert_ulps_eq, vec2, Vector2};
-    use ndarray::array;
+    use cgmath::{assert_abs_diff_eq, assert_ulps_eq, vec2, Vector2};
+    use ndarray::{array, Array2};
     use rstest::rstest;
 
     fn example_world() -> ArrayWorld {
@@ -165,6 +879,18 @@ mod tests {
         ArrayWorld::from(data.map(|x| *x != 0))
     }
 
+    /// A single open cell at (1, 1), walled in on every side.
+    struct Closet;
+
+    impl RaycastableWorld for Closet {
+        fn cell(&self, pos: (isize, isize)) -> Option<CellInfo> {
+            Some(CellInfo {
+                solid: pos != (1, 1),
+                ..CellInfo::default()
+            })
+        }
+    }
+
     #[rstest]
     #[case(vec2(0.75, 0.5), vec2(-1.0, 0.0),  (vec2(0.0, 0.5),    Direction::West))]
     #[case(vec2(0.75, 0.5), vec2(0.0, -1.0),  (vec2(0.75, 0.0),   Direction::South))]
@@ -172,15 +898,17 @@ mod tests {
     #[case(vec2(0.25, 0.5), vec2(1.0, -0.25), (vec2(1.0, 0.3125), Direction::East))]
     #[case(vec2(0.5, 0.25), vec2(1.0, 1.0),   (vec2(1.0, 0.75),   Direction::East))]
     #[case(vec2(0.5, 0.5),  vec2(1.0, 1.0),   (vec2(1.0, 1.0),    Direction::North))]
-    fn test_raycast_in_box(
+    fn raycast_out_of_cell(
         #[case] pos: Vector2<f32>,
         #[case] ray: Vector2<f32>,
         #[case] expected: (Vector2<f32>, Direction),
     ) {
-        let (hit, dir) = raycast_in_box(pos, ray);
+        let offset = vec2(1.0, 1.0);
+
+        let hit = raycast(Closet, pos + offset, ray, 100.0).unwrap();
 
-        assert_eq!(dir, expected.1);
-        assert_ulps_eq!(hit, expected.0);
+        assert_eq!(hit.wall_side, -expected.1);
+        assert_ulps_eq!(hit.hit_pos, expected.0 + offset);
     }
 
     #[rstest]
@@ -188,24 +916,48 @@ mod tests {
         (vec2(2.5, 2.5), vec2(-1.0, 0.0)),
         RaycastHit {
             hit_pos: vec2(1.0, 2.5),
+            distance: 1.5,
             wall: vec2(0, 2),
-            wall_side: Direction::East
+            wall_side: Direction::East,
+            diagonal: None,
+            height: 1.0,
+            material: MaterialId::default(),
+            door: false,
+            thin: false,
+            see_through: false,
+            u: 0.5
         }
     )]
     #[case(
         (vec2(1.05, 1.05), vec2(-0.5, -1.0)),
         RaycastHit {
             hit_pos: vec2(1.025, 1.0),
+            distance: 0.055_901_7,
             wall: vec2(1, 0),
-            wall_side: Direction::North
+            wall_side: Direction::North,
+            diagonal: None,
+            height: 1.0,
+            material: MaterialId::default(),
+            door: false,
+            thin: false,
+            see_through: false,
+            u: 0.025
         }
     )]
     #[case(
         (vec2(3.5, 3.5), vec2(-1.0, -1.0)),
         RaycastHit {
             hit_pos: vec2(1.0, 1.0),
+            distance: 3.535534,
             wall: vec2(1, 0),
-            wall_side: Direction::North
+            wall_side: Direction::North,
+            diagonal: None,
+            height: 1.0,
+            material: MaterialId::default(),
+            door: false,
+            thin: false,
+            see_through: false,
+            u: 0.0
         }
     )]
     fn raycast_edge(#[case] ray: (Vector2<f32>, Vector2<f32>), #[case] expected: RaycastHit) {
@@ -216,6 +968,511 @@ mod tests {
 
         assert_eq!(result.wall_side, expected.wall_side);
         assert_eq!(result.wall, expected.wall);
-        assert_ulps_eq!(result.hit_pos, expected.hit_pos)
+        assert_ulps_eq!(result.hit_pos, expected.hit_pos);
+        assert_ulps_eq!(result.u, expected.u);
+        assert_abs_diff_eq!(result.distance, expected.distance, epsilon = 1e-5);
+    }
+
+    fn diagonal_world() -> ArrayWorld {
+        let mut map = Array2::from_elem((6, 6), Tile::Floor);
+        for i in 0..6 {
+            map[(0, i)] = Tile::Wall;
+            map[(5, i)] = Tile::Wall;
+            map[(i, 0)] = Tile::Wall;
+            map[(i, 5)] = Tile::Wall;
+        }
+        map[(2, 3)] = Tile::Diagonal(Corner::NorthEast);
+        ArrayWorld::from(map)
+    }
+
+    #[rstest]
+    #[case(vec2(1.5, 2.5), vec2(1.0, 0.0), Some((vec2(3.5, 2.5), Direction::West)))]
+    #[case(vec2(3.2, 2.2), vec2(1.0, 0.0), Some((vec2(3.8, 2.2), Direction::West)))]
+    #[case(vec2(3.25, 1.5), vec2(0.0, 1.0), Some((vec2(3.25, 2.75), Direction::South)))]
+    #[case(vec2(3.5, 4.5), vec2(0.0, -1.0), None)]
+    fn raycast_diagonal_wall(
+        #[case] pos: Vector2<f32>,
+        #[case] ray: Vector2<f32>,
+        #[case] expected: Option<(Vector2<f32>, Direction)>,
+    ) {
+        let result = raycast(diagonal_world(), pos, ray, 100.0).unwrap();
+
+        assert_eq!(result.wall, vec2(3, 2));
+        match expected {
+            Some((hit_pos, side)) => {
+                assert_eq!(result.diagonal, Some(Corner::NorthEast));
+                assert_eq!(result.wall_side, side);
+                assert_ulps_eq!(result.hit_pos, hit_pos);
+            }
+            None => {
+                assert_eq!(result.diagonal, None);
+                assert_eq!(result.wall_side, Direction::North);
+                assert_ulps_eq!(result.hit_pos, vec2(3.5, 3.0));
+            }
+        }
+    }
+
+    fn door_world(facing: Direction) -> ArrayWorld {
+        let mut map = Array2::from_elem((5, 5), Tile::Floor);
+        for i in 0..5 {
+            map[(0, i)] = Tile::Wall;
+            map[(4, i)] = Tile::Wall;
+            map[(i, 0)] = Tile::Wall;
+            map[(i, 4)] = Tile::Wall;
+        }
+        map[(2, 2)] = Tile::Door;
+        ArrayWorld::from(map).with_doors(vec![Door::new(2, 2, facing)])
+    }
+
+    #[rstest]
+    #[case(
+        Direction::North,
+        vec2(1.5, 2.5),
+        vec2(1.0, 0.0),
+        vec2(2.5, 2.5),
+        Direction::West
+    )]
+    #[case(Direction::North, vec2(3.5, 2.2), vec2(-1.0, 0.0), vec2(2.5, 2.2), Direction::East)]
+    #[case(
+        Direction::East,
+        vec2(2.3, 1.5),
+        vec2(0.0, 1.0),
+        vec2(2.3, 2.5),
+        Direction::South
+    )]
+    #[case(Direction::East, vec2(2.5, 2.9), vec2(0.5, -1.0), vec2(2.7, 2.5), Direction::North)]
+    fn raycast_closed_door(
+        #[case] facing: Direction,
+        #[case] pos: Vector2<f32>,
+        #[case] ray: Vector2<f32>,
+        #[case] hit_pos: Vector2<f32>,
+        #[case] side: Direction,
+    ) {
+        let result = raycast(door_world(facing), pos, ray, 100.0).unwrap();
+
+        assert!(result.door);
+        assert!(result.thin);
+        assert_eq!(result.wall, vec2(2, 2));
+        assert_eq!(result.wall_side, side);
+        assert_ulps_eq!(result.hit_pos, hit_pos);
+    }
+
+    #[test]
+    fn rays_pass_open_doors() {
+        let mut world = door_world(Direction::North);
+        world.door_mut((2, 2)).unwrap().open();
+
+        let result = raycast(world, vec2(1.5, 2.5), vec2(1.0, 0.0), 100.0).unwrap();
+
+        assert!(!result.door);
+        assert_eq!(result.wall, vec2(4, 2));
+    }
+
+    #[test]
+    fn rays_pass_the_open_half_of_diagonals() {
+        let result = raycast(diagonal_world(), vec2(2.5, 2.9), vec2(1.0, -1.0), 100.0).unwrap();
+
+        assert_eq!(result.diagonal, None);
+        assert_ne!(result.wall, vec2(3, 2));
+    }
+
+    #[test]
+    fn rays_see_through_windows() {
+        let mut map = Array2::from_elem((5, 5), Tile::Wall);
+        map[(2, 1)] = Tile::Floor;
+        map[(2, 2)] = Tile::Window;
+        map[(2, 3)] = Tile::Floor;
+        let world = ArrayWorld::from(map);
+
+        let result = raycast(&world, vec2(1.5, 2.5), vec2(1.0, 0.0), 100.0).unwrap();
+
+        assert_eq!(result.wall, vec2(4, 2));
+        assert!(!world.passable((2, 2)));
+    }
+
+    #[test]
+    fn perpendicular_distance_is_flat_across_a_wall() {
+        let mut map = Array2::from_elem((9, 9), Tile::Floor);
+        map.column_mut(8).fill(Tile::Wall);
+        let camera = CameraParams {
+            pos: vec2(2.5, 4.5),
+            facing_unit: vec2(1.0, 0.0),
+            n_rays: 16,
+            max_dist: 100.0,
+            projection_plane_width: 1.5,
+            pitch: 0.0,
+        };
+
+        let hits = raycast_camera(ArrayWorld::from(map), &camera);
+
+        for (i, r) in hits.iter().enumerate() {
+            let hit = r.hit.as_ref().unwrap();
+            assert_eq!(r.index, i);
+            assert_ulps_eq!(camera.perpendicular_distance(hit), 5.5);
+            assert_abs_diff_eq!(hit.distance, 5.5 * r.ray.magnitude(), epsilon = 1e-4);
+        }
+        let edge = hits[0].hit.as_ref().unwrap();
+        assert!(edge.distance > 5.6);
+    }
+
+    #[rstest]
+    #[case(vec2(1.0, 0.0))]
+    #[case(vec2(-1.0, 0.0))]
+    #[case(vec2(0.0, 1.0))]
+    #[case(vec2(0.0, -1.0))]
+    fn u_increases_left_to_right(#[case] facing: Vector2<f32>) {
+        let mut map = Array2::from_elem((9, 9), Tile::Floor);
+        for i in 0..9 {
+            map[(0, i)] = Tile::Wall;
+            map[(8, i)] = Tile::Wall;
+            map[(i, 0)] = Tile::Wall;
+            map[(i, 8)] = Tile::Wall;
+        }
+        let world = ArrayWorld::from(map);
+        let pos = vec2(4.5, 4.5);
+        // With y pointing down, this is to the left of the facing direction.
+        let left = vec2(facing.y, -facing.x);
+
+        let u_at = |offset: f32| {
+            let ray = facing * 3.5 + left * offset;
+            raycast(&world, pos, ray, 100.0).unwrap().u
+        };
+
+        assert_abs_diff_eq!(u_at(0.25), 0.25, epsilon = 1e-5);
+        assert_abs_diff_eq!(u_at(-0.25), 0.75, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn hits_report_the_wall_material() {
+        let mut materials = Array2::default((6, 6));
+        materials.row_mut(0).fill(MaterialId::TILE);
+        let world = diagonal_world().with_materials(materials);
+
+        let north = raycast(&world, vec2(1.5, 1.5), vec2(0.0, -1.0), 100.0).unwrap();
+        let south = raycast(&world, vec2(1.5, 1.5), vec2(0.0, 1.0), 100.0).unwrap();
+
+        assert_eq!(north.material, MaterialId::TILE);
+        assert_eq!(south.material, MaterialId::default());
+    }
+
+    /// An endless floor with a concrete wall along every tenth column.
+    struct Fences;
+
+    impl RaycastableWorld for Fences {
+        fn cell(&self, (x, _): (isize, isize)) -> Option<CellInfo> {
+            let solid = x.rem_euclid(10) == 0;
+            Some(CellInfo {
+                solid,
+                material: MaterialId::CONCRETE,
+                height: if solid { 2.0 } else { 1.0 },
+                ..CellInfo::default()
+            })
+        }
+    }
+
+    #[test]
+    fn worlds_only_need_to_describe_cells() {
+        assert!(Fences.exists((20, 3)) && !Fences.exists((21, 3)));
+
+        let hit = raycast(Fences, vec2(4.5, 4.5), vec2(1.0, 0.2), 100.0).unwrap();
+
+        assert_eq!(hit.wall.x, 10);
+        assert_eq!(hit.wall_side, Direction::West);
+        assert_eq!(hit.material, MaterialId::CONCRETE);
+        assert_eq!(hit.height, 2.0);
+    }
+
+    /// A screen a quarter of the way into cell (5, 4), in front of a wall
+    /// along row 8.
+    struct Screen;
+
+    impl RaycastableWorld for Screen {
+        fn cell(&self, pos: (isize, isize)) -> Option<CellInfo> {
+            let thin = (pos == (5, 4)).then_some(ThinWall {
+                axis: Axis::Vertical,
+                offset: 0.25,
+                door: false,
+            });
+            Some(CellInfo {
+                solid: pos.1 == 8,
+                thin,
+                ..CellInfo::default()
+            })
+        }
+    }
+
+    #[test]
+    fn rays_stop_at_thin_walls_inside_cells() {
+        let hit = raycast(Screen, vec2(2.5, 4.5), vec2(1.0, 0.0), 100.0).unwrap();
+
+        assert!(hit.thin && !hit.door);
+        assert_eq!(hit.wall, vec2(5, 4));
+        assert_eq!(hit.wall_side, Direction::West);
+        assert_ulps_eq!(hit.hit_pos, vec2(5.25, 4.5));
+
+        // Leaves the cell through its side before reaching the screen.
+        let miss = raycast(Screen, vec2(4.8, 3.5), vec2(1.0, 5.0), 100.0).unwrap();
+
+        assert!(!miss.thin);
+        assert_eq!(miss.wall, vec2(5, 8));
+    }
+
+    #[test]
+    fn rays_record_windows_they_pass_through() {
+        let mut map = Array2::from_elem((7, 9), Tile::Floor);
+        for i in 0..9 {
+            map[(0, i)] = Tile::Wall;
+            map[(6, i)] = Tile::Wall;
+        }
+        for i in 0..7 {
+            map[(i, 0)] = Tile::Wall;
+            map[(i, 8)] = Tile::Wall;
+            // A pane one cell thick, and another two cells thick.
+            map[(i, 2)] = Tile::Window;
+            map[(i, 4)] = Tile::Window;
+            map[(i, 5)] = Tile::Window;
+        }
+        let world = ArrayWorld::from(map);
+
+        let hits = raycast_all(&world, vec2(1.5, 3.5), vec2(1.0, 0.0), 100.0, 0.0);
+
+        let walls = hits
+            .iter()
+            .map(|h| (h.wall.x, h.see_through))
+            .collect::<Vec<_>>();
+        assert_eq!(walls, [(2, true), (4, true), (8, false)]);
+        assert!(hits.iter().all(|h| h.wall_side == Direction::West));
+        let only = raycast(&world, vec2(1.5, 3.5), vec2(1.0, 0.0), 100.0).unwrap();
+        assert_eq!(only.wall.x, 8);
+    }
+
+    #[test]
+    fn rays_see_over_low_walls() {
+        let mut map = Array2::from_elem((5, 8), Tile::Floor);
+        map.column_mut(7).fill(Tile::Wall);
+        map.column_mut(3).fill(Tile::Wall);
+        let mut heights = Array2::from_elem(map.dim(), 1.0);
+        heights.column_mut(3).fill(0.3);
+        let world = ArrayWorld::from(map).with_heights(heights);
+
+        let over = raycast_all(&world, vec2(1.5, 2.5), vec2(1.0, 0.0), 100.0, 0.5);
+        let stopped = raycast_all(&world, vec2(1.5, 2.5), vec2(1.0, 0.0), 100.0, 0.0);
+
+        let walls = over
+            .iter()
+            .map(|h| (h.wall.x, h.height))
+            .collect::<Vec<_>>();
+        assert_eq!(walls, [(3, 0.3), (7, 1.0)]);
+        assert_eq!(stopped.len(), 1);
+        assert_eq!(stopped[0].wall.x, 3);
+    }
+
+    #[test]
+    fn fov_converts_to_and_from_plane_width() {
+        let (_, camera) = fov_camera();
+
+        let wide = camera.clone().with_fov_degrees(90.0);
+
+        assert_ulps_eq!(wide.projection_plane_width, 2.0);
+        assert_abs_diff_eq!(wide.fov_degrees(), 90.0, epsilon = 1e-4);
+        assert_abs_diff_eq!(camera.with_fov(1.2).fov(), 1.2, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn facing_does_not_need_to_be_a_unit_vector() {
+        let (world, camera) = fov_camera();
+        let long = CameraParams {
+            facing_unit: camera.facing_unit * 3.0,
+            ..camera.clone()
+        };
+        let zero = CameraParams {
+            facing_unit: vec2(0.0, 0.0),
+            ..camera.clone()
+        };
+
+        let hits = raycast_camera(&world, &camera);
+        let long_hits = raycast_camera(&world, &long);
+
+        for (a, b) in hits.iter().zip(&long_hits) {
+            let (a, b) = (a.hit.as_ref().unwrap(), b.hit.as_ref().unwrap());
+            assert_abs_diff_eq!(a.hit_pos, b.hit_pos, epsilon = 1e-4);
+            assert_abs_diff_eq!(
+                camera.perpendicular_distance(a),
+                long.perpendicular_distance(b),
+                epsilon = 1e-4
+            );
+        }
+        assert!(raycast_camera(&world, &zero)
+            .iter()
+            .all(|r| r.hit.is_none()));
+    }
+
+    fn fov_camera() -> (ArrayWorld, CameraParams) {
+        let mut map = Array2::from_elem((9, 9), Tile::Floor);
+        map.column_mut(8).fill(Tile::Wall);
+        map.column_mut(0).fill(Tile::Wall);
+        map.row_mut(0).fill(Tile::Wall);
+        map.row_mut(8).fill(Tile::Wall);
+        let camera = CameraParams {
+            pos: vec2(2.5, 4.5),
+            facing_unit: vec2(0.6, 0.8),
+            n_rays: 16,
+            max_dist: 100.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        (ArrayWorld::from(map), camera)
+    }
+
+    /// Open floor up to a wall along column 1500.
+    struct FarWall;
+
+    impl RaycastableWorld for FarWall {
+        fn cell(&self, (x, _): (isize, isize)) -> Option<CellInfo> {
+            Some(CellInfo {
+                solid: x == 1500,
+                ..CellInfo::default()
+            })
+        }
+    }
+
+    #[test]
+    fn long_rays_stay_on_course() {
+        let ray = vec2(1.0, 0.3);
+
+        let hit = raycast(FarWall, vec2(0.5, 0.5), ray, 1e4).unwrap();
+
+        assert_eq!(hit.wall_side, Direction::West);
+        assert_eq!(hit.hit_pos.x, 1500.0);
+        assert_abs_diff_eq!(hit.hit_pos.y, 0.5 + 1499.5 * 0.3, epsilon = 1e-2);
+        assert!(raycast(FarWall, vec2(0.5, 0.5), ray, 1000.0).is_none());
+    }
+
+    #[rstest]
+    #[case(example_world(), vec2(3.3, 2.6))]
+    #[case(diagonal_world(), vec2(2.5, 2.9))]
+    #[case(door_world(Direction::North), vec2(1.5, 1.3))]
+    #[case(example_world(), vec2(-2.5, 1.5))]
+    fn batches_match_single_rays(#[case] world: ArrayWorld, #[case] pos: Vector2<f32>) {
+        let rays = (0..37)
+            .map(|i| {
+                let angle = i as f32 * 0.17;
+                vec2(angle.cos(), angle.sin())
+            })
+            .chain([vec2(0.0, 0.0), vec2(f32::NAN, 1.0), vec2(1.0, 0.0)])
+            .collect::<Vec<_>>();
+
+        let hits = raycast_batch(&world, pos, &rays, 100.0);
+
+        assert_eq!(hits.len(), rays.len());
+        for (ray, hit) in rays.iter().zip(hits) {
+            assert_eq!(hit, raycast(&world, pos, *ray, 100.0));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn parallel_cameras_see_the_same() {
+        let params = CameraParams {
+            pos: vec2(3.3, 2.6),
+            facing_unit: vec2(1.0, 0.4),
+            n_rays: 1200,
+            max_dist: 100.0,
+            projection_plane_width: 1.5,
+            pitch: 0.0,
+        };
+
+        let hits = raycast_camera_par(example_world(), &params);
+
+        assert_eq!(hits, raycast_camera(example_world(), &params));
+    }
+
+    /// Solid everywhere west of x = 0.
+    struct WestWall;
+
+    impl RaycastableWorld for WestWall {
+        fn cell(&self, (x, _): (isize, isize)) -> Option<CellInfo> {
+            Some(CellInfo {
+                solid: x < 0,
+                ..CellInfo::default()
+            })
+        }
+    }
+
+    #[rstest]
+    #[case(vec2(0.5, 0.5), vec2(0.0, 0.0), 10.0, RaycastError::ZeroRay)]
+    #[case(vec2(0.5, 0.5), vec2(f32::NAN, 1.0), 10.0, RaycastError::NonFiniteRay)]
+    #[case(
+        vec2(0.5, 0.5),
+        vec2(1.0, f32::INFINITY),
+        10.0,
+        RaycastError::NonFiniteRay
+    )]
+    #[case(
+        vec2(f32::NAN, 0.5),
+        vec2(1.0, 0.0),
+        10.0,
+        RaycastError::NonFinitePosition
+    )]
+    #[case(vec2(0.5, 0.5), vec2(1.0, 0.0), f32::NAN, RaycastError::NanMaxDist)]
+    #[case(vec2(1e30, 0.5), vec2(1.0, 0.0), 10.0, RaycastError::OutOfRange)]
+    #[case(vec2(0.5, 0.5), vec2(-1.0, 0.0), 10.0, RaycastError::OutOfRange)]
+    fn degenerate_rays_are_errors(
+        #[case] pos: Vector2<f32>,
+        #[case] ray: Vector2<f32>,
+        #[case] max_dist: f32,
+        #[case] error: RaycastError,
+    ) {
+        assert_eq!(try_raycast(WestWall, pos, ray, max_dist), Err(error));
+        assert_eq!(raycast(WestWall, pos, ray, max_dist), None);
+        assert_eq!(raycast_batch(WestWall, pos, &[ray], max_dist), [None]);
+    }
+
+    #[test]
+    fn picking_finds_the_wall_or_target_in_the_middle_of_the_view() {
+        let world = example_world();
+        let camera = CameraParams {
+            pos: vec2(1.5, 2.5),
+            facing_unit: vec2(2.0, 0.0),
+            n_rays: 1,
+            max_dist: 20.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        let target = |x, y| PickTarget {
+            pos: vec2(x, y),
+            radius: 0.5,
+        };
+        // Behind the camera, off to the side, ahead, and nearer but behind
+        // the wall.
+        let targets = [
+            target(0.5, 2.5),
+            target(4.5, 4.0),
+            target(5.5, 2.7),
+            target(9.0, 2.5),
+        ];
+
+        let wall = pick(&world, &camera, &targets[..2]).unwrap();
+        let entity = pick(&world, &camera, &targets).unwrap();
+
+        assert_eq!(wall.cell, (8, 2));
+        assert_eq!(wall.entity, None);
+        assert_eq!(wall.face, Some(Direction::West));
+        assert_ulps_eq!(wall.distance, 6.5);
+        assert_eq!(entity.cell, (5, 2));
+        assert_eq!(entity.entity, Some(2));
+        assert_eq!(entity.face, None);
+        assert_abs_diff_eq!(entity.distance, 4.0 - 0.21f32.sqrt(), epsilon = 1e-5);
+        assert_eq!(
+            pick(
+                &world,
+                &CameraParams {
+                    max_dist: 2.0,
+                    ..camera
+                },
+                &targets
+            ),
+            None
+        );
     }
 }
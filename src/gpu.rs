@@ -0,0 +1,425 @@
+//! Drawing the first-person view of [`render`](crate::render) on the GPU, for
+//! resolutions too big to raycast column by column in software.
+//!
+//! The world's cells are uploaded once as a [`GpuWorld`], and each frame a
+//! compute shader casts a ray through every pixel against them.
+
+use std::{fmt, sync::mpsc};
+
+use image::{Rgb, RgbImage};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::{CameraParams, RaycastableWorld},
+    render::{material_color, Renderer, ViewParams},
+    util::Axis,
+};
+
+const SHADER: &str = include_str!("gpu.wgsl");
+
+/// Pixels drawn by each workgroup along each axis, as in the shader.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Flags of each uploaded cell, as in the shader.
+const SOLID: u32 = 1;
+const THIN: u32 = 2;
+const HORIZONTAL: u32 = 4;
+const DOOR: u32 = 8;
+
+#[derive(Debug)]
+pub enum GpuError {
+    /// No GPU, or software fallback, that can run compute shaders was found.
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+    /// A drawn frame couldn't be read back from the GPU.
+    Readback(wgpu::BufferAsyncError),
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuError::NoAdapter => write!(f, "no GPU adapter found"),
+            GpuError::Device(e) => write!(f, "couldn't open the GPU: {e}"),
+            GpuError::Readback(e) => write!(f, "couldn't read a frame back from the GPU: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+impl From<wgpu::RequestDeviceError> for GpuError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        GpuError::Device(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for GpuError {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        GpuError::Readback(e)
+    }
+}
+
+/// A GPU, ready to draw views of worlds uploaded to it.
+pub struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+/// The cells of a world, uploaded to the GPU by [`GpuRenderer::upload`].
+pub struct GpuWorld {
+    cells: wgpu::Buffer,
+    size: (u32, u32),
+}
+
+impl GpuRenderer {
+    /// Open the first GPU found that can run compute shaders, blocking until
+    /// it's ready.
+    pub fn new() -> Result<Self, GpuError> {
+        pollster::block_on(Self::open(wgpu::PowerPreference::HighPerformance, false))
+    }
+
+    /// Open a software GPU, which is slow but runs anywhere, such as in tests.
+    pub fn fallback() -> Result<Self, GpuError> {
+        pollster::block_on(Self::open(wgpu::PowerPreference::None, true))
+    }
+
+    async fn open(power: wgpu::PowerPreference, fallback: bool) -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: power,
+                force_fallback_adapter: fallback,
+                compatible_surface: None,
+            })
+            .await
+            .ok_or(GpuError::NoAdapter)?;
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return Err(GpuError::NoAdapter);
+        }
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("backrooms"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_defaults()
+                        .using_resolution(adapter.limits()),
+                    memory_hints: wgpu::MemoryHints::default(),
+                },
+                None,
+            )
+            .await?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("view"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("view"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+        })
+    }
+
+    /// Upload the cells of a `(w, h)` area of a world, starting at `(0, 0)`.
+    /// Cells outside the area are empty.
+    ///
+    /// Diagonal walls are uploaded as whole blocks, and see-through cells as
+    /// empty.
+    pub fn upload(&self, world: impl RaycastableWorld, (w, h): (usize, usize)) -> GpuWorld {
+        let mut cells = Vec::with_capacity(w * h * 4);
+        for y in 0..h {
+            for x in 0..w {
+                let cell = world.cell((x as isize, y as isize)).unwrap_or_default();
+                let mut flags = if cell.solid { SOLID } else { 0 };
+                let mut offset = 0.0_f32;
+                if let Some(thin) = cell.thin {
+                    flags |= THIN;
+                    if thin.axis == Axis::Horizontal {
+                        flags |= HORIZONTAL;
+                    }
+                    if thin.door {
+                        flags |= DOOR;
+                    }
+                    offset = thin.offset;
+                }
+                cells.extend([
+                    pack(material_color(cell.material)),
+                    flags,
+                    cell.height.to_bits(),
+                    offset.to_bits(),
+                ]);
+            }
+        }
+        // Empty buffers can't be bound, so a world with no cells gets an empty
+        // one that's never read.
+        if cells.is_empty() {
+            cells.extend([0; 4]);
+        }
+
+        let cells = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("cells"),
+                contents: &bytes(&cells),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        GpuWorld {
+            cells,
+            size: (w as u32, h as u32),
+        }
+    }
+
+    /// Draw a first-person view of an uploaded world, as
+    /// [`render_view`](crate::render::render_view) draws the hits of
+    /// [`raycast_camera`](crate::camera::raycast_camera) in software. Walls,
+    /// doors, the floor and the ceiling are drawn in flat colors, and the
+    /// floor and ceiling always carry on to the horizon, whatever the
+    /// [`void`](ViewParams::void).
+    pub fn render_view(
+        &self,
+        world: &GpuWorld,
+        camera: &CameraParams,
+        params: &ViewParams,
+    ) -> Result<RgbImage, GpuError> {
+        let (w, h) = (params.width, params.height);
+        if w == 0 || h == 0 {
+            return Ok(RgbImage::new(w, h));
+        }
+        let size = w as u64 * h as u64 * 4;
+
+        let uniforms = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: &bytes(&uniforms(world, camera, params)),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let pixels = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pixels"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("view"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: world.cells.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: pixels.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("view"),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("view"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(w.div_ceil(WORKGROUP_SIZE), h.div_ceil(WORKGROUP_SIZE), 1);
+        }
+        encoder.copy_buffer_to_buffer(&pixels, 0, &readback, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .unwrap_or(Err(wgpu::BufferAsyncError))
+            .map_err(GpuError::from)?;
+
+        let data = slice.get_mapped_range();
+        let image = RgbImage::from_fn(w, h, |x, y| {
+            let i = (y * w + x) as usize * 4;
+            Rgb([data[i], data[i + 1], data[i + 2]])
+        });
+        drop(data);
+        readback.unmap();
+        Ok(image)
+    }
+}
+
+/// A [`GpuRenderer`] drawing views of uploaded worlds with the same
+/// [`ViewParams`] each frame.
+pub struct GpuBackend {
+    pub gpu: GpuRenderer,
+    pub view: ViewParams,
+}
+
+impl Renderer<&GpuWorld> for GpuBackend {
+    type Frame = RgbImage;
+    type Error = GpuError;
+
+    fn render(
+        &mut self,
+        world: &GpuWorld,
+        camera: &CameraParams,
+        time: f32,
+    ) -> Result<RgbImage, GpuError> {
+        let view = ViewParams {
+            time,
+            ..self.view.clone()
+        };
+        self.gpu.render_view(world, camera, &view)
+    }
+}
+
+/// The shader's `Params`, word by word.
+fn uniforms(world: &GpuWorld, camera: &CameraParams, params: &ViewParams) -> [u32; 24] {
+    let facing = camera.facing();
+    let focal = params.width as f32 / camera.projection_plane_width;
+    let horizon = params.height as f32 / 2.0 + camera.pitch.tan() * focal;
+    let (roll_sin, roll_cos) = params.roll.sin_cos();
+    let f = f32::to_bits;
+    [
+        f(camera.pos.x),
+        f(camera.pos.y),
+        f(facing.x),
+        f(facing.y),
+        f(camera.projection_plane_width),
+        f(camera.max_dist),
+        f(horizon),
+        f(focal),
+        f(params.eye_height),
+        f(1.0 / params.fog_distance),
+        f(roll_sin),
+        f(roll_cos),
+        f(params.face_light[0]),
+        f(params.face_light[1]),
+        f(params.face_light[2]),
+        f(params.face_light[3]),
+        params.width,
+        params.height,
+        world.size.0,
+        world.size.1,
+        pack(params.ceiling),
+        pack(params.floor),
+        pack(params.door),
+        0,
+    ]
+}
+
+/// A color as the shader stores it, red in the lowest byte.
+fn pack(Rgb([r, g, b]): Rgb<u8>) -> u32 {
+    u32::from_le_bytes([r, g, b, 0])
+}
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec2;
+    use ndarray::Array2;
+
+    use crate::{
+        camera::raycast_camera,
+        render::{render_view, Void, FACE_LIGHT},
+        tile::Tile,
+        util::Direction,
+        world::ArrayWorld,
+        worldgen::doors::Door,
+    };
+
+    use super::*;
+
+    #[test]
+    fn gpu_views_match_software_views() {
+        let gpu = match GpuRenderer::fallback() {
+            Ok(gpu) => gpu,
+            Err(e) => {
+                eprintln!("skipping, {e}");
+                return;
+            }
+        };
+        let mut map = Array2::from_elem((8, 10), Tile::Floor);
+        map.row_mut(0).fill(Tile::Wall);
+        map.row_mut(7).fill(Tile::Wall);
+        map.column_mut(9).fill(Tile::Wall);
+        map[(3, 5)] = Tile::Wall;
+        let world = ArrayWorld::from(map).with_doors(vec![Door::new(4, 4, Direction::East)]);
+        let params = ViewParams {
+            width: 96,
+            height: 64,
+            ceiling: Rgb([10, 10, 10]),
+            floor: Rgb([60, 40, 20]),
+            door: Rgb([150, 90, 40]),
+            eye_height: 0.5,
+            face_light: FACE_LIGHT,
+            fog_distance: 12.0,
+            roll: 0.0,
+            time: 0.0,
+            void: Void::Horizon,
+        };
+        let camera = CameraParams {
+            pos: vec2(1.5, 4.2),
+            facing_unit: vec2(1.0, -0.3),
+            n_rays: 96,
+            max_dist: 100.0,
+            projection_plane_width: 1.2,
+            pitch: 0.1,
+        };
+        let hits = raycast_camera(&world, &camera);
+        assert!(hits
+            .iter()
+            .any(|ray| ray.hit.as_ref().is_some_and(|hit| hit.door)));
+        let uploaded = gpu.upload(&world, (10, 8));
+
+        for roll in [0.0, 0.3] {
+            let params = ViewParams {
+                roll,
+                ..params.clone()
+            };
+            let frame = gpu.render_view(&uploaded, &camera, &params).unwrap();
+            let expected = render_view(&hits, &camera, &params);
+
+            // Rounding differs a little between the GPU and the CPU, so the
+            // odd channel may be off by one and the odd pixel land on the
+            // other side of an edge.
+            let differing = frame
+                .pixels()
+                .zip(expected.pixels())
+                .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > 1))
+                .count();
+            assert!(differing < 40, "{differing} pixels differ");
+        }
+    }
+}
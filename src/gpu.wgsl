@@ -0,0 +1,227 @@
+// The first-person view of `render::render_view`, one invocation per pixel.
+// Each pixel casts the ray of its column through the grid of cells, and is
+// drawn as the wall that ray hits if it covers the pixel, or as the floor or
+// ceiling if not.
+
+struct Params {
+    pos: vec2<f32>,
+    facing: vec2<f32>,
+    plane_width: f32,
+    max_dist: f32,
+    horizon: f32,
+    // Pixels per unit on the projection plane.
+    focal: f32,
+    eye_height: f32,
+    // One over the fog distance, so 0 if there's no fog.
+    fog_scale: f32,
+    roll_sin: f32,
+    roll_cos: f32,
+    face_light: vec4<f32>,
+    size: vec2<u32>,
+    grid: vec2<u32>,
+    ceiling: u32,
+    floor: u32,
+    door: u32,
+}
+
+struct Cell {
+    // Flat color of the material, red in the lowest byte.
+    color: u32,
+    flags: u32,
+    height: f32,
+    // How far across the cell a thin wall stands.
+    offset: f32,
+}
+
+struct Hit {
+    found: bool,
+    pos: vec2<f32>,
+    side: u32,
+    cell: Cell,
+}
+
+const SOLID: u32 = 1u;
+const THIN: u32 = 2u;
+const HORIZONTAL: u32 = 4u;
+const DOOR: u32 = 8u;
+
+// Indices of `Direction`.
+const EAST: u32 = 0u;
+const NORTH: u32 = 1u;
+const WEST: u32 = 2u;
+const SOUTH: u32 = 3u;
+
+const EPSILON: f32 = 1.1920929e-7;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> cells: array<Cell>;
+@group(0) @binding(2) var<storage, read_write> pixels: array<u32>;
+
+fn cell_at(c: vec2<i32>) -> Cell {
+    if c.x < 0 || c.y < 0 || u32(c.x) >= params.grid.x || u32(c.y) >= params.grid.y {
+        return Cell(0u, 0u, 0.0, 0.0);
+    }
+    return cells[u32(c.y) * params.grid.x + u32(c.x)];
+}
+
+fn miss() -> Hit {
+    return Hit(false, vec2<f32>(0.0), 0u, Cell(0u, 0u, 0.0, 0.0));
+}
+
+// As `raycast_thin`: the hit on the thin wall in a cell, from `pos` relative
+// to the cell.
+fn raycast_thin(cell: Cell, pos: vec2<f32>, ray: vec2<f32>) -> Hit {
+    let horizontal = (cell.flags & HORIZONTAL) != 0u;
+    var p = pos;
+    var r = ray;
+    if horizontal {
+        p = pos.yx;
+        r = ray.yx;
+    }
+    if r.x == 0.0 {
+        return miss();
+    }
+    let t = (cell.offset - p.x) / r.x;
+    if t < 0.0 {
+        return miss();
+    }
+    var hit = p + r * t;
+    if hit.y < -EPSILON || hit.y > 1.0 + EPSILON {
+        return miss();
+    }
+    var side = select(EAST, WEST, r.x > 0.0);
+    if horizontal {
+        hit = hit.yx;
+        side = select(NORTH, SOUTH, r.x > 0.0);
+    }
+    return Hit(true, hit, side, cell);
+}
+
+// As `march`, stopping at the first thin or solid wall.
+fn raycast(pos: vec2<f32>, ray: vec2<f32>) -> Hit {
+    var cell = vec2<i32>(floor(pos));
+    let start = cell_at(cell);
+    if (start.flags & THIN) != 0u {
+        let hit = raycast_thin(start, pos - vec2<f32>(cell), ray);
+        if hit.found {
+            return Hit(true, hit.pos + vec2<f32>(cell), hit.side, start);
+        }
+    }
+
+    // Nothing is hit after the ray leaves the grid, so stop there if it's
+    // before the maximum distance.
+    let grid = vec2<f32>(params.grid);
+    let exits = max(-pos / ray, (grid - pos) / ray);
+    let max_t = min(params.max_dist / length(ray), min(exits.x, exits.y));
+
+    let step = vec2<i32>(sign(ray));
+    let corner = floor(pos) + max(sign(ray), vec2<f32>(0.0));
+    var next_t = select(vec2<f32>(1e30), (corner - pos) / ray, ray != vec2<f32>(0.0));
+    let delta_t = select(vec2<f32>(1e30), abs(1.0 / ray), ray != vec2<f32>(0.0));
+
+    loop {
+        var t: f32;
+        var side: u32;
+        var hit_pos: vec2<f32>;
+        if next_t.x < next_t.y {
+            t = next_t.x;
+            next_t.x += delta_t.x;
+            cell.x += step.x;
+            side = select(EAST, WEST, step.x > 0);
+            hit_pos = pos + ray * t;
+            hit_pos.x = f32(select(cell.x + 1, cell.x, step.x > 0));
+        } else {
+            t = next_t.y;
+            next_t.y += delta_t.y;
+            cell.y += step.y;
+            side = select(NORTH, SOUTH, step.y > 0);
+            hit_pos = pos + ray * t;
+            hit_pos.y = f32(select(cell.y + 1, cell.y, step.y > 0));
+        }
+        if !(t <= max_t) {
+            return miss();
+        }
+
+        let probe = cell_at(cell);
+        if (probe.flags & THIN) != 0u {
+            let offset = vec2<f32>(cell);
+            let hit = raycast_thin(probe, hit_pos - offset, ray);
+            if hit.found {
+                return Hit(true, hit.pos + offset, hit.side, probe);
+            }
+        }
+        if (probe.flags & SOLID) != 0u {
+            return Hit(true, hit_pos, side, probe);
+        }
+    }
+    return miss();
+}
+
+fn unpack(c: u32) -> vec3<f32> {
+    return vec3<f32>(f32(c & 255u), f32((c >> 8u) & 255u), f32((c >> 16u) & 255u));
+}
+
+// As `render::shade`, rounding down.
+fn shade(color: vec3<f32>, k: f32) -> vec3<f32> {
+    return floor(color * k);
+}
+
+fn fog(color: vec3<f32>, dist: f32) -> vec3<f32> {
+    if params.fog_scale == 0.0 {
+        return color;
+    }
+    return shade(color, clamp(1.0 - dist * params.fog_scale, 0.0, 1.0));
+}
+
+fn draw(x: u32, y: u32) -> vec3<f32> {
+    let left = vec2<f32>(params.facing.y, -params.facing.x);
+    let offset = params.plane_width * (0.5 - f32(x) / f32(params.size.x));
+    let ray = params.facing + left * offset;
+
+    let hit = raycast(params.pos, ray);
+    if hit.found {
+        let dist = dot(hit.pos - params.pos, params.facing);
+        let scale = params.focal / max(dist, 1e-3);
+        let bottom = params.horizon + params.eye_height * scale;
+        let top = bottom - hit.cell.height * scale;
+        let y0 = u32(max(top, 0.0));
+        let y1 = u32(clamp(bottom, 0.0, f32(params.size.y)));
+        if y >= y0 && y < y1 {
+            var color = unpack(hit.cell.color);
+            if (hit.cell.flags & DOOR) != 0u {
+                color = unpack(params.door);
+            }
+            return fog(shade(color, params.face_light[hit.side]), dist);
+        }
+    }
+
+    let from_horizon = f32(y) + 0.5 - params.horizon;
+    if from_horizon < 0.0 {
+        let dist = (1.0 - params.eye_height) * params.focal / -from_horizon;
+        return fog(unpack(params.ceiling), dist);
+    }
+    let dist = params.eye_height * params.focal / from_horizon;
+    return fog(unpack(params.floor), dist);
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.size.x || id.y >= params.size.y {
+        return;
+    }
+    // As `render::roll`, drawing whichever pixel of the level view ends up
+    // here once the view is rolled.
+    let size = vec2<f32>(params.size);
+    let middle = size / 2.0;
+    let d = vec2<f32>(id.xy) + 0.5 - middle;
+    let source = clamp(
+        middle + vec2<f32>(
+            d.x * params.roll_cos - d.y * params.roll_sin,
+            d.x * params.roll_sin + d.y * params.roll_cos,
+        ),
+        vec2<f32>(0.0),
+        size - 1.0,
+    );
+    let c = vec3<u32>(draw(u32(source.x), u32(source.y)));
+    pixels[id.y * params.size.x + id.x] = c.x | (c.y << 8u) | (c.z << 16u);
+}
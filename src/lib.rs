@@ -1,4 +1,33 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod camera;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(feature = "std")]
+pub mod lighting;
+pub mod math;
+#[cfg(feature = "render")]
+pub mod minimap;
+#[cfg(feature = "render")]
+pub mod overview;
+#[cfg(feature = "std")]
+pub mod player;
+#[cfg(feature = "render")]
+pub mod postprocess;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+#[cfg(feature = "render")]
+pub mod textures;
+pub mod tile;
 pub mod util;
+#[cfg(feature = "window")]
+pub mod window;
+#[cfg(feature = "std")]
 pub mod world;
 pub mod worldgen;
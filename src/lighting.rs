@@ -0,0 +1,229 @@
+//! How brightly lit each point of a world is by its ceiling [`Light`]s, worked
+//! out as needed by [`Lighting`] or baked ahead of time into a [`Lightmap`].
+
+use auto_impl::auto_impl;
+use cgmath::{vec2, MetricSpace, Vector2};
+use ndarray::Array2;
+
+use crate::{
+    camera::{raycast, RaycastableWorld},
+    world::Light,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightingParams {
+    /// Brightness of places no light reaches, in [0, 1].
+    pub ambient: f32,
+
+    /// How far each light reaches, in tiles. A light's brightness falls off
+    /// smoothly to nothing at this distance.
+    pub radius: f32,
+
+    /// Whether walls cast shadows, by raycasting from each lit point to every
+    /// light in reach of it.
+    pub shadows: bool,
+}
+
+/// Anything that can say how brightly lit a point in the world is.
+#[auto_impl(&, Box)]
+pub trait LightSource {
+    /// How brightly lit a point is, in [0, 1].
+    fn light_at(&self, pos: Vector2<f32>) -> f32;
+}
+
+/// The lights of a world at a moment in time.
+#[derive(Debug, Clone)]
+pub struct Lighting<'a, W> {
+    /// The world the lights are in, for casting shadows.
+    pub world: W,
+
+    /// The lights, positioned by the cell they hang in, as passed to
+    /// [`RaycastableWorld::cell`].
+    pub lights: &'a [Light],
+
+    pub params: LightingParams,
+
+    /// The time in seconds, for the brightness of flickering lights.
+    pub time: f32,
+}
+
+impl<W: RaycastableWorld> Lighting<'_, W> {
+    /// How brightly lit a point is, in [0, 1]: the ambient light, plus the
+    /// light from each light in reach, falling off with distance.
+    pub fn at(&self, pos: Vector2<f32>) -> f32 {
+        self.sum(pos, |light| light.brightness(self.time))
+    }
+
+    /// The ambient light plus the light from each light in reach of a point,
+    /// shining as brightly as `brightness` says.
+    fn sum(&self, pos: Vector2<f32>, brightness: impl Fn(&Light) -> f32) -> f32 {
+        let radius = self.params.radius;
+        let light = self
+            .lights
+            .iter()
+            .filter_map(|light| {
+                let center = vec2(light.x as f32 + 0.5, light.y as f32 + 0.5);
+                let dist = pos.distance(center);
+                if dist >= radius || (self.params.shadows && self.blocked(pos, center, dist)) {
+                    return None;
+                }
+                let falloff = 1.0 - (dist / radius).powi(2);
+                Some(brightness(light) * falloff * falloff)
+            })
+            .sum::<f32>();
+        (self.params.ambient + light).clamp(0.0, 1.0)
+    }
+
+    /// Whether a wall stands between a point and a light `dist` away.
+    fn blocked(&self, pos: Vector2<f32>, light: Vector2<f32>, dist: f32) -> bool {
+        dist > 0.0 && raycast(&self.world, pos, light - pos, dist).is_some()
+    }
+
+    /// Work out how brightly the middle of each open cell of a `(w, h)` area
+    /// is lit, with every light shining steadily at its full intensity.
+    /// Solid cells are only lit by the ambient light.
+    pub fn bake(&self, (w, h): (usize, usize)) -> Lightmap {
+        let light = Array2::from_shape_fn((h, w), |(y, x)| {
+            let cell = (x as isize, y as isize);
+            if self.world.cell(cell).is_none_or(|c| c.solid) {
+                return self.params.ambient;
+            }
+            let center = vec2(x as f32 + 0.5, y as f32 + 0.5);
+            self.sum(center, |light| light.intensity)
+        });
+        Lightmap {
+            light,
+            ambient: self.params.ambient,
+        }
+    }
+}
+
+impl<W: RaycastableWorld> LightSource for Lighting<'_, W> {
+    fn light_at(&self, pos: Vector2<f32>) -> f32 {
+        self.at(pos)
+    }
+}
+
+/// How brightly lit each cell of a world is, worked out once by
+/// [`Lighting::bake`] so it costs nothing to look up while drawing.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lightmap {
+    /// Light of each cell, indexed by `(y, x)`.
+    light: Array2<f32>,
+
+    /// Light of cells off the map.
+    ambient: f32,
+}
+
+impl Lightmap {
+    /// How brightly lit a cell is, in [0, 1].
+    pub fn cell(&self, (x, y): (isize, isize)) -> f32 {
+        if x < 0 || y < 0 {
+            return self.ambient;
+        }
+        self.light
+            .get((y as usize, x as usize))
+            .copied()
+            .unwrap_or(self.ambient)
+    }
+}
+
+impl LightSource for Lightmap {
+    /// The light of the cell the point is in.
+    fn light_at(&self, pos: Vector2<f32>) -> f32 {
+        self.cell((pos.x.floor() as isize, pos.y.floor() as isize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use crate::{
+        tile::Tile,
+        world::{ArrayWorld, FlickerCurve},
+    };
+
+    use super::*;
+
+    fn lighting(world: &ArrayWorld, lights: &[Light], shadows: bool) -> f32 {
+        let lighting = Lighting {
+            world,
+            lights,
+            params: LightingParams {
+                ambient: 0.1,
+                radius: 4.0,
+                shadows,
+            },
+            time: 0.0,
+        };
+        lighting.at(vec2(1.5, 2.5))
+    }
+
+    fn light(x: isize, y: isize) -> Light {
+        Light {
+            x,
+            y,
+            intensity: 0.5,
+            flicker: 0.0,
+            phase: 0.0,
+            curve: FlickerCurve::Buzz,
+        }
+    }
+
+    #[test]
+    fn light_falls_off_with_distance() {
+        let world = ArrayWorld::from(Array2::from_elem((8, 8), Tile::Floor));
+
+        let under = lighting(&world, &[light(1, 2)], true);
+        let near = lighting(&world, &[light(3, 2)], true);
+        let far = lighting(&world, &[light(6, 2)], true);
+
+        assert_eq!(under, 0.6);
+        assert!(under > near && near > far);
+        assert_eq!(far, 0.1);
+        assert_eq!(lighting(&world, &[light(1, 2); 4], true), 1.0);
+    }
+
+    #[test]
+    fn baked_light_matches_the_middle_of_each_cell() {
+        let mut map = Array2::from_elem((6, 8), Tile::Floor);
+        map.column_mut(4).fill(Tile::Wall);
+        let world = ArrayWorld::from(map);
+        let lights = [Light {
+            flicker: 1.0,
+            ..light(2, 2)
+        }];
+        let lighting = Lighting {
+            world: &world,
+            lights: &lights,
+            params: LightingParams {
+                ambient: 0.1,
+                radius: 4.0,
+                shadows: true,
+            },
+            time: 0.0,
+        };
+
+        let lightmap = lighting.bake((8, 6));
+
+        assert_eq!(lightmap.cell((2, 2)), 0.6);
+        assert_eq!(lightmap.light_at(vec2(3.9, 2.1)), lightmap.cell((3, 2)));
+        assert!(lightmap.cell((3, 2)) > lightmap.cell((3, 4)));
+        assert_eq!(lightmap.cell((5, 2)), 0.1);
+        assert_eq!(lightmap.cell((4, 2)), 0.1);
+        assert_eq!(lightmap.cell((-1, 2)), 0.1);
+    }
+
+    #[test]
+    fn walls_cast_shadows() {
+        let mut map = Array2::from_elem((8, 8), Tile::Floor);
+        map.column_mut(2).fill(Tile::Wall);
+        let world = ArrayWorld::from(map);
+
+        assert_eq!(lighting(&world, &[light(3, 2)], true), 0.1);
+        assert!(lighting(&world, &[light(3, 2)], false) > 0.1);
+    }
+}
@@ -1,18 +1,31 @@
 use backrooms::{
-    util::{Axis, Rectangle, Line},
+    util::{named_seed, Rectangle},
     worldgen::{
-        hallways::{rbsp, RbspParams},
+        connectivity::connect_regions,
+        doors::place_doors,
+        furnish::{furnish, FurnishParams},
+        hallways::{rbsp, OffsetDistribution, PerimeterRing, RbspParams},
+        loops::{insert_loops, LoopParams},
+        merge::merge_rooms,
+        placement::pick_spawn,
+        raster::rasterize,
         render_to_img,
+        shapes::{shape_rooms, ShapeParams},
+        stats::WorldStats,
     },
 };
-use ndarray::Array2;
 use rand::{rngs::SmallRng, SeedableRng};
 
 pub fn main() {
-    // let mut rng = SmallRng::seed_from_u64(10);
-    let mut rng = SmallRng::from_entropy();
-    let (rooms, lines) = rbsp(
-        &mut rng,
+    let seed = std::env::args()
+        .nth(1)
+        .map(|s| s.parse().expect("seed must be an integer"))
+        .unwrap_or_else(rand::random);
+    println!("seed: {seed}");
+    let rng = |name| SmallRng::seed_from_u64(named_seed(seed, name));
+
+    let tree = rbsp(
+        &mut rng("rbsp"),
         Rectangle {
             x: 0,
             y: 0,
@@ -20,26 +33,57 @@ pub fn main() {
             h: 512,
         },
         RbspParams {
-            min_room_len: 5,
-            max_room_len: 80,
-            p_keep_rooms: 0.3,
-            k_deoblongification: 5.0,
+            hallway_width: 2,
+            offset_distribution: OffsetDistribution::Triangular,
+            perimeter_ring: PerimeterRing::Outer,
+            ..Default::default()
+        },
+    );
+    let tree = merge_rooms(&mut rng("merge"), tree, 0.15);
+    let (rooms, lines) = shape_rooms(
+        &mut rng("shapes"),
+        &tree,
+        &ShapeParams {
+            p_l: 0.15,
+            p_t: 0.1,
+            min_arm: 6,
         },
     );
 
-    let mut a = Array2::zeros((512, 512)).map(|_: &i32| true);
-    for h in lines {
-        draw_hallway(&mut a, h)
+    let doors = place_doors(&mut rng("doors"), &rooms, &lines);
+
+    let mut a = rasterize((512, 512), &rooms, &lines, &doors);
+    // Maps too small to split have no hallways, and may have no rooms either.
+    let spawn = pick_spawn(&a, &rooms).or_else(|| {
+        let l = lines.first()?;
+        Some((l.x as usize, l.y as usize))
+    });
+    if let Some(spawn) = spawn {
+        connect_regions(&mut a, spawn);
     }
+    insert_loops(
+        &mut rng("loops"),
+        &mut a,
+        &LoopParams {
+            loop_factor: 0.3,
+            max_wall_thickness: 1,
+        },
+    );
+    furnish(
+        &mut rng("furnish"),
+        &mut a,
+        &rooms,
+        &FurnishParams {
+            min_area: 400,
+            p_cubicles: 0.5,
+            cubicle_size: 5,
+            prop_density: 5.0,
+            margin: 2,
+        },
+    );
+
+    println!("{}", WorldStats::new(&a, &rooms, &lines));
 
     let img = render_to_img(&a);
     img.save("test.png").unwrap();
 }
-
-pub fn draw_hallway(a: &mut Array2<bool>, l: Line) {
-    for pos in l.points() {
-        if let Some(c) = a.get_mut((pos.0 as usize, pos.1 as usize)) {
-            *c = false
-        }
-    }
-}
@@ -0,0 +1,198 @@
+//! Vectors and float math for the parts of the crate that build without `std`.
+//!
+//! With `std` these are cgmath's. cgmath needs `std`, so without it they're a
+//! stand-in covering just what the core uses. Likewise the float functions are
+//! the standard library's with `std`, and libm's without it.
+
+#[cfg(feature = "std")]
+pub use cgmath::{vec2, BaseNum, InnerSpace, MetricSpace, One, Vector2, Zero};
+
+#[cfg(not(feature = "std"))]
+pub use self::vector::*;
+
+#[cfg(feature = "std")]
+pub fn floor(x: f32) -> f32 {
+    x.floor()
+}
+
+#[cfg(feature = "std")]
+pub fn sqrt(x: f32) -> f32 {
+    x.sqrt()
+}
+
+#[cfg(feature = "std")]
+pub fn powf(x: f32, n: f32) -> f32 {
+    x.powf(n)
+}
+
+#[cfg(feature = "std")]
+pub fn tan(x: f32) -> f32 {
+    x.tan()
+}
+
+#[cfg(feature = "std")]
+pub fn atan(x: f32) -> f32 {
+    x.atan()
+}
+
+#[cfg(not(feature = "std"))]
+pub use libm::{atanf as atan, floorf as floor, powf, sqrtf as sqrt, tanf as tan};
+
+#[cfg(not(feature = "std"))]
+mod vector {
+    use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+
+    use num_traits::{Num, NumCast};
+    pub use num_traits::{One, Zero};
+
+    /// A number vectors and rectangles can be made of.
+    pub trait BaseNum: Copy + PartialOrd + Num + NumCast {}
+
+    impl<T: Copy + PartialOrd + Num + NumCast> BaseNum for T {}
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct Vector2<S> {
+        pub x: S,
+        pub y: S,
+    }
+
+    pub const fn vec2<S>(x: S, y: S) -> Vector2<S> {
+        Vector2 { x, y }
+    }
+
+    impl<S> Vector2<S> {
+        pub const fn new(x: S, y: S) -> Self {
+            vec2(x, y)
+        }
+
+        pub fn map<U>(self, mut f: impl FnMut(S) -> U) -> Vector2<U> {
+            vec2(f(self.x), f(self.y))
+        }
+    }
+
+    impl<S: NumCast + Copy> Vector2<S> {
+        /// Convert each component, or `None` if either doesn't fit.
+        pub fn cast<T: NumCast>(&self) -> Option<Vector2<T>> {
+            Some(vec2(T::from(self.x)?, T::from(self.y)?))
+        }
+    }
+
+    impl<S: Add<Output = S>> Add for Vector2<S> {
+        type Output = Self;
+
+        fn add(self, rhs: Self) -> Self {
+            vec2(self.x + rhs.x, self.y + rhs.y)
+        }
+    }
+
+    impl<S: AddAssign> AddAssign for Vector2<S> {
+        fn add_assign(&mut self, rhs: Self) {
+            self.x += rhs.x;
+            self.y += rhs.y;
+        }
+    }
+
+    impl<S: Sub<Output = S>> Sub for Vector2<S> {
+        type Output = Self;
+
+        fn sub(self, rhs: Self) -> Self {
+            vec2(self.x - rhs.x, self.y - rhs.y)
+        }
+    }
+
+    impl<S: Neg<Output = S>> Neg for Vector2<S> {
+        type Output = Self;
+
+        fn neg(self) -> Self {
+            vec2(-self.x, -self.y)
+        }
+    }
+
+    impl<S: Mul<Output = S> + Copy> Mul<S> for Vector2<S> {
+        type Output = Self;
+
+        fn mul(self, rhs: S) -> Self {
+            vec2(self.x * rhs, self.y * rhs)
+        }
+    }
+
+    impl Mul<Vector2<f32>> for f32 {
+        type Output = Vector2<f32>;
+
+        fn mul(self, rhs: Vector2<f32>) -> Vector2<f32> {
+            rhs * self
+        }
+    }
+
+    impl<S: Div<Output = S> + Copy> Div<S> for Vector2<S> {
+        type Output = Self;
+
+        fn div(self, rhs: S) -> Self {
+            vec2(self.x / rhs, self.y / rhs)
+        }
+    }
+
+    impl<S> From<(S, S)> for Vector2<S> {
+        fn from((x, y): (S, S)) -> Self {
+            vec2(x, y)
+        }
+    }
+
+    impl<S> From<Vector2<S>> for (S, S) {
+        fn from(v: Vector2<S>) -> Self {
+            (v.x, v.y)
+        }
+    }
+
+    /// Lengths and angles, for vectors of `f32`.
+    pub trait InnerSpace: Copy {
+        fn dot(self, other: Self) -> f32;
+
+        fn magnitude2(self) -> f32 {
+            self.dot(self)
+        }
+
+        fn magnitude(self) -> f32 {
+            super::sqrt(self.magnitude2())
+        }
+
+        fn normalize(self) -> Self;
+    }
+
+    impl InnerSpace for Vector2<f32> {
+        fn dot(self, other: Self) -> f32 {
+            self.x * other.x + self.y * other.y
+        }
+
+        fn normalize(self) -> Self {
+            self * (1.0 / self.magnitude())
+        }
+    }
+
+    /// Distances between points, for vectors of `f32`.
+    pub trait MetricSpace: InnerSpace + Sub<Output = Self> {
+        fn distance(self, other: Self) -> f32 {
+            (other - self).magnitude()
+        }
+    }
+
+    impl MetricSpace for Vector2<f32> {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors_measure_like_cgmath() {
+        let v = vec2(3.0f32, 4.0);
+
+        assert_eq!(v.magnitude(), 5.0);
+        assert_eq!(v.normalize(), vec2(0.6, 0.8));
+        assert_eq!(vec2(1.0f32, 1.0).distance(vec2(4.0, 5.0)), 5.0);
+        assert_eq!(
+            vec2(-1.5f32, 2.5).map(floor).cast::<isize>(),
+            Some(vec2(-2, 2))
+        );
+    }
+}
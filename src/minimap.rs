@@ -0,0 +1,243 @@
+//! A small top-down map of the world around the camera, for frontends to draw
+//! into a corner of the first-person view.
+
+use cgmath::{vec2, InnerSpace, Vector2};
+use image::{ImageBuffer, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    camera::{CameraParams, CameraRay},
+    world::ArrayWorld,
+    worldgen::{labels::RoomId, node_color},
+};
+
+#[derive(Debug, Clone)]
+pub struct MinimapParams {
+    /// Size of the minimap, in pixels. The camera is always in the middle.
+    pub width: u32,
+    pub height: u32,
+
+    /// Pixels per tile.
+    pub scale: u32,
+
+    /// How far the cone showing what the camera can see reaches, in tiles.
+    pub cone_length: f32,
+
+    pub cone_color: Rgb<u8>,
+    pub camera_color: Rgb<u8>,
+
+    /// Color of cells that haven't been explored, or are off the map.
+    pub unexplored: Rgb<u8>,
+}
+
+/// How much of the color of the cells below it the facing cone lets through.
+const CONE_ALPHA: f32 = 0.4;
+
+/// Which cells of a world have been seen, for hiding the rest of the minimap.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Explored {
+    /// Indexed by `(y, x)`, as the world's map is.
+    seen: Array2<bool>,
+}
+
+impl Explored {
+    /// Nothing explored yet, in a world the size of `world`.
+    pub fn new(world: &ArrayWorld) -> Self {
+        Self {
+            seen: Array2::from_elem(world.map().dim(), false),
+        }
+    }
+
+    pub fn is_explored(&self, (x, y): (isize, isize)) -> bool {
+        x >= 0 && y >= 0 && self.seen.get((y as usize, x as usize)) == Some(&true)
+    }
+
+    pub fn reveal(&mut self, (x, y): (isize, isize)) {
+        if x < 0 || y < 0 {
+            return;
+        }
+        if let Some(seen) = self.seen.get_mut((y as usize, x as usize)) {
+            *seen = true;
+        }
+    }
+
+    /// Reveal every cell the rays cast for `camera` crossed, and the walls
+    /// they hit. Rays that hit nothing reveal cells out to the camera's
+    /// `max_dist`.
+    pub fn reveal_view(&mut self, camera: &CameraParams, rays: &[CameraRay]) {
+        let cell = |p: Vector2<f32>| (p.x.floor() as isize, p.y.floor() as isize);
+        for ray in rays {
+            let dist = match &ray.hit {
+                Some(hit) => {
+                    let wall = hit.wall.cast().unwrap_or(vec2(-1, -1));
+                    self.reveal(wall.into());
+                    hit.distance
+                }
+                None => camera.max_dist,
+            };
+            // Stop at the edge of the map if there's no maximum distance.
+            let (h, w) = self.seen.dim();
+            let dist = dist.min((w + h) as f32);
+            let dir = ray.ray.normalize();
+            // Quarter-tile steps can cut the corner of a cell, but never miss
+            // one the ray passes through the middle of.
+            let steps = (dist * 4.0) as usize;
+            for i in 0..=steps {
+                self.reveal(cell(camera.pos + dir * (i as f32 / 4.0)));
+            }
+        }
+    }
+}
+
+/// Draw the map around the camera from above, with the camera as a dot in
+/// the middle and a cone showing which way it faces.
+///
+/// Cells are drawn in their [tile color](crate::tile::Tile::color), except
+/// that open cells of rooms and hallways are drawn in the color of the room
+/// they belong to if `rooms` labels them, as by [`label_rooms`]. If `explored`
+/// is given, only cells it has seen are drawn.
+///
+/// [`label_rooms`]: crate::worldgen::labels::label_rooms
+pub fn render_minimap(
+    world: &ArrayWorld,
+    camera: &CameraParams,
+    explored: Option<&Explored>,
+    rooms: Option<&Array2<Option<RoomId>>>,
+    params: &MinimapParams,
+) -> RgbImage {
+    let (w, h) = (params.width, params.height);
+    let scale = params.scale.max(1) as f32;
+    let middle = vec2(w as f32, h as f32) / 2.0;
+    let facing = camera.facing();
+    let left = vec2(facing.y, -facing.x);
+    let spread = camera.projection_plane_width / 2.0;
+
+    ImageBuffer::from_fn(w, h, |x, y| {
+        let offset = (vec2(x as f32, y as f32) + vec2(0.5, 0.5) - middle) / scale;
+        if offset.magnitude() * scale <= 1.5 {
+            return params.camera_color;
+        }
+
+        let pos = camera.pos + offset;
+        let cell = (pos.x.floor() as isize, pos.y.floor() as isize);
+        let color = match world.tile(cell) {
+            Some(_) if explored.is_some_and(|e| !e.is_explored(cell)) => params.unexplored,
+            None => params.unexplored,
+            Some(tile) => {
+                let room = rooms
+                    .and_then(|rooms| rooms.get((cell.1 as usize, cell.0 as usize)))
+                    .copied()
+                    .flatten();
+                match room {
+                    Some(room) if !tile.is_solid() => node_color(room),
+                    _ => tile.color(),
+                }
+            }
+        };
+
+        // Inside the cone if it's in front of the camera and no further to
+        // the side than the edges of the projection plane.
+        let ahead = offset.dot(facing);
+        let across = offset.dot(left).abs();
+        if ahead > 0.0 && across <= ahead * spread && offset.magnitude() <= params.cone_length {
+            blend(params.cone_color, color, 1.0 - CONE_ALPHA)
+        } else {
+            color
+        }
+    })
+}
+
+fn blend(Rgb(over): Rgb<u8>, Rgb(under): Rgb<u8>, alpha: f32) -> Rgb<u8> {
+    Rgb(std::array::from_fn(|i| {
+        (over[i] as f32 * alpha + under[i] as f32 * (1.0 - alpha)) as u8
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{camera::raycast_camera, tile::Tile, worldgen::graph::Node};
+
+    use super::*;
+
+    fn params() -> MinimapParams {
+        MinimapParams {
+            width: 40,
+            height: 40,
+            scale: 4,
+            cone_length: 3.0,
+            cone_color: Rgb([255, 0, 0]),
+            camera_color: Rgb([0, 255, 0]),
+            unexplored: Rgb([20, 20, 20]),
+        }
+    }
+
+    /// A room with a wall down the middle, and the camera in the west half
+    /// facing east.
+    fn world() -> (ArrayWorld, CameraParams) {
+        let mut map = Array2::from_elem((10, 10), Tile::Floor);
+        map.row_mut(0).fill(Tile::Wall);
+        map.row_mut(9).fill(Tile::Wall);
+        map.column_mut(0).fill(Tile::Wall);
+        map.column_mut(9).fill(Tile::Wall);
+        map.column_mut(5).fill(Tile::Wall);
+        let camera = CameraParams {
+            pos: vec2(2.5, 4.5),
+            facing_unit: vec2(1.0, 0.0),
+            n_rays: 16,
+            max_dist: 20.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        (ArrayWorld::from(map), camera)
+    }
+
+    #[test]
+    fn the_camera_sits_in_the_middle_facing_its_cone() {
+        let (world, camera) = world();
+
+        let img = render_minimap(&world, &camera, None, None, &params());
+
+        assert_eq!(*img.get_pixel(20, 20), Rgb([0, 255, 0]));
+        // A tile ahead of the camera is under the cone, and one behind isn't.
+        assert_eq!(
+            *img.get_pixel(26, 20),
+            blend(Rgb([255, 0, 0]), Tile::Floor.color(), 1.0 - CONE_ALPHA)
+        );
+        assert_eq!(*img.get_pixel(14, 20), Tile::Floor.color());
+        // The wall in the middle, a tile beyond the cone.
+        assert_eq!(*img.get_pixel(32, 20), Tile::Wall.color());
+        // Off the edge of the map.
+        assert_eq!(*img.get_pixel(0, 0), Rgb([20, 20, 20]));
+    }
+
+    #[test]
+    fn only_what_has_been_seen_is_drawn() {
+        let (world, camera) = world();
+        let mut explored = Explored::new(&world);
+
+        explored.reveal_view(&camera, &raycast_camera(&world, &camera));
+        let img = render_minimap(&world, &camera, Some(&explored), None, &params());
+
+        assert!(explored.is_explored((4, 4)));
+        assert!(explored.is_explored((5, 4)));
+        assert!(!explored.is_explored((6, 4)));
+        assert!(!explored.is_explored((2, 1)));
+        // Beyond the wall, and behind the camera.
+        assert_eq!(*img.get_pixel(38, 20), Rgb([20, 20, 20]));
+        assert_eq!(*img.get_pixel(10, 20), Rgb([20, 20, 20]));
+    }
+
+    #[test]
+    fn rooms_are_drawn_in_their_own_colors() {
+        let (world, camera) = world();
+        let rooms = world
+            .map()
+            .mapv(|t| (!t.is_solid()).then_some(Node::Room(3)));
+
+        let img = render_minimap(&world, &camera, None, Some(&rooms), &params());
+
+        assert_eq!(*img.get_pixel(14, 20), node_color(Node::Room(3)));
+        assert_eq!(*img.get_pixel(32, 20), Tile::Wall.color());
+    }
+}
@@ -0,0 +1,239 @@
+//! Whole-world overviews, drawn from above or at an isometric angle with walls
+//! raised to their heights, for previews, thumbnails of generated seeds and
+//! checking maps with walls of several heights.
+
+use image::{ImageBuffer, Rgb, RgbImage};
+
+use crate::{render::material_color, tile::Tile, world::ArrayWorld};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    /// Straight down, with taller walls drawn brighter.
+    TopDown,
+    /// From above the south east corner, with x running down to the right
+    /// and y down to the left, and walls standing up out of the floor.
+    Isometric,
+}
+
+#[derive(Debug, Clone)]
+pub struct OverviewParams {
+    pub projection: Projection,
+
+    /// Width of each tile, in pixels. Isometric tiles are half as tall as
+    /// they are wide.
+    pub tile_size: u32,
+
+    /// How tall a wall one unit high is drawn in isometric views, in pixels.
+    pub wall_scale: f32,
+
+    /// Color around the edges of isometric views.
+    pub background: Rgb<u8>,
+}
+
+/// How brightly lit the top, south and east faces of isometric walls are.
+const FACE_SHADES: [f32; 3] = [1.0, 0.6, 0.8];
+
+/// Draw a whole world from above. Walls are drawn in the flat color of their
+/// material, and everything else in its [tile color](Tile::color).
+pub fn render_overview(world: &ArrayWorld, params: &OverviewParams) -> RgbImage {
+    match params.projection {
+        Projection::TopDown => render_top_down(world, params),
+        Projection::Isometric => render_isometric(world, params),
+    }
+}
+
+/// The color of a tile, and how high it stands, in wall units.
+fn column(world: &ArrayWorld, pos: (isize, isize), tile: Tile) -> (Rgb<u8>, f32) {
+    match tile {
+        Tile::Wall | Tile::SecretWall => (material_color(world.material(pos)), world.height(pos)),
+        _ if tile.is_solid() => (tile.color(), world.height(pos)),
+        _ => (tile.color(), 0.0),
+    }
+}
+
+fn render_top_down(world: &ArrayWorld, params: &OverviewParams) -> RgbImage {
+    let (h, w) = world.map().dim();
+    let size = params.tile_size.max(1);
+    let tallest = tallest(world);
+    ImageBuffer::from_fn(w as u32 * size, h as u32 * size, |x, y| {
+        let pos = ((x / size) as isize, (y / size) as isize);
+        match column(world, pos, world.map()[(pos.1 as usize, pos.0 as usize)]) {
+            (color, height) if height > 0.0 => shade(color, 0.5 + 0.5 * height / tallest),
+            (color, _) => color,
+        }
+    })
+}
+
+fn render_isometric(world: &ArrayWorld, params: &OverviewParams) -> RgbImage {
+    let (h, w) = world.map().dim();
+    let half_w = params.tile_size.max(2) as f32 / 2.0;
+    let half_h = half_w / 2.0;
+    let rise = (tallest(world) * params.wall_scale).ceil();
+
+    let width = ((w + h) as f32 * half_w).ceil() as u32;
+    let height = ((w + h) as f32 * half_h + rise).ceil() as u32;
+    let mut img = RgbImage::from_pixel(width, height, params.background);
+    if w == 0 || h == 0 {
+        return img;
+    }
+
+    // Where the north west corner of a cell is drawn, at floor level.
+    let corner = |x: f32, y: f32| ((x - y + h as f32) * half_w, (x + y) * half_h + rise);
+
+    // Back to front, so nearer walls are drawn over farther ones.
+    for diagonal in 0..w + h {
+        for x in diagonal.saturating_sub(h - 1)..=diagonal.min(w - 1) {
+            let y = diagonal - x;
+            let pos = (x as isize, y as isize);
+            let Some(tile) = world.tile(pos) else {
+                continue;
+            };
+            let (color, height) = column(world, pos, tile);
+            let lift = height * params.wall_scale;
+            let (x, y) = (x as f32, y as f32);
+
+            // The south face runs from the west corner to the south corner,
+            // and the east face from there to the east corner.
+            let (west, south, east) = (
+                corner(x, y + 1.0),
+                corner(x + 1.0, y + 1.0),
+                corner(x + 1.0, y),
+            );
+            if lift > 0.0 {
+                fill_face(&mut img, west, south, lift, shade(color, FACE_SHADES[1]));
+                fill_face(&mut img, south, east, lift, shade(color, FACE_SHADES[2]));
+            }
+            let (cx, cy) = corner(x + 0.5, y + 0.5);
+            fill_diamond(
+                &mut img,
+                (cx, cy - lift),
+                half_w,
+                half_h,
+                shade(color, FACE_SHADES[0]),
+            );
+        }
+    }
+    img
+}
+
+/// The height of the tallest wall, or 1 if there are none.
+fn tallest(world: &ArrayWorld) -> f32 {
+    let (h, w) = world.map().dim();
+    let tallest = (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x as isize, y as isize)))
+        .filter(|&pos| world.tile(pos).is_some_and(Tile::is_solid))
+        .map(|pos| world.height(pos))
+        .fold(0.0, f32::max);
+    if tallest > 0.0 {
+        tallest
+    } else {
+        1.0
+    }
+}
+
+/// Fill the face standing `lift` pixels up from the floor edge running from
+/// `a` to `b`, left to right.
+fn fill_face(img: &mut RgbImage, a: (f32, f32), b: (f32, f32), lift: f32, color: Rgb<u8>) {
+    let (w, h) = img.dimensions();
+    let (x0, x1) = (a.0.round().max(0.0) as u32, (b.0.round() as u32).min(w));
+    for x in x0..x1 {
+        let t = (x as f32 + 0.5 - a.0) / (b.0 - a.0);
+        let floor = a.1 + (b.1 - a.1) * t;
+        let (y0, y1) = (
+            (floor - lift).round().max(0.0) as u32,
+            (floor.round() as u32).min(h),
+        );
+        for y in y0..y1 {
+            img.put_pixel(x, y, color);
+        }
+    }
+}
+
+/// Fill the diamond with its middle at `center`, reaching `half_w` pixels to
+/// either side and `half_h` up and down.
+fn fill_diamond(img: &mut RgbImage, center: (f32, f32), half_w: f32, half_h: f32, color: Rgb<u8>) {
+    let (w, h) = img.dimensions();
+    let (cx, cy) = center;
+    let (x0, x1) = (
+        (cx - half_w).max(0.0) as u32,
+        ((cx + half_w).ceil() as u32).min(w),
+    );
+    let (y0, y1) = (
+        (cy - half_h).max(0.0) as u32,
+        ((cy + half_h).ceil() as u32).min(h),
+    );
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let dx = (x as f32 + 0.5 - cx).abs() / half_w;
+            let dy = (y as f32 + 0.5 - cy).abs() / half_h;
+            if dx + dy <= 1.0 {
+                img.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+fn shade(Rgb(c): Rgb<u8>, k: f32) -> Rgb<u8> {
+    Rgb(c.map(|v| (v as f32 * k) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use crate::tile::MaterialId;
+
+    use super::*;
+
+    /// A floor with a short wall to the west and a tall one to the east.
+    fn world() -> ArrayWorld {
+        let mut map = Array2::from_elem((4, 6), Tile::Floor);
+        map[(1, 1)] = Tile::Wall;
+        map[(1, 4)] = Tile::Wall;
+        let mut heights = Array2::from_elem((4, 6), 1.0);
+        heights[(1, 1)] = 0.5;
+        heights[(1, 4)] = 2.0;
+        ArrayWorld::from(map).with_heights(heights)
+    }
+
+    fn params(projection: Projection) -> OverviewParams {
+        OverviewParams {
+            projection,
+            tile_size: 8,
+            wall_scale: 8.0,
+            background: Rgb([1, 2, 3]),
+        }
+    }
+
+    #[test]
+    fn taller_walls_are_brighter_from_above() {
+        let img = render_overview(&world(), &params(Projection::TopDown));
+
+        let wall = material_color(MaterialId::default());
+        assert_eq!(img.dimensions(), (48, 32));
+        assert_eq!(*img.get_pixel(36, 12), wall);
+        assert_eq!(*img.get_pixel(12, 12), shade(wall, 0.625));
+        assert_eq!(*img.get_pixel(20, 20), Tile::Floor.color());
+    }
+
+    #[test]
+    fn isometric_walls_stand_up_from_the_floor() {
+        let world = world();
+        let params = params(Projection::Isometric);
+
+        let img = render_overview(&world, &params);
+
+        // Ten tiles along each diagonal, 8 by 4 pixels each, and 16 pixels
+        // above them for the tallest wall.
+        assert_eq!(img.dimensions(), (40, 36));
+        assert_eq!(*img.get_pixel(0, 0), Rgb([1, 2, 3]));
+        // The middle of the floor tile at (2, 2).
+        assert_eq!(*img.get_pixel(16, 26), Tile::Floor.color());
+        // The top of the tall wall at (4, 1), 16 pixels above the middle of
+        // its tile, and its south face below that.
+        let wall = material_color(MaterialId::default());
+        assert_eq!(*img.get_pixel(28, 12), wall);
+        assert_eq!(*img.get_pixel(26, 20), shade(wall, FACE_SHADES[1]));
+    }
+}
@@ -0,0 +1,341 @@
+//! Something that walks around a world and sees it through a camera, bumping
+//! into walls as a circle rather than a point and sliding along them.
+
+use cgmath::{vec2, InnerSpace, MetricSpace, Vector2, Zero};
+
+use crate::{camera::CameraParams, world::ArrayWorld};
+
+/// How far from its middle the player touches walls, in tiles, unless set
+/// otherwise.
+pub const DEFAULT_RADIUS: f32 = 0.2;
+
+/// How a [`Player`] speeds up, slows down and turns in [`Player::update`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MovementParams {
+    /// Top walking speed, in tiles per second.
+    pub max_speed: f32,
+
+    /// How much faster than `max_speed` sprinting is.
+    pub sprint: f32,
+
+    /// How quickly the player gets up to speed while walking, in tiles per
+    /// second per second.
+    pub acceleration: f32,
+
+    /// How quickly the player comes to a stop once they stop walking, in
+    /// tiles per second per second.
+    pub friction: f32,
+
+    /// How fast the player turns, in radians per second.
+    pub turn_rate: f32,
+}
+
+impl Default for MovementParams {
+    fn default() -> Self {
+        Self {
+            max_speed: 2.5,
+            sprint: 1.8,
+            acceleration: 12.0,
+            friction: 10.0,
+            turn_rate: 2.0,
+        }
+    }
+}
+
+/// Which way the player is trying to move, as from held keys or a stick.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MoveInput {
+    /// Forwards, or backwards if negative, from -1 to 1.
+    pub forward: f32,
+
+    /// To the right, or to the left if negative, from -1 to 1.
+    pub strafe: f32,
+
+    /// How fast to turn, as a fraction of [`MovementParams::turn_rate`], to
+    /// the right, or to the left if negative, from -1 to 1.
+    pub turn: f32,
+
+    pub sprint: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// Where the player is and which way they face. Hand this to the
+    /// renderers as it is.
+    pub camera: CameraParams,
+
+    /// How fast the player moved over their last step, in tiles per second,
+    /// after sliding along or stopping at walls.
+    pub velocity: Vector2<f32>,
+
+    /// How close the player's middle can get to anything that isn't
+    /// [passable](ArrayWorld::passable), in tiles. Should be less than half a
+    /// tile, or the player can't fit through one-tile hallways.
+    pub radius: f32,
+}
+
+impl Player {
+    pub fn new(camera: CameraParams) -> Self {
+        Self {
+            camera,
+            velocity: Vector2::zero(),
+            radius: DEFAULT_RADIUS,
+        }
+    }
+
+    pub fn with_radius(self, radius: f32) -> Self {
+        Self { radius, ..self }
+    }
+
+    /// Walk forwards at `speed` tiles per second for `dt` seconds, or
+    /// backwards if `speed` is negative.
+    pub fn move_forward(&mut self, world: &ArrayWorld, speed: f32, dt: f32) {
+        let facing = self.camera.facing();
+        self.walk(world, facing * speed, dt);
+    }
+
+    /// Step sideways at `speed` tiles per second for `dt` seconds, to the
+    /// right, or to the left if `speed` is negative.
+    pub fn strafe(&mut self, world: &ArrayWorld, speed: f32, dt: f32) {
+        let facing = self.camera.facing();
+        self.walk(world, vec2(-facing.y, facing.x) * speed, dt);
+    }
+
+    /// Turn at `rate` radians per second for `dt` seconds, to the right, or to
+    /// the left if `rate` is negative.
+    pub fn rotate(&mut self, rate: f32, dt: f32) {
+        let facing = self.camera.facing();
+        // With y pointing down, turning right is turning clockwise on screen.
+        let (sin, cos) = (rate * dt).sin_cos();
+        self.camera.facing_unit = vec2(
+            facing.x * cos - facing.y * sin,
+            facing.x * sin + facing.y * cos,
+        );
+    }
+
+    /// Turn and walk for `dt` seconds as `input` asks, speeding up towards
+    /// the top speed while walking and slowing down while not, rather than
+    /// starting and stopping dead.
+    pub fn update(
+        &mut self,
+        world: &ArrayWorld,
+        input: &MoveInput,
+        params: &MovementParams,
+        dt: f32,
+    ) {
+        self.rotate(input.turn.clamp(-1.0, 1.0) * params.turn_rate, dt);
+
+        let facing = self.camera.facing();
+        let wish = facing * input.forward + vec2(-facing.y, facing.x) * input.strafe;
+        // Walking diagonally is no faster than walking straight.
+        let wish = if wish.magnitude2() > 1.0 {
+            wish.normalize()
+        } else {
+            wish
+        };
+        let speed = if input.sprint {
+            params.max_speed * params.sprint
+        } else {
+            params.max_speed
+        };
+        let rate = if wish.is_zero() {
+            params.friction
+        } else {
+            params.acceleration
+        };
+        let velocity = approach(self.velocity, wish * speed, rate * dt);
+        self.walk(world, velocity, dt);
+    }
+
+    /// Move at `velocity` tiles per second for `dt` seconds, sliding along
+    /// whatever is in the way.
+    pub fn walk(&mut self, world: &ArrayWorld, velocity: Vector2<f32>, dt: f32) {
+        let start = self.camera.pos;
+        let step = velocity * dt;
+        // Steps shorter than the radius can't carry the player's middle past
+        // the edge of a wall, so nothing gets walked through at low frame
+        // rates.
+        let n = (step.magnitude() / (self.radius * 0.5).max(1e-3))
+            .ceil()
+            .max(1.0);
+        for _ in 0..n as usize {
+            self.camera.pos = push_out(world, self.camera.pos + step / n, self.radius);
+        }
+        self.velocity = if dt > 0.0 {
+            (self.camera.pos - start) / dt
+        } else {
+            Vector2::zero()
+        };
+    }
+}
+
+/// The camera part of the way from `from` to `to`, where `t` is 0 at `from`
+/// and 1 at `to`, for drawing frames between fixed-rate updates.
+///
+/// The facing direction turns the short way round between the two. Everything
+/// but the position, facing and pitch is taken from `to`.
+pub fn interpolate(from: &CameraParams, to: &CameraParams, t: f32) -> CameraParams {
+    let (a, b) = (from.facing(), to.facing());
+    let angle = a.perp_dot(b).atan2(a.dot(b)) * t;
+    let (sin, cos) = angle.sin_cos();
+    CameraParams {
+        pos: from.pos + (to.pos - from.pos) * t,
+        facing_unit: vec2(a.x * cos - a.y * sin, a.x * sin + a.y * cos),
+        pitch: from.pitch + (to.pitch - from.pitch) * t,
+        ..to.clone()
+    }
+}
+
+/// Move `from` towards `to` by no more than `max_step`.
+fn approach(from: Vector2<f32>, to: Vector2<f32>, max_step: f32) -> Vector2<f32> {
+    let gap = to - from;
+    let dist = gap.magnitude();
+    if dist <= max_step {
+        to
+    } else {
+        from + gap * (max_step / dist)
+    }
+}
+
+/// Move a circle out of any tiles that aren't passable and that it overlaps,
+/// by the shortest way out of each.
+///
+/// A circle whose middle is inside such a tile is left where it is, since
+/// there's no telling which way is out; walking in short enough steps never
+/// gets it there.
+fn push_out(world: &ArrayWorld, pos: Vector2<f32>, radius: f32) -> Vector2<f32> {
+    let nearest = |pos: Vector2<f32>, (x, y): (isize, isize)| {
+        let (x, y) = (x as f32, y as f32);
+        vec2(pos.x.clamp(x, x + 1.0), pos.y.clamp(y, y + 1.0))
+    };
+    let (x0, x1) = ((pos.x - radius).floor(), (pos.x + radius).floor());
+    let (y0, y1) = ((pos.y - radius).floor(), (pos.y + radius).floor());
+    let mut blocked: Vec<_> = (y0 as isize..=y1 as isize)
+        .flat_map(|y| (x0 as isize..=x1 as isize).map(move |x| (x, y)))
+        .filter(|&cell| !world.passable(cell))
+        .collect();
+    // Nearest first, so that pushing out of the tile the circle runs into
+    // face on moves it clear of the corners of the tiles either side, rather
+    // than those corners nudging it sideways along a flat wall.
+    blocked.sort_by(|&a, &b| {
+        let (a, b) = (
+            nearest(pos, a).distance2(pos),
+            nearest(pos, b).distance2(pos),
+        );
+        a.total_cmp(&b)
+    });
+
+    let mut pos = pos;
+    for cell in blocked {
+        let away = pos - nearest(pos, cell);
+        let dist = away.magnitude();
+        if dist > 0.0 && dist < radius {
+            pos += away * ((radius - dist) / dist);
+        }
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+
+    use crate::tile::Tile;
+
+    use super::*;
+
+    /// A room with walls all around, and a player in it facing east.
+    fn player() -> (ArrayWorld, Player) {
+        let mut map = Array2::from_elem((6, 6), Tile::Floor);
+        map.row_mut(0).fill(Tile::Wall);
+        map.row_mut(5).fill(Tile::Wall);
+        map.column_mut(0).fill(Tile::Wall);
+        map.column_mut(5).fill(Tile::Wall);
+        let camera = CameraParams {
+            pos: vec2(2.5, 2.5),
+            facing_unit: vec2(1.0, 0.0),
+            n_rays: 8,
+            max_dist: 10.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        (ArrayWorld::from(map), Player::new(camera))
+    }
+
+    #[test]
+    fn walls_stop_the_player_a_radius_away() {
+        let (world, mut player) = player();
+
+        // Far enough in one step to go through the wall without collisions.
+        player.move_forward(&world, 10.0, 1.0);
+
+        let pos = player.camera.pos;
+        assert!((pos.x - (5.0 - DEFAULT_RADIUS)).abs() < 1e-4, "{pos:?}");
+        assert!((pos.y - 2.5).abs() < 1e-4, "{pos:?}");
+        assert!(player.velocity.x < 10.0);
+    }
+
+    #[test]
+    fn walking_into_walls_at_an_angle_slides_along_them() {
+        let (world, mut player) = player();
+        player.camera.pos = vec2(4.7, 2.5);
+
+        player.walk(&world, vec2(1.0, 1.0), 1.0);
+
+        let pos = player.camera.pos;
+        assert!((pos.x - (5.0 - DEFAULT_RADIUS)).abs() < 1e-4, "{pos:?}");
+        assert!((pos.y - 3.5).abs() < 1e-4, "{pos:?}");
+    }
+
+    #[test]
+    fn turning_and_strafing_are_to_the_right() {
+        let (world, mut player) = player();
+
+        player.strafe(&world, 1.0, 1.0);
+        player.rotate(std::f32::consts::FRAC_PI_2, 1.0);
+
+        assert!((player.camera.pos - vec2(2.5, 3.5)).magnitude() < 1e-4);
+        assert!((player.camera.facing() - vec2(0.0, 1.0)).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn players_speed_up_and_slow_down_gradually() {
+        let (world, mut player) = player();
+        let params = MovementParams::default();
+        let walk = MoveInput {
+            forward: 1.0,
+            ..Default::default()
+        };
+
+        player.update(&world, &walk, &params, 0.1);
+        let first = player.velocity.x;
+        for _ in 0..2 {
+            player.update(&world, &walk, &params, 0.1);
+        }
+        let top = player.velocity.x;
+        player.update(&world, &MoveInput::default(), &params, 0.1);
+        let stopping = player.velocity.x;
+
+        assert!((first - params.acceleration * 0.1).abs() < 1e-4);
+        assert!((top - params.max_speed).abs() < 1e-4);
+        assert!((stopping - (params.max_speed - params.friction * 0.1)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn interpolating_turns_the_short_way_round() {
+        let (_, player) = player();
+        let from = player.camera;
+        let to = CameraParams {
+            pos: vec2(3.5, 2.5),
+            facing_unit: vec2(0.0, -2.0),
+            ..from.clone()
+        };
+
+        let half = interpolate(&from, &to, 0.5);
+
+        assert!((half.pos - vec2(3.0, 2.5)).magnitude() < 1e-4);
+        let diagonal = vec2(1.0, -1.0).normalize();
+        assert!((half.facing() - diagonal).magnitude() < 1e-4);
+    }
+}
@@ -0,0 +1,223 @@
+//! Effects run over a drawn frame to give it the look of old hardware or worn
+//! tape, chained together by [`PostProcess`].
+
+use image::{ImageBuffer, Rgb, RgbImage};
+
+use crate::util::derive_seed;
+
+/// An effect run over a whole frame, such as a vignette or film grain.
+pub trait PostEffect {
+    /// Apply the effect to a frame drawn at `time`, in seconds.
+    fn apply(&self, image: &mut RgbImage, time: f32);
+}
+
+impl<F: Fn(&mut RgbImage, f32)> PostEffect for F {
+    fn apply(&self, image: &mut RgbImage, time: f32) {
+        self(image, time)
+    }
+}
+
+/// An ordered list of effects.
+#[derive(Default)]
+pub struct PostProcess {
+    effects: Vec<Box<dyn PostEffect>>,
+}
+
+impl PostProcess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an effect to the end of the list.
+    pub fn then(mut self, effect: impl PostEffect + 'static) -> Self {
+        self.effects.push(Box::new(effect));
+        self
+    }
+
+    /// Run every effect over a frame drawn at `time`, in the order they were
+    /// added.
+    pub fn run(&self, image: &mut RgbImage, time: f32) {
+        for effect in &self.effects {
+            effect.apply(image, time);
+        }
+    }
+}
+
+/// Darkens the frame towards its corners.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vignette {
+    /// How much of its brightness the very corners lose, in [0, 1].
+    pub strength: f32,
+}
+
+impl PostEffect for Vignette {
+    fn apply(&self, image: &mut RgbImage, _: f32) {
+        let (w, h) = image.dimensions();
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let (u, v) = centered(x, y, w, h);
+            let k = 1.0 - self.strength * (u * u + v * v) / 2.0;
+            *pixel = scale(*pixel, k);
+        }
+    }
+}
+
+/// Speckles the frame with random noise that changes every frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Grain {
+    /// The most the noise brightens or darkens a channel by, out of 255.
+    pub amount: f32,
+
+    pub seed: u64,
+}
+
+impl PostEffect for Grain {
+    fn apply(&self, image: &mut RgbImage, time: f32) {
+        let frame = derive_seed(self.seed, [time.to_bits() as u64]);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let hash = derive_seed(frame, [x as u64, y as u64]);
+            let noise = (hash >> 40) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+            pixel.0 = pixel.0.map(|c| (c as f32 + noise * self.amount) as u8);
+        }
+    }
+}
+
+/// Ordered dithering to a palette of `levels` evenly spaced values of each
+/// channel, like a console with few colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dither {
+    /// Values of each channel in the palette, at least 2.
+    pub levels: u8,
+}
+
+/// Thresholds of a 4x4 ordered dither, out of 16.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl PostEffect for Dither {
+    fn apply(&self, image: &mut RgbImage, _: f32) {
+        let steps = self.levels.max(2) as f32 - 1.0;
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let threshold = (BAYER[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0;
+            pixel.0 = pixel.0.map(|c| {
+                let level = (c as f32 / 255.0 * steps + threshold).floor().min(steps);
+                (level / steps * 255.0).round() as u8
+            });
+        }
+    }
+}
+
+/// The curved glass and scanlines of a CRT screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Crt {
+    /// How much of its brightness every other row loses, in [0, 1].
+    pub scanlines: f32,
+
+    /// How far the frame bulges out, stretching its middle and leaving its
+    /// corners black. 0 is flat.
+    pub warp: f32,
+}
+
+impl PostEffect for Crt {
+    fn apply(&self, image: &mut RgbImage, _: f32) {
+        let (w, h) = image.dimensions();
+        let source = image.clone();
+        *image = ImageBuffer::from_fn(w, h, |x, y| {
+            let (u, v) = centered(x, y, w, h);
+            let bulge = 1.0 + self.warp * (u * u + v * v);
+            let (u, v) = (u * bulge, v * bulge);
+            if u.abs() > 1.0 || v.abs() > 1.0 {
+                return Rgb([0, 0, 0]);
+            }
+            let sx = ((u + 1.0) / 2.0 * w as f32).min(w as f32 - 1.0);
+            let sy = ((v + 1.0) / 2.0 * h as f32).min(h as f32 - 1.0);
+            let pixel = *source.get_pixel(sx as u32, sy as u32);
+            if y % 2 == 1 {
+                scale(pixel, 1.0 - self.scanlines)
+            } else {
+                pixel
+            }
+        });
+    }
+}
+
+/// The middle of a pixel, from -1 at the left or top edge of the frame to 1 at
+/// the right or bottom.
+fn centered(x: u32, y: u32, w: u32, h: u32) -> (f32, f32) {
+    (
+        (x as f32 + 0.5) / w as f32 * 2.0 - 1.0,
+        (y as f32 + 0.5) / h as f32 * 2.0 - 1.0,
+    )
+}
+
+fn scale(Rgb(c): Rgb<u8>, k: f32) -> Rgb<u8> {
+    Rgb(c.map(|v| (v as f32 * k) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(w: u32, h: u32, v: u8) -> RgbImage {
+        RgbImage::from_pixel(w, h, Rgb([v; 3]))
+    }
+
+    #[test]
+    fn effects_run_in_order() {
+        let mut img = gray(16, 16, 200);
+        let post = PostProcess::new()
+            .then(Vignette { strength: 0.8 })
+            .then(|img: &mut RgbImage, time: f32| img.put_pixel(0, 0, Rgb([time as u8; 3])));
+
+        post.run(&mut img, 3.0);
+
+        assert_eq!(*img.get_pixel(0, 0), Rgb([3; 3]));
+        assert!(img.get_pixel(15, 15).0[0] < img.get_pixel(8, 8).0[0]);
+        assert!(img.get_pixel(8, 8).0[0] > 195);
+    }
+
+    #[test]
+    fn dithering_mixes_palette_colors() {
+        let mut img = gray(8, 8, 128);
+
+        Dither { levels: 2 }.apply(&mut img, 0.0);
+
+        assert!(img.pixels().all(|p| [0, 255].contains(&p.0[0])));
+        let white = img.pixels().filter(|p| p.0[0] == 255).count();
+        assert_eq!(white, 32);
+    }
+
+    #[test]
+    fn grain_changes_every_frame() {
+        let grain = Grain {
+            amount: 20.0,
+            seed: 1,
+        };
+        let frame = |time| {
+            let mut img = gray(8, 8, 128);
+            grain.apply(&mut img, time);
+            img
+        };
+
+        assert_eq!(frame(0.5), frame(0.5));
+        assert_ne!(frame(0.5), frame(0.6));
+        assert!(frame(0.5).pixels().all(|p| p.0[0].abs_diff(128) <= 20));
+    }
+
+    #[test]
+    fn crts_have_scanlines_and_dark_corners() {
+        let mut img = gray(32, 32, 200);
+
+        Crt {
+            scanlines: 0.5,
+            warp: 0.3,
+        }
+        .apply(&mut img, 0.0);
+
+        assert_eq!(*img.get_pixel(0, 0), Rgb([0; 3]));
+        assert_eq!(*img.get_pixel(16, 16), Rgb([200; 3]));
+        assert_eq!(*img.get_pixel(16, 17), Rgb([100; 3]));
+    }
+}
@@ -0,0 +1,1036 @@
+//! Drawing a first-person view from the hits of [`raycast_camera`].
+//!
+//! [`raycast_camera`]: crate::camera::raycast_camera
+
+use cgmath::{vec2, InnerSpace, Vector2};
+use image::{ImageBuffer, Rgb, RgbImage, RgbaImage};
+use web_time::Instant;
+
+use crate::{
+    camera::{raycast_camera, CameraParams, CameraRay, PickTarget, RaycastHit, RaycastableWorld},
+    lighting::LightSource,
+    stats::{CountCells, FrameStats},
+    textures::{sample, Textures},
+    tile::MaterialId,
+};
+
+/// The default [`ViewParams::face_light`]: walls facing east or west are a
+/// little darker than walls facing north or south, so corners stand out.
+pub const FACE_LIGHT: [f32; 4] = [0.75, 1.0, 0.75, 1.0];
+
+/// A flat image standing upright in the world, always turned to face the
+/// camera, such as an entity or a light fixture.
+#[derive(Debug, Clone)]
+pub struct Sprite<'a> {
+    /// Where the middle of the bottom edge of the sprite stands, in world
+    /// coordinates.
+    pub pos: Vector2<f32>,
+
+    /// How far above the floor the bottom edge is, in wall units, such as for
+    /// fixtures hanging from the ceiling.
+    pub elevation: f32,
+
+    /// Size of the sprite, in wall units.
+    pub width: f32,
+    pub height: f32,
+
+    /// Pixels with an alpha of 0 are see-through, and others are blended over
+    /// whatever is behind them.
+    pub image: &'a RgbaImage,
+}
+
+impl From<&Sprite<'_>> for PickTarget {
+    /// Sprites are picked anywhere within half their width of their middle.
+    fn from(sprite: &Sprite) -> Self {
+        Self {
+            pos: sprite.pos,
+            radius: sprite.width / 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewParams {
+    /// Size of the image to draw, in pixels.
+    pub width: u32,
+    pub height: u32,
+
+    /// Colors of untextured floors, ceilings and doors.
+    pub ceiling: Rgb<u8>,
+    pub floor: Rgb<u8>,
+    pub door: Rgb<u8>,
+
+    /// Height of the camera above the floor, in wall units. The ceiling is one
+    /// unit above the floor.
+    pub eye_height: f32,
+
+    /// How brightly lit walls facing each [`Direction`](crate::util::Direction)
+    /// are, indexed by direction, in [0, 1]. See [`FACE_LIGHT`].
+    pub face_light: [f32; 4],
+
+    /// Distance at which walls, floor and ceiling have faded to black. Nothing
+    /// fades if this is infinite.
+    pub fog_distance: f32,
+
+    /// How far the camera is tilted to the right, in radians, turning the
+    /// whole view anticlockwise. Applied after everything is drawn, so the
+    /// depth of each column is as if the camera were level.
+    pub roll: f32,
+
+    /// The time in seconds, for animated textures.
+    pub time: f32,
+
+    /// What's drawn beyond the camera's `max_dist`.
+    pub void: Void,
+}
+
+/// What's drawn where the floor and ceiling reach past the camera's
+/// `max_dist`, beyond which rays stop looking for walls, so open areas and the
+/// edges of the map don't just stop.
+#[derive(Debug, Clone, Default)]
+pub enum Void {
+    /// The floor and ceiling carry on to the horizon.
+    #[default]
+    Horizon,
+    Color(Rgb<u8>),
+    /// Fades from `top` at the top of the view to `bottom` at the bottom.
+    Gradient {
+        top: Rgb<u8>,
+        bottom: Rgb<u8>,
+    },
+    /// An image wrapped all the way around the camera, indexed across by the
+    /// direction each column looks in, with its middle row on the horizon.
+    Panorama(RgbImage),
+}
+
+impl Void {
+    /// The color of row `y` of a column looking along `ray`, out of `h` rows.
+    /// None if the floor and ceiling are drawn instead.
+    fn color(&self, ray: Vector2<f32>, y: u32, h: u32, horizon: f32) -> Option<Rgb<u8>> {
+        let row = y as f32 + 0.5;
+        match self {
+            Void::Horizon => None,
+            Void::Color(color) => Some(*color),
+            Void::Gradient { top, bottom } => {
+                let t = row / h as f32;
+                Some(blend(*bottom, *top, t))
+            }
+            Void::Panorama(image) => {
+                let u = (ray.y.atan2(ray.x) / std::f32::consts::TAU).rem_euclid(1.0);
+                let v = ((row - horizon) / h as f32 + 0.5).clamp(0.0, 0.999);
+                Some(sample(image, u, v))
+            }
+        }
+    }
+}
+
+/// The bobbing and swaying of a camera carried by someone walking.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeadBob {
+    /// How far the eye rises and falls with each step, in wall units.
+    pub amplitude: f32,
+
+    /// Steps taken per unit walked. The faster the camera moves, the faster it
+    /// bobs.
+    pub frequency: f32,
+
+    /// How far the view rolls to either side with each step, in radians.
+    pub sway: f32,
+}
+
+impl HeadBob {
+    /// The view from a camera that has walked `distance` units in total. The
+    /// eye rises and falls once per step, and the view sways from side to side
+    /// once every two steps.
+    pub fn apply(&self, params: &ViewParams, distance: f32) -> ViewParams {
+        let phase = std::f32::consts::PI * self.frequency * distance;
+        ViewParams {
+            eye_height: params.eye_height + self.amplitude * (2.0 * phase).sin(),
+            roll: params.roll + self.sway * phase.sin(),
+            ..params.clone()
+        }
+    }
+}
+
+/// A drawn view, and how far away the wall in each column of it is.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub image: RgbImage,
+
+    /// The [perpendicular distance] to the wall drawn in each column of the
+    /// image, or infinity where no wall was hit. Sprites don't count.
+    ///
+    /// [perpendicular distance]: CameraParams::perpendicular_distance
+    pub depth: Vec<f32>,
+}
+
+/// Somewhere drawn frames are shown, such as a window, so drawing doesn't
+/// depend on how its frames end up on screen.
+pub trait Surface {
+    type Error;
+
+    /// Size of the surface in pixels. Frames are best drawn at this size, or
+    /// a whole fraction of it.
+    fn size(&self) -> (u32, u32);
+
+    /// Show a frame, stretched to fill the surface.
+    fn present(&mut self, image: &RgbImage) -> Result<(), Self::Error>;
+}
+
+/// An image is a surface that keeps the last frame shown on it.
+impl Surface for RgbImage {
+    type Error = std::convert::Infallible;
+
+    fn size(&self) -> (u32, u32) {
+        self.dimensions()
+    }
+
+    fn present(&mut self, image: &RgbImage) -> Result<(), Self::Error> {
+        let (w, h) = self.dimensions();
+        *self = stretch(image, w, h);
+        Ok(())
+    }
+}
+
+/// Stretch or squeeze an image to a new size, keeping its pixels sharp.
+pub fn stretch(image: &RgbImage, w: u32, h: u32) -> RgbImage {
+    let (iw, ih) = image.dimensions();
+    if (iw, ih) == (w, h) {
+        return image.clone();
+    }
+    if iw == 0 || ih == 0 {
+        return RgbImage::new(w, h);
+    }
+    ImageBuffer::from_fn(w, h, |x, y| {
+        *image.get_pixel(x * iw / w.max(1), y * ih / h.max(1))
+    })
+}
+
+/// Something that draws first-person views of worlds of type `W`, so
+/// frontends can swap between drawing in software, on the GPU or as text
+/// without changing.
+pub trait Renderer<W> {
+    /// A drawn view, such as an image or a grid of characters.
+    type Frame;
+    type Error;
+
+    /// Draw the view of `world` from `camera` at `time`, in seconds. Each
+    /// renderer decides how many rays to cast from the size it draws at.
+    fn render(
+        &mut self,
+        world: W,
+        camera: &CameraParams,
+        time: f32,
+    ) -> Result<Self::Frame, Self::Error>;
+}
+
+/// Draws views in software as [`render_textured_view`] does, casting a ray
+/// for each column of pixels.
+#[derive(Debug, Clone)]
+pub struct SoftwareRenderer {
+    pub view: ViewParams,
+    pub textures: Textures,
+
+    /// Set to `Some` to collect stats about each frame, which are replaced
+    /// every time one is drawn. Left `None`, nothing is counted or timed.
+    pub stats: Option<FrameStats>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(view: ViewParams, textures: Textures) -> Self {
+        Self {
+            view,
+            textures,
+            stats: None,
+        }
+    }
+}
+
+impl<W: RaycastableWorld> Renderer<W> for SoftwareRenderer {
+    type Frame = Frame;
+    type Error = std::convert::Infallible;
+
+    fn render(&mut self, world: W, camera: &CameraParams, time: f32) -> Result<Frame, Self::Error> {
+        let view = ViewParams {
+            time,
+            ..self.view.clone()
+        };
+        let camera = CameraParams {
+            n_rays: view.width as usize,
+            ..camera.clone()
+        };
+        let Some(stats) = &mut self.stats else {
+            let hits = raycast_camera(world, &camera);
+            return Ok(render_textured_view(
+                &hits,
+                &camera,
+                &view,
+                &self.textures,
+                &[],
+            ));
+        };
+
+        let start = Instant::now();
+        let world = CountCells::new(world);
+        let hits = raycast_camera(&world, &camera);
+        let raycast = start.elapsed();
+        let frame = render_textured_view(&hits, &camera, &view, &self.textures, &[]);
+        *stats = FrameStats {
+            rays: hits.len(),
+            cells: world.count(),
+            raycast,
+            draw: start.elapsed() - raycast,
+        };
+        Ok(frame)
+    }
+}
+
+/// Draw a first-person view, one column of pixels per ray, from the hits of
+/// the rays cast for `camera`. Rays are stretched or squeezed to fill the
+/// width of the image.
+///
+/// Walls are drawn with the camera `eye_height` up a wall one unit tall, and
+/// scaled by their [perpendicular distance], so flat walls look flat.
+/// Walls are shaded by the `face_light` of the direction they face. Use
+/// [`RgbImage::into_raw`] to get a plain framebuffer.
+///
+/// [perpendicular distance]: CameraParams::perpendicular_distance
+pub fn render_view(hits: &[CameraRay], camera: &CameraParams, params: &ViewParams) -> RgbImage {
+    render_textured_view(hits, camera, params, &Textures::default(), &[]).image
+}
+
+/// Draw a first-person view as [`render_view`] does, but with walls that have
+/// a texture drawn with it. Each column takes a slice of the texture at the
+/// hit's [`u`], stretched to the height of the wall, and repeated up walls
+/// taller than one unit.
+///
+/// Floor and ceiling textures are cast row by row onto the planes below and
+/// above the camera, repeating once per tile.
+///
+/// Sprites are drawn over the walls as by [`draw_sprites`]. The depth of each
+/// column is returned along with the image, for drawing more on top.
+///
+/// [`u`]: RaycastHit::u
+pub fn render_textured_view(
+    hits: &[CameraRay],
+    camera: &CameraParams,
+    params: &ViewParams,
+    textures: &Textures,
+    sprites: &[Sprite],
+) -> Frame {
+    let columns = hits.iter().map(|r| r.hit.as_slice()).collect::<Vec<_>>();
+    render_columns(&columns, camera, params, textures, sprites, None)
+}
+
+/// Draw a first-person view as [`render_textured_view`] does, with walls,
+/// floor and ceiling shaded by how brightly `lighting` lights them. Walls are
+/// lit once per column, just in front of where the ray hit them, and the floor
+/// and ceiling once per pixel.
+///
+/// Pass a [`Lighting`] to light the view afresh each frame, or a baked
+/// [`Lightmap`] to look the light up instead.
+///
+/// [`Lighting`]: crate::lighting::Lighting
+/// [`Lightmap`]: crate::lighting::Lightmap
+pub fn render_lit_view(
+    hits: &[CameraRay],
+    camera: &CameraParams,
+    params: &ViewParams,
+    textures: &Textures,
+    sprites: &[Sprite],
+    lighting: &impl LightSource,
+) -> Frame {
+    let columns = hits.iter().map(|r| r.hit.as_slice()).collect::<Vec<_>>();
+    let light = |pos| lighting.light_at(pos);
+    render_columns(&columns, camera, params, textures, sprites, Some(&light))
+}
+
+/// Draw a first-person view as [`render_textured_view`] does, from every hit
+/// along each ray, as returned by [`raycast_camera_all`]. Each column is
+/// painted from its farthest hit to its nearest, so walls show over lower walls
+/// in front of them, and see-through walls are blended over whatever is
+/// behind them.
+///
+/// The depth of each column is that of its farthest hit.
+///
+/// [`raycast_camera_all`]: crate::camera::raycast_camera_all
+pub fn render_layered_view(
+    hits: &[Vec<RaycastHit>],
+    camera: &CameraParams,
+    params: &ViewParams,
+    textures: &Textures,
+    sprites: &[Sprite],
+) -> Frame {
+    let columns = hits.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    render_columns(&columns, camera, params, textures, sprites, None)
+}
+
+/// How opaque see-through walls are drawn.
+const GLASS_ALPHA: f32 = 0.3;
+
+/// Draw each column from its hits, with everything shaded by `light` at its
+/// position in the world, if given.
+fn render_columns(
+    hits: &[&[RaycastHit]],
+    camera: &CameraParams,
+    params: &ViewParams,
+    textures: &Textures,
+    sprites: &[Sprite],
+    light: Option<&dyn Fn(Vector2<f32>) -> f32>,
+) -> Frame {
+    let (w, h) = (params.width, params.height);
+    let horizon = horizon(camera, params);
+    // Pixels per unit on the projection plane, one unit in front of the camera.
+    let focal = w as f32 / camera.projection_plane_width;
+
+    // The distance to the floor or ceiling depends only on the row.
+    let rows = (0..h)
+        .map(|y| {
+            let from_horizon = y as f32 + 0.5 - horizon;
+            let (plane, color, texture) = if from_horizon < 0.0 {
+                (1.0 - params.eye_height, params.ceiling, &textures.ceiling)
+            } else {
+                (params.eye_height, params.floor, &textures.floor)
+            };
+            (plane * focal / from_horizon.abs(), color, texture.as_ref())
+        })
+        .collect::<Vec<_>>();
+
+    // The ray through the left edge of each column, as in `raycast_camera`.
+    let facing = camera.facing();
+    let left = vec2(facing.y, -facing.x);
+    let column_ray = |x: u32| {
+        let offset = camera.projection_plane_width * (0.5 - x as f32 / w as f32);
+        facing + left * offset
+    };
+
+    let mut img = ImageBuffer::from_fn(w, h, |x, y| {
+        let (dist, color, texture) = rows[y as usize];
+        let ray = column_ray(x);
+        if dist * ray.magnitude() > camera.max_dist {
+            if let Some(color) = params.void.color(ray, y, h, horizon) {
+                return color;
+            }
+        }
+        let p = camera.pos + ray * dist;
+        let color = match texture {
+            Some(texture) => sample(texture, p.x.rem_euclid(1.0), p.y),
+            None => color,
+        };
+        let color = match light {
+            Some(light) => shade(color, light(p)),
+            None => color,
+        };
+        fog(color, dist, params.fog_distance)
+    });
+    // How far away the wall drawn in each column is.
+    let mut depth = vec![f32::INFINITY; w as usize];
+    for x in 0..w {
+        let ray = (x as usize * hits.len() / w as usize).min(hits.len().saturating_sub(1));
+        let column = hits.get(ray).copied().unwrap_or_default();
+        if let Some(last) = column.last() {
+            depth[x as usize] = camera.perpendicular_distance(last);
+        }
+
+        for hit in column.iter().rev() {
+            let dist = camera.perpendicular_distance(hit);
+            let scale = focal / dist.max(1e-3);
+            let bottom = horizon + params.eye_height * scale;
+            let top = bottom - hit.height * scale;
+            let (y0, y1) = (top.max(0.0) as u32, bottom.clamp(0.0, h as f32) as u32);
+
+            let texture = textures.for_hit(hit, params.time);
+            let flat = wall_color(hit, params);
+            // Lit from just in front of the wall, so it doesn't shadow itself.
+            let normal = Vector2::<isize>::from(hit.wall_side).cast().unwrap();
+            let lit = light.map_or(1.0, |light| light(hit.hit_pos + normal * 1e-3));
+            for y in y0..y1 {
+                let color = match texture {
+                    // Measured down from the top of the wall, in wall units.
+                    Some(t) => side_shade(
+                        hit,
+                        params,
+                        sample(t, hit.u, (y as f32 + 0.5 - top) / scale),
+                    ),
+                    None => flat,
+                };
+                let mut color = fog(shade(color, lit), dist, params.fog_distance);
+                if hit.see_through {
+                    color = blend(color, *img.get_pixel(x, y), GLASS_ALPHA);
+                }
+                img.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    let mut frame = Frame { image: img, depth };
+    draw_sprites(&mut frame, sprites, camera, params);
+    if params.roll != 0.0 {
+        frame.image = roll(&frame.image, params.roll);
+    }
+    frame
+}
+
+/// Turn an image anticlockwise about its middle, stretching its edges out to
+/// fill the corners.
+fn roll(img: &RgbImage, angle: f32) -> RgbImage {
+    let (w, h) = img.dimensions();
+    let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+    let (sin, cos) = angle.sin_cos();
+    ImageBuffer::from_fn(w, h, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let sx = (cx + dx * cos - dy * sin).clamp(0.0, w as f32 - 1.0);
+        let sy = (cy + dx * sin + dy * cos).clamp(0.0, h as f32 - 1.0);
+        *img.get_pixel(sx as u32, sy as u32)
+    })
+}
+
+/// Draw sprites over a frame from farthest to nearest, hiding them in each
+/// column behind any wall nearer to the camera than they are.
+pub fn draw_sprites(
+    frame: &mut Frame,
+    sprites: &[Sprite],
+    camera: &CameraParams,
+    params: &ViewParams,
+) {
+    let (w, h) = (params.width, params.height);
+    let horizon = horizon(camera, params);
+    let focal = w as f32 / camera.projection_plane_width;
+    let facing = camera.facing();
+    let left = vec2(facing.y, -facing.x);
+
+    let mut sprites = sprites
+        .iter()
+        .map(|s| ((s.pos - camera.pos).dot(facing), s))
+        .filter(|(dist, _)| *dist > 1e-3)
+        .collect::<Vec<_>>();
+    sprites.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    for (dist, sprite) in sprites {
+        let scale = focal / dist;
+        let center = w as f32 / 2.0 - (sprite.pos - camera.pos).dot(left) * scale;
+        let (x0, x1) = (
+            center - sprite.width * scale / 2.0,
+            center + sprite.width * scale / 2.0,
+        );
+        let bottom = horizon + (params.eye_height - sprite.elevation) * scale;
+        let top = bottom - sprite.height * scale;
+
+        let (iw, ih) = sprite.image.dimensions();
+        let columns = (x0.max(0.0) as u32..x1.clamp(0.0, w as f32) as u32)
+            .filter(|&x| frame.depth.get(x as usize).is_none_or(|d| dist < *d));
+        for x in columns {
+            let u = (x as f32 + 0.5 - x0) / (x1 - x0);
+            let ix = ((u * iw as f32) as u32).min(iw - 1);
+            for y in top.max(0.0) as u32..bottom.clamp(0.0, h as f32) as u32 {
+                let v = (y as f32 + 0.5 - top) / (bottom - top);
+                let iy = ((v * ih as f32) as u32).min(ih - 1);
+                let [r, g, b, a] = sprite.image.get_pixel(ix, iy).0;
+                if a == 0 {
+                    continue;
+                }
+                let color = fog(Rgb([r, g, b]), dist, params.fog_distance);
+                let behind = *frame.image.get_pixel(x, y);
+                frame
+                    .image
+                    .put_pixel(x, y, blend(color, behind, a as f32 / 255.0));
+            }
+        }
+    }
+}
+
+fn blend(Rgb(over): Rgb<u8>, Rgb(under): Rgb<u8>, alpha: f32) -> Rgb<u8> {
+    Rgb(std::array::from_fn(|i| {
+        (over[i] as f32 * alpha + under[i] as f32 * (1.0 - alpha)) as u8
+    }))
+}
+
+/// The row of the image level with the camera. Looking up or down shears the
+/// view rather than turning it, so the horizon just moves down or up.
+fn horizon(camera: &CameraParams, params: &ViewParams) -> f32 {
+    let focal = params.width as f32 / camera.projection_plane_width;
+    params.height as f32 / 2.0 + camera.pitch.tan() * focal
+}
+
+/// The flat color of an untextured wall before fog.
+fn wall_color(hit: &RaycastHit, params: &ViewParams) -> Rgb<u8> {
+    let base = if hit.door {
+        params.door
+    } else {
+        material_color(hit.material)
+    };
+    side_shade(hit, params, base)
+}
+
+fn side_shade(hit: &RaycastHit, params: &ViewParams, color: Rgb<u8>) -> Rgb<u8> {
+    shade(color, params.face_light[hit.wall_side as usize])
+}
+
+/// A flat color for each built-in material, and a neutral gray for any other.
+pub fn material_color(material: MaterialId) -> Rgb<u8> {
+    match material {
+        MaterialId::YELLOW_WALLPAPER => Rgb([200, 180, 90]),
+        MaterialId::CONCRETE => Rgb([140, 140, 135]),
+        MaterialId::TILE => Rgb([225, 230, 230]),
+        MaterialId::DAMAGED => Rgb([150, 130, 90]),
+        _ => Rgb([128, 128, 128]),
+    }
+}
+
+fn fog(color: Rgb<u8>, dist: f32, fog_distance: f32) -> Rgb<u8> {
+    shade(color, (1.0 - dist / fog_distance).clamp(0.0, 1.0))
+}
+
+fn shade(Rgb(c): Rgb<u8>, k: f32) -> Rgb<u8> {
+    Rgb(c.map(|v| (v as f32 * k) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{assert_ulps_eq, vec2};
+    use ndarray::Array2;
+
+    use crate::{
+        camera::raycast_camera_all,
+        lighting::{Lighting, LightingParams},
+        tile::Tile,
+        world::{ArrayWorld, FlickerCurve, Light},
+    };
+
+    use super::*;
+
+    fn params() -> ViewParams {
+        ViewParams {
+            width: 64,
+            height: 48,
+            ceiling: Rgb([10, 10, 10]),
+            floor: Rgb([60, 40, 20]),
+            door: Rgb([150, 90, 40]),
+            eye_height: 0.5,
+            face_light: FACE_LIGHT,
+            fog_distance: f32::INFINITY,
+            roll: 0.0,
+            time: 0.0,
+            void: Void::Horizon,
+        }
+    }
+
+    /// A long corridor running east, with the camera at its west end.
+    fn corridor(len: usize) -> (ArrayWorld, CameraParams) {
+        let mut map = Array2::from_elem((3, len + 2), Tile::Wall);
+        map.row_mut(1)
+            .slice_mut(ndarray::s![1..len + 1])
+            .fill(Tile::Floor);
+        let camera = CameraParams {
+            pos: vec2(1.5, 1.5),
+            facing_unit: vec2(1.0, 0.0),
+            n_rays: 32,
+            max_dist: 100.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        (ArrayWorld::from(map), camera)
+    }
+
+    fn wall_pixels(img: &RgbImage, x: u32) -> usize {
+        let p = params();
+        (0..img.height())
+            .filter(|&y| ![p.ceiling, p.floor].contains(img.get_pixel(x, y)))
+            .count()
+    }
+
+    #[test]
+    fn nearer_walls_are_taller() {
+        let (near, camera) = corridor(3);
+        let (far, _) = corridor(8);
+
+        let near = render_view(&raycast_camera(&near, &camera), &camera, &params());
+        let far = render_view(&raycast_camera(&far, &camera), &camera, &params());
+
+        let (near_wall, far_wall) = (wall_pixels(&near, 32), wall_pixels(&far, 32));
+        assert!(far_wall > 0 && near_wall > far_wall);
+        assert_eq!(*far.get_pixel(32, 0), params().ceiling);
+        assert_eq!(*far.get_pixel(32, 47), params().floor);
+    }
+
+    #[test]
+    fn flat_walls_look_flat() {
+        let mut map = Array2::from_elem((21, 21), Tile::Floor);
+        map.column_mut(20).fill(Tile::Wall);
+        let camera = CameraParams {
+            pos: vec2(10.5, 10.5),
+            facing_unit: vec2(1.0, 0.0),
+            n_rays: 256,
+            max_dist: 100.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        let params = ViewParams {
+            width: 256,
+            height: 96,
+            ..params()
+        };
+        let hits = raycast_camera(ArrayWorld::from(map), &camera);
+
+        let img = render_view(&hits, &camera, &params);
+
+        let heights = (0..256).map(|x| wall_pixels(&img, x)).collect::<Vec<_>>();
+        assert!(heights[128] > 20);
+        assert!(heights.iter().all(|h| h.abs_diff(heights[128]) <= 1));
+    }
+
+    #[test]
+    fn lights_brighten_what_is_near_them() {
+        let (world, camera) = corridor(8);
+        let hits = raycast_camera(&world, &camera);
+        let lights = [Light {
+            x: 8,
+            y: 1,
+            intensity: 0.8,
+            flicker: 0.0,
+            phase: 0.0,
+            curve: FlickerCurve::Buzz,
+        }];
+        let lighting = Lighting {
+            world: &world,
+            lights: &lights,
+            params: LightingParams {
+                ambient: 0.2,
+                radius: 3.0,
+                shadows: true,
+            },
+            time: 0.0,
+        };
+
+        let img = render_lit_view(
+            &hits,
+            &camera,
+            &params(),
+            &Textures::default(),
+            &[],
+            &lighting,
+        )
+        .image;
+
+        // The floor at the camera's feet is out of reach of the light.
+        assert_eq!(*img.get_pixel(32, 47), shade(params().floor, 0.2));
+        let end_wall = shade(material_color(MaterialId::default()), 0.75);
+        assert!(img.get_pixel(32, 24).0[0] > shade(end_wall, 0.8).0[0]);
+    }
+
+    #[test]
+    fn baked_lights_light_whole_tiles() {
+        let (world, camera) = corridor(8);
+        // Lights are positioned by their index into the map, like doors.
+        let world = world.with_lights(vec![Light {
+            x: 1,
+            y: 8,
+            intensity: 0.8,
+            flicker: 0.5,
+            phase: 0.0,
+            curve: FlickerCurve::Buzz,
+        }]);
+        let lightmap = world.bake_lightmap(&LightingParams {
+            ambient: 0.2,
+            radius: 3.0,
+            shadows: true,
+        });
+        let hits = raycast_camera(&world, &camera);
+
+        let img = render_lit_view(
+            &hits,
+            &camera,
+            &params(),
+            &Textures::default(),
+            &[],
+            &lightmap,
+        )
+        .image;
+
+        assert_eq!(lightmap.cell((1, 1)), 0.2);
+        assert_eq!(lightmap.cell((8, 1)), 1.0);
+        assert_eq!(*img.get_pixel(32, 47), shade(params().floor, 0.2));
+        let end_wall = shade(material_color(MaterialId::default()), 0.75);
+        assert_eq!(*img.get_pixel(32, 24), end_wall);
+    }
+
+    #[test]
+    fn images_show_frames_stretched_to_fit() {
+        let frame = RgbImage::from_fn(2, 2, |x, y| Rgb([x as u8, y as u8, 0]));
+        let mut surface = RgbImage::new(4, 6);
+
+        surface.present(&frame).unwrap();
+
+        assert_eq!(surface.size(), (4, 6));
+        assert_eq!(*surface.get_pixel(1, 2), Rgb([0, 0, 0]));
+        assert_eq!(*surface.get_pixel(2, 2), Rgb([1, 0, 0]));
+        assert_eq!(*surface.get_pixel(3, 5), Rgb([1, 1, 0]));
+    }
+
+    #[test]
+    fn renderers_cast_a_ray_per_column() {
+        let (world, camera) = corridor(3);
+        let mut renderer = SoftwareRenderer::new(params(), Textures::default());
+
+        let frame = renderer.render(&world, &camera, 2.0).unwrap();
+
+        let camera = CameraParams {
+            n_rays: 64,
+            ..camera
+        };
+        let hits = raycast_camera(&world, &camera);
+        assert_eq!(frame.image, render_view(&hits, &camera, &params()));
+        assert_eq!(frame.depth.len(), 64);
+    }
+
+    #[test]
+    fn renderers_count_the_rays_and_cells_of_each_frame() {
+        let (world, camera) = corridor(3);
+        let mut renderer = SoftwareRenderer::new(params(), Textures::default());
+        let plain = renderer.render(&world, &camera, 0.0).unwrap();
+
+        renderer.stats = Some(FrameStats::default());
+        let counted = renderer.render(&world, &camera, 0.0).unwrap();
+
+        let stats = renderer.stats.unwrap();
+        assert_eq!(counted.image, plain.image);
+        assert_eq!(stats.rays, 64);
+        // Every ray starts in the corridor and ends at a wall.
+        assert!(stats.cells_per_ray() >= 2.0);
+        assert_eq!(stats.total(), stats.raycast + stats.draw);
+    }
+
+    #[test]
+    fn walls_are_lit_by_the_way_they_face() {
+        let (world, camera) = corridor(3);
+        let hits = raycast_camera(&world, &camera);
+        let params = ViewParams {
+            face_light: [1.0, 1.0, 0.5, 1.0],
+            ..params()
+        };
+
+        let img = render_view(&hits, &camera, &params);
+
+        // The end wall faces west, and the side walls north and south.
+        let base = material_color(MaterialId::default());
+        assert_eq!(*img.get_pixel(32, 24), shade(base, 0.5));
+        assert_eq!(*img.get_pixel(0, 24), base);
+    }
+
+    #[test]
+    fn textured_walls_sample_their_texture() {
+        let (world, camera) = corridor(3);
+        let hits = raycast_camera(&world, &camera);
+        let mut textures = Textures::default();
+        // Red on the left half of the texture, blue on the right.
+        let texture = RgbImage::from_fn(2, 2, |x, _| Rgb([200 * (1 - x as u8), 0, 200 * x as u8]));
+        textures.insert(MaterialId::default(), texture);
+
+        let img = render_textured_view(&hits, &camera, &params(), &textures, &[]).image;
+
+        let colors = (0..64)
+            .flat_map(|x| (0..48).map(move |y| (x, y)))
+            .map(|(x, y)| *img.get_pixel(x, y))
+            .collect::<Vec<_>>();
+        // The end wall faces west, so is shaded.
+        assert!(colors.contains(&Rgb([150, 0, 0])));
+        assert!(colors.contains(&Rgb([0, 0, 150])));
+        let flat = shade(material_color(MaterialId::default()), 0.75);
+        assert!(!colors.contains(&flat));
+    }
+
+    #[test]
+    fn floor_textures_shrink_towards_the_horizon() {
+        let (_, camera) = corridor(3);
+        let mut textures = Textures::default();
+        // Red on the west half of every tile, blue on the east half.
+        let texture = RgbImage::from_fn(2, 2, |x, _| Rgb([200 * (1 - x as u8), 0, 200 * x as u8]));
+        textures.floor = Some(texture);
+        let params = ViewParams {
+            height: 96,
+            ..params()
+        };
+
+        let img = render_textured_view(&[], &camera, &params, &textures, &[]).image;
+
+        // Count how many rows each stripe takes up, from the bottom up.
+        let mut stripes = vec![];
+        for y in (48..96).rev() {
+            match stripes.last_mut() {
+                Some((color, n)) if color == img.get_pixel(32, y) => *n += 1,
+                _ => stripes.push((*img.get_pixel(32, y), 1)),
+            }
+        }
+        assert!(stripes.len() > 4);
+        let rows = stripes.iter().map(|(_, n)| *n).collect::<Vec<_>>();
+        // The bottom stripe is cut off by the edge of the image, and the
+        // farthest alias into single rows.
+        assert!(rows[1] > rows[2] && rows[2] > rows[3]);
+        assert_eq!(*img.get_pixel(32, 0), params.ceiling);
+    }
+
+    #[test]
+    fn sprites_are_hidden_behind_nearer_things() {
+        let (world, camera) = corridor(8);
+        let hits = raycast_camera(&world, &camera);
+        let solid = |c: [u8; 3]| RgbaImage::from_pixel(4, 4, image::Rgba([c[0], c[1], c[2], 255]));
+        let (red, blue, green) = (solid([255, 0, 0]), solid([0, 0, 255]), solid([0, 255, 0]));
+        let sprite = |x: f32, size: f32, image| Sprite {
+            pos: vec2(x, 1.5),
+            elevation: 0.0,
+            width: size,
+            height: size,
+            image,
+        };
+        // The blue sprite is behind the red one, and the green one is behind
+        // the wall at the end of the corridor.
+        let sprites = [
+            sprite(3.5, 0.5, &red),
+            sprite(6.5, 0.5, &blue),
+            sprite(20.5, 1.0, &green),
+        ];
+
+        let frame = render_textured_view(&hits, &camera, &params(), &Textures::default(), &sprites);
+
+        assert_eq!(*frame.image.get_pixel(32, 35), Rgb([255, 0, 0]));
+        assert!(frame
+            .image
+            .pixels()
+            .all(|p| *p != Rgb([0, 0, 255]) && *p != Rgb([0, 255, 0])));
+    }
+
+    #[test]
+    fn frames_keep_the_depth_of_each_column() {
+        let (world, camera) = corridor(8);
+        let hits = raycast_camera(&world, &camera);
+
+        let frame = render_textured_view(&hits, &camera, &params(), &Textures::default(), &[]);
+
+        assert_eq!(frame.depth.len(), 64);
+        assert_ulps_eq!(frame.depth[32], 7.5);
+        assert!(frame.depth.iter().all(|d| *d > 0.0 && *d <= 7.5));
+        let empty = render_textured_view(&[], &camera, &params(), &Textures::default(), &[]);
+        assert!(empty.depth.iter().all(|d| d.is_infinite()));
+    }
+
+    #[test]
+    fn walls_show_over_lower_walls() {
+        let (world, camera) = corridor(8);
+        // A concrete counter a quarter as tall as the walls, across the
+        // corridor.
+        let mut map = world.map().clone();
+        map[(1, 4)] = Tile::Wall;
+        let mut heights = Array2::from_elem(map.dim(), 1.0);
+        heights[(1, 4)] = 0.25;
+        let mut materials = Array2::default(map.dim());
+        materials[(1, 4)] = MaterialId::CONCRETE;
+        let world = ArrayWorld::from(map)
+            .with_heights(heights)
+            .with_materials(materials);
+
+        let stopped = render_view(&raycast_camera(&world, &camera), &camera, &params());
+        let hits = raycast_camera_all(&world, &camera, 0.5);
+        let layered = render_layered_view(&hits, &camera, &params(), &Textures::default(), &[]);
+
+        let wall = shade(material_color(MaterialId::default()), 0.75);
+        let counter = shade(material_color(MaterialId::CONCRETE), 0.75);
+        assert_eq!(*stopped.get_pixel(32, 20), params().ceiling);
+        assert_eq!(*layered.image.get_pixel(32, 20), wall);
+        assert_eq!(*layered.image.get_pixel(32, 30), counter);
+        assert_ulps_eq!(layered.depth[32], 7.5);
+    }
+
+    #[test]
+    fn looking_up_moves_the_horizon_down() {
+        let (world, level) = corridor(8);
+        let up = CameraParams {
+            pitch: 0.25f32.atan(),
+            ..level.clone()
+        };
+        let wall_top = |img: &RgbImage| {
+            (0..img.height())
+                .find(|&y| *img.get_pixel(32, y) != params().ceiling)
+                .unwrap()
+        };
+
+        let level = render_view(&raycast_camera(&world, &level), &level, &params());
+        let up = render_view(&raycast_camera(&world, &up), &up, &params());
+
+        // A quarter of the focal length of 64 pixels lower, give or take
+        // rounding.
+        let shift = wall_top(&up) - wall_top(&level);
+        assert!((15..=16).contains(&shift));
+        assert!(wall_pixels(&up, 32).abs_diff(wall_pixels(&level, 32)) <= 1);
+    }
+
+    #[test]
+    fn the_void_shows_past_the_maximum_distance() {
+        let (world, camera) = corridor(20);
+        let camera = CameraParams {
+            max_dist: 4.0,
+            ..camera
+        };
+        let hits = raycast_camera(&world, &camera);
+        let void = |void| {
+            let params = ViewParams { void, ..params() };
+            render_view(&hits, &camera, &params)
+        };
+        let panorama = RgbImage::from_fn(4, 2, |x, y| Rgb([x as u8, y as u8, 9]));
+
+        let horizon = void(Void::Horizon);
+        let color = void(Void::Color(Rgb([1, 2, 3])));
+        let gradient = void(Void::Gradient {
+            top: Rgb([0, 0, 0]),
+            bottom: Rgb([200, 200, 200]),
+        });
+        let panorama = void(Void::Panorama(panorama));
+
+        // Looking east down the corridor, the middle of the view is past the
+        // maximum distance, and the floor near the camera isn't.
+        assert_eq!(*horizon.get_pixel(32, 24), params().floor);
+        assert_eq!(*color.get_pixel(32, 24), Rgb([1, 2, 3]));
+        assert_eq!(*color.get_pixel(32, 47), params().floor);
+        assert!(gradient.get_pixel(32, 25).0[0] > gradient.get_pixel(32, 22).0[0]);
+        assert_eq!(*panorama.get_pixel(32, 23), Rgb([0, 0, 9]));
+        assert_eq!(*panorama.get_pixel(32, 24), Rgb([0, 1, 9]));
+    }
+
+    #[test]
+    fn rolling_tilts_the_horizon() {
+        let (_, camera) = corridor(3);
+        let params = ViewParams {
+            roll: 0.3,
+            ..params()
+        };
+
+        let img = render_view(&[], &camera, &params);
+
+        // Turned anticlockwise, the horizon is lower on the left.
+        let horizon = |x| {
+            (0..48)
+                .find(|&y| *img.get_pixel(x, y) == params.floor)
+                .unwrap()
+        };
+        assert!(horizon(8) > 24 && horizon(56) < 24);
+    }
+
+    #[test]
+    fn head_bob_follows_the_steps() {
+        let bob = HeadBob {
+            amplitude: 0.05,
+            frequency: 1.0,
+            sway: 0.02,
+        };
+
+        let still = bob.apply(&params(), 0.0);
+        let mid_step = bob.apply(&params(), 0.25);
+        let stepped = bob.apply(&params(), 1.0);
+
+        assert_eq!(still.eye_height, 0.5);
+        assert_eq!(still.roll, 0.0);
+        assert!((mid_step.eye_height - 0.55).abs() < 1e-6);
+        assert!((stepped.eye_height - 0.5).abs() < 1e-6);
+        assert!((stepped.roll - 0.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,102 @@
+//! Counts and timings of the work done to draw a frame, for catching the
+//! raycaster getting slower and for drawing debug overlays.
+
+use std::{cell::Cell, time::Duration};
+
+use crate::camera::{CellInfo, RaycastableWorld};
+
+/// What went into drawing one frame.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStats {
+    /// How many rays were cast.
+    pub rays: usize,
+
+    /// How many times the rays looked up a cell with
+    /// [`RaycastableWorld::cell`], all together. Each ray looks up the cell it
+    /// starts in and every cell it steps into, so this follows the number of
+    /// cells traversed, but a cell looked up twice counts twice.
+    pub cells: usize,
+
+    /// Time spent casting rays.
+    pub raycast: Duration,
+
+    /// Time spent drawing the view from the rays' hits.
+    pub draw: Duration,
+}
+
+impl FrameStats {
+    /// How many cell lookups each ray made on average, or 0 if no rays were
+    /// cast.
+    pub fn cells_per_ray(&self) -> f32 {
+        if self.rays == 0 {
+            return 0.0;
+        }
+        self.cells as f32 / self.rays as f32
+    }
+
+    /// Time spent on the whole frame.
+    pub fn total(&self) -> Duration {
+        self.raycast + self.draw
+    }
+}
+
+/// A world that counts how many times its cells are looked up, such as by the
+/// rays cast through it. Every call to [`RaycastableWorld::cell`] counts,
+/// including repeats of the same cell.
+#[derive(Debug)]
+pub struct CountCells<W> {
+    world: W,
+    count: Cell<usize>,
+}
+
+impl<W> CountCells<W> {
+    pub fn new(world: W) -> Self {
+        Self {
+            world,
+            count: Cell::new(0),
+        }
+    }
+
+    /// How many cell lookups have been made so far.
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+}
+
+impl<W: RaycastableWorld> RaycastableWorld for CountCells<W> {
+    fn cell(&self, pos: (isize, isize)) -> Option<CellInfo> {
+        self.count.set(self.count.get() + 1);
+        self.world.cell(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec2;
+
+    use crate::camera::raycast;
+
+    use super::*;
+
+    /// Open ground with a wall along x = 4.
+    struct Wall;
+
+    impl RaycastableWorld for Wall {
+        fn cell(&self, (x, _): (isize, isize)) -> Option<CellInfo> {
+            Some(CellInfo {
+                solid: x == 4,
+                ..CellInfo::default()
+            })
+        }
+    }
+
+    #[test]
+    fn every_cell_a_ray_passes_through_is_counted() {
+        let world = CountCells::new(Wall);
+
+        raycast(&world, vec2(0.5, 0.5), vec2(1.0, 0.0), 10.0);
+
+        // The cell the ray starts in, the three after it, and the wall.
+        assert_eq!(world.count(), 5);
+    }
+}
@@ -0,0 +1,455 @@
+//! Drawing the first-person view as colored text, so worlds can be explored
+//! in a terminal, such as over SSH.
+//!
+//! [`TerminalView`] draws the hits of [`raycast_camera`] as a [`Widget`] in
+//! characters and 256-color shades, and [`TerminalSurface`] shows frames drawn
+//! by [`render`](crate::render) in half-height blocks, so either can be used
+//! wherever a terminal is all there is.
+
+use std::{
+    convert::Infallible,
+    io::{self, Stdout},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute, terminal,
+};
+use image::{Rgb, RgbImage};
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    buffer::Buffer,
+    layout::Rect,
+    style::Color,
+    widgets::Widget,
+    Terminal,
+};
+
+use crate::{
+    camera::{raycast_camera, CameraParams, CameraRay, RaycastableWorld},
+    player::Player,
+    render::{material_color, stretch, Renderer, Surface},
+    world::ArrayWorld,
+};
+
+/// How far [`run`] walks with each key press, in tiles.
+const WALK_STEP: f32 = 0.25;
+
+/// How far [`run`] turns with each key press, in radians.
+const TURN_STEP: f32 = 0.12;
+
+/// Characters walls are drawn with, from brightest to darkest.
+const WALL_RAMP: [char; 4] = ['█', '▓', '▒', '░'];
+
+/// Characters the floor is drawn with, from nearest to farthest.
+const FLOOR_RAMP: [char; 4] = ['#', 'x', '-', '.'];
+
+#[derive(Debug, Clone)]
+pub struct TerminalParams {
+    /// Colors of floors, ceilings and doors.
+    pub ceiling: Rgb<u8>,
+    pub floor: Rgb<u8>,
+    pub door: Rgb<u8>,
+
+    /// Height of the camera above the floor, in wall units.
+    pub eye_height: f32,
+
+    /// How brightly lit walls facing each direction are, as in
+    /// [`ViewParams::face_light`](crate::render::ViewParams::face_light).
+    pub face_light: [f32; 4],
+
+    /// Distance at which everything has faded to black. Nothing fades if this
+    /// is infinite.
+    pub fog_distance: f32,
+
+    /// How many times taller than it is wide a character is, so walls aren't
+    /// drawn stretched. Usually about 2.
+    pub cell_aspect: f32,
+}
+
+/// A first-person view drawn in text, one column of characters per ray.
+///
+/// Walls are drawn in block characters that get sparser as they get darker,
+/// the floor in characters that get sparser as it gets farther away, and the
+/// ceiling as a plain background.
+pub struct TerminalView<'a> {
+    pub hits: &'a [CameraRay],
+    pub camera: &'a CameraParams,
+    pub params: &'a TerminalParams,
+}
+
+impl Widget for TerminalView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (camera, params) = (self.camera, self.params);
+        let (w, h) = (area.width as u32, area.height as u32);
+        if w == 0 || h == 0 {
+            return;
+        }
+        // Characters per unit on the projection plane, across and down.
+        let focal = w as f32 / camera.projection_plane_width;
+        let focal_y = focal / params.cell_aspect;
+        let horizon = h as f32 / 2.0 + camera.pitch.tan() * focal_y;
+
+        for x in 0..w {
+            let ray = (x as usize * self.hits.len() / w as usize).min(self.hits.len().max(1) - 1);
+            let hit = self.hits.get(ray).and_then(|ray| ray.hit.as_ref());
+            let wall = hit.map(|hit| {
+                let dist = camera.perpendicular_distance(hit);
+                let scale = focal_y / dist.max(1e-3);
+                let bottom = horizon + params.eye_height * scale;
+                (bottom - hit.height * scale, bottom, dist, hit)
+            });
+
+            for y in 0..h {
+                let cell = buf.get_mut(area.x + x as u16, area.y + y as u16);
+                let row = y as f32 + 0.5;
+                match wall {
+                    Some((top, bottom, dist, hit)) if (top..bottom).contains(&row) => {
+                        let base = if hit.door {
+                            params.door
+                        } else {
+                            material_color(hit.material)
+                        };
+                        let k = params.face_light[hit.wall_side as usize] * fog(dist, params);
+                        cell.set_char(ramp(&WALL_RAMP, 1.0 - k))
+                            .set_fg(Color::Indexed(ansi256(shade(base, k))))
+                            .set_bg(Color::Reset);
+                    }
+                    _ if row < horizon => {
+                        let dist = (1.0 - params.eye_height) * focal_y / (horizon - row);
+                        let color = shade(params.ceiling, fog(dist, params));
+                        cell.set_char(' ')
+                            .set_fg(Color::Reset)
+                            .set_bg(Color::Indexed(ansi256(color)));
+                    }
+                    _ => {
+                        let dist = params.eye_height * focal_y / (row - horizon);
+                        let k = fog(dist, params);
+                        // The floor is at its sparsest 8 tiles away, or half
+                        // the fog distance if that's nearer.
+                        let far = (params.fog_distance / 2.0).min(8.0);
+                        cell.set_char(ramp(&FLOOR_RAMP, dist / far))
+                            .set_fg(Color::Indexed(ansi256(shade(params.floor, k))))
+                            .set_bg(Color::Reset);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws views as text into a buffer of characters, as [`TerminalView`] does,
+/// casting a ray for each column of characters.
+#[derive(Debug, Clone)]
+pub struct TerminalRenderer {
+    pub params: TerminalParams,
+
+    /// Size of the view, in characters.
+    pub size: (u16, u16),
+}
+
+impl<W: RaycastableWorld> Renderer<W> for TerminalRenderer {
+    type Frame = Buffer;
+    type Error = Infallible;
+
+    fn render(&mut self, world: W, camera: &CameraParams, _: f32) -> Result<Buffer, Infallible> {
+        let area = Rect::new(0, 0, self.size.0, self.size.1);
+        let camera = CameraParams {
+            n_rays: area.width as usize,
+            ..camera.clone()
+        };
+        let hits = raycast_camera(world, &camera);
+        let mut buf = Buffer::empty(area);
+        TerminalView {
+            hits: &hits,
+            camera: &camera,
+            params: &self.params,
+        }
+        .render(area, &mut buf);
+        Ok(buf)
+    }
+}
+
+/// How much of its brightness something `dist` away keeps through the fog.
+fn fog(dist: f32, params: &TerminalParams) -> f32 {
+    (1.0 - dist / params.fog_distance).clamp(0.0, 1.0)
+}
+
+fn shade(Rgb(c): Rgb<u8>, k: f32) -> Rgb<u8> {
+    Rgb(c.map(|v| (v as f32 * k) as u8))
+}
+
+/// The character `t` of the way along a ramp, in [0, 1].
+fn ramp(chars: &[char], t: f32) -> char {
+    let i = (t.clamp(0.0, 1.0) * chars.len() as f32) as usize;
+    chars[i.min(chars.len() - 1)]
+}
+
+/// The nearest color to `color` of the 256 colors most terminals have, out of
+/// the 6x6x6 color cube and the 24 grays.
+pub fn ansi256(Rgb([r, g, b]): Rgb<u8>) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v - 35) / 40,
+    };
+    let (cr, cg, cb) = (level(r), level(g), level(b));
+    let cube = [
+        LEVELS[cr as usize],
+        LEVELS[cg as usize],
+        LEVELS[cb as usize],
+    ];
+
+    let mean = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = (mean.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_index;
+
+    let dist = |[x, y, z]: [u8; 3]| {
+        [(x, r), (y, g), (z, b)]
+            .iter()
+            .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+    if dist([gray; 3]) < dist(cube) {
+        232 + gray_index
+    } else {
+        16 + 36 * cr + 6 * cg + cb
+    }
+}
+
+/// A terminal that shows frames drawn in pixels, two pixels to each
+/// character using half blocks.
+pub struct TerminalSurface<B: Backend> {
+    terminal: Terminal<B>,
+}
+
+impl<B: Backend> TerminalSurface<B> {
+    pub fn new(terminal: Terminal<B>) -> Self {
+        Self { terminal }
+    }
+
+    pub fn terminal(&mut self) -> &mut Terminal<B> {
+        &mut self.terminal
+    }
+}
+
+impl<B: Backend> Surface for TerminalSurface<B> {
+    type Error = io::Error;
+
+    /// Characters across, and twice the characters down.
+    fn size(&self) -> (u32, u32) {
+        self.terminal
+            .size()
+            .map_or((0, 0), |r| (r.width as u32, r.height as u32 * 2))
+    }
+
+    fn present(&mut self, image: &RgbImage) -> io::Result<()> {
+        self.terminal.draw(|frame| {
+            let area = frame.size();
+            frame.render_widget(HalfBlocks(image), area);
+        })?;
+        Ok(())
+    }
+}
+
+/// An image stretched over an area, each character showing one pixel in its
+/// top half and the pixel below it in its bottom half.
+struct HalfBlocks<'a>(&'a RgbImage);
+
+impl Widget for HalfBlocks<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let image = stretch(self.0, area.width as u32, area.height as u32 * 2);
+        let color = |x, y| Color::Indexed(ansi256(*image.get_pixel(x, y)));
+        for y in 0..area.height {
+            for x in 0..area.width {
+                let (px, py) = (x as u32, y as u32 * 2);
+                buf.get_mut(area.x + x, area.y + y)
+                    .set_char('▀')
+                    .set_fg(color(px, py))
+                    .set_bg(color(px, py + 1));
+            }
+        }
+    }
+}
+
+/// Take over the terminal to walk around `world` from `camera`, until `q` or
+/// Escape is pressed.
+///
+/// WASD or the arrow keys walk and turn a step at a time, and the camera
+/// can't walk through anything the world says isn't
+/// [passable](ArrayWorld::passable).
+pub fn run(world: &ArrayWorld, camera: CameraParams, params: &TerminalParams) -> io::Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = explore(&mut terminal, world, camera, params);
+
+    // Give the terminal back even if exploring failed.
+    terminal::disable_raw_mode()?;
+    execute!(io::stdout(), terminal::LeaveAlternateScreen, cursor::Show)?;
+    result
+}
+
+fn explore(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    world: &ArrayWorld,
+    camera: CameraParams,
+    params: &TerminalParams,
+) -> io::Result<()> {
+    let mut player = Player::new(camera);
+    loop {
+        terminal.draw(|frame| {
+            let area = frame.size();
+            let camera = CameraParams {
+                n_rays: area.width as usize,
+                ..player.camera.clone()
+            };
+            let hits = raycast_camera(world, &camera);
+            let view = TerminalView {
+                hits: &hits,
+                camera: &camera,
+                params,
+            };
+            frame.render_widget(view, area);
+        })?;
+
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let (walk, turn) = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('w') | KeyCode::Up => (1.0, 0.0),
+            KeyCode::Char('s') | KeyCode::Down => (-1.0, 0.0),
+            KeyCode::Char('a') | KeyCode::Left => (0.0, -1.0),
+            KeyCode::Char('d') | KeyCode::Right => (0.0, 1.0),
+            _ => continue,
+        };
+        player.move_forward(world, walk * WALK_STEP, 1.0);
+        player.rotate(turn * TURN_STEP, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec2;
+    use ndarray::Array2;
+    use ratatui::backend::TestBackend;
+
+    use crate::{render::FACE_LIGHT, tile::Tile};
+
+    use super::*;
+
+    fn params() -> TerminalParams {
+        TerminalParams {
+            ceiling: Rgb([10, 10, 10]),
+            floor: Rgb([60, 40, 20]),
+            door: Rgb([150, 90, 40]),
+            eye_height: 0.5,
+            face_light: FACE_LIGHT,
+            fog_distance: f32::INFINITY,
+            cell_aspect: 2.0,
+        }
+    }
+
+    /// How many characters of a column are wall.
+    fn wall_rows(buf: &Buffer, x: u16) -> usize {
+        (0..buf.area.height)
+            .filter(|&y| {
+                WALL_RAMP
+                    .iter()
+                    .any(|c| buf.get(x, y).symbol == c.to_string())
+            })
+            .count()
+    }
+
+    #[test]
+    fn nearer_walls_are_taller_and_floors_thin_out() {
+        let mut map = Array2::from_elem((3, 12), Tile::Wall);
+        map.row_mut(1)
+            .slice_mut(ndarray::s![1..11])
+            .fill(Tile::Floor);
+        let world = ArrayWorld::from(map);
+        let camera = CameraParams {
+            pos: vec2(1.5, 1.5),
+            facing_unit: vec2(1.0, 0.0),
+            n_rays: 40,
+            max_dist: 100.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        let area = Rect::new(0, 0, 40, 20);
+        let mut buf = Buffer::empty(area);
+
+        let hits = raycast_camera(&world, &camera);
+        TerminalView {
+            hits: &hits,
+            camera: &camera,
+            params: &params(),
+        }
+        .render(area, &mut buf);
+
+        // The side walls of the corridor are near at the edges of the view,
+        // and the end wall is far away in the middle.
+        assert!(wall_rows(&buf, 0) > wall_rows(&buf, 20));
+        assert!(wall_rows(&buf, 20) > 0);
+        assert_eq!(buf.get(20, 0).symbol, " ");
+        assert_eq!(buf.get(20, 19).symbol, "#");
+        assert_eq!(buf.get(20, 11).symbol, ".");
+    }
+
+    #[test]
+    fn renderers_draw_the_view_at_their_size() {
+        let world = ArrayWorld::from(Array2::from_elem((4, 4), Tile::Wall));
+        let camera = CameraParams {
+            pos: vec2(1.5, 1.5),
+            facing_unit: vec2(0.0, 1.0),
+            n_rays: 0,
+            max_dist: 10.0,
+            projection_plane_width: 1.0,
+            pitch: 0.0,
+        };
+        let mut renderer = TerminalRenderer {
+            params: params(),
+            size: (12, 6),
+        };
+
+        let buf = renderer.render(&world, &camera, 0.0).unwrap();
+
+        assert_eq!(buf.area, Rect::new(0, 0, 12, 6));
+        assert!((0..12).all(|x| wall_rows(&buf, x) == 6));
+    }
+
+    #[test]
+    fn colors_map_to_the_nearest_of_256() {
+        assert_eq!(ansi256(Rgb([0, 0, 0])), 16);
+        assert_eq!(ansi256(Rgb([255, 0, 0])), 196);
+        assert_eq!(ansi256(Rgb([255, 255, 255])), 231);
+        assert_eq!(ansi256(Rgb([128, 128, 128])), 244);
+        assert_eq!(ansi256(Rgb([200, 180, 90])), 179);
+    }
+
+    #[test]
+    fn surfaces_show_two_pixels_per_character() {
+        let mut surface = TerminalSurface::new(Terminal::new(TestBackend::new(2, 1)).unwrap());
+        let image = RgbImage::from_fn(2, 2, |x, y| Rgb([255 * (x ^ y) as u8, 0, 0]));
+
+        surface.present(&image).unwrap();
+
+        assert_eq!(surface.size(), (2, 2));
+        let buf = surface.terminal().backend().buffer().clone();
+        assert_eq!(buf.get(0, 0).symbol, "▀");
+        assert_eq!(buf.get(0, 0).fg, Color::Indexed(16));
+        assert_eq!(buf.get(0, 0).bg, Color::Indexed(196));
+        assert_eq!(buf.get(1, 0).fg, Color::Indexed(196));
+    }
+}
@@ -0,0 +1,185 @@
+//! Wall textures for the first-person view drawn by [`render`].
+//!
+//! [`render`]: crate::render
+
+use std::{collections::HashMap, path::Path};
+
+use image::{imageops, ImageResult, Rgb, RgbImage};
+
+use crate::{camera::RaycastHit, tile::MaterialId};
+
+/// The textures to draw each material, doors, the floor and the ceiling with.
+/// Anything without a texture is drawn in a flat color.
+#[derive(Debug, Clone, Default)]
+pub struct Textures {
+    materials: HashMap<MaterialId, RgbImage>,
+    animated: HashMap<MaterialId, AnimatedTexture>,
+    door: Option<RgbImage>,
+
+    /// Repeated once per tile across the whole floor, such as carpet.
+    pub floor: Option<RgbImage>,
+
+    /// Repeated once per tile across the whole ceiling, such as ceiling tiles.
+    pub ceiling: Option<RgbImage>,
+}
+
+impl Textures {
+    /// Cut an atlas into tiles of `tile_size` and give them to `materials` in
+    /// order, reading the atlas left to right and then top to bottom. Extra
+    /// tiles or materials are ignored.
+    pub fn from_atlas(atlas: &RgbImage, tile_size: (u32, u32), materials: &[MaterialId]) -> Self {
+        let tiles = split_atlas(atlas, tile_size);
+        Self {
+            materials: materials.iter().copied().zip(tiles).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn insert(&mut self, material: MaterialId, texture: RgbImage) {
+        self.materials.insert(material, texture);
+    }
+
+    /// Animate a material, in place of any still texture it has.
+    pub fn insert_animated(&mut self, material: MaterialId, texture: AnimatedTexture) {
+        self.animated.insert(material, texture);
+    }
+
+    pub fn set_door(&mut self, texture: RgbImage) {
+        self.door = Some(texture);
+    }
+
+    pub fn get(&self, material: MaterialId) -> Option<&RgbImage> {
+        self.materials.get(&material)
+    }
+
+    /// The texture of a material at `time`, in seconds. Animated materials
+    /// show the frame for that time.
+    pub fn get_at(&self, material: MaterialId, time: f32) -> Option<&RgbImage> {
+        match self.animated.get(&material) {
+            Some(animated) => animated.frame(time),
+            None => self.get(material),
+        }
+    }
+
+    /// The texture for whatever a ray hit at `time`, if it has one.
+    pub fn for_hit(&self, hit: &RaycastHit, time: f32) -> Option<&RgbImage> {
+        if hit.door {
+            self.door.as_ref()
+        } else {
+            self.get_at(hit.material, time)
+        }
+    }
+}
+
+/// A texture that loops through a series of frames, such as a flickering
+/// screen or dripping water.
+#[derive(Debug, Clone)]
+pub struct AnimatedTexture {
+    pub frames: Vec<RgbImage>,
+
+    /// How many frames are shown each second.
+    pub fps: f32,
+}
+
+impl AnimatedTexture {
+    /// The frame shown at `time`, in seconds, looping back to the first
+    /// frame after the last. None if there are no frames.
+    pub fn frame(&self, time: f32) -> Option<&RgbImage> {
+        let n = self.frames.len() as i64;
+        let i = ((time * self.fps).floor() as i64).checked_rem_euclid(n)?;
+        self.frames.get(i as usize)
+    }
+}
+
+/// Load a texture or atlas from an image file.
+pub fn load(path: impl AsRef<Path>) -> ImageResult<RgbImage> {
+    Ok(image::open(path)?.into_rgb8())
+}
+
+/// Cut an atlas into tiles of the given size, left to right and then top to
+/// bottom. Partial tiles along the right and bottom edges are dropped.
+pub fn split_atlas(atlas: &RgbImage, (tw, th): (u32, u32)) -> Vec<RgbImage> {
+    if tw == 0 || th == 0 {
+        return vec![];
+    }
+    let (cols, rows) = (atlas.width() / tw, atlas.height() / th);
+    (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| (col, row)))
+        .map(|(col, row)| imageops::crop_imm(atlas, col * tw, row * th, tw, th).to_image())
+        .collect()
+}
+
+/// Sample a texture at `u` across, in [0, 1], and `v` down. `v` wraps around
+/// every unit, so a texture repeats up walls taller than one unit.
+pub fn sample(texture: &RgbImage, u: f32, v: f32) -> Rgb<u8> {
+    let (w, h) = texture.dimensions();
+    let x = ((u.clamp(0.0, 1.0) * w as f32) as u32).min(w - 1);
+    let y = ((v.rem_euclid(1.0) * h as f32) as u32).min(h - 1);
+    *texture.get_pixel(x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atlas_tiles_go_to_materials_in_order() {
+        let atlas = RgbImage::from_fn(8, 4, |x, y| Rgb([(x / 4) as u8, (y / 2) as u8, 0]));
+
+        let textures = Textures::from_atlas(
+            &atlas,
+            (4, 2),
+            &[MaterialId::CONCRETE, MaterialId::TILE, MaterialId::DAMAGED],
+        );
+
+        assert_eq!(split_atlas(&atlas, (4, 2)).len(), 4);
+        assert_eq!(
+            textures.get(MaterialId::CONCRETE).unwrap()[(0, 0)],
+            Rgb([0, 0, 0])
+        );
+        assert_eq!(
+            textures.get(MaterialId::TILE).unwrap()[(3, 1)],
+            Rgb([1, 0, 0])
+        );
+        assert_eq!(
+            textures.get(MaterialId::DAMAGED).unwrap()[(0, 0)],
+            Rgb([0, 1, 0])
+        );
+        assert!(textures.get(MaterialId::YELLOW_WALLPAPER).is_none());
+    }
+
+    #[test]
+    fn animations_loop_through_their_frames() {
+        let frames = (0..3).map(|i| RgbImage::from_pixel(1, 1, Rgb([i, 0, 0])));
+        let mut textures = Textures::default();
+        textures.insert(MaterialId::TILE, RgbImage::new(1, 1));
+        textures.insert_animated(
+            MaterialId::TILE,
+            AnimatedTexture {
+                frames: frames.collect(),
+                fps: 4.0,
+            },
+        );
+
+        let frame = |t| textures.get_at(MaterialId::TILE, t).unwrap()[(0, 0)].0[0];
+
+        assert_eq!([0.0, 0.3, 0.5, 0.8, -0.1].map(frame), [0, 1, 2, 0, 2]);
+        assert!(textures.get_at(MaterialId::CONCRETE, 0.0).is_none());
+        assert!(AnimatedTexture {
+            frames: vec![],
+            fps: 4.0
+        }
+        .frame(1.0)
+        .is_none());
+    }
+
+    #[test]
+    fn sampling_wraps_vertically() {
+        let texture = RgbImage::from_fn(4, 4, |x, y| Rgb([x as u8, y as u8, 0]));
+
+        assert_eq!(sample(&texture, 0.0, 0.0), Rgb([0, 0, 0]));
+        assert_eq!(sample(&texture, 1.0, 0.99), Rgb([3, 3, 0]));
+        assert_eq!(sample(&texture, 0.5, -0.1), Rgb([2, 3, 0]));
+        assert_eq!(sample(&texture, 0.3, 1.6), Rgb([1, 2, 0]));
+    }
+}
@@ -0,0 +1,175 @@
+#[cfg(feature = "render")]
+use image::Rgb;
+
+use crate::util::Corner;
+
+/// What occupies a single grid cell of the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tile {
+    #[default]
+    Wall,
+    Floor,
+    Door,
+    Carpet,
+    Pillar,
+    Stairs,
+    Elevator,
+    Desk,
+    Shelf,
+    /// A low cubicle partition.
+    Partition,
+    /// A wall filling the given corner of the cell, split along the diagonal.
+    Diagonal(Corner),
+    /// Looks and renders like a wall, but can be walked through.
+    SecretWall,
+    /// Standing water, such as a pool.
+    Water(WaterDepth),
+    /// A gentle slope down into a pool.
+    Ramp,
+    /// A wall that can be seen through but not walked through.
+    Window,
+}
+
+/// How deep a tile of water is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WaterDepth {
+    /// Ankle to waist deep, for wading through.
+    Shallow,
+    /// Too deep to stand in.
+    Deep,
+}
+
+impl Tile {
+    pub const ALL: [Tile; 19] = [
+        Tile::Wall,
+        Tile::Floor,
+        Tile::Door,
+        Tile::Carpet,
+        Tile::Pillar,
+        Tile::Stairs,
+        Tile::Elevator,
+        Tile::Desk,
+        Tile::Shelf,
+        Tile::Partition,
+        Tile::Diagonal(Corner::NorthEast),
+        Tile::Diagonal(Corner::NorthWest),
+        Tile::Diagonal(Corner::SouthEast),
+        Tile::Diagonal(Corner::SouthWest),
+        Tile::SecretWall,
+        Tile::Water(WaterDepth::Shallow),
+        Tile::Water(WaterDepth::Deep),
+        Tile::Ramp,
+        Tile::Window,
+    ];
+
+    /// Returns true if this tile blocks movement and rays.
+    ///
+    /// Diagonal walls count as solid, since nothing can walk through them, but
+    /// rays only stop at the filled half. Secret walls count as solid so that
+    /// they are drawn and generated around like walls, even though they can be
+    /// walked through; see [`Tile::is_passable`]. Windows are solid, but don't
+    /// block rays; see [`Tile::is_opaque`].
+    #[inline]
+    pub fn is_solid(self) -> bool {
+        match self {
+            Tile::Wall
+            | Tile::Pillar
+            | Tile::Desk
+            | Tile::Shelf
+            | Tile::Partition
+            | Tile::Diagonal(_)
+            | Tile::SecretWall
+            | Tile::Window => true,
+            Tile::Floor
+            | Tile::Door
+            | Tile::Carpet
+            | Tile::Stairs
+            | Tile::Elevator
+            | Tile::Water(_)
+            | Tile::Ramp => false,
+        }
+    }
+
+    /// Returns true if this tile stops rays, so it's drawn rather than seen
+    /// through.
+    #[inline]
+    pub fn is_opaque(self) -> bool {
+        self.is_solid() && self != Tile::Window
+    }
+
+    /// Returns true if the player can walk through this tile.
+    #[inline]
+    pub fn is_passable(self) -> bool {
+        !self.is_solid() || self == Tile::SecretWall
+    }
+
+    /// The color used to draw this tile on a top-down map.
+    #[cfg(feature = "render")]
+    pub fn color(self) -> Rgb<u8> {
+        match self {
+            Tile::Wall => Rgb([0, 0, 0]),
+            Tile::Floor => Rgb([255, 255, 255]),
+            Tile::Door => Rgb([150, 90, 40]),
+            Tile::Carpet => Rgb([220, 200, 120]),
+            Tile::Pillar => Rgb([100, 100, 100]),
+            Tile::Stairs => Rgb([80, 120, 220]),
+            Tile::Elevator => Rgb([200, 60, 200]),
+            Tile::Desk => Rgb([120, 80, 50]),
+            Tile::Shelf => Rgb([70, 60, 50]),
+            Tile::Partition => Rgb([160, 160, 180]),
+            Tile::Diagonal(_) => Rgb([60, 60, 60]),
+            Tile::SecretWall => Rgb([40, 0, 40]),
+            Tile::Water(WaterDepth::Shallow) => Rgb([110, 190, 235]),
+            Tile::Water(WaterDepth::Deep) => Rgb([30, 100, 200]),
+            Tile::Ramp => Rgb([190, 220, 235]),
+            Tile::Window => Rgb([150, 230, 250]),
+        }
+    }
+
+    /// How deep this tile is, if it's water.
+    pub fn water(self) -> Option<WaterDepth> {
+        match self {
+            Tile::Water(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// The corner filled by this tile, if it's a diagonal wall.
+    pub fn diagonal(self) -> Option<Corner> {
+        match self {
+            Tile::Diagonal(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// What the walls and floor of a tile are covered in, so renderers can pick
+/// textures. The built-in materials are the associated constants, and other
+/// ids are free for renderers to give their own meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaterialId(pub u8);
+
+impl MaterialId {
+    /// Yellow wallpaper over damp carpet, the default.
+    pub const YELLOW_WALLPAPER: MaterialId = MaterialId(0);
+    /// Bare concrete.
+    pub const CONCRETE: MaterialId = MaterialId(1);
+    /// White ceramic tile.
+    pub const TILE: MaterialId = MaterialId(2);
+    /// Crumbling, water-stained plaster.
+    pub const DAMAGED: MaterialId = MaterialId(3);
+}
+
+impl From<bool> for Tile {
+    /// Convert a wall/empty boolean into a plain wall or floor tile.
+    fn from(solid: bool) -> Self {
+        if solid {
+            Tile::Wall
+        } else {
+            Tile::Floor
+        }
+    }
+}
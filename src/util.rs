@@ -1,12 +1,15 @@
-use std::{
+use alloc::{vec, vec::Vec};
+use core::{
     cmp::Ordering,
     ops::{Add, Sub},
 };
 
-use cgmath::{vec2, BaseNum, One, Vector2, Zero};
 use rand::{distributions::Standard, prelude::Distribution, seq::SliceRandom, Rng};
 
+use crate::math::{vec2, BaseNum, InnerSpace, One, Vector2, Zero};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     East = 0,
     North = 1,
@@ -15,6 +18,7 @@ pub enum Direction {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Axis {
     Horizontal,
     Vertical,
@@ -31,7 +35,7 @@ impl From<Vector2<f32>> for Direction {
     }
 }
 
-impl std::ops::Neg for Direction {
+impl core::ops::Neg for Direction {
     type Output = Direction;
 
     fn neg(self) -> Self::Output {
@@ -46,7 +50,7 @@ impl std::ops::Neg for Direction {
 
 impl<S> From<Direction> for Vector2<S>
 where
-    S: One + Zero + std::ops::Neg<Output = S>,
+    S: One + Zero + core::ops::Neg<Output = S>,
 {
     fn from(value: Direction) -> Self {
         match value {
@@ -102,7 +106,57 @@ impl Direction {
     }
 }
 
+/// A corner of a grid cell, such as the half of a cell filled by a diagonal
+/// wall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Corner {
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+impl Corner {
+    pub const ALL: [Corner; 4] = [
+        Corner::NorthEast,
+        Corner::NorthWest,
+        Corner::SouthEast,
+        Corner::SouthWest,
+    ];
+
+    /// The two sides of a cell that meet at this corner, east or west first.
+    pub fn sides(self) -> (Direction, Direction) {
+        match self {
+            Corner::NorthEast => (Direction::East, Direction::North),
+            Corner::NorthWest => (Direction::West, Direction::North),
+            Corner::SouthEast => (Direction::East, Direction::South),
+            Corner::SouthWest => (Direction::West, Direction::South),
+        }
+    }
+
+    /// The diagonal across a unit cell that cuts off this corner, as a normal
+    /// pointing into the corner and the offset `c` such that the diagonal is
+    /// the set of points `p` with `normal . p = c`.
+    pub fn diagonal(self) -> (Vector2<f32>, f32) {
+        match self {
+            Corner::NorthEast => (vec2(1.0, 1.0), 1.0),
+            Corner::NorthWest => (vec2(-1.0, 1.0), 0.0),
+            Corner::SouthEast => (vec2(1.0, -1.0), 0.0),
+            Corner::SouthWest => (vec2(-1.0, -1.0), -1.0),
+        }
+    }
+
+    /// Returns true if a point in the unit cell bounded by (0, 0) and (1, 1)
+    /// lies in the half of the cell on this corner's side of the diagonal.
+    pub fn contains(self, p: Vector2<f32>) -> bool {
+        let (normal, c) = self.diagonal();
+        normal.dot(p) >= c
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rectangle<O, L> {
     pub x: O,
     pub y: O,
@@ -136,6 +190,86 @@ impl<O: BaseNum, L: BaseNum> Rectangle<O, L> {
     }
 }
 
+impl Rectangle<isize, usize> {
+    /// Exclusive right edge.
+    #[inline]
+    pub fn x_end(&self) -> isize {
+        self.x + self.w as isize
+    }
+
+    /// Exclusive bottom edge.
+    #[inline]
+    pub fn y_end(&self) -> isize {
+        self.y + self.h as isize
+    }
+
+    /// Returns true if the two rectangles share at least one tile.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.x < other.x_end()
+            && other.x < self.x_end()
+            && self.y < other.y_end()
+            && other.y < self.y_end()
+    }
+
+    /// Returns true if the two rectangles share at least one tile, or lie side
+    /// by side with a common edge. Rectangles meeting only at a corner don't
+    /// touch.
+    pub fn touches(&self, other: &Self) -> bool {
+        let x_overlap = self.x < other.x_end() && other.x < self.x_end();
+        let y_overlap = self.y < other.y_end() && other.y < self.y_end();
+        let x_touch = self.x <= other.x_end() && other.x <= self.x_end();
+        let y_touch = self.y <= other.y_end() && other.y <= self.y_end();
+        (x_overlap && y_touch) || (y_overlap && x_touch)
+    }
+}
+
+/// A shape made of a union of non-overlapping rectangles, such as an L or T
+/// shaped room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Region {
+    pub rects: Vec<Rectangle<isize, usize>>,
+}
+
+impl Region {
+    pub fn contains(&self, (x, y): (isize, isize)) -> bool {
+        self.rects
+            .iter()
+            .any(|r| x >= r.x && x < r.x_end() && y >= r.y && y < r.y_end())
+    }
+
+    pub fn area(&self) -> usize {
+        self.rects.iter().map(|r| r.w * r.h).sum()
+    }
+
+    /// The smallest rectangle containing the whole region.
+    pub fn bounds(&self) -> Rectangle<isize, usize> {
+        let x0 = self.rects.iter().map(|r| r.x).min().unwrap_or(0);
+        let y0 = self.rects.iter().map(|r| r.y).min().unwrap_or(0);
+        let x1 = self.rects.iter().map(|r| r.x_end()).max().unwrap_or(0);
+        let y1 = self.rects.iter().map(|r| r.y_end()).max().unwrap_or(0);
+        Rectangle {
+            x: x0,
+            y: y0,
+            w: (x1 - x0) as usize,
+            h: (y1 - y0) as usize,
+        }
+    }
+
+    /// Every tile covered by the region.
+    pub fn cells(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
+        self.rects
+            .iter()
+            .flat_map(|r| (r.x..r.x_end()).flat_map(move |x| (r.y..r.y_end()).map(move |y| (x, y))))
+    }
+}
+
+impl From<Rectangle<isize, usize>> for Region {
+    fn from(r: Rectangle<isize, usize>) -> Self {
+        Self { rects: vec![r] }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RelativeBounds<T> {
     pub forward: T,
@@ -226,18 +360,69 @@ impl<T> RelativeBounds<T> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Line {
     pub x: isize,
     pub y: isize,
     pub length: usize,
+    /// How many tiles thick the line is, extending in the positive direction
+    /// perpendicular to its axis.
+    pub width: usize,
     pub axis: Axis,
 }
 
 impl Line {
+    /// The tiles covered by this line, as a rectangle.
+    pub fn bounds(&self) -> Rectangle<isize, usize> {
+        match self.axis {
+            Axis::Horizontal => Rectangle {
+                x: self.x,
+                y: self.y,
+                w: self.length + 1,
+                h: self.width,
+            },
+            Axis::Vertical => Rectangle {
+                x: self.x,
+                y: self.y,
+                w: self.width,
+                h: self.length + 1,
+            },
+        }
+    }
+
     pub fn points(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
-        (0..=self.length as isize).map(|i| match self.axis {
-            Axis::Horizontal => (self.x + i, self.y),
-            Axis::Vertical => (self.x, self.y + i),
+        (0..=self.length as isize).flat_map(move |i| {
+            (0..self.width as isize).map(move |j| match self.axis {
+                Axis::Horizontal => (self.x + i, self.y + j),
+                Axis::Vertical => (self.x + j, self.y + i),
+            })
         })
     }
 }
+
+/// The SplitMix64 output function, which scrambles a 64-bit value such that
+/// nearby inputs produce unrelated outputs.
+#[inline]
+pub fn splitmix64(z: u64) -> u64 {
+    let z = z.wrapping_add(0x9e3779b97f4a7c15);
+    let z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Derive a new seed from a parent seed and a sequence of values.
+pub fn derive_seed(seed: u64, values: impl IntoIterator<Item = u64>) -> u64 {
+    values
+        .into_iter()
+        .fold(splitmix64(seed), |acc, v| splitmix64(acc ^ v))
+}
+
+/// Derive a seed for a named stage of generation. Unlike the index of the
+/// stage, its name doesn't change when other stages are added or removed.
+pub fn named_seed(seed: u64, name: &str) -> u64 {
+    // FNV-1a, which unlike std's hashers is guaranteed to never change.
+    let hash = name.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    derive_seed(seed, [hash])
+}
@@ -0,0 +1,277 @@
+//! A window to walk around a world in, drawing the first-person view of
+//! [`render`](crate::render) in real time.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    num::NonZeroU32,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use image::RgbImage;
+use softbuffer::SoftBufferError;
+use winit::{
+    application::ApplicationHandler,
+    error::{EventLoopError, OsError},
+    event::{ElementState, KeyEvent, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Window, WindowId},
+};
+
+use crate::{
+    camera::CameraParams,
+    player::{MoveInput, MovementParams, Player},
+    render::{Renderer, SoftwareRenderer, Surface, ViewParams},
+    textures::Textures,
+    world::ArrayWorld,
+};
+
+#[derive(Debug, Clone)]
+pub struct WindowParams {
+    pub title: String,
+
+    /// Size of each drawn pixel on screen, in screen pixels. Bigger pixels are
+    /// quicker to draw, and look chunkier.
+    pub pixel_size: u32,
+
+    /// How many frames are drawn each second.
+    pub fps: f32,
+
+    /// How fast the camera walks and turns.
+    pub movement: MovementParams,
+}
+
+impl Default for WindowParams {
+    fn default() -> Self {
+        Self {
+            title: "backrooms".into(),
+            pixel_size: 3,
+            fps: 60.0,
+            movement: MovementParams::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WindowError {
+    EventLoop(EventLoopError),
+    Os(OsError),
+    Surface(SoftBufferError),
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowError::EventLoop(e) => write!(f, "event loop failed: {e}"),
+            WindowError::Os(e) => write!(f, "couldn't open a window: {e}"),
+            WindowError::Surface(e) => write!(f, "couldn't draw to the window: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for WindowError {}
+
+impl From<EventLoopError> for WindowError {
+    fn from(e: EventLoopError) -> Self {
+        WindowError::EventLoop(e)
+    }
+}
+
+impl From<OsError> for WindowError {
+    fn from(e: OsError) -> Self {
+        WindowError::Os(e)
+    }
+}
+
+impl From<SoftBufferError> for WindowError {
+    fn from(e: SoftBufferError) -> Self {
+        WindowError::Surface(e)
+    }
+}
+
+/// A window's contents, drawn to in software.
+pub struct WindowSurface {
+    window: Rc<Window>,
+    surface: softbuffer::Surface<Rc<Window>, Rc<Window>>,
+}
+
+impl WindowSurface {
+    pub fn new(window: Rc<Window>) -> Result<Self, SoftBufferError> {
+        let context = softbuffer::Context::new(window.clone())?;
+        let surface = softbuffer::Surface::new(&context, window.clone())?;
+        Ok(Self { window, surface })
+    }
+}
+
+impl Surface for WindowSurface {
+    type Error = SoftBufferError;
+
+    fn size(&self) -> (u32, u32) {
+        self.window.inner_size().into()
+    }
+
+    fn present(&mut self, image: &RgbImage) -> Result<(), SoftBufferError> {
+        let (w, h) = self.size();
+        let (Some(nw), Some(nh)) = (NonZeroU32::new(w), NonZeroU32::new(h)) else {
+            // Minimized, so there's nothing to draw to.
+            return Ok(());
+        };
+        self.surface.resize(nw, nh)?;
+        let (iw, ih) = image.dimensions();
+        let mut buffer = self.surface.buffer_mut()?;
+        for (i, pixel) in buffer.iter_mut().enumerate() {
+            let (x, y) = (i as u32 % w, i as u32 / w);
+            let [r, g, b] = image.get_pixel(x * iw / w, y * ih / h).0;
+            *pixel = (r as u32) << 16 | (g as u32) << 8 | b as u32;
+        }
+        buffer.present()
+    }
+}
+
+/// Open a window onto `world` from `camera`, and let the player walk around it
+/// until the window is closed.
+///
+/// WASD or the arrow keys walk and turn, Q and E step sideways, and Shift
+/// sprints. The camera can't walk through anything the world says isn't
+/// [passable](ArrayWorld::passable). The size of the view follows the size of
+/// the window.
+pub fn run(
+    world: ArrayWorld,
+    camera: CameraParams,
+    view: ViewParams,
+    textures: Textures,
+    params: WindowParams,
+) -> Result<(), WindowError> {
+    let event_loop = EventLoop::new()?;
+    let mut app = App {
+        world,
+        player: Player::new(camera),
+        renderer: SoftwareRenderer::new(view, textures),
+        params,
+        surface: None,
+        held: HashSet::new(),
+        start: Instant::now(),
+        last_frame: Instant::now(),
+        error: None,
+    };
+    event_loop.run_app(&mut app)?;
+    match app.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+struct App {
+    world: ArrayWorld,
+    player: Player,
+    renderer: SoftwareRenderer,
+    params: WindowParams,
+    surface: Option<WindowSurface>,
+
+    /// Keys being held down.
+    held: HashSet<KeyCode>,
+
+    start: Instant,
+    last_frame: Instant,
+
+    /// What went wrong, if the window had to close because of an error.
+    error: Option<WindowError>,
+}
+
+impl App {
+    fn open(&mut self, event_loop: &ActiveEventLoop) -> Result<(), WindowError> {
+        let attributes = Window::default_attributes().with_title(&self.params.title);
+        let window = Rc::new(event_loop.create_window(attributes)?);
+        self.surface = Some(WindowSurface::new(window)?);
+        Ok(())
+    }
+
+    /// Walk and turn for the keys held over the last `dt` seconds.
+    fn step(&mut self, dt: f32) {
+        let held = |keys: &[KeyCode]| keys.iter().any(|k| self.held.contains(k)) as i32 as f32;
+        let input = MoveInput {
+            forward: held(&[KeyCode::KeyW, KeyCode::ArrowUp])
+                - held(&[KeyCode::KeyS, KeyCode::ArrowDown]),
+            strafe: held(&[KeyCode::KeyE]) - held(&[KeyCode::KeyQ]),
+            turn: held(&[KeyCode::KeyD, KeyCode::ArrowRight])
+                - held(&[KeyCode::KeyA, KeyCode::ArrowLeft]),
+            sprint: held(&[KeyCode::ShiftLeft, KeyCode::ShiftRight]) > 0.0,
+        };
+        self.player
+            .update(&self.world, &input, &self.params.movement, dt);
+    }
+
+    fn draw(&mut self) -> Result<(), WindowError> {
+        let Some(surface) = &mut self.surface else {
+            return Ok(());
+        };
+        let (w, h) = surface.size();
+        let pixel = self.params.pixel_size.max(1);
+        let view = &mut self.renderer.view;
+        view.width = (w / pixel).max(1);
+        view.height = (h / pixel).max(1);
+        let time = self.start.elapsed().as_secs_f32();
+        let Ok(frame) = self.renderer.render(&self.world, &self.player.camera, time);
+        surface.present(&frame.image)?;
+        Ok(())
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.surface.is_some() {
+            return;
+        }
+        if let Err(e) = self.open(event_loop) {
+            self.error = Some(e);
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => match (key, state) {
+                (KeyCode::Escape, _) => event_loop.exit(),
+                (_, ElementState::Pressed) => {
+                    self.held.insert(key);
+                }
+                (_, ElementState::Released) => {
+                    self.held.remove(&key);
+                }
+            },
+            WindowEvent::RedrawRequested => {
+                let now = Instant::now();
+                let dt = now.duration_since(self.last_frame).as_secs_f32();
+                self.last_frame = now;
+                self.step(dt);
+                if let Err(e) = self.draw() {
+                    self.error = Some(e);
+                    event_loop.exit();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(surface) = &self.surface else {
+            return;
+        };
+        let next = self.last_frame + Duration::from_secs_f32(1.0 / self.params.fps.max(1.0));
+        if Instant::now() >= next {
+            surface.window.request_redraw();
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(next));
+    }
+}
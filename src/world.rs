@@ -1,26 +1,461 @@
+use std::{collections::BTreeMap, f32::consts::TAU};
+
+use cgmath::{vec2, InnerSpace, Vector2};
 use ndarray::Array2;
 
-use crate::camera::RaycastableWorld;
+use crate::{
+    camera::{CellGrid, CellInfo, RaycastableWorld, ThinWall},
+    lighting::{Lighting, LightingParams, Lightmap},
+    tile::{MaterialId, Tile},
+    util::{derive_seed, Axis, Direction},
+    worldgen::doors::Door,
+};
+
+/// How many times per second a flickering light buzzes.
+const FLICKER_HZ: f32 = 6.0;
+
+/// A fluorescent ceiling light fixture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Light {
+    /// Position of the fixture, in the same coordinates as the map it was
+    /// placed on.
+    pub x: isize,
+    pub y: isize,
+
+    /// Brightness of the light when it isn't flickering, in [0, 1].
+    pub intensity: f32,
+
+    /// How far the light dims when it flickers, as a fraction of its intensity.
+    /// Steady lights have a flicker of 0.
+    pub flicker: f32,
+
+    /// Offset of the flicker cycle in radians, so lights don't flicker in sync.
+    pub phase: f32,
+
+    /// The way the light flickers.
+    pub curve: FlickerCurve,
+}
+
+/// How a flickering light dims over time.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlickerCurve {
+    /// The buzz of a fluorescent tube: full intensity most of the time, with
+    /// short sharp dips several times a second.
+    #[default]
+    Buzz,
+
+    /// A failing tube, stuttering at random. Each seed stutters differently.
+    Stutter { seed: u64 },
+
+    /// An emergency strobe, flashing `hz` times a second and lit for `duty` of
+    /// each flash, in [0, 1].
+    Strobe { hz: f32, duty: f32 },
+}
+
+impl Light {
+    /// Brightness of the light at time `t`, in seconds, between its intensity
+    /// dimmed by its flicker and its full intensity.
+    pub fn brightness(&self, t: f32) -> f32 {
+        let dim = match self.curve {
+            FlickerCurve::Buzz => {
+                let wave = 0.5 + 0.5 * (t * FLICKER_HZ * TAU + self.phase).sin();
+                wave.powi(8)
+            }
+            FlickerCurve::Stutter { seed } => {
+                value_noise(seed, t * FLICKER_HZ + self.phase / TAU).powi(4)
+            }
+            FlickerCurve::Strobe { hz, duty } => {
+                let cycle = (t * hz + self.phase / TAU).rem_euclid(1.0);
+                if cycle < duty {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        };
+        self.intensity * (1.0 - self.flicker * dim)
+    }
+}
+
+/// Smooth 1D noise in [0, 1], with a random value at each integer that's eased
+/// between.
+fn value_noise(seed: u64, x: f32) -> f32 {
+    let at = |i: f32| (derive_seed(seed, [i as i64 as u64]) >> 40) as f32 / (1 << 24) as f32;
+    let i = x.floor();
+    let t = x - i;
+    let t = t * t * (3.0 - 2.0 * t);
+    at(i) + (at(i + 1.0) - at(i)) * t
+}
+
+/// Paint on the floor of a tile, such as the lane markings of a parking garage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Decal {
+    /// Position of the tile the decal is painted on, in the same coordinates
+    /// as the map it was placed on.
+    pub x: isize,
+    pub y: isize,
+
+    pub kind: DecalKind,
+
+    /// The direction the decal points. Lines run along it.
+    pub facing: Direction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecalKind {
+    /// A dash of the line down the middle of a driving lane.
+    LaneLine,
+
+    /// A line between two parking bays.
+    BayLine,
+
+    /// An arrow showing which way traffic goes.
+    Arrow,
+}
+
+impl DecalKind {
+    pub const ALL: [DecalKind; 3] = [DecalKind::LaneLine, DecalKind::BayLine, DecalKind::Arrow];
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArrayWorld {
-    map: Array2<bool>,
+    map: Array2<Tile>,
+    lights: Vec<Light>,
+
+    /// Ceiling height of every tile, indexed like `map`, in wall units.
+    heights: Option<Array2<f32>>,
+
+    /// Material of every tile, indexed like `map`.
+    materials: Option<Array2<MaterialId>>,
+
+    /// Doors, positioned by their index into `map`.
+    doors: Vec<Door>,
+
+    /// Which of `doors` is on each tile, indexed like `map`, so that rays
+    /// don't search every door at every step.
+    door_at: Array2<Option<usize>>,
+
+    /// Whether rays need to look up each tile, indexed like `map`, for
+    /// [`RaycastableWorld::grid`].
+    grid: Array2<u8>,
+
+    /// Floor markings, positioned by their index into `map`.
+    decals: Vec<Decal>,
+
+    /// Light baked from `lights`.
+    lightmap: Option<Lightmap>,
+
+    /// Named per-tile scalar fields, each indexed like `map`.
+    fields: BTreeMap<String, Array2<f32>>,
 }
 
-impl RaycastableWorld for ArrayWorld {
-    fn exists(&self, (x, y): (isize, isize)) -> bool {
+impl ArrayWorld {
+    pub fn with_lights(self, lights: Vec<Light>) -> Self {
+        Self { lights, ..self }
+    }
+
+    /// Set the ceiling height of every tile. `heights` must have the same
+    /// shape as the map.
+    pub fn with_heights(self, heights: Array2<f32>) -> Self {
+        assert_eq!(heights.dim(), self.map.dim(), "height map has wrong shape");
+        Self {
+            heights: Some(heights),
+            ..self
+        }
+    }
+
+    /// Set the material of every tile. `materials` must have the same shape as
+    /// the map.
+    pub fn with_materials(self, materials: Array2<MaterialId>) -> Self {
+        assert_eq!(
+            materials.dim(),
+            self.map.dim(),
+            "material map has wrong shape"
+        );
+        Self {
+            materials: Some(materials),
+            ..self
+        }
+    }
+
+    /// Set the doors of the world. Each door's position is its index into the
+    /// map, so doors from a generated world can be used as they are.
+    pub fn with_doors(self, doors: Vec<Door>) -> Self {
+        let mut door_at = Array2::from_elem(self.map.dim(), None);
+        for (i, d) in doors.iter().enumerate() {
+            if d.x < 0 || d.y < 0 {
+                continue;
+            }
+            if let Some(cell @ None) = door_at.get_mut((d.x as usize, d.y as usize)) {
+                *cell = Some(i);
+            }
+        }
+        Self {
+            grid: ray_grid(&self.map, &door_at),
+            doors,
+            door_at,
+            ..self
+        }
+    }
+
+    /// Set the floor markings of the world. Like doors, each decal's position
+    /// is its index into the map.
+    pub fn with_decals(self, decals: Vec<Decal>) -> Self {
+        Self { decals, ..self }
+    }
+
+    /// Add a named per-tile scalar field, such as one stored by
+    /// [`NoiseOverlay`](crate::worldgen::noise::NoiseOverlay), replacing any
+    /// field with the same name. `field` must have the same shape as the map.
+    pub fn with_field(mut self, name: impl Into<String>, field: Array2<f32>) -> Self {
+        assert_eq!(field.dim(), self.map.dim(), "field has wrong shape");
+        self.fields.insert(name.into(), field);
+        self
+    }
+
+    /// Set the baked light of the world, as from [`ArrayWorld::bake_lightmap`].
+    pub fn with_lightmap(self, lightmap: Lightmap) -> Self {
+        Self {
+            lightmap: Some(lightmap),
+            ..self
+        }
+    }
+
+    /// Work out how brightly each tile is lit by the world's lights, for
+    /// [`ArrayWorld::with_lightmap`]. Like doors, each light's position is its
+    /// index into the map.
+    pub fn bake_lightmap(&self, params: &LightingParams) -> Lightmap {
+        let lights = self
+            .lights
+            .iter()
+            .map(|l| Light {
+                x: l.y,
+                y: l.x,
+                ..*l
+            })
+            .collect::<Vec<_>>();
+        let lighting = Lighting {
+            world: self,
+            lights: &lights,
+            params: *params,
+            time: 0.0,
+        };
+        let (h, w) = self.map.dim();
+        lighting.bake((w, h))
+    }
+
+    pub fn map(&self) -> &Array2<Tile> {
+        &self.map
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn doors(&self) -> &[Door] {
+        &self.doors
+    }
+
+    pub fn decals(&self) -> &[Decal] {
+        &self.decals
+    }
+
+    pub fn lightmap(&self) -> Option<&Lightmap> {
+        self.lightmap.as_ref()
+    }
+
+    /// Index into `doors` of the door at a tile, if there is one.
+    fn door_index(&self, (x, y): (isize, isize)) -> Option<usize> {
         if x < 0 || y < 0 {
-            return false;
+            return None;
         }
-        self.map
+        self.door_at
             .get((y as usize, x as usize))
             .copied()
-            .unwrap_or(false)
+            .flatten()
+    }
+
+    /// The door at a tile, if there is one.
+    pub fn door(&self, pos: (isize, isize)) -> Option<&Door> {
+        self.door_index(pos).map(|i| &self.doors[i])
+    }
+
+    /// The door at a tile, if there is one, for opening, closing and locking.
+    /// Doors are found by where they were when set with
+    /// [`ArrayWorld::with_doors`], so moving one here doesn't move it in the
+    /// world.
+    pub fn door_mut(&mut self, pos: (isize, isize)) -> Option<&mut Door> {
+        self.door_index(pos).map(|i| &mut self.doors[i])
+    }
+
+    pub fn tile(&self, (x, y): (isize, isize)) -> Option<Tile> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        self.map.get((y as usize, x as usize)).copied()
+    }
+
+    /// Returns true if the player can walk into a tile. Secret walls are drawn
+    /// as walls but are passable, and so are open doors.
+    pub fn passable(&self, pos: (isize, isize)) -> bool {
+        let door_open = self.door(pos).is_none_or(Door::is_open);
+        door_open && self.tile(pos).is_some_and(Tile::is_passable)
+    }
+
+    /// Move from `pos` by `step`, sliding along anything that isn't
+    /// [passable](Self::passable) rather than stopping dead at it.
+    pub fn slide(&self, pos: Vector2<f32>, step: Vector2<f32>) -> Vector2<f32> {
+        if step.magnitude2() == 0.0 {
+            return pos;
+        }
+        let passable =
+            |p: Vector2<f32>| self.passable((p.x.floor() as isize, p.y.floor() as isize));
+        let mut pos = pos;
+        for axis in [vec2(step.x, 0.0), vec2(0.0, step.y)] {
+            if passable(pos + axis) {
+                pos += axis;
+            }
+        }
+        pos
+    }
+
+    /// The ceiling height at a tile, in wall units. Worlds without a height
+    /// map have a uniform height of 1.
+    pub fn height(&self, (x, y): (isize, isize)) -> f32 {
+        if x < 0 || y < 0 {
+            return 1.0;
+        }
+        self.heights
+            .as_ref()
+            .and_then(|h| h.get((y as usize, x as usize)).copied())
+            .unwrap_or(1.0)
+    }
+
+    /// The material of a tile. Worlds without a material map, and tiles off
+    /// the map, have the default material.
+    pub fn material(&self, (x, y): (isize, isize)) -> MaterialId {
+        if x < 0 || y < 0 {
+            return MaterialId::default();
+        }
+        self.materials
+            .as_ref()
+            .and_then(|m| m.get((y as usize, x as usize)).copied())
+            .unwrap_or_default()
+    }
+
+    /// The named per-tile field, indexed like the map, if the world has one.
+    pub fn field(&self, name: &str) -> Option<&Array2<f32>> {
+        self.fields.get(name)
+    }
+}
+
+impl RaycastableWorld for ArrayWorld {
+    fn cell(&self, pos: (isize, isize)) -> Option<CellInfo> {
+        let tile = self.tile(pos)?;
+        let door = self.door(pos).filter(|d| !d.is_open()).map(|d| ThinWall {
+            // The map is indexed by `(y, x)`, so a door facing east or west in
+            // map coordinates spans the x axis here.
+            axis: match d.facing {
+                Direction::East | Direction::West => Axis::Horizontal,
+                Direction::North | Direction::South => Axis::Vertical,
+            },
+            // Closed doors are set into the middle of their doorway.
+            offset: 0.5,
+            door: true,
+        });
+        Some(CellInfo {
+            solid: tile.is_opaque(),
+            material: self.material(pos),
+            height: self.height(pos),
+            diagonal: tile.diagonal(),
+            thin: door,
+            see_through: tile == Tile::Window,
+        })
+    }
+
+    fn grid(&self) -> Option<CellGrid<'_>> {
+        Some(CellGrid {
+            cells: self.grid.as_slice()?,
+            width: self.map.ncols(),
+        })
+    }
+}
+
+/// Mark every tile rays can't pass straight through, as it would be looked
+/// up by [`ArrayWorld::cell`]. Doors are marked whether they're open or not,
+/// since they can be closed later.
+fn ray_grid(map: &Array2<Tile>, door_at: &Array2<Option<usize>>) -> Array2<u8> {
+    Array2::from_shape_fn(map.dim(), |i| {
+        let tile = map[i];
+        let empty = !tile.is_opaque() && tile.diagonal().is_none() && tile != Tile::Window;
+        (!empty || door_at[i].is_some()) as u8
+    })
+}
+
+impl From<Array2<Tile>> for ArrayWorld {
+    fn from(map: Array2<Tile>) -> Self {
+        let door_at = Array2::from_elem(map.dim(), None);
+        Self {
+            grid: ray_grid(&map, &door_at),
+            door_at,
+            map,
+            lights: vec![],
+            heights: None,
+            materials: None,
+            doors: vec![],
+            decals: vec![],
+            lightmap: None,
+            fields: BTreeMap::new(),
+        }
     }
 }
 
 impl From<Array2<bool>> for ArrayWorld {
     fn from(map: Array2<bool>) -> Self {
-        Self { map }
+        map.mapv(Tile::from).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::worldgen::doors::DoorState;
+
+    use super::*;
+
+    #[test]
+    fn walking_slides_along_walls() {
+        let mut map = Array2::from_elem((4, 4), Tile::Floor);
+        map.column_mut(2).fill(Tile::Wall);
+        let world = ArrayWorld::from(map);
+
+        let pos = world.slide(vec2(1.5, 1.5), vec2(0.8, 0.3));
+
+        assert_eq!(pos, vec2(1.5, 1.8));
+        assert_eq!(world.slide(pos, vec2(-0.5, 0.0)), vec2(1.0, 1.8));
+    }
+
+    #[test]
+    fn doors_are_found_by_their_tile() {
+        let map = Array2::from_elem((4, 5), Tile::Door);
+        let door = |x, y| Door {
+            x,
+            y,
+            facing: Direction::East,
+            state: DoorState::Closed,
+            key: None,
+        };
+        // Off the map, then at map index (1, 3), which is x = 3, y = 1.
+        let mut world = ArrayWorld::from(map).with_doors(vec![door(-1, 2), door(1, 3)]);
+
+        assert!(world.door((1, 3)).is_none());
+        assert!(!world.passable((3, 1)));
+        world.door_mut((3, 1)).unwrap().open();
+        assert!(world.passable((3, 1)));
+        assert_eq!(world.door((3, 1)), Some(&world.doors()[1]));
     }
 }
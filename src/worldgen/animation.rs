@@ -0,0 +1,200 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, DynamicImage, Frame, ImageResult,
+};
+use ndarray::Array2;
+use rand::Rng;
+
+use crate::{
+    tile::Tile,
+    util::{Line, Rectangle, Region},
+};
+
+use super::{
+    hallways::BspNode,
+    observer::GenerationObserver,
+    pipeline::{GeneratedWorld, Pipeline, WorldGenerator},
+    raster::rasterize,
+    render_to_img,
+};
+
+/// One frame per partition of a BSP tree, showing the map being split up one
+/// hallway at a time.
+///
+/// Partitions and rings are added in breadth-first order, so the largest
+/// splits appear first. The first frame is the whole area as a single room,
+/// and the last is the finished layout. Frames are indexed by `(x, y)` and
+/// cover `dim`.
+pub fn split_frames(tree: &BspNode, dim: (usize, usize)) -> Vec<Array2<Tile>> {
+    let mut order = HashMap::new();
+    let mut queue = VecDeque::from([tree]);
+    while let Some(node) = queue.pop_front() {
+        if !node.children().is_empty() {
+            order.insert(node as *const BspNode, order.len());
+            queue.extend(node.children());
+        }
+    }
+
+    (0..=order.len())
+        .map(|k| {
+            let (mut rooms, mut lines) = (vec![], vec![]);
+            collect_until(tree, &order, k, &mut rooms, &mut lines);
+            let rooms = rooms.into_iter().map(Region::from).collect::<Vec<_>>();
+            rasterize(dim, &rooms, &lines, &[])
+        })
+        .collect()
+}
+
+/// Collect the rooms and lines of the tree as it looked after its first `k`
+/// partitions.
+fn collect_until(
+    node: &BspNode,
+    order: &HashMap<*const BspNode, usize>,
+    k: usize,
+    rooms: &mut Vec<Rectangle<isize, usize>>,
+    lines: &mut Vec<Line>,
+) {
+    match node {
+        BspNode::Partition { line, children, .. } if order[&(node as *const BspNode)] < k => {
+            lines.push(line.clone());
+            for c in children.iter() {
+                collect_until(c, order, k, rooms, lines);
+            }
+        }
+        BspNode::Ring {
+            lines: ring, child, ..
+        } if order[&(node as *const BspNode)] < k => {
+            lines.extend_from_slice(ring);
+            collect_until(child, order, k, rooms, lines);
+        }
+        _ => rooms.push(node.bounds().clone()),
+    }
+}
+
+impl<G: WorldGenerator> Pipeline<G> {
+    /// Like [`WorldGenerator::generate`], but also returns a snapshot of the
+    /// map after the generator and after every pass.
+    pub fn generate_recorded(
+        &self,
+        rng: &mut impl Rng,
+        bounds: Rectangle<isize, usize>,
+    ) -> (GeneratedWorld, Vec<Array2<Tile>>) {
+        let mut recorder = Recorder::default();
+        let world = self.generate_observed(rng, bounds, &mut recorder);
+        (world, recorder.frames)
+    }
+}
+
+/// Records a snapshot of the map after every stage of a pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    pub frames: Vec<Array2<Tile>>,
+}
+
+impl GenerationObserver for Recorder {
+    fn on_generated(&mut self, world: &GeneratedWorld) {
+        self.frames.push(world.tiles.clone());
+    }
+
+    fn on_pass_complete(&mut self, _index: usize, _name: &str, world: &GeneratedWorld) {
+        self.frames.push(world.tiles.clone());
+    }
+}
+
+/// Encode frames as an infinitely looping animated GIF, showing each frame for
+/// `delay_ms` milliseconds.
+pub fn write_gif(w: impl Write, frames: &[Array2<Tile>], delay_ms: u32) -> ImageResult<()> {
+    let mut encoder = GifEncoder::new(w);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+    encoder.encode_frames(frames.iter().map(|f| {
+        let img = DynamicImage::ImageRgb8(render_to_img(f)).into_rgba8();
+        Frame::from_parts(img, 0, 0, delay)
+    }))
+}
+
+/// Save frames as an animated GIF. See [`write_gif`].
+pub fn save_gif(path: impl AsRef<Path>, frames: &[Array2<Tile>], delay_ms: u32) -> ImageResult<()> {
+    write_gif(BufWriter::new(File::create(path)?), frames, delay_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::{
+        hallways::{rbsp, RbspParams},
+        pipeline::{ConnectRegions, PlaceDoors},
+    };
+
+    use super::*;
+
+    fn bounds() -> Rectangle<isize, usize> {
+        Rectangle {
+            x: 0,
+            y: 0,
+            w: 48,
+            h: 32,
+        }
+    }
+
+    #[test]
+    fn one_frame_per_split() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let tree = rbsp(
+            &mut rng,
+            bounds(),
+            RbspParams {
+                max_room_len: 20,
+                ..Default::default()
+            },
+        );
+        let (rooms, lines) = tree.clone().into_flat();
+        let rooms = rooms.into_iter().map(Region::from).collect::<Vec<_>>();
+
+        let frames = split_frames(&tree, (48, 32));
+
+        assert_eq!(frames.len(), lines.len() + 1);
+        assert_eq!(frames[0], rasterize((48, 32), &[bounds().into()], &[], &[]));
+        assert_eq!(
+            frames.last(),
+            Some(&rasterize((48, 32), &rooms, &lines, &[]))
+        );
+    }
+
+    #[test]
+    fn pipeline_records_every_stage() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let pipeline = Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(PlaceDoors)
+        .then(ConnectRegions);
+
+        let (world, frames) = pipeline.generate_recorded(&mut rng, bounds());
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames.last(), Some(&world.tiles));
+    }
+
+    #[test]
+    fn writes_gif() {
+        let frames = vec![
+            Array2::from_elem((8, 8), Tile::Wall),
+            Array2::from_elem((8, 8), Tile::Floor),
+        ];
+        let mut buf = vec![];
+
+        write_gif(&mut buf, &frames, 100).unwrap();
+
+        assert!(buf.starts_with(b"GIF89a"));
+    }
+}
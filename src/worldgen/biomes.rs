@@ -0,0 +1,166 @@
+use ndarray::{s, Array2};
+use rand::{distributions::Standard, prelude::Distribution, seq::SliceRandom, Rng};
+
+use crate::{tile::MaterialId, util::Region};
+
+use super::hallways::BspNode;
+
+/// The theme of a region of the map, which renderers can use to vary textures
+/// and props.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Biome {
+    Office,
+    ParkingGarage,
+    Pool,
+    Maintenance,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [
+        Biome::Office,
+        Biome::ParkingGarage,
+        Biome::Pool,
+        Biome::Maintenance,
+    ];
+
+    /// The material rooms of this biome are built from.
+    pub fn material(self) -> MaterialId {
+        match self {
+            Biome::Office => MaterialId::YELLOW_WALLPAPER,
+            Biome::ParkingGarage | Biome::Maintenance => MaterialId::CONCRETE,
+            Biome::Pool => MaterialId::TILE,
+        }
+    }
+}
+
+impl Distribution<Biome> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Biome {
+        *Biome::ALL.choose(rng).unwrap()
+    }
+}
+
+/// Pick a biome for every subtree rooted `depth` partitions below the root of
+/// the tree, so that neighboring rooms tend to share a biome.
+///
+/// Returns one biome per room, in the same order as [BspNode::rooms].
+pub fn assign_biomes(rng: &mut impl Rng, tree: &BspNode, depth: usize) -> Vec<Biome> {
+    let mut biomes = vec![];
+    assign_subtree(rng, tree, depth, None, &mut biomes);
+    biomes
+}
+
+fn assign_subtree(
+    rng: &mut impl Rng,
+    node: &BspNode,
+    depth: usize,
+    inherited: Option<Biome>,
+    out: &mut Vec<Biome>,
+) {
+    let biome = match inherited {
+        Some(b) => Some(b),
+        None if depth == 0 => Some(rng.gen()),
+        None => None,
+    };
+    match node {
+        BspNode::Room(_) => out.push(biome.unwrap_or_else(|| rng.gen())),
+        BspNode::Partition { children, .. } => {
+            for c in children.iter() {
+                assign_subtree(rng, c, depth.saturating_sub(1), biome, out);
+            }
+        }
+        BspNode::Ring { child, .. } => assign_subtree(rng, child, depth, biome, out),
+    }
+}
+
+/// Tag every tile of a map, indexed by `(x, y)`, with the biome of the room
+/// that covers it. Tiles outside every room, such as hallways, are left `None`.
+pub fn biome_map(dim: (usize, usize), rooms: &[Region], biomes: &[Biome]) -> Array2<Option<Biome>> {
+    let mut map = Array2::from_elem(dim, None);
+    let clip = |v: isize, max: usize| v.clamp(0, max as isize) as usize;
+    for (region, b) in rooms.iter().zip(biomes) {
+        for r in &region.rects {
+            let (x0, x1) = (clip(r.x, dim.0), clip(r.x_end(), dim.0));
+            let (y0, y1) = (clip(r.y, dim.1), clip(r.y_end(), dim.1));
+            map.slice_mut(s![x0..x1, y0..y1]).fill(Some(*b));
+        }
+    }
+    map
+}
+
+/// Tag every tile of a map, indexed by `(x, y)`, with the material of the
+/// biome of the room that covers it. Tiles outside every room get the default
+/// material.
+pub fn material_map(dim: (usize, usize), rooms: &[Region], biomes: &[Biome]) -> Array2<MaterialId> {
+    biome_map(dim, rooms, biomes).mapv(|b| b.map_or(MaterialId::default(), Biome::material))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::{
+        util::{Line, Rectangle},
+        worldgen::hallways::{rbsp, RbspParams},
+    };
+
+    use super::*;
+
+    fn tree(seed: u64) -> BspNode {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        rbsp(
+            &mut rng,
+            Rectangle {
+                x: 0,
+                y: 0,
+                w: 256,
+                h: 256,
+            },
+            RbspParams::default(),
+        )
+    }
+
+    #[test]
+    fn subtrees_share_biomes() {
+        let tree = tree(0);
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let biomes = assign_biomes(&mut rng, &tree, 2);
+
+        assert_eq!(biomes.len(), tree.rooms().count());
+        let BspNode::Partition { children, .. } = &tree else {
+            panic!("tree was not partitioned");
+        };
+        let mut i = 0;
+        for child in children.iter() {
+            if let BspNode::Partition { children, .. } = child {
+                for grandchild in children.iter() {
+                    let n = grandchild.rooms().count();
+                    assert!(biomes[i..i + n].iter().all(|b| *b == biomes[i]));
+                    i += n;
+                }
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn every_room_tile_gets_a_biome() {
+        let tree = tree(1);
+        let mut rng = SmallRng::seed_from_u64(1);
+        let biomes = assign_biomes(&mut rng, &tree, 1);
+        let rooms = tree.rooms().cloned().map(Region::from).collect::<Vec<_>>();
+
+        let map = biome_map((256, 256), &rooms, &biomes);
+
+        for (room, biome) in rooms.iter().zip(&biomes) {
+            assert!(room
+                .cells()
+                .all(|(x, y)| map[(x as usize, y as usize)] == Some(*biome)));
+        }
+        for (x, y) in tree.lines().flat_map(Line::points) {
+            assert_eq!(map[(x as usize, y as usize)], None);
+        }
+    }
+}
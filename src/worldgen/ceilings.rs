@@ -0,0 +1,119 @@
+use ndarray::{s, Array2};
+use rand::Rng;
+
+use crate::util::Region;
+
+/// Controls how high the ceilings of rooms are, in wall units, so an ordinary
+/// wall is 1 high.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CeilingParams {
+    /// Ceiling height of ordinary rooms.
+    pub base_height: f32,
+
+    /// Rooms covering at least this many tiles, such as merged halls, get
+    /// `hall_height` instead.
+    pub hall_min_area: usize,
+
+    /// Ceiling height of halls.
+    pub hall_height: f32,
+
+    /// Every room's height is varied by up to this much either way.
+    pub jitter: f32,
+}
+
+/// Pick a ceiling height for every room, in the same order as `rooms`.
+pub fn assign_heights(rng: &mut impl Rng, rooms: &[Region], params: &CeilingParams) -> Vec<f32> {
+    rooms
+        .iter()
+        .map(|r| {
+            let height = if r.area() >= params.hall_min_area {
+                params.hall_height
+            } else {
+                params.base_height
+            };
+            let jitter = if params.jitter > 0.0 {
+                rng.gen_range(-params.jitter..=params.jitter)
+            } else {
+                0.0
+            };
+            (height + jitter).max(0.0)
+        })
+        .collect()
+}
+
+/// Tag every tile of a map, indexed by `(x, y)`, with the ceiling height of the
+/// room that covers it. Tiles outside every room, such as hallways, get
+/// `default`.
+pub fn height_map(
+    dim: (usize, usize),
+    rooms: &[Region],
+    heights: &[f32],
+    default: f32,
+) -> Array2<f32> {
+    let mut map = Array2::from_elem(dim, default);
+    let clip = |v: isize, max: usize| v.clamp(0, max as isize) as usize;
+    for (region, h) in rooms.iter().zip(heights) {
+        for r in &region.rects {
+            let (x0, x1) = (clip(r.x, dim.0), clip(r.x_end(), dim.0));
+            let (y0, y1) = (clip(r.y, dim.1), clip(r.y_end(), dim.1));
+            map.slice_mut(s![x0..x1, y0..y1]).fill(*h);
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::util::Rectangle;
+
+    use super::*;
+
+    fn rooms() -> Vec<Region> {
+        vec![
+            Rectangle {
+                x: 0,
+                y: 0,
+                w: 4,
+                h: 4,
+            }
+            .into(),
+            Rectangle {
+                x: 5,
+                y: 0,
+                w: 10,
+                h: 10,
+            }
+            .into(),
+        ]
+    }
+
+    #[test]
+    fn halls_are_taller() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let params = CeilingParams {
+            base_height: 1.0,
+            hall_min_area: 50,
+            hall_height: 2.0,
+            jitter: 0.1,
+        };
+
+        let heights = assign_heights(&mut rng, &rooms(), &params);
+
+        assert_eq!(heights.len(), 2);
+        assert!((heights[0] - 1.0).abs() <= 0.1);
+        assert!((heights[1] - 2.0).abs() <= 0.1);
+    }
+
+    #[test]
+    fn height_map_covers_rooms() {
+        let map = height_map((16, 12), &rooms(), &[1.5, 3.0], 1.0);
+
+        assert_eq!(map[(2, 2)], 1.5);
+        assert_eq!(map[(4, 2)], 1.0);
+        assert_eq!(map[(14, 9)], 3.0);
+        assert_eq!(map[(15, 11)], 1.0);
+    }
+}
@@ -0,0 +1,152 @@
+use ndarray::Array2;
+use rand::Rng;
+
+use crate::{
+    tile::Tile,
+    util::{Rectangle, Region},
+};
+
+use super::connectivity::label_regions;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CellularParams {
+    /// A probability in [0, 1] that each cell starts out as a wall.
+    pub fill_probability: f32,
+
+    /// How many smoothing steps to run.
+    pub iterations: usize,
+
+    /// A cell becomes a wall if at least this many of its 8 neighbors are walls,
+    /// and floor otherwise. Cells outside the map count as walls.
+    pub wall_threshold: usize,
+}
+
+/// Output of [cellular].
+#[derive(Debug, Clone)]
+pub struct CaveMap {
+    /// The rasterized map, indexed by `(x, y)`.
+    pub tiles: Array2<Tile>,
+
+    /// The open floor of the cave, as a single room made of one rectangle per
+    /// run of floor along each row. Empty if the whole map filled in.
+    pub rooms: Vec<Region>,
+}
+
+/// Generate an organic cave-like map, indexed by `(x, y)`.
+///
+/// The map is randomly filled, smoothed with a cellular automaton, and then
+/// every floor region except the largest one is filled in, so the whole map is
+/// connected.
+pub fn cellular(rng: &mut impl Rng, (w, h): (usize, usize), params: &CellularParams) -> CaveMap {
+    let mut map = Array2::from_shape_simple_fn((w, h), || {
+        Tile::from(rng.gen::<f32>() < params.fill_probability)
+    });
+
+    for _ in 0..params.iterations {
+        map = smooth(&map, params.wall_threshold);
+    }
+
+    let (labels, sizes) = label_regions(&map);
+    let largest = (0..sizes.len()).max_by_key(|i| sizes[*i]);
+    map.zip_mut_with(&labels, |t, l| {
+        if *l != largest {
+            *t = Tile::Wall;
+        }
+    });
+
+    let cave = floor_region(&map);
+    CaveMap {
+        tiles: map,
+        rooms: if cave.rects.is_empty() {
+            vec![]
+        } else {
+            vec![cave]
+        },
+    }
+}
+
+/// Every open tile of a map, as one rectangle per run of open tiles along
+/// each row.
+fn floor_region(map: &Array2<Tile>) -> Region {
+    let (w, h) = map.dim();
+    let mut rects = vec![];
+    for y in 0..h {
+        let mut x = 0;
+        while x < w {
+            if map[(x, y)].is_solid() {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < w && !map[(x, y)].is_solid() {
+                x += 1;
+            }
+            rects.push(Rectangle {
+                x: start as isize,
+                y: y as isize,
+                w: x - start,
+                h: 1,
+            });
+        }
+    }
+    Region { rects }
+}
+
+fn smooth(map: &Array2<Tile>, wall_threshold: usize) -> Array2<Tile> {
+    let (w, h) = map.dim();
+    Array2::from_shape_fn((w, h), |(x, y)| {
+        let mut walls = 0;
+        for dx in -1..=1isize {
+            for dy in -1..=1isize {
+                if (dx, dy) == (0, 0) {
+                    continue;
+                }
+                let solid = match (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
+                    (Some(nx), Some(ny)) if nx < w && ny < h => map[(nx, ny)].is_solid(),
+                    _ => true,
+                };
+                walls += solid as usize;
+            }
+        }
+        Tile::from(walls >= wall_threshold)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::connectivity::label_regions;
+
+    use super::*;
+
+    #[test]
+    fn caves_are_connected() {
+        for i in 0..20 {
+            let mut rng = SmallRng::seed_from_u64(i);
+
+            let cave = cellular(
+                &mut rng,
+                (64, 48),
+                &CellularParams {
+                    fill_probability: 0.45,
+                    iterations: 4,
+                    wall_threshold: 5,
+                },
+            );
+
+            let (_, sizes) = label_regions(&cave.tiles);
+            assert_eq!(sizes.len(), 1);
+            // The room covers exactly the open tiles.
+            assert_eq!(cave.rooms.len(), 1);
+            assert_eq!(cave.rooms[0].area(), sizes[0]);
+            for ((x, y), t) in cave.tiles.indexed_iter() {
+                assert_eq!(
+                    cave.rooms[0].contains((x as isize, y as isize)),
+                    !t.is_solid()
+                );
+            }
+        }
+    }
+}
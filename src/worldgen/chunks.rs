@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::util::{derive_seed, Axis, Line, Rectangle};
+
+use super::hallways::{rbsp, BspNode, RbspParams};
+
+/// Tags mixed into seeds so that chunks and the edges between them never share
+/// a random stream.
+const CHUNK_TAG: u64 = 0;
+const VERTICAL_EDGE_TAG: u64 = 1;
+const HORIZONTAL_EDGE_TAG: u64 = 2;
+
+/// A fixed-size square piece of an infinite world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub cx: isize,
+    pub cy: isize,
+    pub tree: BspNode,
+
+    /// Hallways joining the chunk's interior hallways to the crossing point on
+    /// each of its four edges, in west, east, south, north order.
+    pub stubs: Vec<Line>,
+}
+
+impl Chunk {
+    pub fn bounds(&self) -> &Rectangle<isize, usize> {
+        self.tree.bounds()
+    }
+
+    pub fn rooms(&self) -> impl Iterator<Item = &Rectangle<isize, usize>> {
+        self.tree.rooms()
+    }
+
+    /// All the hallways in this chunk, including the edge stubs.
+    pub fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.tree.lines().chain(self.stubs.iter())
+    }
+}
+
+/// Deterministically generates chunks of an infinite world.
+///
+/// A chunk only depends on the world seed and its own coordinates, so chunks may
+/// be generated in any order. Neighboring chunks agree on where hallways cross
+/// the edge between them, so the hallway network stays connected across chunks.
+#[derive(Debug, Clone)]
+pub struct ChunkGenerator {
+    pub world_seed: u64,
+
+    /// Width and height of each chunk, in tiles.
+    pub chunk_size: usize,
+
+    pub params: RbspParams,
+}
+
+impl ChunkGenerator {
+    /// The chunk containing the given tile.
+    pub fn chunk_coords(&self, (x, y): (isize, isize)) -> (isize, isize) {
+        let size = self.chunk_size as isize;
+        (x.div_euclid(size), y.div_euclid(size))
+    }
+
+    pub fn chunk_bounds(&self, cx: isize, cy: isize) -> Rectangle<isize, usize> {
+        let size = self.chunk_size as isize;
+        Rectangle {
+            x: cx * size,
+            y: cy * size,
+            w: self.chunk_size,
+            h: self.chunk_size,
+        }
+    }
+
+    pub fn generate(&self, cx: isize, cy: isize) -> Chunk {
+        let bounds = self.chunk_bounds(cx, cy);
+        let mut rng = SmallRng::seed_from_u64(self.seed(CHUNK_TAG, cx, cy));
+        let tree = rbsp(&mut rng, bounds.clone(), self.params.clone());
+
+        let (x0, y0) = (bounds.x, bounds.y);
+        let (x1, y1) = (bounds.x_end() - 1, bounds.y_end() - 1);
+        let west = y0 + self.edge_offset(VERTICAL_EDGE_TAG, cx, cy);
+        let east = y0 + self.edge_offset(VERTICAL_EDGE_TAG, cx + 1, cy);
+        let south = x0 + self.edge_offset(HORIZONTAL_EDGE_TAG, cx, cy);
+        let north = x0 + self.edge_offset(HORIZONTAL_EDGE_TAG, cx, cy + 1);
+
+        let hits_hallway = |pos: (isize, isize)| {
+            tree.lines()
+                .any(|l| l.bounds().intersects(&point_rect(pos)))
+        };
+        let walk = |from: isize, to: isize, pos: &dyn Fn(isize) -> (isize, isize)| {
+            let step = (to - from).signum();
+            let mut i = from;
+            while i != to && !hits_hallway(pos(i)) {
+                i += step;
+            }
+            i
+        };
+
+        let width = self.params.hallway_width;
+        let stubs = vec![
+            {
+                let x = walk(x0, x1, &|x| (x, west));
+                line_between(x0, x, west, width, Axis::Horizontal)
+            },
+            {
+                let x = walk(x1, x0, &|x| (x, east));
+                line_between(x, x1, east, width, Axis::Horizontal)
+            },
+            {
+                let y = walk(y0, y1, &|y| (south, y));
+                line_between(y0, y, south, width, Axis::Vertical)
+            },
+            {
+                let y = walk(y1, y0, &|y| (north, y));
+                line_between(y, y1, north, width, Axis::Vertical)
+            },
+        ];
+
+        Chunk {
+            cx,
+            cy,
+            tree,
+            stubs,
+        }
+    }
+
+    fn seed(&self, tag: u64, cx: isize, cy: isize) -> u64 {
+        derive_seed(self.world_seed, [tag, cx as u64, cy as u64])
+    }
+
+    /// Where the hallway crosses the west (or south) edge of the given chunk,
+    /// relative to the chunk's origin.
+    fn edge_offset(&self, tag: u64, cx: isize, cy: isize) -> isize {
+        let mut rng = SmallRng::seed_from_u64(self.seed(tag, cx, cy));
+        let margin = self.params.hallway_width + 1;
+        rng.gen_range(1..self.chunk_size.saturating_sub(margin).max(2)) as isize
+    }
+}
+
+fn point_rect((x, y): (isize, isize)) -> Rectangle<isize, usize> {
+    Rectangle { x, y, w: 1, h: 1 }
+}
+
+fn line_between(from: isize, to: isize, at: isize, width: usize, axis: Axis) -> Line {
+    let (x, y) = match axis {
+        Axis::Horizontal => (from, at),
+        Axis::Vertical => (at, from),
+    };
+    Line {
+        x,
+        y,
+        length: (to - from) as usize,
+        width,
+        axis,
+    }
+}
+
+/// An infinite world whose chunks are generated on first access.
+#[derive(Debug, Clone)]
+pub struct ChunkedWorld {
+    pub generator: ChunkGenerator,
+    chunks: HashMap<(isize, isize), Chunk>,
+}
+
+impl ChunkedWorld {
+    pub fn new(generator: ChunkGenerator) -> Self {
+        Self {
+            generator,
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Get a chunk, generating it if it has not been visited yet.
+    pub fn chunk(&mut self, cx: isize, cy: isize) -> &Chunk {
+        let generator = &self.generator;
+        self.chunks
+            .entry((cx, cy))
+            .or_insert_with(|| generator.generate(cx, cy))
+    }
+
+    /// Get the chunk containing the given tile.
+    pub fn chunk_at(&mut self, pos: (isize, isize)) -> &Chunk {
+        let (cx, cy) = self.generator.chunk_coords(pos);
+        self.chunk(cx, cy)
+    }
+
+    /// Forget a previously generated chunk. It will be regenerated identically
+    /// the next time it is accessed.
+    pub fn unload(&mut self, cx: isize, cy: isize) -> Option<Chunk> {
+        self.chunks.remove(&(cx, cy))
+    }
+
+    pub fn loaded_chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn generator() -> ChunkGenerator {
+        ChunkGenerator {
+            world_seed: 1234,
+            chunk_size: 128,
+            params: RbspParams {
+                max_room_len: 40,
+                hallway_width: 2,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn chunks_are_deterministic() {
+        let g = generator();
+
+        assert_eq!(g.generate(3, -7), g.generate(3, -7));
+        assert_ne!(g.generate(3, -7), g.generate(-7, 3));
+    }
+
+    #[test]
+    fn stubs_meet_across_edges() {
+        let g = generator();
+        for (cx, cy) in [(0, 0), (-1, 4), (10, -3)] {
+            let here = g.generate(cx, cy);
+            let east = g.generate(cx + 1, cy);
+            let north = g.generate(cx, cy + 1);
+
+            let (e, w) = (&here.stubs[1], &east.stubs[0]);
+            assert_eq!(e.y, w.y);
+            assert_eq!(e.x + e.length as isize + 1, w.x);
+
+            let (n, s) = (&here.stubs[3], &north.stubs[2]);
+            assert_eq!(n.x, s.x);
+            assert_eq!(n.y + n.length as isize + 1, s.y);
+        }
+    }
+
+    #[test]
+    fn chunk_coords_round_down() {
+        let g = generator();
+
+        assert_eq!(g.chunk_coords((0, 127)), (0, 0));
+        assert_eq!(g.chunk_coords((-1, 128)), (-1, 1));
+    }
+}
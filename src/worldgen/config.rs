@@ -0,0 +1,250 @@
+//! Generation driven by plain data, so worlds can be described in a config
+//! file rather than in code.
+//!
+//! With the `serde` feature, [`GenerationConfig`] can be read from any format
+//! serde supports, such as JSON or TOML. Generators and passes are tagged with
+//! a `type` field:
+//!
+//! ```json
+//! {
+//!     "seed": 42,
+//!     "width": 128,
+//!     "height": 96,
+//!     "generator": { "type": "maze", "cell_size": 2, "wall_thickness": 1, "loop_fraction": 0.1 },
+//!     "passes": [{ "type": "dead_ends", "max_length": 4, "action": "Remove" }]
+//! }
+//! ```
+
+use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+
+use crate::util::Rectangle;
+
+use super::{
+    ceilings::CeilingParams,
+    cellular::CellularParams,
+    corridors::RoutedRbspParams,
+    deadends::DeadEndParams,
+    drunkard::DrunkardParams,
+    erosion::ErosionParams,
+    furnish::FurnishParams,
+    garage::GarageParams,
+    hallways::RbspParams,
+    locks::LockParams,
+    loops::LoopParams,
+    maze::MazeParams,
+    noise::NoiseOverlay,
+    pillars::PillarParams,
+    pipeline::{
+        AssignBiomes, AssignMaterials, ConnectRegions, GeneratedWorld, InsetRooms, Passes,
+        PlaceDoors, WorldGenerator, WorldPass,
+    },
+    pools::PoolParams,
+    presets::Preset,
+    secrets::SecretParams,
+    teasers::TeaserParams,
+    voronoi::VoronoiParams,
+};
+
+/// Everything needed to generate a world.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenerationConfig {
+    pub seed: u64,
+
+    /// Size of the world, in tiles.
+    pub width: usize,
+    pub height: usize,
+
+    pub generator: GeneratorConfig,
+
+    /// Passes to run after the generator, in order.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub passes: Vec<PassConfig>,
+}
+
+/// One of the built-in generators and its parameters.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum GeneratorConfig {
+    Rbsp(RbspParams),
+    RoutedRbsp(RoutedRbspParams),
+    Maze(MazeParams),
+    Cellular(CellularParams),
+    Drunkard(DrunkardParams),
+    Pillars(PillarParams),
+    Voronoi(VoronoiParams),
+    Pools(PoolParams),
+    Garage(GarageParams),
+    Preset { preset: Preset },
+}
+
+/// One of the built-in passes and its parameters.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum PassConfig {
+    Doors,
+    Biomes(AssignBiomes),
+    Materials,
+    ConnectRegions,
+    InsetRooms(InsetRooms),
+    Ceilings(CeilingParams),
+    Locks(LockParams),
+    Loops(LoopParams),
+    Secrets(SecretParams),
+    Teasers(TeaserParams),
+    DeadEnds(DeadEndParams),
+    Erosion(ErosionParams),
+    Furnish(FurnishParams),
+    Noise(NoiseOverlay),
+}
+
+impl WorldGenerator for GeneratorConfig {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        match self {
+            GeneratorConfig::Rbsp(p) => p.generate(rng, bounds),
+            GeneratorConfig::RoutedRbsp(p) => p.generate(rng, bounds),
+            GeneratorConfig::Maze(p) => p.generate(rng, bounds),
+            GeneratorConfig::Cellular(p) => p.generate(rng, bounds),
+            GeneratorConfig::Drunkard(p) => p.generate(rng, bounds),
+            GeneratorConfig::Pillars(p) => p.generate(rng, bounds),
+            GeneratorConfig::Voronoi(p) => p.generate(rng, bounds),
+            GeneratorConfig::Pools(p) => p.generate(rng, bounds),
+            GeneratorConfig::Garage(p) => p.generate(rng, bounds),
+            GeneratorConfig::Preset { preset } => preset.generate(rng, bounds),
+        }
+    }
+}
+
+impl WorldPass for PassConfig {
+    fn apply(&self, rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        match self {
+            PassConfig::Doors => PlaceDoors.apply(rng, world),
+            PassConfig::Biomes(p) => p.apply(rng, world),
+            PassConfig::Materials => AssignMaterials.apply(rng, world),
+            PassConfig::ConnectRegions => ConnectRegions.apply(rng, world),
+            PassConfig::InsetRooms(p) => p.apply(rng, world),
+            PassConfig::Ceilings(p) => p.apply(rng, world),
+            PassConfig::Locks(p) => p.apply(rng, world),
+            PassConfig::Loops(p) => p.apply(rng, world),
+            PassConfig::Secrets(p) => p.apply(rng, world),
+            PassConfig::Teasers(p) => p.apply(rng, world),
+            PassConfig::DeadEnds(p) => p.apply(rng, world),
+            PassConfig::Erosion(p) => p.apply(rng, world),
+            PassConfig::Furnish(p) => p.apply(rng, world),
+            PassConfig::Noise(p) => p.apply(rng, world),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            PassConfig::Doors => PlaceDoors.name(),
+            PassConfig::Biomes(p) => p.name(),
+            PassConfig::Materials => AssignMaterials.name(),
+            PassConfig::ConnectRegions => ConnectRegions.name(),
+            PassConfig::InsetRooms(p) => p.name(),
+            PassConfig::Ceilings(p) => p.name(),
+            PassConfig::Locks(p) => p.name(),
+            PassConfig::Loops(p) => p.name(),
+            PassConfig::Secrets(p) => p.name(),
+            PassConfig::Teasers(p) => p.name(),
+            PassConfig::DeadEnds(p) => p.name(),
+            PassConfig::Erosion(p) => p.name(),
+            PassConfig::Furnish(p) => p.name(),
+            PassConfig::Noise(p) => p.name(),
+        }
+    }
+}
+
+/// Generate the world described by a config. The same config always gives the
+/// same world.
+pub fn generate_from_config(config: &GenerationConfig) -> GeneratedWorld {
+    let mut rng = SmallRng::seed_from_u64(config.seed);
+    let bounds = Rectangle {
+        x: 0,
+        y: 0,
+        w: config.width,
+        h: config.height,
+    };
+    let mut world = config.generator.generate(&mut rng, bounds);
+    config
+        .passes
+        .iter()
+        .cloned()
+        .fold(Passes::new(), |passes, p| passes.then(p))
+        .run(&mut rng, &mut world);
+    world
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::worldgen::deadends::DeadEndAction;
+
+    use super::*;
+
+    fn config() -> GenerationConfig {
+        GenerationConfig {
+            seed: 42,
+            width: 64,
+            height: 48,
+            generator: GeneratorConfig::Maze(MazeParams {
+                cell_size: 2,
+                wall_thickness: 1,
+                loop_fraction: 0.1,
+            }),
+            passes: vec![PassConfig::DeadEnds(DeadEndParams {
+                max_length: 4,
+                action: DeadEndAction::Remove,
+            })],
+        }
+    }
+
+    #[test]
+    fn configs_are_deterministic() {
+        let a = generate_from_config(&config());
+        let b = generate_from_config(&config());
+
+        assert_eq!(a.tiles.dim(), (64, 48));
+        assert_eq!(a.tiles, b.tiles);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reads_json() {
+        let json = r#"{
+            "seed": 42,
+            "width": 64,
+            "height": 48,
+            "generator": { "type": "maze", "cell_size": 2, "wall_thickness": 1, "loop_fraction": 0.1 },
+            "passes": [{ "type": "dead_ends", "max_length": 4, "action": "Remove" }]
+        }"#;
+
+        let loaded: GenerationConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            generate_from_config(&loaded).tiles,
+            generate_from_config(&config()).tiles
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn rbsp_params_have_defaults() {
+        let json = r#"{
+            "type": "rbsp",
+            "min_room_len": 5,
+            "max_room_len": 20,
+            "p_keep_rooms": 0.3,
+            "k_deoblongification": 5.0,
+            "hallway_width": 1
+        }"#;
+
+        let loaded: GeneratorConfig = serde_json::from_str(json).unwrap();
+
+        let GeneratorConfig::Rbsp(params) = loaded else {
+            panic!("expected rbsp params");
+        };
+        assert_eq!(params.max_aspect_ratio, f32::INFINITY);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+
+use ndarray::Array2;
+
+use crate::tile::Tile;
+
+/// Offsets to the four orthogonal neighbors of a cell.
+const NEIGHBORS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// The orthogonal neighbors of a cell that lie within a map of the given size.
+pub(crate) fn neighbors(
+    (w, h): (usize, usize),
+    (x, y): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    NEIGHBORS.into_iter().filter_map(move |(dx, dy)| {
+        let nx = x.checked_add_signed(dx)?;
+        let ny = y.checked_add_signed(dy)?;
+        (nx < w && ny < h).then_some((nx, ny))
+    })
+}
+
+/// Flood-fill the non-solid cells of a rasterized map, returning a mask of every
+/// cell reachable from `start`.
+pub fn flood_fill(map: &Array2<Tile>, start: (usize, usize)) -> Array2<bool> {
+    let mut reached = Array2::from_elem(map.dim(), false);
+    let mut queue = VecDeque::from([start]);
+    reached[start] = true;
+
+    while let Some(c) = queue.pop_front() {
+        for n in neighbors(map.dim(), c) {
+            if !map[n].is_solid() && !reached[n] {
+                reached[n] = true;
+                queue.push_back(n);
+            }
+        }
+    }
+
+    reached
+}
+
+/// Breadth-first search over the non-solid cells of a map, returning the
+/// walking distance from `start` to every cell, or `None` for cells that can't
+/// be reached.
+pub fn distances(map: &Array2<Tile>, start: (usize, usize)) -> Array2<Option<usize>> {
+    let mut dist = Array2::from_elem(map.dim(), None);
+    let mut queue = VecDeque::from([start]);
+    dist[start] = Some(0);
+
+    while let Some(c) = queue.pop_front() {
+        let d = dist[c].unwrap();
+        for n in neighbors(map.dim(), c) {
+            if !map[n].is_solid() && dist[n].is_none() {
+                dist[n] = Some(d + 1);
+                queue.push_back(n);
+            }
+        }
+    }
+
+    dist
+}
+
+/// Label every connected region of non-solid cells with an ID, returning the
+/// labels and the number of cells in each region. Solid cells are labeled `None`.
+pub fn label_regions(map: &Array2<Tile>) -> (Array2<Option<usize>>, Vec<usize>) {
+    let mut labels = Array2::from_elem(map.dim(), None);
+    let mut sizes = vec![];
+
+    for (start, t) in map.indexed_iter() {
+        if t.is_solid() || labels[start].is_some() {
+            continue;
+        }
+        let id = sizes.len();
+        let mut size = 0;
+        let mut queue = VecDeque::from([start]);
+        labels[start] = Some(id);
+        while let Some(c) = queue.pop_front() {
+            size += 1;
+            for n in neighbors(map.dim(), c) {
+                if !map[n].is_solid() && labels[n].is_none() {
+                    labels[n] = Some(id);
+                    queue.push_back(n);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    (labels, sizes)
+}
+
+/// All non-solid cells that cannot be reached from `start`.
+pub fn unreachable_cells(map: &Array2<Tile>, start: (usize, usize)) -> Vec<(usize, usize)> {
+    let reached = flood_fill(map, start);
+    map.indexed_iter()
+        .filter(|(i, t)| !t.is_solid() && !reached[*i])
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Carve floor corridors through solid tiles until every non-solid cell is
+/// reachable from `start`.
+///
+/// Each isolated pocket is joined to the reachable area along the shortest
+/// possible path through walls, so the number of carved cells stays minimal.
+/// Returns the cells carved for each corridor, starting from the reachable side.
+pub fn connect_regions(map: &mut Array2<Tile>, start: (usize, usize)) -> Vec<Vec<(usize, usize)>> {
+    let mut corridors = vec![];
+    if map[start].is_solid() {
+        map[start] = Tile::Floor;
+    }
+
+    loop {
+        let reached = flood_fill(map, start);
+        let Some(path) = shortest_tunnel(map, &reached) else {
+            break;
+        };
+        for &c in &path {
+            map[c] = Tile::Floor;
+        }
+        corridors.push(path);
+    }
+
+    corridors
+}
+
+/// Breadth-first search outwards from every reached cell, through walls, until
+/// an unreached floor cell is found. Returns the wall cells along the way.
+fn shortest_tunnel(map: &Array2<Tile>, reached: &Array2<bool>) -> Option<Vec<(usize, usize)>> {
+    let mut came_from: Array2<Option<(usize, usize)>> = Array2::from_elem(map.dim(), None);
+    let mut visited = reached.clone();
+    let mut queue = reached
+        .indexed_iter()
+        .filter(|(_, r)| **r)
+        .map(|(i, _)| i)
+        .collect::<VecDeque<_>>();
+
+    while let Some(c) = queue.pop_front() {
+        for n in neighbors(map.dim(), c) {
+            if visited[n] {
+                continue;
+            }
+            visited[n] = true;
+            came_from[n] = Some(c);
+
+            if !map[n].is_solid() {
+                let mut path = vec![];
+                let mut cur = c;
+                while !reached[cur] {
+                    path.push(cur);
+                    cur = came_from[cur].unwrap();
+                }
+                path.reverse();
+                return Some(path);
+            }
+            queue.push_back(n);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    fn example_map() -> Array2<Tile> {
+        array![
+            [1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 0, 1, 1, 1, 1],
+            [1, 0, 0, 1, 1, 0, 1],
+            [1, 1, 1, 1, 1, 0, 1],
+            [1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 1, 1, 1, 1, 1],
+        ]
+        .map(|x| Tile::from(*x != 0))
+    }
+
+    #[test]
+    fn finds_unreachable_cells() {
+        let map = example_map();
+
+        let cells = unreachable_cells(&map, (1, 1));
+
+        assert_eq!(cells, vec![(2, 5), (3, 5), (5, 1)]);
+    }
+
+    #[test]
+    fn walking_distances() {
+        let map = example_map();
+
+        let dist = distances(&map, (1, 1));
+
+        assert_eq!(dist[(2, 2)], Some(2));
+        assert_eq!(dist[(1, 2)], Some(1));
+        assert_eq!(dist[(5, 1)], None);
+    }
+
+    #[test]
+    fn labels_regions() {
+        let map = example_map();
+
+        let (labels, sizes) = label_regions(&map);
+
+        assert_eq!(sizes, vec![4, 2, 1]);
+        assert_eq!(labels[(2, 2)], Some(0));
+        assert_eq!(labels[(3, 5)], Some(1));
+        assert_eq!(labels[(0, 0)], None);
+    }
+
+    #[test]
+    fn connects_every_region() {
+        let mut map = example_map();
+
+        let corridors = connect_regions(&mut map, (1, 1));
+
+        assert!(unreachable_cells(&map, (1, 1)).is_empty());
+        assert_eq!(corridors.len(), 2);
+        assert_eq!(corridors.iter().map(Vec::len).sum::<usize>(), 4);
+    }
+}
@@ -0,0 +1,443 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use cgmath::Vector2;
+use ndarray::{s, Array2};
+use rand::Rng;
+
+use crate::util::{Axis, Direction, Line, Rectangle, Region};
+
+use super::{
+    doors::Door,
+    hallways::{BspNode, RbspParams},
+};
+
+/// Offsets to the four orthogonal neighbors of a cell.
+const STEPS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// Cost of walking over open ground, and over a tile that's already part of a
+/// corridor. Reusing corridors is cheaper, so that they merge rather than run
+/// side by side.
+const OPEN_COST: u32 = 2;
+const CORRIDOR_COST: u32 = 1;
+
+/// How corridors find their way between rooms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CorridorStyle {
+    /// One straight run along each axis, cutting through whatever is in the way.
+    LShaped,
+
+    /// The cheapest path around rooms, preferring existing corridors and few
+    /// turns.
+    AStar,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorridorParams {
+    pub style: CorridorStyle,
+
+    /// Tiles left free between each room and the edge of its partition, for
+    /// corridors to run through. Rooms end up twice this far apart. Rooms too
+    /// small for this margin on both sides shrink to their middle tile or two
+    /// instead.
+    pub room_margin: usize,
+
+    /// Extra cost of every turn an A* corridor makes, in tiles walked.
+    pub turn_cost: usize,
+}
+
+/// Rooms from rbsp, joined by routed corridors instead of the partition lines.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoutedRbspParams {
+    /// Partition parameters. `hallway_width` is unused, since the partition
+    /// lines never become hallways.
+    pub rbsp: RbspParams,
+
+    pub corridors: CorridorParams,
+}
+
+/// Rooms, corridors and the doors between them, as produced by
+/// [`route_corridors`].
+#[derive(Debug, Clone)]
+pub struct Corridors {
+    /// The leaf rooms of the tree, shrunk by the room margin, in the same order
+    /// as [`BspNode::rooms`].
+    pub rooms: Vec<Region>,
+
+    /// Corridors as one-tile-wide lines, one per straight run.
+    pub lines: Vec<Line>,
+
+    pub doors: Vec<Door>,
+}
+
+/// Lay out the rooms of a partition tree on a map of size `dim` and join them
+/// with corridors rather than the partition lines.
+///
+/// The two halves of every partition are joined by a corridor between their
+/// closest pair of rooms, with a door at either end, so every room can be
+/// reached from every other.
+pub fn route_corridors(
+    rng: &mut impl Rng,
+    tree: &BspNode,
+    dim: (usize, usize),
+    params: &CorridorParams,
+) -> Corridors {
+    let inset = |r: &Rectangle<isize, usize>| {
+        // Every room keeps at least one tile, inside its partition.
+        let mx = params.room_margin.min(r.w.saturating_sub(1) / 2);
+        let my = params.room_margin.min(r.h.saturating_sub(1) / 2);
+        Rectangle {
+            x: r.x + mx as isize,
+            y: r.y + my as isize,
+            w: r.w - 2 * mx,
+            h: r.h - 2 * my,
+        }
+    };
+    let rooms = tree.rooms().map(inset).collect::<Vec<_>>();
+
+    let mut costs = Array2::from_elem(dim, Some(OPEN_COST));
+    for r in &rooms {
+        let (x, y) = ((r.x as usize).min(dim.0), (r.y as usize).min(dim.1));
+        let (x_end, y_end) = ((x + r.w).min(dim.0), (y + r.h).min(dim.1));
+        costs.slice_mut(s![x..x_end, y..y_end]).fill(None);
+    }
+
+    let (mut lines, mut doors) = (vec![], vec![]);
+    for node in tree.iter() {
+        let BspNode::Partition { line, children, .. } = node else {
+            continue;
+        };
+        let (near, far) = match line.axis {
+            Axis::Vertical => (Direction::East, Direction::West),
+            Axis::Horizontal => (Direction::North, Direction::South),
+        };
+        let a_rooms = children[0].rooms().map(inset).collect::<Vec<_>>();
+        let b_rooms = children[1].rooms().map(inset).collect::<Vec<_>>();
+        let Some((a, b)) = closest_doors(&a_rooms, &b_rooms, near, far) else {
+            continue;
+        };
+
+        let (start, goal) = (step(a, near), step(b, far));
+        let path = match params.style {
+            CorridorStyle::AStar => astar(&costs, start, goal, params.turn_cost as u32),
+            CorridorStyle::LShaped => None,
+        }
+        .unwrap_or_else(|| l_path(rng, start, goal));
+
+        for &(x, y) in &path {
+            if let Some(c) = costs.get_mut((x as usize, y as usize)) {
+                *c = Some(CORRIDOR_COST);
+            }
+        }
+        lines.extend(path_lines(&path));
+        doors.push(Door::new(a.0, a.1, near));
+        doors.push(Door::new(b.0, b.1, far));
+    }
+
+    Corridors {
+        rooms: rooms.into_iter().map(Region::from).collect(),
+        lines,
+        doors,
+    }
+}
+
+/// The closest pair of door positions between a room in `a` facing `near` and
+/// a room in `b` facing `far`. Rooms too small to have a floor are only used if
+/// there is nothing else.
+fn closest_doors(
+    a: &[Rectangle<isize, usize>],
+    b: &[Rectangle<isize, usize>],
+    near: Direction,
+    far: Direction,
+) -> Option<((isize, isize), (isize, isize))> {
+    let open = |rooms: &[Rectangle<isize, usize>]| {
+        let open = rooms
+            .iter()
+            .filter(|r| r.w >= 3 && r.h >= 3)
+            .cloned()
+            .collect::<Vec<_>>();
+        if open.is_empty() {
+            rooms.to_vec()
+        } else {
+            open
+        }
+    };
+    let (a, b) = (open(a), open(b));
+
+    a.iter()
+        .flat_map(|ra| b.iter().map(move |rb| (ra, rb)))
+        .map(|(ra, rb)| {
+            (
+                door_cell(ra, near, center(rb)),
+                door_cell(rb, far, center(ra)),
+            )
+        })
+        .min_by_key(|&(da, db)| da.0.abs_diff(db.0) + da.1.abs_diff(db.1))
+}
+
+fn center(r: &Rectangle<isize, usize>) -> (isize, isize) {
+    (r.x + r.w as isize / 2, r.y + r.h as isize / 2)
+}
+
+/// The tile on the `facing` edge of a room closest to `toward`, keeping off the
+/// room's corners.
+fn door_cell(
+    r: &Rectangle<isize, usize>,
+    facing: Direction,
+    toward: (isize, isize),
+) -> (isize, isize) {
+    let along = |start: isize, len: usize, t: isize| {
+        if len >= 3 {
+            t.clamp(start + 1, start + len as isize - 2)
+        } else {
+            start + len as isize / 2
+        }
+    };
+    match facing {
+        Direction::East => (r.x_end() - 1, along(r.y, r.h, toward.1)),
+        Direction::West => (r.x, along(r.y, r.h, toward.1)),
+        Direction::North => (along(r.x, r.w, toward.0), r.y_end() - 1),
+        Direction::South => (along(r.x, r.w, toward.0), r.y),
+    }
+}
+
+fn step((x, y): (isize, isize), d: Direction) -> (isize, isize) {
+    let v = Vector2::<isize>::from(d);
+    (x + v.x, y + v.y)
+}
+
+/// The cheapest path from `start` to `goal` over a cost field, where `None`
+/// tiles can't be walked on, and each turn costs `turn_cost` extra.
+fn astar(
+    costs: &Array2<Option<u32>>,
+    start: (isize, isize),
+    goal: (isize, isize),
+    turn_cost: u32,
+) -> Option<Vec<(isize, isize)>> {
+    let (w, h) = costs.dim();
+    let cell = |(x, y): (isize, isize)| {
+        let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+        (x < w && y < h).then_some((x, y))
+    };
+    let (start, goal) = (cell(start)?, cell(goal)?);
+    costs[goal]?;
+
+    // Search states are a tile and the direction it was entered from, with an
+    // extra direction for the start.
+    let index = |(x, y): (usize, usize), d: usize| (x * h + y) * 5 + d;
+    let heuristic =
+        |(x, y): (usize, usize)| (x.abs_diff(goal.0) + y.abs_diff(goal.1)) as u32 * CORRIDOR_COST;
+    let mut best = vec![u32::MAX; w * h * 5];
+    let mut prev = vec![usize::MAX; w * h * 5];
+    let mut queue = BinaryHeap::new();
+    best[index(start, 4)] = 0;
+    queue.push(Reverse((heuristic(start), 0, start, 4)));
+
+    while let Some(Reverse((_, g, pos, d))) = queue.pop() {
+        if pos == goal {
+            let mut path = vec![];
+            let mut i = index(pos, d);
+            while i != usize::MAX {
+                let c = i / 5;
+                path.push(((c / h) as isize, (c % h) as isize));
+                i = prev[i];
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if g > best[index(pos, d)] {
+            continue;
+        }
+        for (nd, (dx, dy)) in STEPS.into_iter().enumerate() {
+            let Some(next) = cell((pos.0 as isize + dx, pos.1 as isize + dy)) else {
+                continue;
+            };
+            let Some(cost) = costs[next] else {
+                continue;
+            };
+            let turn = if d != 4 && d != nd { turn_cost } else { 0 };
+            let ng = g + cost + turn;
+            if ng < best[index(next, nd)] {
+                best[index(next, nd)] = ng;
+                prev[index(next, nd)] = index(pos, d);
+                queue.push(Reverse((ng + heuristic(next), ng, next, nd)));
+            }
+        }
+    }
+    None
+}
+
+/// A path from `start` to `goal` with a single turn, going along a random axis
+/// first.
+fn l_path(rng: &mut impl Rng, start: (isize, isize), goal: (isize, isize)) -> Vec<(isize, isize)> {
+    let elbow = if rng.gen() {
+        (goal.0, start.1)
+    } else {
+        (start.0, goal.1)
+    };
+    let mut path = vec![start];
+    for target in [elbow, goal] {
+        let mut pos = *path.last().unwrap();
+        while pos != target {
+            pos.0 += (target.0 - pos.0).signum();
+            pos.1 += (target.1 - pos.1).signum();
+            path.push(pos);
+        }
+    }
+    path
+}
+
+/// Split a path of orthogonally adjacent tiles into one line per straight run.
+/// Consecutive lines share the tile at the turn between them.
+fn path_lines(path: &[(isize, isize)]) -> Vec<Line> {
+    if let [(x, y)] = *path {
+        return vec![Line {
+            x,
+            y,
+            length: 0,
+            width: 1,
+            axis: Axis::Horizontal,
+        }];
+    }
+
+    let mut lines = vec![];
+    let mut i = 0;
+    while i + 1 < path.len() {
+        let horizontal = |j: usize| path[j].1 == path[j + 1].1;
+        let mut j = i + 1;
+        while j + 1 < path.len() && horizontal(j) == horizontal(i) {
+            j += 1;
+        }
+        let ((x0, y0), (x1, y1)) = (path[i], path[j]);
+        lines.push(Line {
+            x: x0.min(x1),
+            y: y0.min(y1),
+            length: x0.abs_diff(x1) + y0.abs_diff(y1),
+            width: 1,
+            axis: if horizontal(i) {
+                Axis::Horizontal
+            } else {
+                Axis::Vertical
+            },
+        });
+        i = j;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+    use rstest::rstest;
+
+    use crate::{
+        tile::Tile,
+        worldgen::{connectivity::label_regions, hallways::rbsp, raster::rasterize},
+    };
+
+    use super::*;
+
+    fn routed(seed: u64, style: CorridorStyle, room_margin: usize) -> (Corridors, Array2<Tile>) {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let tree = rbsp(
+            &mut rng,
+            Rectangle {
+                x: 0,
+                y: 0,
+                w: 96,
+                h: 80,
+            },
+            RbspParams {
+                min_room_len: 10,
+                max_room_len: 30,
+                ..Default::default()
+            },
+        );
+        let corridors = route_corridors(
+            &mut rng,
+            &tree,
+            (96, 80),
+            &CorridorParams {
+                style,
+                room_margin,
+                turn_cost: 3,
+            },
+        );
+        let map = rasterize(
+            (96, 80),
+            &corridors.rooms,
+            &corridors.lines,
+            &corridors.doors,
+        );
+        (corridors, map)
+    }
+
+    #[test]
+    fn astar_levels_are_connected() {
+        for seed in 0..10 {
+            let (corridors, map) = routed(seed, CorridorStyle::AStar, 1);
+
+            assert_eq!(corridors.doors.len(), 2 * (corridors.rooms.len() - 1));
+            assert_eq!(label_regions(&map).1.len(), 1, "seed {seed}");
+        }
+    }
+
+    #[rstest]
+    #[case(CorridorStyle::AStar)]
+    #[case(CorridorStyle::LShaped)]
+    fn corridors_reach_every_door(#[case] style: CorridorStyle) {
+        let (corridors, map) = routed(0, style, 1);
+
+        for d in &corridors.doors {
+            let outside = step((d.x, d.y), d.facing);
+            assert_eq!(map[(d.x as usize, d.y as usize)], Tile::Door);
+            assert!(corridors
+                .lines
+                .iter()
+                .any(|l| l.points().any(|p| p == outside)));
+        }
+    }
+
+    #[test]
+    fn astar_corridors_go_around_rooms() {
+        let (corridors, _) = routed(0, CorridorStyle::AStar, 1);
+
+        for pos in corridors.lines.iter().flat_map(Line::points) {
+            assert!(corridors.rooms.iter().all(|r| !r.contains(pos)));
+        }
+    }
+
+    #[test]
+    fn margins_wider_than_rooms_leave_a_tile_of_each() {
+        let (corridors, map) = routed(0, CorridorStyle::AStar, 100);
+
+        for r in corridors.rooms.iter().map(Region::bounds) {
+            assert!((1..=2).contains(&r.w) && (1..=2).contains(&r.h), "{r:?}");
+        }
+        assert_eq!(label_regions(&map).1.len(), 1);
+    }
+
+    #[test]
+    fn paths_split_into_runs() {
+        let path = [(0, 0), (1, 0), (2, 0), (2, 1), (2, 2), (1, 2)];
+
+        let lines = path_lines(&path);
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines.iter().flat_map(Line::points).collect::<Vec<_>>(),
+            vec![
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (2, 0),
+                (2, 1),
+                (2, 2),
+                (1, 2),
+                (2, 2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use ndarray::{s, Array2};
+
+use crate::tile::Tile;
+
+use super::connectivity::neighbors;
+
+/// What to do with a short dead end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeadEndAction {
+    /// Fill the dead end back in with wall.
+    Remove,
+
+    /// Carve a square room of the given size around the end of the corridor.
+    Cap { room_size: usize },
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeadEndParams {
+    /// Dead ends longer than this are left alone.
+    pub max_length: usize,
+
+    pub action: DeadEndAction,
+}
+
+/// Find one-tile-wide corridors that lead nowhere and either remove them or
+/// cap them with a small room, on a map indexed by `(x, y)`.
+///
+/// A dead end is traced back from its tip until it reaches a junction. Corridors
+/// that never reach a junction are left alone, so isolated pieces of the map are
+/// never deleted outright. Returns how many dead ends were handled.
+pub fn prune_dead_ends(map: &mut Array2<Tile>, params: &DeadEndParams) -> usize {
+    let mut handled = 0;
+    loop {
+        let tips = map
+            .indexed_iter()
+            .filter(|(pos, t)| !t.is_solid() && open_neighbors(map, *pos).count() == 1)
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>();
+
+        let mut changed = false;
+        for tip in tips {
+            // An earlier removal may have changed this tip's surroundings.
+            if map[tip].is_solid() || open_neighbors(map, tip).count() != 1 {
+                continue;
+            }
+            let Some(path) = trace(map, tip, params.max_length) else {
+                continue;
+            };
+
+            match params.action {
+                DeadEndAction::Remove => {
+                    for c in path {
+                        map[c] = Tile::Wall;
+                    }
+                    changed = true;
+                }
+                DeadEndAction::Cap { room_size } => cap(map, tip, room_size),
+            }
+            handled += 1;
+        }
+
+        if !changed {
+            return handled;
+        }
+    }
+}
+
+fn open_neighbors(
+    map: &Array2<Tile>,
+    pos: (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    neighbors(map.dim(), pos).filter(|n| !map[*n].is_solid())
+}
+
+/// Follow a corridor from its tip until reaching a junction, returning the
+/// corridor's cells, not including the junction.
+fn trace(
+    map: &Array2<Tile>,
+    tip: (usize, usize),
+    max_length: usize,
+) -> Option<Vec<(usize, usize)>> {
+    let mut path = vec![tip];
+    let mut prev = tip;
+    let mut cur = open_neighbors(map, tip).next()?;
+
+    loop {
+        let next = open_neighbors(map, cur)
+            .filter(|n| *n != prev)
+            .collect::<Vec<_>>();
+        match next.len() {
+            // Reached another tip without finding a junction.
+            0 => return None,
+            1 => {
+                path.push(cur);
+                if path.len() > max_length {
+                    return None;
+                }
+                (prev, cur) = (cur, next[0]);
+            }
+            _ => return Some(path),
+        }
+    }
+}
+
+/// Carve a room centered on the given cell, without touching the map's outer ring.
+fn cap(map: &mut Array2<Tile>, (x, y): (usize, usize), size: usize) {
+    let (w, h) = map.dim();
+    let half = size / 2;
+    let x0 = x.saturating_sub(half).max(1);
+    let y0 = y.saturating_sub(half).max(1);
+    let x1 = (x0 + size).min(w.saturating_sub(1));
+    let y1 = (y0 + size).min(h.saturating_sub(1));
+    if x0 < x1 && y0 < y1 {
+        map.slice_mut(s![x0..x1, y0..y1]).mapv_inplace(|t| {
+            if t == Tile::Wall {
+                Tile::Floor
+            } else {
+                t
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    fn example_map() -> Array2<Tile> {
+        array![
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 0, 0, 0, 0, 1],
+            [1, 0, 1, 1, 0, 1, 1, 0, 1],
+            [1, 0, 1, 1, 0, 1, 1, 0, 1],
+            [1, 0, 0, 0, 0, 1, 1, 0, 1],
+            [1, 1, 1, 1, 1, 1, 1, 0, 1],
+            [1, 1, 1, 1, 1, 1, 1, 0, 1],
+            [1, 1, 1, 1, 1, 1, 1, 0, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+        ]
+        .map(|x| Tile::from(*x != 0))
+    }
+
+    #[test]
+    fn removes_only_short_dead_ends() {
+        let mut map = example_map();
+
+        let n = prune_dead_ends(
+            &mut map,
+            &DeadEndParams {
+                max_length: 8,
+                action: DeadEndAction::Remove,
+            },
+        );
+
+        // The spur around the top right corner is 9 tiles long, so it stays.
+        assert_eq!(n, 0);
+        assert_eq!(map, example_map());
+
+        let n = prune_dead_ends(
+            &mut map,
+            &DeadEndParams {
+                max_length: 9,
+                action: DeadEndAction::Remove,
+            },
+        );
+
+        assert_eq!(n, 1);
+        assert!(map[(7, 7)].is_solid());
+        assert!(map[(1, 5)].is_solid());
+        assert!(!map[(1, 4)].is_solid());
+    }
+
+    #[test]
+    fn caps_dead_ends_with_rooms() {
+        let mut map = example_map();
+
+        let n = prune_dead_ends(
+            &mut map,
+            &DeadEndParams {
+                max_length: 9,
+                action: DeadEndAction::Cap { room_size: 3 },
+            },
+        );
+
+        assert_eq!(n, 1);
+        assert!(!map[(6, 6)].is_solid());
+        assert!(!map[(7, 6)].is_solid());
+    }
+}
@@ -0,0 +1,225 @@
+use rand::{seq::SliceRandom, Rng};
+
+use crate::util::{Axis, Direction, Line, Rectangle, Region};
+
+/// Whether a door can be walked and seen through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DoorState {
+    Open,
+    #[default]
+    Closed,
+    /// Closed, and only opened by unlocking it with the door's key.
+    Locked,
+}
+
+impl DoorState {
+    pub const ALL: [DoorState; 3] = [DoorState::Open, DoorState::Closed, DoorState::Locked];
+}
+
+/// A doorway punched through the edge of a room into an adjacent hallway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Door {
+    /// Position of the doorway tile, on the edge of the room closest to the hallway.
+    pub x: isize,
+    pub y: isize,
+
+    /// The direction the door opens towards, out of the room and into the hallway.
+    pub facing: Direction,
+
+    pub state: DoorState,
+
+    /// The key that unlocks this door, if it has a lock.
+    pub key: Option<u32>,
+}
+
+impl Door {
+    /// A closed, unlocked door.
+    pub fn new(x: isize, y: isize, facing: Direction) -> Self {
+        Self {
+            x,
+            y,
+            facing,
+            state: DoorState::Closed,
+            key: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == DoorState::Open
+    }
+
+    /// Open the door unless it's locked. Returns true if the door is now open.
+    pub fn open(&mut self) -> bool {
+        if self.state == DoorState::Closed {
+            self.state = DoorState::Open;
+        }
+        self.is_open()
+    }
+
+    pub fn close(&mut self) {
+        if self.state == DoorState::Open {
+            self.state = DoorState::Closed;
+        }
+    }
+
+    /// Close the door and lock it, so that it only unlocks with `key`.
+    pub fn lock(&mut self, key: u32) {
+        self.state = DoorState::Locked;
+        self.key = Some(key);
+    }
+
+    /// Unlock the door, leaving it closed, if `key` fits its lock. Returns
+    /// true if the door is now unlocked.
+    pub fn unlock(&mut self, key: u32) -> bool {
+        if self.state == DoorState::Locked && self.key == Some(key) {
+            self.state = DoorState::Closed;
+        }
+        self.state != DoorState::Locked
+    }
+}
+
+/// Punch one doorway into every room that touches at least one partition line.
+///
+/// Doors are never placed on the corners of a room.
+pub fn place_doors(rng: &mut impl Rng, rooms: &[Region], lines: &[Line]) -> Vec<Door> {
+    rooms
+        .iter()
+        .filter_map(|region| {
+            let candidates = region
+                .rects
+                .iter()
+                .flat_map(|r| lines.iter().flat_map(move |l| door_candidates(r, l)))
+                .collect::<Vec<_>>();
+            candidates.choose(rng).cloned()
+        })
+        .collect()
+}
+
+/// All the places a door could go to connect the given room to the given line.
+pub(crate) fn door_candidates(r: &Rectangle<isize, usize>, l: &Line) -> Vec<Door> {
+    let (x0, y0) = (r.x, r.y);
+    let (x1, y1) = (r.x + r.w as isize - 1, r.y + r.h as isize - 1);
+
+    match l.axis {
+        Axis::Vertical => {
+            let (l0, l1) = (l.y, l.y + l.length as isize);
+            let (x, facing) = if l.x == x1 + 1 {
+                (x1, Direction::East)
+            } else if l.x + l.width as isize == x0 {
+                (x0, Direction::West)
+            } else {
+                return vec![];
+            };
+            (isize::max(y0 + 1, l0)..=isize::min(y1 - 1, l1))
+                .map(|y| Door::new(x, y, facing))
+                .collect()
+        }
+        Axis::Horizontal => {
+            let (l0, l1) = (l.x, l.x + l.length as isize);
+            let (y, facing) = if l.y == y1 + 1 {
+                (y1, Direction::North)
+            } else if l.y + l.width as isize == y0 {
+                (y0, Direction::South)
+            } else {
+                return vec![];
+            };
+            (isize::max(x0 + 1, l0)..=isize::min(x1 - 1, l1))
+                .map(|x| Door::new(x, y, facing))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::hallways::{make_partition, rbsp, RbspParams};
+
+    use super::*;
+
+    #[test]
+    fn doors_on_both_sides_of_partition() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (r1, p, r2) = make_partition(
+            &Rectangle {
+                x: 2,
+                y: 5,
+                w: 10,
+                h: 8,
+            },
+            5,
+            Axis::Horizontal,
+            1,
+        );
+
+        let doors = place_doors(&mut rng, &[r1.into(), r2.into()], &[p]);
+
+        assert_eq!(doors.len(), 2);
+        assert_eq!((doors[0].x, doors[0].facing), (6, Direction::East));
+        assert_eq!((doors[1].x, doors[1].facing), (8, Direction::West));
+        for d in doors {
+            assert!((6..=11).contains(&d.y));
+        }
+    }
+
+    #[test]
+    fn doors_clear_wide_hallways() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (r1, p, r2) = make_partition(
+            &Rectangle {
+                x: 2,
+                y: 5,
+                w: 10,
+                h: 8,
+            },
+            4,
+            Axis::Vertical,
+            3,
+        );
+
+        let doors = place_doors(&mut rng, &[r1.into(), r2.into()], &[p]);
+
+        assert_eq!((doors[0].y, doors[0].facing), (8, Direction::North));
+        assert_eq!((doors[1].y, doors[1].facing), (12, Direction::South));
+    }
+
+    #[test]
+    fn locked_doors_need_their_key() {
+        let mut door = Door::new(0, 0, Direction::East);
+        assert!(door.open());
+        door.close();
+
+        door.lock(3);
+        assert!(!door.open());
+        assert!(!door.unlock(4));
+        assert!(door.unlock(3));
+        assert_eq!(door.state, DoorState::Closed);
+        assert!(door.open());
+    }
+
+    #[test]
+    fn every_room_gets_a_door() {
+        for i in 0..100 {
+            let mut rng = SmallRng::seed_from_u64(i);
+            let (rooms, lines) = rbsp(
+                &mut rng,
+                Rectangle {
+                    x: 0,
+                    y: 0,
+                    w: 256,
+                    h: 256,
+                },
+                RbspParams::default(),
+            )
+            .into_flat();
+
+            let rooms = rooms.into_iter().map(Region::from).collect::<Vec<_>>();
+            let doors = place_doors(&mut rng, &rooms, &lines);
+
+            assert_eq!(doors.len(), rooms.len());
+        }
+    }
+}
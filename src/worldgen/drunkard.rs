@@ -0,0 +1,108 @@
+use cgmath::Vector2;
+use ndarray::Array2;
+use rand::Rng;
+
+use crate::{
+    tile::Tile,
+    util::{Direction, TurnDir, Turnable},
+};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrunkardParams {
+    /// How many walkers to start. Only used by [drunkards].
+    pub walkers: usize,
+
+    /// How many steps each walker takes before stopping.
+    pub steps: usize,
+
+    /// A probability in [0, 1] that a walker turns left or right before each
+    /// step. Low values give long straight corridors, high values give blobs.
+    pub p_turn: f32,
+}
+
+/// Carve winding corridors into an existing map, indexed by `(x, y)`, by
+/// sending a random walker out from each of the given start points.
+///
+/// Walkers never touch the outermost ring of the map, and turn around when
+/// they would.
+pub fn drunkard_walk(
+    rng: &mut impl Rng,
+    map: &mut Array2<Tile>,
+    starts: &[(usize, usize)],
+    params: &DrunkardParams,
+) {
+    let (w, h) = map.dim();
+    if w < 3 || h < 3 {
+        return;
+    }
+    let inside = |x: isize, y: isize| x >= 1 && y >= 1 && x < w as isize - 1 && y < h as isize - 1;
+
+    for &(sx, sy) in starts {
+        let (mut x, mut y) = (sx as isize, sy as isize);
+        let mut dir: Direction = rng.gen();
+        if inside(x, y) {
+            map[(x as usize, y as usize)] = Tile::Floor;
+        }
+
+        for _ in 0..params.steps {
+            if rng.gen::<f32>() < params.p_turn {
+                dir = dir.rotate(rng.gen::<TurnDir>());
+            }
+            let v = Vector2::<isize>::from(dir);
+            if !inside(x + v.x, y + v.y) {
+                dir = dir.rotate_180();
+                continue;
+            }
+            (x, y) = (x + v.x, y + v.y);
+            map[(x as usize, y as usize)] = Tile::Floor;
+        }
+    }
+}
+
+/// Generate a map of tunnels from walkers starting at random points.
+pub fn drunkards(
+    rng: &mut impl Rng,
+    (w, h): (usize, usize),
+    params: &DrunkardParams,
+) -> Array2<Tile> {
+    let mut map = Array2::from_elem((w, h), Tile::Wall);
+    let starts = (0..params.walkers)
+        .map(|_| (rng.gen_range(0..w.max(1)), rng.gen_range(0..h.max(1))))
+        .collect::<Vec<_>>();
+    drunkard_walk(rng, &mut map, &starts, params);
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::connectivity::label_regions;
+
+    use super::*;
+
+    #[test]
+    fn single_walker_carves_connected_tunnel() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut map = Array2::from_elem((32, 32), Tile::Wall);
+
+        drunkard_walk(
+            &mut rng,
+            &mut map,
+            &[(16, 16)],
+            &DrunkardParams {
+                walkers: 1,
+                steps: 500,
+                p_turn: 0.3,
+            },
+        );
+
+        let (_, sizes) = label_regions(&map);
+        assert_eq!(sizes.len(), 1);
+        for x in 0..32 {
+            assert!(map[(x, 0)].is_solid() && map[(x, 31)].is_solid());
+            assert!(map[(0, x)].is_solid() && map[(31, x)].is_solid());
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use ndarray::Array2;
+use rand::Rng;
+
+use crate::tile::{MaterialId, Tile};
+
+use super::noise::{noise_field, NoiseParams};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErosionParams {
+    /// The shape of the damage. Larger scales give fewer, bigger patches.
+    pub noise: NoiseParams,
+
+    /// Walls where the noise is at least this are marked as damaged.
+    pub damage_threshold: f32,
+
+    /// Damaged walls where the noise is at least this crumble away entirely,
+    /// leaving a hole. Should be above `damage_threshold`.
+    pub hole_threshold: f32,
+}
+
+/// Damage walls in patches following a noise field, on a map indexed by
+/// `(x, y)`. Damaged walls get [`MaterialId::DAMAGED`], and the worst of them
+/// crumble into floor.
+///
+/// Only walls facing open floor crumble, so holes bite into the sides of walls
+/// rather than hollowing out pockets inside them. Walls one tile thick can be
+/// holed through, making shortcuts between the areas on either side. The edge
+/// of the map is never holed. Returns the cells that crumbled.
+pub fn erode_walls(
+    rng: &mut impl Rng,
+    map: &mut Array2<Tile>,
+    materials: &mut Array2<MaterialId>,
+    params: &ErosionParams,
+) -> Vec<(usize, usize)> {
+    let (w, h) = map.dim();
+    let noise = noise_field(rng.gen(), (w, h), &params.noise);
+    let original = map.clone();
+    let open = |x: usize, y: usize| original.get((x, y)).is_some_and(|t| !t.is_solid());
+
+    let mut holes = vec![];
+    for ((x, y), n) in noise.indexed_iter() {
+        if original[(x, y)] != Tile::Wall || *n < params.damage_threshold {
+            continue;
+        }
+        materials[(x, y)] = MaterialId::DAMAGED;
+
+        let edge = x == 0 || y == 0 || x + 1 == w || y + 1 == h;
+        let faces_open =
+            !edge && (open(x - 1, y) || open(x + 1, y) || open(x, y - 1) || open(x, y + 1));
+        if *n >= params.hole_threshold && faces_open {
+            map[(x, y)] = Tile::Floor;
+            holes.push((x, y));
+        }
+    }
+    holes
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    fn params(damage_threshold: f32, hole_threshold: f32) -> ErosionParams {
+        ErosionParams {
+            noise: NoiseParams {
+                scale: 8.0,
+                octaves: 2,
+                persistence: 0.5,
+                lacunarity: 2.0,
+            },
+            damage_threshold,
+            hole_threshold,
+        }
+    }
+
+    /// Rows of floor two tiles apart, walled in and between each other.
+    fn striped() -> Array2<Tile> {
+        Array2::from_shape_fn((64, 64), |(x, y)| {
+            Tile::from(x == 0 || x == 63 || y == 0 || y == 63 || y % 2 == 0)
+        })
+    }
+
+    #[test]
+    fn holes_open_shortcuts_through_thin_walls() {
+        let mut map = striped();
+        let mut materials = Array2::default(map.dim());
+
+        let holes = erode_walls(
+            &mut SmallRng::seed_from_u64(0),
+            &mut map,
+            &mut materials,
+            &params(0.5, 0.6),
+        );
+
+        assert!(!holes.is_empty());
+        for &(x, y) in &holes {
+            assert_eq!(map[(x, y)], Tile::Floor);
+            assert_eq!(materials[(x, y)], MaterialId::DAMAGED);
+            assert!(x > 0 && y > 0 && x < 63 && y < 63);
+        }
+        let damaged = materials
+            .indexed_iter()
+            .filter(|(pos, m)| **m == MaterialId::DAMAGED && map[*pos] == Tile::Wall)
+            .count();
+        assert!(damaged > 0);
+    }
+
+    #[test]
+    fn high_thresholds_leave_walls_alone() {
+        let mut map = striped();
+        let mut materials = Array2::default(map.dim());
+
+        let holes = erode_walls(
+            &mut SmallRng::seed_from_u64(0),
+            &mut map,
+            &mut materials,
+            &params(2.0, 2.0),
+        );
+
+        assert!(holes.is_empty());
+        assert_eq!(map, striped());
+        assert!(materials.iter().all(|m| *m == MaterialId::default()));
+    }
+}
@@ -0,0 +1,199 @@
+use ndarray::{s, Array2};
+use rand::Rng;
+
+use crate::{
+    tile::Tile,
+    util::{Axis, Rectangle, Region},
+};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FurnishParams {
+    /// Rooms with a smaller area than this are left empty.
+    pub min_area: usize,
+
+    /// A probability in [0, 1] that a furnished room is filled with a grid of
+    /// cubicles rather than scattered desks and shelves.
+    pub p_cubicles: f32,
+
+    /// Width and height of each cubicle, including its partition walls.
+    pub cubicle_size: usize,
+
+    /// How many desks and shelves to attempt to place per 100 tiles of room area.
+    pub prop_density: f32,
+
+    /// Tiles left clear along the inside edges of each room, so that doors
+    /// are never blocked.
+    pub margin: usize,
+}
+
+/// Fill large rooms with office furniture, on a map indexed by `(x, y)`.
+///
+/// Furniture is only ever placed over floor tiles, and never cuts a room's
+/// floor into disconnected pieces.
+pub fn furnish(
+    rng: &mut impl Rng,
+    map: &mut Array2<Tile>,
+    rooms: &[Region],
+    params: &FurnishParams,
+) {
+    for region in rooms {
+        if region.area() < params.min_area {
+            continue;
+        }
+        let cubicles = rng.gen::<f32>() < params.p_cubicles;
+        for r in &region.rects {
+            let Some(inner) = inset(map.dim(), r, params.margin) else {
+                continue;
+            };
+            if cubicles {
+                place_cubicles(map, &inner, params.cubicle_size);
+            } else {
+                place_props(rng, map, &inner, params.prop_density);
+            }
+        }
+    }
+}
+
+/// Shrink a rectangle by `margin` on every side and clip it to the map, as
+/// `(x0, y0, x1, y1)` with exclusive ends.
+fn inset(
+    (w, h): (usize, usize),
+    r: &Rectangle<isize, usize>,
+    margin: usize,
+) -> Option<(usize, usize, usize, usize)> {
+    let m = margin as isize;
+    let x0 = (r.x + m).max(0) as usize;
+    let y0 = (r.y + m).max(0) as usize;
+    let x1 = ((r.x_end() - m).max(0) as usize).min(w);
+    let y1 = ((r.y_end() - m).max(0) as usize).min(h);
+    (x0 < x1 && y0 < y1).then_some((x0, y0, x1, y1))
+}
+
+/// Lay out a grid of cubicles. Each cubicle is walled on all sides, with an
+/// opening in the middle of its north wall, so every cubicle can be reached by
+/// walking north through the ones above it.
+fn place_cubicles(
+    map: &mut Array2<Tile>,
+    &(x0, y0, x1, y1): &(usize, usize, usize, usize),
+    size: usize,
+) {
+    if size < 3 {
+        return;
+    }
+    // Leave one free row and column on the far sides for the closing walls.
+    let (nx, ny) = ((x1 - x0 - 1) / size, (y1 - y0 - 1) / size);
+    for i in 0..nx {
+        for j in 0..ny {
+            let (x, y) = (x0 + i * size, y0 + j * size);
+            draw_partition(map, (x, y), size + 1, Axis::Vertical);
+            draw_partition(map, (x + size, y), size + 1, Axis::Vertical);
+            draw_partition(map, (x, y), size + 1, Axis::Horizontal);
+            draw_partition(map, (x, y + size), size + 1, Axis::Horizontal);
+        }
+    }
+    for i in 0..nx {
+        for j in 0..ny {
+            let gap = &mut map[(x0 + i * size + size / 2, y0 + j * size)];
+            if *gap == Tile::Partition {
+                *gap = Tile::Floor;
+            }
+        }
+    }
+}
+
+/// Draw a straight run of partition over floor tiles.
+fn draw_partition(map: &mut Array2<Tile>, (x, y): (usize, usize), len: usize, axis: Axis) {
+    for i in 0..len {
+        let p = match axis {
+            Axis::Horizontal => (x + i, y),
+            Axis::Vertical => (x, y + i),
+        };
+        if let Some(t) = map.get_mut(p) {
+            if *t == Tile::Floor {
+                *t = Tile::Partition;
+            }
+        }
+    }
+}
+
+/// Scatter desks and shelves, keeping at least one tile of floor around each.
+fn place_props(
+    rng: &mut impl Rng,
+    map: &mut Array2<Tile>,
+    &(x0, y0, x1, y1): &(usize, usize, usize, usize),
+    density: f32,
+) {
+    let attempts = ((x1 - x0) * (y1 - y0)) as f32 * density / 100.0;
+    for _ in 0..attempts.round() as usize {
+        let (tile, mut w, mut h) = if rng.gen() {
+            (Tile::Desk, 2, 1)
+        } else {
+            (Tile::Shelf, 3, 1)
+        };
+        if rng.gen() {
+            (w, h) = (h, w);
+        }
+        if x1 - x0 < w + 2 || y1 - y0 < h + 2 {
+            continue;
+        }
+        let x = rng.gen_range(x0 + 1..=x1 - w - 1);
+        let y = rng.gen_range(y0 + 1..=y1 - h - 1);
+
+        let surroundings = map.slice(s![x - 1..x + w + 1, y - 1..y + h + 1]);
+        if surroundings.iter().all(|t| *t == Tile::Floor) {
+            map.slice_mut(s![x..x + w, y..y + h]).fill(tile);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::connectivity::label_regions;
+
+    use super::*;
+
+    fn furnished(seed: u64, p_cubicles: f32) -> Array2<Tile> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut map = Array2::from_elem((40, 30), Tile::Floor);
+        furnish(
+            &mut rng,
+            &mut map,
+            &[Rectangle {
+                x: 0,
+                y: 0,
+                w: 40,
+                h: 30,
+            }
+            .into()],
+            &FurnishParams {
+                min_area: 100,
+                p_cubicles,
+                cubicle_size: 4,
+                prop_density: 10.0,
+                margin: 1,
+            },
+        );
+        map
+    }
+
+    #[test]
+    fn cubicles_stay_connected() {
+        let map = furnished(0, 1.0);
+
+        assert!(map.iter().any(|t| *t == Tile::Partition));
+        assert_eq!(label_regions(&map).1.len(), 1);
+    }
+
+    #[test]
+    fn props_stay_connected() {
+        for seed in 0..20 {
+            let map = furnished(seed, 0.0);
+
+            assert!(map.iter().any(|t| matches!(t, Tile::Desk | Tile::Shelf)));
+            assert_eq!(label_regions(&map).1.len(), 1);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use ndarray::{s, Array2};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    tile::Tile,
+    util::{derive_seed, Direction, Rectangle},
+    world::{Decal, DecalKind},
+};
+
+use super::levels::{Connector, ConnectorKind, MultiLevelWorld};
+
+/// Length of each dash of a lane's center line, and of the gaps between them.
+const DASH: usize = 2;
+
+/// Distance between the arrows painted along a lane.
+const ARROW_SPACING: usize = 16;
+
+/// Parameters for one floor of a parking garage.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GarageParams {
+    /// Distance between neighboring columns along a row.
+    pub column_spacing: usize,
+
+    /// Width and height of each square column.
+    pub column_size: usize,
+
+    /// Width of each parking bay, along its lane.
+    pub bay_width: usize,
+
+    /// Depth of each parking bay, from its lane to the back of the bay.
+    pub bay_depth: usize,
+
+    /// Width of the driving lanes.
+    pub lane_width: usize,
+}
+
+/// Output of [garage].
+#[derive(Debug, Clone)]
+pub struct Garage {
+    /// The rasterized map, indexed by `(x, y)`.
+    pub tiles: Array2<Tile>,
+
+    /// Lane and bay markings.
+    pub decals: Vec<Decal>,
+
+    /// The driving lanes, from south to north.
+    pub lanes: Vec<Rectangle<isize, usize>>,
+}
+
+/// Generate one floor of a parking garage: an open floor inside a perimeter
+/// wall, cut into east-west driving lanes with a row of parking bays along
+/// either side. Rows of columns stand where the backs of the bays meet.
+///
+/// Every lane is one-way, in a random direction.
+pub fn garage(rng: &mut impl Rng, (w, h): (usize, usize), params: &GarageParams) -> Garage {
+    let mut tiles = Array2::from_shape_fn((w, h), |(x, y)| {
+        if x == 0 || y == 0 || x + 1 == w || y + 1 == h {
+            Tile::Wall
+        } else {
+            Tile::Floor
+        }
+    });
+    let (inner_w, inner_h) = (w.saturating_sub(2), h.saturating_sub(2));
+    let bay = params.bay_depth;
+    let period = 2 * bay + params.lane_width;
+    let n_lanes = inner_h.checked_div(period).unwrap_or(0);
+
+    // Columns go between back-to-back rows of bays.
+    let size = params.column_size;
+    for row in 1..n_lanes {
+        let y = (1 + row * period).saturating_sub(size / 2);
+        for x in (1..w).step_by(params.column_spacing.max(1)).skip(1) {
+            let x = x - size / 2;
+            if x + size < w && y + size < h {
+                tiles
+                    .slice_mut(s![x..x + size, y..y + size])
+                    .fill(Tile::Pillar);
+            }
+        }
+    }
+
+    let mut decals = vec![];
+    let mut lanes = vec![];
+    for i in 0..n_lanes {
+        let y0 = 1 + i * period;
+        let lane = Rectangle {
+            x: 1,
+            y: (y0 + bay) as isize,
+            w: inner_w,
+            h: params.lane_width,
+        };
+
+        // A dashed line down the middle, with an arrow in some of the gaps.
+        let facing = if rng.gen() {
+            Direction::East
+        } else {
+            Direction::West
+        };
+        let mid = (y0 + bay + params.lane_width / 2) as isize;
+        for x in 1..inner_w + 1 {
+            let kind = if x % (2 * DASH) < DASH {
+                DecalKind::LaneLine
+            } else if x % ARROW_SPACING == DASH {
+                DecalKind::Arrow
+            } else {
+                continue;
+            };
+            decals.push(Decal {
+                x: x as isize,
+                y: mid,
+                kind,
+                facing,
+            });
+        }
+
+        // Lines between the bays on either side.
+        let bays = (y0..y0 + bay).chain(y0 + bay + params.lane_width..y0 + period);
+        for y in bays {
+            for x in (1..inner_w + 1).step_by(params.bay_width.max(1)) {
+                if tiles[(x, y)] == Tile::Floor {
+                    decals.push(Decal {
+                        x: x as isize,
+                        y: y as isize,
+                        kind: DecalKind::BayLine,
+                        facing: Direction::North,
+                    });
+                }
+            }
+        }
+
+        lanes.push(lane);
+    }
+
+    Garage {
+        tiles,
+        decals,
+        lanes,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GarageLevelParams {
+    /// Number of floors in the stack.
+    pub floors: usize,
+
+    /// Width and height of every floor, in tiles.
+    pub dim: (usize, usize),
+
+    pub garage: GarageParams,
+
+    /// Length of the ramps between floors, along their lane.
+    pub ramp_len: usize,
+}
+
+/// Generate a stack of garage floors, each joined to the next by a ramp.
+///
+/// Ramps run along a lane, taking turns between the lanes and between their
+/// west and east ends, so the two ramps on a floor never overlap. If the lanes
+/// are too short for that, there are no ramps.
+pub fn garage_levels(seed: u64, params: &GarageLevelParams) -> MultiLevelWorld {
+    let mut garages = (0..params.floors)
+        .map(|i| {
+            let mut rng = SmallRng::seed_from_u64(derive_seed(seed, [i as u64]));
+            garage(&mut rng, params.dim, &params.garage)
+        })
+        .collect::<Vec<_>>();
+
+    // Only lane directions are random, so every floor has the same lanes.
+    let lanes = garages.first().map_or(vec![], |g| g.lanes.clone());
+    let mut connectors = vec![];
+    for i in 0..params.floors.saturating_sub(1) {
+        let lane = lanes.get(i % lanes.len().max(1));
+        let Some(lane) = lane.filter(|l| l.w >= 2 * params.ramp_len + 2) else {
+            break;
+        };
+        let x = if i % 2 == 0 {
+            lane.x + 1
+        } else {
+            lane.x_end() - 1 - params.ramp_len as isize
+        };
+        connectors.push(Connector {
+            kind: ConnectorKind::Ramp,
+            floors: i..i + 2,
+            bounds: Rectangle {
+                x,
+                w: params.ramp_len,
+                ..lane.clone()
+            },
+        });
+    }
+
+    for c in &connectors {
+        for floor in c.floors.clone() {
+            stamp_ramp(&mut garages[floor], &c.bounds);
+        }
+    }
+
+    let (floors, decals) = garages.into_iter().map(|g| (g.tiles, g.decals)).unzip();
+    MultiLevelWorld {
+        floors,
+        connectors,
+        decals,
+    }
+}
+
+/// Pave a ramp, scrubbing any markings off it.
+fn stamp_ramp(garage: &mut Garage, r: &Rectangle<isize, usize>) {
+    let (x, y) = (r.x as usize, r.y as usize);
+    garage
+        .tiles
+        .slice_mut(s![x..x + r.w, y..y + r.h])
+        .fill(Tile::Ramp);
+    garage
+        .decals
+        .retain(|d| !(d.x >= r.x && d.x < r.x_end() && d.y >= r.y && d.y < r.y_end()));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::worldgen::connectivity::unreachable_cells;
+
+    use super::*;
+
+    fn params() -> GarageParams {
+        GarageParams {
+            column_spacing: 9,
+            column_size: 2,
+            bay_width: 3,
+            bay_depth: 5,
+            lane_width: 6,
+        }
+    }
+
+    #[test]
+    fn lanes_are_clear_and_marked() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let g = garage(&mut rng, (80, 60), &params());
+
+        assert_eq!(g.lanes.len(), 3);
+        assert!(g.tiles.iter().any(|t| *t == Tile::Pillar));
+        for lane in &g.lanes {
+            let (x, y) = (lane.x as usize, lane.y as usize);
+            let tiles = g.tiles.slice(s![x..x + lane.w, y..y + lane.h]);
+            assert!(tiles.iter().all(|t| *t == Tile::Floor));
+            let marked = |kind| g.decals.iter().any(|d| d.kind == kind && d.y >= lane.y);
+            assert!(marked(DecalKind::LaneLine) && marked(DecalKind::Arrow));
+        }
+        for d in &g.decals {
+            assert_eq!(g.tiles[(d.x as usize, d.y as usize)], Tile::Floor);
+        }
+        assert!(unreachable_cells(&g.tiles, (1, 1)).is_empty());
+    }
+
+    #[test]
+    fn ramps_join_neighboring_floors() {
+        let params = GarageLevelParams {
+            floors: 4,
+            dim: (80, 60),
+            garage: params(),
+            ramp_len: 10,
+        };
+
+        let world = garage_levels(7, &params);
+
+        assert_eq!(world.floors.len(), 4);
+        assert_eq!(world.connectors.len(), 3);
+        for c in &world.connectors {
+            for floor in c.floors.clone() {
+                assert_eq!(world.floors[floor][c.center()], Tile::Ramp);
+                assert!(world
+                    .connectors_on(floor)
+                    .all(|o| o == c || !o.bounds.intersects(&c.bounds)));
+            }
+        }
+        assert_eq!(world.floors[3][world.connectors[0].center()], Tile::Floor);
+    }
+}
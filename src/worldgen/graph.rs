@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+
+use crate::util::{Line, Region};
+
+use super::doors::door_candidates;
+
+/// A node in the connectivity graph, indexing into the room or hallway list it
+/// was built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Node {
+    Room(usize),
+    Hallway(usize),
+}
+
+/// Adjacency between rooms and hallway segments.
+///
+/// A room is adjacent to a hallway if a door could be placed between them, and
+/// two hallways are adjacent if they cross or meet. Rooms are never directly
+/// adjacent to each other, since rbsp always separates them with a hallway.
+#[derive(Debug, Clone)]
+pub struct ConnectivityGraph {
+    n_rooms: usize,
+    adjacency: Vec<Vec<Node>>,
+}
+
+impl ConnectivityGraph {
+    pub fn new(rooms: &[Region], lines: &[Line]) -> Self {
+        let mut graph = Self {
+            n_rooms: rooms.len(),
+            adjacency: vec![vec![]; rooms.len() + lines.len()],
+        };
+
+        for (ri, r) in rooms.iter().enumerate() {
+            for (li, l) in lines.iter().enumerate() {
+                if r.rects.iter().any(|r| !door_candidates(r, l).is_empty()) {
+                    graph.add_edge(Node::Room(ri), Node::Hallway(li));
+                }
+            }
+        }
+
+        for (i, a) in lines.iter().enumerate() {
+            for (j, b) in lines.iter().enumerate().skip(i + 1) {
+                if a.bounds().touches(&b.bounds()) {
+                    graph.add_edge(Node::Hallway(i), Node::Hallway(j));
+                }
+            }
+        }
+
+        graph
+    }
+
+    #[inline]
+    fn index(&self, node: Node) -> usize {
+        match node {
+            Node::Room(i) => i,
+            Node::Hallway(i) => self.n_rooms + i,
+        }
+    }
+
+    #[inline]
+    fn node(&self, index: usize) -> Node {
+        if index < self.n_rooms {
+            Node::Room(index)
+        } else {
+            Node::Hallway(index - self.n_rooms)
+        }
+    }
+
+    pub fn add_edge(&mut self, a: Node, b: Node) {
+        let (ia, ib) = (self.index(a), self.index(b));
+        if !self.adjacency[ia].contains(&b) {
+            self.adjacency[ia].push(b);
+            self.adjacency[ib].push(a);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.adjacency.is_empty()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        (0..self.len()).map(|i| self.node(i))
+    }
+
+    pub fn neighbors(&self, node: Node) -> &[Node] {
+        &self.adjacency[self.index(node)]
+    }
+
+    /// Every edge exactly once, with the lesser node first.
+    pub fn edges(&self) -> impl Iterator<Item = (Node, Node)> + '_ {
+        self.nodes().flat_map(move |a| {
+            self.neighbors(a)
+                .iter()
+                .filter(move |b| a < **b)
+                .map(move |b| (a, *b))
+        })
+    }
+
+    /// Breadth-first search from the given node, returning the hop distance to
+    /// every node, or `None` for unreachable nodes. Indexed like `nodes()`.
+    pub fn distances_from(&self, start: Node) -> Vec<Option<usize>> {
+        let mut dist = vec![None; self.len()];
+        let mut queue = VecDeque::from([start]);
+        dist[self.index(start)] = Some(0);
+
+        while let Some(n) = queue.pop_front() {
+            let d = dist[self.index(n)].unwrap();
+            for &m in self.neighbors(n) {
+                let slot = &mut dist[self.index(m)];
+                if slot.is_none() {
+                    *slot = Some(d + 1);
+                    queue.push_back(m);
+                }
+            }
+        }
+
+        dist
+    }
+
+    /// Returns true if every node can be reached from every other node.
+    pub fn is_connected(&self) -> bool {
+        self.nodes()
+            .next()
+            .is_none_or(|n| self.distances_from(n).iter().all(Option::is_some))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::{
+        util::{Axis, Rectangle},
+        worldgen::hallways::{make_partition, rbsp, RbspParams},
+    };
+
+    use super::*;
+
+    #[test]
+    fn single_partition_graph() {
+        let (r1, p, r2) = make_partition(
+            &Rectangle {
+                x: 2,
+                y: 5,
+                w: 10,
+                h: 8,
+            },
+            5,
+            Axis::Horizontal,
+            1,
+        );
+
+        let graph = ConnectivityGraph::new(&[r1.into(), r2.into()], &[p]);
+
+        assert_eq!(
+            graph.edges().collect::<Vec<_>>(),
+            vec![
+                (Node::Room(0), Node::Hallway(0)),
+                (Node::Room(1), Node::Hallway(0))
+            ]
+        );
+    }
+
+    #[test]
+    fn rbsp_output_is_connected() {
+        for i in 0..100 {
+            let mut rng = SmallRng::seed_from_u64(i);
+            let (rooms, lines) = rbsp(
+                &mut rng,
+                Rectangle {
+                    x: 0,
+                    y: 0,
+                    w: 256,
+                    h: 256,
+                },
+                RbspParams::default(),
+            )
+            .into_flat();
+
+            let rooms = rooms.into_iter().map(Region::from).collect::<Vec<_>>();
+            let graph = ConnectivityGraph::new(&rooms, &lines);
+
+            assert!(graph.is_connected());
+        }
+    }
+}
@@ -1,8 +1,16 @@
-use cgmath::BaseNum;
-use rand::{seq::IteratorRandom, Rng};
+use alloc::{boxed::Box, vec, vec::Vec};
 
-use crate::util::{Axis, Line, Rectangle};
+use rand::{prelude::Distribution, rngs::SmallRng, Rng, SeedableRng};
 
+use crate::{
+    math::{self, BaseNum},
+    util::{derive_seed, Axis, Line, Rectangle},
+};
+
+use super::observer::GenerationObserver;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RbspParams {
     /// Rooms with a width or height shorter than this size will never be created.
     pub min_room_len: usize,
@@ -24,51 +32,590 @@ pub struct RbspParams {
     ///
     /// Square will not be affected by this parameter.
     pub k_deoblongification: f32,
+
+    /// How many tiles wide the hallways along partition lines are.
+    pub hallway_width: usize,
+
+    /// Where along a room's length partitions are placed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub offset_distribution: OffsetDistribution,
+
+    /// Rooms whose long side is more than this many times their short side are
+    /// always cut across their long side, regardless of `p_keep_rooms` and
+    /// `k_deoblongification`. Use infinity to allow any shape.
+    ///
+    /// Rooms too short to be cut without going under `min_room_len` are still
+    /// kept, so this can't be enforced for ratios close to 1.
+    #[cfg_attr(feature = "serde", serde(default = "unlimited_aspect_ratio"))]
+    pub max_aspect_ratio: f32,
+
+    /// Scales `p_keep_rooms` by room area, to aim for a particular spread of
+    /// room sizes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub keep_weight: KeepWeight,
+
+    /// Which rectangles get a corridor carved around their edges before their
+    /// inside is partitioned.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub perimeter_ring: PerimeterRing,
+}
+
+impl Default for RbspParams {
+    fn default() -> Self {
+        Self {
+            min_room_len: 5,
+            max_room_len: 80,
+            p_keep_rooms: 0.3,
+            k_deoblongification: 5.0,
+            hallway_width: 1,
+            offset_distribution: OffsetDistribution::default(),
+            max_aspect_ratio: f32::INFINITY,
+            keep_weight: KeepWeight::default(),
+            perimeter_ring: PerimeterRing::default(),
+        }
+    }
+}
+
+/// Infinity can't be written in every format, so it's the default instead.
+#[cfg(feature = "serde")]
+fn unlimited_aspect_ratio() -> f32 {
+    f32::INFINITY
+}
+
+/// Where to carve ring corridors, like the perimeter circulation around an
+/// office floor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PerimeterRing {
+    /// No rings; rectangles are only ever cut in two.
+    #[default]
+    None,
+
+    /// A single ring around the whole generated rectangle.
+    Outer,
+
+    /// A ring around the whole rectangle, and around every subtree whose
+    /// shorter side is at least `min_len` tiles.
+    Subtrees { min_len: usize },
+}
+
+/// A weight in [0, 1] applied to the chance of keeping a room, as a function of
+/// its area in tiles.
+///
+/// Rooms larger than `max_room_len` squared are still always partitioned, so
+/// raise it to allow rare giant rooms.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeepWeight {
+    /// Every room has the same chance of being kept.
+    #[default]
+    Constant,
+
+    /// `(max_area, weight)` buckets, sorted by area. A room gets the weight of
+    /// the first bucket its area fits in, or 0 if it's larger than every bucket.
+    Histogram(Vec<(usize, f32)>),
+
+    /// An arbitrary function of the room's area. Can't be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Function(fn(usize) -> f32),
+}
+
+impl KeepWeight {
+    pub fn weight(&self, area: usize) -> f32 {
+        match self {
+            KeepWeight::Constant => 1.0,
+            KeepWeight::Histogram(buckets) => buckets
+                .iter()
+                .find(|(max_area, _)| area <= *max_area)
+                .map_or(0.0, |(_, w)| *w),
+            KeepWeight::Function(f) => f(area),
+        }
+    }
+}
+
+/// A distribution over where to cut a room, as a fraction in [0, 1) of the
+/// range of valid cut positions.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OffsetDistribution {
+    /// Every position is equally likely.
+    #[default]
+    Uniform,
+
+    /// Positions near the middle are more likely, giving more balanced splits.
+    Triangular,
+
+    /// Always cut at one of the two golden ratio points, plus or minus the
+    /// given fraction of jitter.
+    GoldenRatio { jitter: f32 },
+}
+
+impl Distribution<f32> for OffsetDistribution {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        let t = match *self {
+            OffsetDistribution::Uniform => rng.gen::<f32>(),
+            OffsetDistribution::Triangular => (rng.gen::<f32>() + rng.gen::<f32>()) / 2.0,
+            OffsetDistribution::GoldenRatio { jitter } => {
+                let phi_inv = 2.0 / (1.0 + math::sqrt(5.0));
+                let point = if rng.gen() { phi_inv } else { 1.0 - phi_inv };
+                point + rng.gen_range(-1.0..=1.0) * jitter
+            }
+        };
+        t.clamp(0.0, 1.0 - f32::EPSILON)
+    }
+}
+
+/// A node of the tree produced by [rbsp].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BspNode {
+    /// A room that was kept without being partitioned any further.
+    Room(Rectangle<isize, usize>),
+
+    /// A rectangle that was cut in two by a hallway.
+    Partition {
+        bounds: Rectangle<isize, usize>,
+        line: Line,
+        children: Box<[BspNode; 2]>,
+    },
+
+    /// A rectangle with a corridor around its edges, in west, east, south,
+    /// north order, and its inside partitioned further.
+    Ring {
+        bounds: Rectangle<isize, usize>,
+        lines: [Line; 4],
+        child: Box<BspNode>,
+    },
+}
+
+impl BspNode {
+    /// The rectangle covered by this subtree.
+    pub fn bounds(&self) -> &Rectangle<isize, usize> {
+        match self {
+            BspNode::Room(r) => r,
+            BspNode::Partition { bounds, .. } | BspNode::Ring { bounds, .. } => bounds,
+        }
+    }
+
+    /// The direct children of this node.
+    pub fn children(&self) -> &[BspNode] {
+        match self {
+            BspNode::Room(_) => &[],
+            BspNode::Partition { children, .. } => children.as_slice(),
+            BspNode::Ring { child, .. } => core::slice::from_ref(child),
+        }
+    }
+
+    /// Iterate over every node of this subtree in pre-order, starting with this one.
+    pub fn iter(&self) -> impl Iterator<Item = &BspNode> {
+        let mut stack = vec![self];
+        core::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.children().iter().rev());
+            Some(node)
+        })
+    }
+
+    /// All the leaf rooms of this subtree.
+    pub fn rooms(&self) -> impl Iterator<Item = &Rectangle<isize, usize>> {
+        self.iter().filter_map(|n| match n {
+            BspNode::Room(r) => Some(r),
+            BspNode::Partition { .. } | BspNode::Ring { .. } => None,
+        })
+    }
+
+    /// All the partition and ring lines of this subtree.
+    pub fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.iter().flat_map(|n| match n {
+            BspNode::Room(_) => &[],
+            BspNode::Partition { line, .. } => core::slice::from_ref(line),
+            BspNode::Ring { lines, .. } => lines.as_slice(),
+        })
+    }
+
+    /// The number of partitions between this node and its deepest leaf.
+    pub fn depth(&self) -> usize {
+        match self {
+            BspNode::Room(_) => 0,
+            BspNode::Partition { children, .. } => {
+                1 + usize::max(children[0].depth(), children[1].depth())
+            }
+            BspNode::Ring { child, .. } => child.depth(),
+        }
+    }
+
+    /// Flatten the tree into its rooms and partition lines.
+    pub fn into_flat(self) -> (Vec<Rectangle<isize, usize>>, Vec<Line>) {
+        (
+            self.rooms().cloned().collect(),
+            self.lines().cloned().collect(),
+        )
+    }
 }
 
 /// random binary space partition
-pub fn rbsp(
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(rng, params)))]
+pub fn rbsp(rng: &mut impl Rng, full_rect: Rectangle<isize, usize>, params: RbspParams) -> BspNode {
+    rbsp_subtree(rng, full_rect, &params, true, &mut ())
+}
+
+/// Like [rbsp], but reports every partition and kept room to `observer` as
+/// the tree is built, in pre-order.
+pub fn rbsp_observed(
     rng: &mut impl Rng,
     full_rect: Rectangle<isize, usize>,
     params: RbspParams,
-) -> (Vec<Rectangle<isize, usize>>, Vec<Line>) {
-    let mut examining = vec![full_rect];
-    let mut safe = vec![];
-    let mut partitions = vec![];
-
-    loop {
-        let Some(i) = (0..examining.len()).choose(rng) else {
-            break;
-        };
-        let r = examining.remove(i);
+    observer: &mut impl GenerationObserver,
+) -> BspNode {
+    rbsp_subtree(rng, full_rect, &params, true, observer)
+}
+
+/// Generate the same kind of tree as [rbsp], building independent subtrees in
+/// parallel. Without the `parallel` feature, subtrees are built one after
+/// another, giving the same tree.
+///
+/// Every subtree draws from its own random stream, derived from `seed` and its
+/// position in the tree, so the result only depends on the seed and not on how
+/// work is scheduled across threads. It does not match the output of [rbsp]
+/// for any seed.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(params)))]
+pub fn rbsp_par(seed: u64, full_rect: Rectangle<isize, usize>, params: RbspParams) -> BspNode {
+    rbsp_par_subtree(seed, full_rect, &params, true)
+}
+
+/// A single decision made by [RbspStepper].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RbspStep {
+    /// A rectangle was cut in two by a hallway.
+    Partition {
+        bounds: Rectangle<isize, usize>,
+        line: Line,
+    },
+
+    /// A rectangle was kept as a room.
+    Room(Rectangle<isize, usize>),
+
+    /// A corridor was carved around the edges of a rectangle. Its inside is
+    /// the next pending rectangle.
+    Ring {
+        bounds: Rectangle<isize, usize>,
+        lines: [Line; 4],
+    },
+}
+
+/// Runs [rbsp] one decision at a time, so the algorithm can be single-stepped
+/// or spread over several frames of a game loop.
+///
+/// Each call to `next` either splits or keeps one rectangle. Decisions are made
+/// in the same order as [rbsp], so stepping to the end with the same random
+/// number generator produces the same tree.
+#[derive(Debug, Clone)]
+pub struct RbspStepper<R> {
+    rng: R,
+    params: RbspParams,
+    pending: Vec<Rectangle<isize, usize>>,
+    steps: Vec<RbspStep>,
+}
 
-        if usize::min(r.w, r.h) / 2 <= params.min_room_len {
-            // Cannot partition this room any further without going less than min_room_len,
-            // so place in "acceptable" set
-            safe.push(r);
-            continue;
+impl<R: Rng> RbspStepper<R> {
+    pub fn new(rng: R, full_rect: Rectangle<isize, usize>, params: RbspParams) -> Self {
+        Self {
+            rng,
+            params,
+            pending: vec![full_rect],
+            steps: vec![],
         }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Rectangles that haven't been split or kept yet, next one last.
+    pub fn pending(&self) -> &[Rectangle<isize, usize>] {
+        &self.pending
+    }
+
+    /// Every decision made so far, in order.
+    pub fn steps(&self) -> &[RbspStep] {
+        &self.steps
+    }
+
+    /// The rooms as they currently stand: every kept room and every pending
+    /// rectangle.
+    pub fn rooms(&self) -> impl Iterator<Item = &Rectangle<isize, usize>> {
+        self.steps
+            .iter()
+            .filter_map(|s| match s {
+                RbspStep::Room(r) => Some(r),
+                RbspStep::Partition { .. } | RbspStep::Ring { .. } => None,
+            })
+            .chain(self.pending.iter())
+    }
 
-        let avged_size: f32 = (r.w as f32 * r.h as f32).powf(0.5);
-        if avged_size <= params.max_room_len as f32 && rng.gen::<f32>() < params.p_keep_rooms {
-            safe.push(r);
-            continue;
+    /// Every hallway placed so far.
+    pub fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.steps.iter().flat_map(|s| match s {
+            RbspStep::Room(_) => &[],
+            RbspStep::Partition { line, .. } => core::slice::from_ref(line),
+            RbspStep::Ring { lines, .. } => lines.as_slice(),
+        })
+    }
+
+    /// The finished tree, or `None` if there are still steps left.
+    pub fn tree(&self) -> Option<BspNode> {
+        if !self.is_done() {
+            return None;
+        }
+        fn build<'a>(steps: &mut impl Iterator<Item = &'a RbspStep>) -> BspNode {
+            match steps.next().unwrap() {
+                RbspStep::Room(r) => BspNode::Room(r.clone()),
+                RbspStep::Partition { bounds, line } => BspNode::Partition {
+                    bounds: bounds.clone(),
+                    line: line.clone(),
+                    children: Box::new([build(steps), build(steps)]),
+                },
+                RbspStep::Ring { bounds, lines } => BspNode::Ring {
+                    bounds: bounds.clone(),
+                    lines: lines.clone(),
+                    child: Box::new(build(steps)),
+                },
+            }
         }
+        Some(build(&mut self.steps.iter()))
+    }
+}
+
+impl<R: Rng> Iterator for RbspStepper<R> {
+    type Item = RbspStep;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let r = self.pending.pop()?;
+        // The inside of a ring is never ringed again straight away.
+        let just_ringed = matches!(self.steps.last(), Some(RbspStep::Ring { .. }));
+        let ringed = if just_ringed {
+            None
+        } else {
+            ring(&r, &self.params, self.steps.is_empty())
+        };
+        let step = if let Some((lines, inner)) = ringed {
+            self.pending.push(inner);
+            RbspStep::Ring { bounds: r, lines }
+        } else {
+            match split(&mut self.rng, &r, &self.params) {
+                Some((r1, line, r2)) => {
+                    self.pending.push(r2);
+                    self.pending.push(r1);
+                    RbspStep::Partition { bounds: r, line }
+                }
+                None => RbspStep::Room(r),
+            }
+        };
+        self.steps.push(step.clone());
+        Some(step)
+    }
+}
+
+/// Subtrees covering fewer tiles than this are built on the current thread,
+/// since splitting them off costs more than it saves.
+#[cfg(feature = "parallel")]
+const PAR_MIN_AREA: usize = 128 * 128;
+
+fn rbsp_subtree(
+    rng: &mut impl Rng,
+    r: Rectangle<isize, usize>,
+    params: &RbspParams,
+    root: bool,
+    observer: &mut impl GenerationObserver,
+) -> BspNode {
+    let Some((lines, inner)) = ring(&r, params, root) else {
+        return rbsp_split_subtree(rng, r, params, observer);
+    };
+    observer.on_ring(&r, &lines);
+    BspNode::Ring {
+        bounds: r,
+        lines,
+        child: Box::new(rbsp_split_subtree(rng, inner, params, observer)),
+    }
+}
+
+fn rbsp_split_subtree(
+    rng: &mut impl Rng,
+    r: Rectangle<isize, usize>,
+    params: &RbspParams,
+    observer: &mut impl GenerationObserver,
+) -> BspNode {
+    let Some((r1, line, r2)) = split(rng, &r, params) else {
+        observer.on_room_kept(&r);
+        return BspNode::Room(r);
+    };
+    observer.on_partition(&r, &line);
+
+    let children = Box::new([
+        rbsp_subtree(rng, r1, params, false, observer),
+        rbsp_subtree(rng, r2, params, false, observer),
+    ]);
+    BspNode::Partition {
+        bounds: r,
+        line,
+        children,
+    }
+}
+
+fn rbsp_par_subtree(
+    seed: u64,
+    r: Rectangle<isize, usize>,
+    params: &RbspParams,
+    root: bool,
+) -> BspNode {
+    match ring(&r, params, root) {
+        Some((lines, inner)) => BspNode::Ring {
+            bounds: r,
+            lines,
+            child: Box::new(rbsp_par_split_subtree(seed, inner, params)),
+        },
+        None => rbsp_par_split_subtree(seed, r, params),
+    }
+}
+
+fn rbsp_par_split_subtree(seed: u64, r: Rectangle<isize, usize>, params: &RbspParams) -> BspNode {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let Some((r1, line, r2)) = split(&mut rng, &r, params) else {
+        return BspNode::Room(r);
+    };
+
+    let (s1, s2) = (derive_seed(seed, [0]), derive_seed(seed, [1]));
+    #[cfg(feature = "parallel")]
+    let (c1, c2) = if r.w * r.h >= PAR_MIN_AREA {
+        rayon::join(
+            || rbsp_par_subtree(s1, r1, params, false),
+            || rbsp_par_subtree(s2, r2, params, false),
+        )
+    } else {
+        (
+            rbsp_par_subtree(s1, r1, params, false),
+            rbsp_par_subtree(s2, r2, params, false),
+        )
+    };
+    #[cfg(not(feature = "parallel"))]
+    let (c1, c2) = (
+        rbsp_par_subtree(s1, r1, params, false),
+        rbsp_par_subtree(s2, r2, params, false),
+    );
+    BspNode::Partition {
+        bounds: r,
+        line,
+        children: Box::new([c1, c2]),
+    }
+}
 
-        let axis = pick_axis(rng, &r, params.k_deoblongification);
-        println!("{}, {}", r.axis_length(axis), params.min_room_len);
-        let distribution_width = r.axis_length(axis) - params.min_room_len + 1;
-        let partition_offset = rng.gen_range(0..distribution_width) + params.min_room_len / 2;
-        let (r1, p, r2) = make_partition(&r, partition_offset, axis);
+/// Decide whether to carve a ring corridor around a rectangle, returning the
+/// corridor and the rectangle left inside it.
+///
+/// The east and west sides of the ring run the full height of the rectangle,
+/// and the north and south sides fill the gaps between them, so the ring and
+/// the inside rectangle exactly cover the original one.
+fn ring(
+    r: &Rectangle<isize, usize>,
+    params: &RbspParams,
+    root: bool,
+) -> Option<([Line; 4], Rectangle<isize, usize>)> {
+    let wanted = match params.perimeter_ring {
+        PerimeterRing::None => false,
+        PerimeterRing::Outer => root,
+        PerimeterRing::Subtrees { min_len } => root || usize::min(r.w, r.h) >= min_len,
+    };
+    let width = params.hallway_width;
+    if !wanted || usize::min(r.w, r.h) < params.min_room_len.max(1) + 2 * width {
+        return None;
+    }
+
+    let vertical = |x| Line {
+        x,
+        y: r.y,
+        length: r.h - 1,
+        width,
+        axis: Axis::Vertical,
+    };
+    let horizontal = |y| Line {
+        x: r.x + width as isize,
+        y,
+        length: r.w - 2 * width - 1,
+        width,
+        axis: Axis::Horizontal,
+    };
+    let lines = [
+        vertical(r.x),
+        vertical(r.x_end() - width as isize),
+        horizontal(r.y),
+        horizontal(r.y_end() - width as isize),
+    ];
+    let inner = Rectangle {
+        x: r.x + width as isize,
+        y: r.y + width as isize,
+        w: r.w - 2 * width,
+        h: r.h - 2 * width,
+    };
+    Some((lines, inner))
+}
 
-        examining.push(r1);
-        examining.push(r2);
-        partitions.push(p);
+/// The two halves of a rectangle and the hallway between them.
+type Split = (Rectangle<isize, usize>, Line, Rectangle<isize, usize>);
+
+/// Decide whether to keep a rectangle as a room, and if not, where to cut it.
+fn split(rng: &mut impl Rng, r: &Rectangle<isize, usize>, params: &RbspParams) -> Option<Split> {
+    let long_axis = if r.w >= r.h {
+        Axis::Horizontal
+    } else {
+        Axis::Vertical
+    };
+    // Cutting takes away the hallway's width, so only the rest is shared between
+    // the two halves.
+    let room_len = |len: usize| len.saturating_sub(params.hallway_width);
+    let aspect_ratio = usize::max(r.w, r.h) as f32 / usize::min(r.w, r.h).max(1) as f32;
+    // Empty rectangles are never cut, since a hallway across one would cover
+    // tiles outside of it.
+    if aspect_ratio > params.max_aspect_ratio
+        && r.w * r.h > 0
+        && room_len(r.axis_length(long_axis)) / 2 > params.min_room_len
+    {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?r, aspect_ratio, "splitting oblong room");
+        return Some(cut(rng, r, long_axis, params));
     }
 
-    println!("{safe:#?}");
+    if room_len(usize::min(r.w, r.h)) / 2 <= params.min_room_len {
+        // Cannot partition this room any further without going less than min_room_len,
+        // so keep it
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?r, "keeping room at minimum size");
+        return None;
+    }
+
+    let avged_size: f32 = math::powf(r.w as f32 * r.h as f32, 0.5);
+    let p_keep = params.p_keep_rooms * params.keep_weight.weight(r.w * r.h);
+    if avged_size <= params.max_room_len as f32 && rng.gen::<f32>() < p_keep {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?r, "keeping room by chance");
+        return None;
+    }
 
-    (safe, partitions)
+    let axis = pick_axis(rng, r, params.k_deoblongification);
+    Some(cut(rng, r, axis, params))
+}
+
+/// Cut a rectangle across the given axis at a random offset.
+///
+/// `r` must be longer than `min_room_len` plus `hallway_width` along `axis`,
+/// which [split] checks before cutting.
+fn cut(rng: &mut impl Rng, r: &Rectangle<isize, usize>, axis: Axis, params: &RbspParams) -> Split {
+    let room_len = r.axis_length(axis) - params.hallway_width;
+    let distribution_width = room_len - params.min_room_len + 1;
+    let t = rng.sample(params.offset_distribution);
+    let partition_offset = (t * distribution_width as f32) as usize + params.min_room_len / 2;
+    #[cfg(feature = "tracing")]
+    tracing::trace!(?r, ?axis, offset = partition_offset, "splitting");
+    make_partition(r, partition_offset, axis, params.hallway_width)
 }
 
 fn pick_axis<O: BaseNum, L: BaseNum>(
@@ -76,8 +623,8 @@ fn pick_axis<O: BaseNum, L: BaseNum>(
     rect: &Rectangle<O, L>,
     k_deoblongification: f32,
 ) -> Axis {
-    let w_weight = rect.w.to_f32().unwrap().powf(k_deoblongification);
-    let h_weight = rect.h.to_f32().unwrap().powf(k_deoblongification);
+    let w_weight = math::powf(rect.w.to_f32().unwrap(), k_deoblongification);
+    let h_weight = math::powf(rect.h.to_f32().unwrap(), k_deoblongification);
 
     let p_horiz = w_weight / (w_weight + h_weight);
 
@@ -88,10 +635,14 @@ fn pick_axis<O: BaseNum, L: BaseNum>(
     }
 }
 
+/// Cut a rectangle in two `offset` tiles along `axis`, with a hallway `width`
+/// tiles wide between the halves. The halves and the hallway exactly cover the
+/// rectangle.
 pub fn make_partition(
     r: &Rectangle<isize, usize>,
     offset: usize,
     axis: Axis,
+    width: usize,
 ) -> (Rectangle<isize, usize>, Line, Rectangle<isize, usize>) {
     match axis {
         Axis::Horizontal => {
@@ -102,15 +653,16 @@ pub fn make_partition(
                 h: r.h,
             };
             let r2 = Rectangle {
-                x: r.x + offset as isize,
+                x: r.x + (offset + width) as isize,
                 y: r.y,
-                w: r.w - offset,
+                w: r.w - offset - width,
                 h: r.h,
             };
             let p = Line {
                 x: r.x + offset as isize,
                 y: r.y,
-                length: r.h,
+                length: r.h.saturating_sub(1),
+                width,
                 axis: Axis::Vertical,
             };
             (r1, p, r2)
@@ -124,14 +676,15 @@ pub fn make_partition(
             };
             let r2 = Rectangle {
                 x: r.x,
-                y: r.y + offset as isize,
+                y: r.y + (offset + width) as isize,
                 w: r.w,
-                h: r.h - offset,
+                h: r.h - offset - width,
             };
             let p = Line {
                 x: r.x,
                 y: r.y + offset as isize,
-                length: r.w,
+                length: r.w.saturating_sub(1),
+                width,
                 axis: Axis::Horizontal,
             };
             (r1, p, r2)
@@ -150,8 +703,8 @@ where
 {
     let walls_percents = [L::zero()]
         .into_iter()
-        .chain(divider_percents.into_iter())
-        .chain([L::one()].into_iter());
+        .chain(divider_percents)
+        .chain([L::one()]);
 
     let mut wall_offsets = walls_percents
         .map(|p| match axis {
@@ -183,12 +736,107 @@ where
 
 #[cfg(test)]
 mod tests {
-    use rand::{rngs::SmallRng, SeedableRng};
+    use proptest::{
+        prop_assert, prop_assert_eq, prop_oneof, proptest,
+        strategy::{Just, Strategy},
+    };
 
     use crate::util::{Line, Rectangle};
 
     use super::*;
 
+    fn arb_params() -> impl Strategy<Value = RbspParams> {
+        (
+            1..20usize,
+            1..100usize,
+            0.0..=1.0f32,
+            0.1..10.0f32,
+            0..4usize,
+            prop_oneof![
+                Just(OffsetDistribution::Uniform),
+                Just(OffsetDistribution::Triangular),
+                (0.0..0.5f32).prop_map(|jitter| OffsetDistribution::GoldenRatio { jitter }),
+            ],
+            prop_oneof![Just(f32::INFINITY), 1.0..10.0f32],
+            prop_oneof![
+                Just(PerimeterRing::None),
+                Just(PerimeterRing::Outer),
+                (1..64usize).prop_map(|min_len| PerimeterRing::Subtrees { min_len }),
+            ],
+        )
+            .prop_map(
+                |(
+                    min_room_len,
+                    max_room_len,
+                    p_keep_rooms,
+                    k_deoblongification,
+                    hallway_width,
+                    offset_distribution,
+                    max_aspect_ratio,
+                    perimeter_ring,
+                )| RbspParams {
+                    min_room_len,
+                    max_room_len,
+                    p_keep_rooms,
+                    k_deoblongification,
+                    hallway_width,
+                    offset_distribution,
+                    max_aspect_ratio,
+                    perimeter_ring,
+                    ..Default::default()
+                },
+            )
+    }
+
+    fn arb_bounds() -> impl Strategy<Value = Rectangle<isize, usize>> {
+        (-1000..1000isize, -1000..1000isize, 0..160usize, 0..160usize)
+            .prop_map(|(x, y, w, h)| Rectangle { x, y, w, h })
+    }
+
+    proptest! {
+        #[test]
+        fn rbsp_rooms_respect_params(
+            seed: u64,
+            bounds in arb_bounds(),
+            params in arb_params(),
+        ) {
+            let tree = rbsp(&mut SmallRng::seed_from_u64(seed), bounds.clone(), params.clone());
+
+            prop_assert_eq!(tree.bounds(), &bounds);
+            if bounds.w > 0 && bounds.h > 0 {
+                let area = |r: &Rectangle<isize, usize>| r.w * r.h;
+                let covered = tree.rooms().map(area).sum::<usize>()
+                    + tree.lines().map(|l| area(&l.bounds())).sum::<usize>();
+                prop_assert_eq!(covered, area(&bounds));
+            }
+            for r in tree.rooms() {
+                prop_assert!(
+                    r.x >= bounds.x
+                        && r.y >= bounds.y
+                        && r.x_end() <= bounds.x_end()
+                        && r.y_end() <= bounds.y_end(),
+                    "{:?} is outside {:?}",
+                    r,
+                    bounds
+                );
+
+                // Rooms are only ever cut down to half the minimum length, and
+                // never below what the bounds started with.
+                let half = params.min_room_len / 2;
+                prop_assert!(r.w >= half.min(bounds.w) && r.h >= half.min(bounds.h), "{:?}", r);
+
+                let room_len = usize::min(r.w, r.h).saturating_sub(params.hallway_width);
+                let splittable = room_len / 2 > params.min_room_len;
+                let avged_size = (r.w as f32 * r.h as f32).powf(0.5);
+                prop_assert!(
+                    !splittable || avged_size <= params.max_room_len as f32,
+                    "{:?} should have been split",
+                    r
+                );
+            }
+        }
+    }
+
     #[test]
     fn generation_smoke_test() {
         for i in 0..1000 {
@@ -201,14 +849,291 @@ mod tests {
                     w: 512,
                     h: 512,
                 },
+                RbspParams::default(),
+            );
+        }
+    }
+
+    #[test]
+    fn tree_has_one_more_room_than_lines() {
+        for i in 0..100 {
+            let mut rng = SmallRng::seed_from_u64(i);
+            let tree = rbsp(
+                &mut rng,
+                Rectangle {
+                    x: 0,
+                    y: 0,
+                    w: 256,
+                    h: 256,
+                },
+                RbspParams::default(),
+            );
+
+            assert_eq!(tree.rooms().count(), tree.lines().count() + 1);
+            for node in tree.iter() {
+                if let BspNode::Partition {
+                    bounds,
+                    line,
+                    children,
+                } = node
+                {
+                    let area = |r: &Rectangle<isize, usize>| r.w * r.h;
+                    assert_eq!(
+                        area(children[0].bounds())
+                            + area(&line.bounds())
+                            + area(children[1].bounds()),
+                        area(bounds)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rooms_and_hallways_tile_bounds() {
+        let params = RbspParams {
+            max_room_len: 40,
+            hallway_width: 2,
+            perimeter_ring: PerimeterRing::Subtrees { min_len: 60 },
+            ..Default::default()
+        };
+        let r = Rectangle {
+            x: -20,
+            y: 10,
+            w: 200,
+            h: 150,
+        };
+        for i in 0..20 {
+            let tree = rbsp(&mut SmallRng::seed_from_u64(i), r.clone(), params.clone());
+
+            let mut covered = vec![0; r.w * r.h];
+            let rects = tree.rooms().cloned().chain(tree.lines().map(Line::bounds));
+            for rect in rects {
+                for x in rect.x..rect.x_end() {
+                    for y in rect.y..rect.y_end() {
+                        covered[(x - r.x) as usize * r.h + (y - r.y) as usize] += 1;
+                    }
+                }
+            }
+
+            assert!(covered.iter().all(|&n| n == 1));
+        }
+    }
+
+    #[test]
+    fn observer_sees_every_node() {
+        #[derive(Default)]
+        struct Counter {
+            partitions: usize,
+            area: usize,
+        }
+        impl GenerationObserver for Counter {
+            fn on_partition(&mut self, _: &Rectangle<isize, usize>, line: &Line) {
+                let b = line.bounds();
+                self.partitions += 1;
+                self.area += b.w * b.h;
+            }
+
+            fn on_room_kept(&mut self, room: &Rectangle<isize, usize>) {
+                self.area += room.w * room.h;
+            }
+        }
+        let mut counter = Counter::default();
+        let params = RbspParams::default();
+        let r = Rectangle {
+            x: 0,
+            y: 0,
+            w: 256,
+            h: 128,
+        };
+
+        let tree = rbsp_observed(
+            &mut SmallRng::seed_from_u64(3),
+            r.clone(),
+            params.clone(),
+            &mut counter,
+        );
+
+        assert_eq!(tree, rbsp(&mut SmallRng::seed_from_u64(3), r, params));
+        assert_eq!(counter.partitions, tree.lines().count());
+        assert_eq!(counter.area, 256 * 128);
+    }
+
+    #[test]
+    fn oblong_rooms_are_cut() {
+        for i in 0..50 {
+            let tree = rbsp(
+                &mut SmallRng::seed_from_u64(i),
+                Rectangle {
+                    x: 0,
+                    y: 0,
+                    w: 256,
+                    h: 256,
+                },
                 RbspParams {
-                    min_room_len: 5,
-                    max_room_len: 80,
-                    p_keep_rooms: 0.3,
-                    k_deoblongification: 5.0,
+                    p_keep_rooms: 0.5,
+                    k_deoblongification: 0.5,
+                    max_aspect_ratio: 3.0,
+                    ..Default::default()
                 },
             );
+
+            for r in tree.rooms() {
+                let (long, short) = (r.w.max(r.h), r.w.min(r.h));
+                assert!(
+                    long as f32 <= 3.0 * short as f32 || (long - 1) / 2 <= 5,
+                    "{r:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keep_weights_shape_room_sizes() {
+        let params = |keep_weight| RbspParams {
+            p_keep_rooms: 1.0,
+            keep_weight,
+            ..Default::default()
+        };
+        let r = Rectangle {
+            x: 0,
+            y: 0,
+            w: 256,
+            h: 256,
+        };
+
+        let only_small = rbsp(
+            &mut SmallRng::seed_from_u64(0),
+            r.clone(),
+            params(KeepWeight::Histogram(vec![(400, 1.0)])),
+        );
+        let no_small = rbsp(
+            &mut SmallRng::seed_from_u64(0),
+            r,
+            params(KeepWeight::Function(
+                |area| if area < 1000 { 0.0 } else { 1.0 },
+            )),
+        );
+
+        for room in only_small.rooms() {
+            let unsplittable = (room.w.min(room.h) - 1) / 2 <= 5;
+            assert!(room.w * room.h <= 400 || unsplittable, "{room:?}");
+        }
+        let big = no_small.rooms().filter(|r| r.w * r.h >= 1000).count();
+        assert!(big > 0);
+    }
+
+    #[test]
+    fn stepper_matches_rbsp() {
+        let params = RbspParams::default();
+        let r = Rectangle {
+            x: 0,
+            y: 0,
+            w: 256,
+            h: 256,
+        };
+        for i in 0..20 {
+            let tree = rbsp(&mut SmallRng::seed_from_u64(i), r.clone(), params.clone());
+            let mut stepper =
+                RbspStepper::new(SmallRng::seed_from_u64(i), r.clone(), params.clone());
+
+            assert_eq!(stepper.tree(), None);
+            let first = stepper.next();
+            assert!(matches!(first, Some(RbspStep::Partition { .. })));
+            assert_eq!(stepper.rooms().count(), 2);
+            let n = stepper.by_ref().count();
+
+            assert_eq!(n + 1, tree.iter().count());
+            assert_eq!(stepper.tree(), Some(tree));
+        }
+    }
+
+    #[test]
+    fn perimeter_rings() {
+        let params = RbspParams {
+            hallway_width: 2,
+            perimeter_ring: PerimeterRing::Subtrees { min_len: 100 },
+            ..Default::default()
+        };
+        let r = Rectangle {
+            x: 0,
+            y: 0,
+            w: 256,
+            h: 256,
+        };
+        for i in 0..20 {
+            let tree = rbsp(&mut SmallRng::seed_from_u64(i), r.clone(), params.clone());
+            let mut stepper =
+                RbspStepper::new(SmallRng::seed_from_u64(i), r.clone(), params.clone());
+
+            let BspNode::Ring { lines, child, .. } = &tree else {
+                panic!("no ring around the outside");
+            };
+            let edges = lines.iter().map(Line::bounds).collect::<Vec<_>>();
+            assert_eq!(
+                edges[0],
+                Rectangle {
+                    x: 0,
+                    y: 0,
+                    w: 2,
+                    h: 256
+                }
+            );
+            assert_eq!(
+                edges[3],
+                Rectangle {
+                    x: 2,
+                    y: 254,
+                    w: 252,
+                    h: 2
+                }
+            );
+            assert!(!matches!(**child, BspNode::Ring { .. }));
+            assert!(tree.lines().count() > tree.rooms().count() - 1);
+
+            assert_eq!(stepper.by_ref().count(), tree.iter().count());
+            assert_eq!(stepper.tree(), Some(tree));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn parallel_generation_ignores_thread_count() {
+        let params = RbspParams::default();
+        let r = Rectangle {
+            x: 0,
+            y: 0,
+            w: 1024,
+            h: 1024,
+        };
+        let single = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+
+        let a = rbsp_par(7, r.clone(), params.clone());
+        let b = single.install(|| rbsp_par(7, r.clone(), params.clone()));
+
+        assert_eq!(a, b);
+        assert_ne!(a, rbsp_par(8, r, params));
+        assert_eq!(a.rooms().count(), a.lines().count() + 1);
+    }
+
+    #[test]
+    fn offset_distributions_stay_in_range() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for d in [
+            OffsetDistribution::Uniform,
+            OffsetDistribution::Triangular,
+            OffsetDistribution::GoldenRatio { jitter: 0.5 },
+        ] {
+            for _ in 0..1000 {
+                assert!((0.0..1.0).contains(&rng.sample(d)));
+            }
         }
+
+        let t = rng.sample(OffsetDistribution::GoldenRatio { jitter: 0.0 });
+        assert!((t - 0.382).abs() < 0.001 || (t - 0.618).abs() < 0.001);
     }
 
     #[test]
@@ -222,6 +1147,7 @@ mod tests {
             },
             5,
             Axis::Horizontal,
+            1,
         );
 
         let expected = (
@@ -234,13 +1160,14 @@ mod tests {
             Line {
                 x: 7,
                 y: 5,
-                length: 8,
+                length: 7,
+                width: 1,
                 axis: Axis::Vertical,
             },
             Rectangle {
-                x: 7,
+                x: 8,
                 y: 5,
-                w: 5,
+                w: 4,
                 h: 8,
             },
         );
@@ -259,6 +1186,7 @@ mod tests {
             },
             4,
             Axis::Vertical,
+            1,
         );
 
         let expected = (
@@ -271,14 +1199,15 @@ mod tests {
             Line {
                 x: 2,
                 y: 9,
-                length: 10,
+                length: 9,
+                width: 1,
                 axis: Axis::Horizontal,
             },
             Rectangle {
                 x: 2,
-                y: 9,
+                y: 10,
                 w: 10,
-                h: 4,
+                h: 3,
             },
         );
 
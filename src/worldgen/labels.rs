@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+use ndarray::Array2;
+
+use crate::{
+    tile::Tile,
+    util::{Line, Region},
+};
+
+use super::{connectivity::neighbors, graph::Node};
+
+/// Identifies the room or hallway segment a cell belongs to, indexing into the
+/// room and hallway lists the map was rasterized from.
+pub type RoomId = Node;
+
+/// Label every non-solid cell of a rasterized map with the room or hallway it
+/// belongs to.
+///
+/// Hallways take priority over rooms where they overlap. Cells outside every
+/// room and hallway, such as tunnels carved by later passes, take the label of
+/// the nearest labeled cell they are connected to. Solid cells, and cells not
+/// connected to any labeled cell, are `None`.
+pub fn label_rooms(map: &Array2<Tile>, rooms: &[Region], lines: &[Line]) -> Array2<Option<RoomId>> {
+    let mut labels = Array2::from_elem(map.dim(), None);
+    let mut label = |pos: (isize, isize), id: RoomId| {
+        let (Ok(x), Ok(y)) = (usize::try_from(pos.0), usize::try_from(pos.1)) else {
+            return;
+        };
+        if let (Some(t), Some(l)) = (map.get((x, y)), labels.get_mut((x, y))) {
+            if !t.is_solid() && l.is_none() {
+                *l = Some(id);
+            }
+        }
+    };
+
+    for (i, l) in lines.iter().enumerate() {
+        for pos in l.points() {
+            label(pos, Node::Hallway(i));
+        }
+    }
+    for (i, r) in rooms.iter().enumerate() {
+        for pos in r.cells() {
+            label(pos, Node::Room(i));
+        }
+    }
+
+    let mut queue = labels
+        .indexed_iter()
+        .filter(|(_, l)| l.is_some())
+        .map(|(i, _)| i)
+        .collect::<VecDeque<_>>();
+    while let Some(c) = queue.pop_front() {
+        for n in neighbors(map.dim(), c) {
+            if !map[n].is_solid() && labels[n].is_none() {
+                labels[n] = labels[c];
+                queue.push_back(n);
+            }
+        }
+    }
+
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        util::{Axis, Rectangle},
+        worldgen::raster::rasterize,
+    };
+
+    use super::*;
+
+    #[test]
+    fn labels_rooms_hallways_and_tunnels() {
+        let rooms = [
+            Region::from(Rectangle {
+                x: 0,
+                y: 2,
+                w: 10,
+                h: 5,
+            }),
+            Region::from(Rectangle {
+                x: 0,
+                y: 7,
+                w: 10,
+                h: 5,
+            }),
+        ];
+        let lines = [Line {
+            x: 0,
+            y: 0,
+            length: 9,
+            width: 1,
+            axis: Axis::Horizontal,
+        }];
+        let mut map = rasterize((10, 12), &rooms, &lines, &[]);
+        map[(5, 1)] = Tile::Floor;
+
+        let labels = label_rooms(&map, &rooms, &lines);
+
+        assert_eq!(labels[(3, 0)], Some(Node::Hallway(0)));
+        assert_eq!(labels[(5, 1)], Some(Node::Hallway(0)));
+        assert_eq!(labels[(5, 4)], Some(Node::Room(0)));
+        assert_eq!(labels[(5, 9)], Some(Node::Room(1)));
+        assert_eq!(labels[(0, 4)], None);
+    }
+}
@@ -0,0 +1,471 @@
+use std::ops::Range;
+
+use ndarray::{s, Array2};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    tile::Tile,
+    util::{derive_seed, Rectangle, Region},
+    world::Decal,
+};
+
+use super::{
+    connectivity::connect_regions,
+    doors::place_doors,
+    hallways::{rbsp, RbspParams},
+    pipeline::{GeneratedWorld, WorldGenerator},
+    placement::{pick_exits, pick_spawn},
+    presets::Preset,
+    raster::rasterize,
+};
+
+/// Tags mixed into seeds so that floors and connector placement never share a
+/// random stream.
+const FLOOR_TAG: u64 = 0;
+const CONNECTOR_TAG: u64 = 1;
+const EXIT_TAG: u64 = 2;
+
+/// How many times to retry placing a connector before giving up on it.
+const MAX_PLACEMENT_ATTEMPTS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectorKind {
+    /// Joins two neighboring floors.
+    Stairwell,
+
+    /// Joins every floor in a contiguous range.
+    Elevator,
+
+    /// A driveway sloping between two neighboring floors.
+    Ramp,
+}
+
+impl ConnectorKind {
+    /// The tile placed in the middle of the connector's shaft, or all over a
+    /// ramp.
+    pub fn tile(self) -> Tile {
+        match self {
+            ConnectorKind::Stairwell => Tile::Stairs,
+            ConnectorKind::Elevator => Tile::Elevator,
+            ConnectorKind::Ramp => Tile::Ramp,
+        }
+    }
+}
+
+/// A vertical shaft occupying the same square footprint on several floors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connector {
+    pub kind: ConnectorKind,
+
+    /// The floors this connector appears on.
+    pub floors: Range<usize>,
+
+    /// The footprint of the shaft, identical on every floor it appears on.
+    pub bounds: Rectangle<isize, usize>,
+}
+
+impl Connector {
+    /// The tile in the middle of the shaft, where the stairs or elevator is.
+    pub fn center(&self) -> (usize, usize) {
+        (
+            (self.bounds.x + self.bounds.w as isize / 2) as usize,
+            (self.bounds.y + self.bounds.h as isize / 2) as usize,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelParams {
+    /// Number of floors in the stack.
+    pub floors: usize,
+
+    /// Width and height of every floor, in tiles.
+    pub dim: (usize, usize),
+
+    /// Parameters used to generate the layout of each floor.
+    pub rbsp: RbspParams,
+
+    /// Number of stairwells between each pair of neighboring floors.
+    pub stairwells_per_floor: usize,
+
+    /// Number of elevators, each running through every floor.
+    pub elevators: usize,
+
+    /// Width and height of the open area cleared around each connector.
+    pub shaft_size: usize,
+}
+
+/// A stack of floors joined by stairwells and elevators.
+#[derive(Debug, Clone)]
+pub struct MultiLevelWorld {
+    /// Each floor's map, indexed by `(x, y)`, from the top floor down.
+    pub floors: Vec<Array2<Tile>>,
+
+    pub connectors: Vec<Connector>,
+
+    /// The floor markings of each floor, in the same order as `floors`.
+    pub decals: Vec<Vec<Decal>>,
+}
+
+impl MultiLevelWorld {
+    /// All connectors that appear on the given floor.
+    pub fn connectors_on(&self, floor: usize) -> impl Iterator<Item = &Connector> {
+        self.connectors
+            .iter()
+            .filter(move |c| c.floors.contains(&floor))
+    }
+}
+
+/// Generate a stack of floors with vertically aligned stairwells and elevators.
+///
+/// Every floor is fully connected, including the connectors placed on it, so a
+/// connector can always be reached from anywhere on its floors.
+pub fn generate_levels(seed: u64, params: &LevelParams) -> MultiLevelWorld {
+    let connectors = place_connectors(seed, params);
+
+    let floors = (0..params.floors)
+        .map(|i| {
+            let mut rng = SmallRng::seed_from_u64(derive_seed(seed, [FLOOR_TAG, i as u64]));
+            let bounds = Rectangle {
+                x: 0,
+                y: 0,
+                w: params.dim.0,
+                h: params.dim.1,
+            };
+            let (rooms, lines) = rbsp(&mut rng, bounds, params.rbsp.clone()).into_flat();
+            let rooms = rooms.into_iter().map(Region::from).collect::<Vec<_>>();
+            let doors = place_doors(&mut rng, &rooms, &lines);
+            let mut map = rasterize(params.dim, &rooms, &lines, &doors);
+
+            for c in connectors.iter().filter(|c| c.floors.contains(&i)) {
+                stamp(&mut map, c);
+            }
+            // Start from a connector on this floor, so it's never the one
+            // walled off, or from a room on floors without any.
+            let start = connectors
+                .iter()
+                .find(|c| c.floors.contains(&i))
+                .map(Connector::center)
+                .or_else(|| pick_spawn(&map, &rooms));
+            if let Some(start) = start {
+                connect_regions(&mut map, start);
+            }
+            map
+        })
+        .collect();
+
+    MultiLevelWorld {
+        floors,
+        connectors,
+        decals: vec![vec![]; params.floors],
+    }
+}
+
+/// Pick non-overlapping footprints for every connector. Connectors that can't
+/// be fit in are dropped.
+fn place_connectors(seed: u64, params: &LevelParams) -> Vec<Connector> {
+    let mut rng = SmallRng::seed_from_u64(derive_seed(seed, [CONNECTOR_TAG]));
+    let n = params.floors;
+
+    let elevators = (0..params.elevators).map(|_| (ConnectorKind::Elevator, 0..n));
+    let stairwells = (0..n.saturating_sub(1)).flat_map(|i| {
+        (0..params.stairwells_per_floor).map(move |_| (ConnectorKind::Stairwell, i..i + 2))
+    });
+
+    let mut placed: Vec<Connector> = vec![];
+    for (kind, floors) in elevators.chain(stairwells) {
+        let fits = |bounds: &Rectangle<isize, usize>| {
+            !placed.iter().any(|c| {
+                c.floors.start < floors.end
+                    && floors.start < c.floors.end
+                    && c.bounds.intersects(bounds)
+            })
+        };
+        let bounds = (0..MAX_PLACEMENT_ATTEMPTS)
+            .filter_map(|_| random_footprint(&mut rng, params.dim, params.shaft_size))
+            .find(fits);
+        if let Some(bounds) = bounds {
+            placed.push(Connector {
+                kind,
+                floors,
+                bounds,
+            });
+        }
+    }
+    placed
+}
+
+/// A random square of the given size, at least one tile away from the map edge.
+fn random_footprint(
+    rng: &mut impl Rng,
+    (w, h): (usize, usize),
+    size: usize,
+) -> Option<Rectangle<isize, usize>> {
+    if size + 2 > w || size + 2 > h {
+        return None;
+    }
+    Some(Rectangle {
+        x: rng.gen_range(1..=w - size - 1) as isize,
+        y: rng.gen_range(1..=h - size - 1) as isize,
+        w: size,
+        h: size,
+    })
+}
+
+/// Clear the connector's footprint and put its stairs or elevator in the middle.
+fn stamp(map: &mut Array2<Tile>, c: &Connector) {
+    let (x, y) = (c.bounds.x as usize, c.bounds.y as usize);
+    map.slice_mut(s![x..x + c.bounds.w, y..y + c.bounds.h])
+        .fill(Tile::Floor);
+    map[c.center()] = c.kind.tile();
+}
+
+/// One level of a [`LevelGraph`], with everything needed to generate it again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LevelNode {
+    pub preset: Preset,
+    pub seed: u64,
+
+    /// The ways out of this level, or `None` if they haven't been placed yet.
+    pub exits: Option<Vec<LevelExit>>,
+}
+
+/// A spot on one level that leads to another, such as a patch of wall to
+/// noclip through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LevelExit {
+    /// Position of the exit, indexed like the level's tiles.
+    pub pos: (usize, usize),
+
+    /// Index of the level the exit leads to.
+    pub to: usize,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExitParams {
+    /// How many exits to place on each level. Fewer are placed on levels
+    /// without enough room for them.
+    pub exits_per_level: usize,
+
+    /// The presets levels behind exits are picked from. Every preset is used
+    /// if this is empty.
+    pub presets: Vec<Preset>,
+}
+
+/// Levels joined by exits, discovered one level at a time so a game can
+/// generate levels on demand as the player moves between them.
+///
+/// Each level's seed is derived from the level it was first reached from, so
+/// the same starting seed always leads to the same levels, whichever order
+/// they are explored in. Only their indices depend on the order.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LevelGraph {
+    /// Width and height of every level, in tiles.
+    pub dim: (usize, usize),
+
+    /// Every level discovered so far, starting with the first.
+    pub levels: Vec<LevelNode>,
+}
+
+impl LevelGraph {
+    /// A graph holding just the level the player starts on.
+    pub fn new(seed: u64, start: Preset, dim: (usize, usize)) -> Self {
+        Self {
+            dim,
+            levels: vec![LevelNode {
+                preset: start,
+                seed,
+                exits: None,
+            }],
+        }
+    }
+
+    /// Generate the map of a level. The same level always gives the same map.
+    pub fn generate(&self, level: usize) -> GeneratedWorld {
+        let node = &self.levels[level];
+        let bounds = Rectangle {
+            x: 0,
+            y: 0,
+            w: self.dim.0,
+            h: self.dim.1,
+        };
+        node.preset
+            .generate(&mut SmallRng::seed_from_u64(node.seed), bounds)
+    }
+
+    /// The exits out of a level, given its map as made by
+    /// [`generate`](Self::generate). The first time a level's exits are asked
+    /// for, they are placed far apart and far from the level's spawn, each
+    /// leading to a new level.
+    pub fn exits(
+        &mut self,
+        level: usize,
+        world: &GeneratedWorld,
+        params: &ExitParams,
+    ) -> &[LevelExit] {
+        if self.levels[level].exits.is_none() {
+            let exits = self.place_exits(level, world, params);
+            self.levels[level].exits = Some(exits);
+        }
+        self.levels[level].exits.as_deref().unwrap()
+    }
+
+    fn place_exits(
+        &mut self,
+        level: usize,
+        world: &GeneratedWorld,
+        params: &ExitParams,
+    ) -> Vec<LevelExit> {
+        let spawn = pick_spawn(&world.tiles, &world.rooms).or_else(|| {
+            let mut open = world.tiles.indexed_iter().filter(|(_, t)| !t.is_solid());
+            open.next().map(|(pos, _)| pos)
+        });
+        let Some(spawn) = spawn else {
+            return vec![];
+        };
+        let presets = match params.presets.as_slice() {
+            [] => &Preset::ALL[..],
+            presets => presets,
+        };
+
+        let parent = self.levels[level].seed;
+        pick_exits(&world.tiles, spawn, params.exits_per_level)
+            .into_iter()
+            .enumerate()
+            .map(|(i, exit)| {
+                let seed = derive_seed(parent, [EXIT_TAG, i as u64]);
+                let mut rng = SmallRng::seed_from_u64(seed);
+                self.levels.push(LevelNode {
+                    preset: *presets.choose(&mut rng).unwrap(),
+                    seed,
+                    exits: None,
+                });
+                LevelExit {
+                    pos: exit.pos,
+                    to: self.levels.len() - 1,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::worldgen::connectivity::unreachable_cells;
+
+    use super::*;
+
+    fn params() -> LevelParams {
+        LevelParams {
+            floors: 4,
+            dim: (96, 96),
+            rbsp: RbspParams {
+                max_room_len: 30,
+                ..Default::default()
+            },
+            stairwells_per_floor: 2,
+            elevators: 1,
+            shaft_size: 3,
+        }
+    }
+
+    #[test]
+    fn connectors_are_aligned_across_floors() {
+        let world = generate_levels(7, &params());
+
+        assert_eq!(world.floors.len(), 4);
+        assert_eq!(world.connectors.len(), 7);
+        for c in &world.connectors {
+            for i in c.floors.clone() {
+                assert_eq!(world.floors[i][c.center()], c.kind.tile());
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(0)]
+    fn every_floor_is_connected(#[case] elevators: usize) {
+        let params = LevelParams {
+            elevators,
+            ..params()
+        };
+        let world = generate_levels(3, &params);
+
+        for (i, floor) in world.floors.iter().enumerate() {
+            for c in world.connectors_on(i) {
+                assert!(unreachable_cells(floor, c.center()).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn connectors_do_not_overlap() {
+        let world = generate_levels(11, &params());
+
+        for i in 0..world.floors.len() {
+            let on_floor = world.connectors_on(i).collect::<Vec<_>>();
+            for (j, a) in on_floor.iter().enumerate() {
+                for b in &on_floor[j + 1..] {
+                    assert!(!a.bounds.intersects(&b.bounds));
+                }
+            }
+        }
+    }
+
+    fn exit_params() -> ExitParams {
+        ExitParams {
+            exits_per_level: 3,
+            presets: vec![Preset::Level2Maintenance, Preset::PoolRooms],
+        }
+    }
+
+    #[test]
+    fn exits_lead_to_new_levels() {
+        let mut graph = LevelGraph::new(5, Preset::Office, (96, 64));
+        let world = graph.generate(0);
+
+        let exits = graph.exits(0, &world, &exit_params()).to_vec();
+
+        assert_eq!(exits.len(), 3);
+        assert_eq!(graph.levels.len(), 4);
+        for (i, exit) in exits.iter().enumerate() {
+            assert_eq!(exit.to, i + 1);
+            assert!(!world.tiles[exit.pos].is_solid());
+            assert!(exit_params()
+                .presets
+                .contains(&graph.levels[exit.to].preset));
+        }
+        assert_eq!(graph.exits(0, &world, &exit_params()), exits);
+        assert_eq!(graph.levels.len(), 4);
+    }
+
+    #[test]
+    fn level_graphs_are_reproducible() {
+        let explore = |order: &[usize]| {
+            let mut graph = LevelGraph::new(9, Preset::Level0, (64, 64));
+            let world = graph.generate(0);
+            graph.exits(0, &world, &exit_params());
+            for &level in order {
+                let world = graph.generate(level);
+                graph.exits(level, &world, &exit_params());
+            }
+            graph
+        };
+
+        let a = explore(&[1, 2]);
+        let b = explore(&[2, 1]);
+
+        let find = |g: &LevelGraph, seed| g.levels.iter().find(|l| l.seed == seed).cloned();
+        for level in &a.levels {
+            assert_eq!(find(&b, level.seed).map(|l| l.preset), Some(level.preset));
+        }
+        assert_eq!(a.generate(2).tiles, b.generate(2).tiles);
+    }
+}
@@ -0,0 +1,183 @@
+use std::f32::consts::TAU;
+
+use ndarray::Array2;
+use rand::Rng;
+
+use crate::{
+    tile::Tile,
+    world::{FlickerCurve, Light},
+};
+
+use super::scatter::poisson_disk;
+
+/// How lights are spread over the floor.
+#[derive(Debug, Clone)]
+pub enum LightLayout {
+    /// One light every `spacing` tiles on each axis, like a drop ceiling.
+    Grid { spacing: usize },
+
+    /// Randomly scattered lights, no closer than `min_distance` to each other.
+    PoissonDisk { min_distance: f32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct LightParams {
+    pub layout: LightLayout,
+
+    /// Range that each light's steady intensity is picked from, in [0, 1].
+    pub min_intensity: f32,
+    pub max_intensity: f32,
+
+    /// Probability in [0, 1] that a light flickers.
+    pub p_flicker: f32,
+
+    /// Upper bound on how much a flickering light dims, in [0, 1].
+    pub max_flicker: f32,
+
+    /// Probability in [0, 1] that a flickering light stutters at random
+    /// rather than buzzing.
+    pub p_stutter: f32,
+}
+
+/// Place ceiling lights over the non-solid tiles of a map indexed by `(x, y)`.
+pub fn place_lights(rng: &mut impl Rng, map: &Array2<Tile>, params: &LightParams) -> Vec<Light> {
+    let positions = match params.layout {
+        LightLayout::Grid { spacing } => grid(map, spacing.max(1)),
+        LightLayout::PoissonDisk { min_distance } => {
+            poisson_disk(rng, map.dim(), min_distance, |p| !map[p].is_solid())
+        }
+    };
+
+    positions
+        .into_iter()
+        .map(|(x, y)| {
+            let (flicker, curve) = if rng.gen::<f32>() < params.p_flicker {
+                let curve = if rng.gen::<f32>() < params.p_stutter {
+                    FlickerCurve::Stutter { seed: rng.gen() }
+                } else {
+                    FlickerCurve::Buzz
+                };
+                (rng.gen_range(0.0..=params.max_flicker), curve)
+            } else {
+                (0.0, FlickerCurve::Buzz)
+            };
+            Light {
+                x: x as isize,
+                y: y as isize,
+                intensity: rng.gen_range(params.min_intensity..=params.max_intensity),
+                flicker,
+                phase: rng.gen_range(0.0..TAU),
+                curve,
+            }
+        })
+        .collect()
+}
+
+fn grid(map: &Array2<Tile>, spacing: usize) -> Vec<(usize, usize)> {
+    let offset = spacing / 2;
+    map.indexed_iter()
+        .filter(|((x, y), t)| x % spacing == offset && y % spacing == offset && !t.is_solid())
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::scatter::distance;
+
+    use super::*;
+
+    fn params(layout: LightLayout) -> LightParams {
+        LightParams {
+            layout,
+            min_intensity: 0.6,
+            max_intensity: 1.0,
+            p_flicker: 0.5,
+            max_flicker: 0.8,
+            p_stutter: 0.5,
+        }
+    }
+
+    fn half_walled_map() -> Array2<Tile> {
+        Array2::from_shape_fn((40, 40), |(x, _)| Tile::from(x >= 20))
+    }
+
+    #[test]
+    fn grid_lights_only_over_floor() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let map = half_walled_map();
+
+        let lights = place_lights(&mut rng, &map, &params(LightLayout::Grid { spacing: 4 }));
+
+        assert_eq!(lights.len(), 5 * 10);
+        for l in &lights {
+            assert_eq!((l.x % 4, l.y % 4), (2, 2));
+            assert_eq!(map[(l.x as usize, l.y as usize)], Tile::Floor);
+        }
+    }
+
+    #[test]
+    fn poisson_lights_keep_their_distance() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let map = half_walled_map();
+
+        let lights = place_lights(
+            &mut rng,
+            &map,
+            &params(LightLayout::PoissonDisk { min_distance: 5.0 }),
+        );
+
+        assert!(lights.len() > 10);
+        for (i, a) in lights.iter().enumerate() {
+            assert_eq!(map[(a.x as usize, a.y as usize)], Tile::Floor);
+            for b in &lights[i + 1..] {
+                let d = distance((a.x as usize, a.y as usize), (b.x as usize, b.y as usize));
+                assert!(d >= 5.0);
+            }
+        }
+    }
+
+    #[test]
+    fn brightness_stays_within_intensity() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let map = half_walled_map();
+
+        let lights = place_lights(&mut rng, &map, &params(LightLayout::Grid { spacing: 4 }));
+
+        for l in &lights {
+            for i in 0..100 {
+                let b = l.brightness(i as f32 * 0.01);
+                assert!(b <= l.intensity && b >= l.intensity * (1.0 - l.flicker) - 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn strobes_flash_and_stutters_follow_their_seed() {
+        let light = |curve| Light {
+            x: 0,
+            y: 0,
+            intensity: 1.0,
+            flicker: 1.0,
+            phase: 0.0,
+            curve,
+        };
+        let strobe = light(FlickerCurve::Strobe {
+            hz: 2.0,
+            duty: 0.25,
+        });
+        let stutter = light(FlickerCurve::Stutter { seed: 1 });
+        let other = light(FlickerCurve::Stutter { seed: 2 });
+
+        assert_eq!(strobe.brightness(0.1), 1.0);
+        assert_eq!(strobe.brightness(0.3), 0.0);
+        assert_eq!(strobe.brightness(0.6), 1.0);
+        let times = (0..200).map(|i| i as f32 * 0.01).collect::<Vec<_>>();
+        let curve = |l: &Light| times.iter().map(|t| l.brightness(*t)).collect::<Vec<_>>();
+        assert_eq!(curve(&stutter), curve(&stutter.clone()));
+        assert_ne!(curve(&stutter), curve(&other));
+        assert!(curve(&stutter).iter().all(|b| (0.0..=1.0).contains(b)));
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{HashSet, VecDeque};
+
+use cgmath::Vector2;
+use ndarray::Array2;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    tile::Tile,
+    util::{Line, Region},
+};
+
+use super::{
+    doors::{Door, DoorState},
+    graph::{ConnectivityGraph, Node},
+};
+
+/// A key lying on the floor, which unlocks every door locked with its id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key {
+    pub id: u32,
+
+    /// Position of the key, in the same coordinates as the map it was placed on.
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LockParams {
+    /// How many doors to lock. Fewer are locked if there aren't enough doors
+    /// that can be locked without making the level unsolvable.
+    pub n_locks: usize,
+}
+
+/// Lock some of `doors` and place a key for each one, such that starting from
+/// `spawn` every key can be picked up before reaching the door it unlocks.
+///
+/// Only doors that cut something off are locked, and each key is placed in a
+/// room that can be reached with the keys placed before it. Keys already in
+/// `keys` are taken into account, and new keys get ids after theirs.
+///
+/// `graph` must have been built from the same doors, though their states may
+/// have changed since.
+pub fn place_locks(
+    rng: &mut impl Rng,
+    map: &Array2<Tile>,
+    graph: &DoorGraph,
+    doors: &mut [Door],
+    keys: &[Key],
+    spawn: Node,
+    params: &LockParams,
+) -> Vec<Key> {
+    let rooms = graph.rooms;
+    let mut placed = graph.key_rooms(keys);
+    let first_id = doors
+        .iter()
+        .filter_map(|d| d.key)
+        .chain(keys.iter().map(|k| k.id))
+        .max()
+        .map_or(0, |id| id + 1);
+    let mut new_keys = vec![];
+
+    for next_id in (first_id..).take(params.n_locks) {
+        let mut candidates = (0..doors.len())
+            .filter(|&i| doors[i].state != DoorState::Locked && graph.door_ends[i].is_some())
+            .collect::<Vec<_>>();
+        candidates.shuffle(rng);
+
+        let found = candidates.into_iter().find_map(|i| {
+            let unlocked = doors[i].clone();
+            doors[i].lock(next_id);
+            let (have, reach) = graph.collect_keys(doors, &placed, spawn);
+            let (room, _) = graph.door_ends[i].unwrap();
+            if have.len() == placed.len() && !reach[room] {
+                Some((i, unlocked, reach))
+            } else {
+                doors[i] = unlocked;
+                None
+            }
+        });
+        let Some((door, unlocked, reach)) = found else {
+            break;
+        };
+
+        let taken = placed.iter().map(|(_, r)| *r).collect::<HashSet<_>>();
+        let reachable = (0..rooms.len()).filter(|&r| reach[r]).collect::<Vec<_>>();
+        let preferred = reachable
+            .iter()
+            .copied()
+            .filter(|r| Node::Room(*r) != spawn && !taken.contains(r))
+            .collect::<Vec<_>>();
+        let Some(&room) = preferred.choose(rng).or_else(|| reachable.choose(rng)) else {
+            // Only happens when spawning in a hallway with no reachable rooms.
+            doors[door] = unlocked;
+            break;
+        };
+
+        let cells = rooms[room]
+            .cells()
+            .filter(|&(x, y)| {
+                let pos = (usize::try_from(x), usize::try_from(y));
+                matches!(pos, (Ok(x), Ok(y)) if map.get((x, y)).is_some_and(|t| !t.is_solid()))
+            })
+            .collect::<Vec<_>>();
+        let (x, y) = match cells.choose(rng) {
+            Some(&pos) => pos,
+            None => {
+                let b = rooms[room].bounds();
+                (b.x + b.w as isize / 2, b.y + b.h as isize / 2)
+            }
+        };
+
+        placed.push((next_id, room));
+        new_keys.push(Key { id: next_id, x, y });
+    }
+
+    new_keys
+}
+
+/// Returns true if every locked door's key can be reached from `spawn`, picking
+/// up keys along the way.
+pub fn is_solvable(graph: &DoorGraph, doors: &[Door], keys: &[Key], spawn: Node) -> bool {
+    let (have, _) = graph.collect_keys(doors, &graph.key_rooms(keys), spawn);
+    doors
+        .iter()
+        .filter(|d| d.state == DoorState::Locked)
+        .all(|d| d.key.is_some_and(|k| have.contains(&k)))
+}
+
+fn room_at(rooms: &[Region], pos: (isize, isize)) -> Option<usize> {
+    rooms.iter().position(|r| r.contains(pos))
+}
+
+/// Rooms and hallways, connected by doors between rooms and hallways and by
+/// hallways meeting each other. Nodes are numbered with rooms first.
+#[derive(Debug, Clone)]
+pub struct DoorGraph<'a> {
+    rooms: &'a [Region],
+
+    /// Neighbors of every node, with the door in between, if there is one.
+    adjacency: Vec<Vec<(usize, Option<usize>)>>,
+
+    /// The room and hallway node on either side of every door, if the door
+    /// joins a room to a hallway.
+    door_ends: Vec<Option<(usize, usize)>>,
+}
+
+impl<'a> DoorGraph<'a> {
+    pub fn new(rooms: &'a [Region], lines: &[Line], doors: &[Door]) -> Self {
+        let n_rooms = rooms.len();
+        let mut adjacency = vec![vec![]; n_rooms + lines.len()];
+
+        for (a, b) in ConnectivityGraph::new(rooms, lines).edges() {
+            if let (Node::Hallway(a), Node::Hallway(b)) = (a, b) {
+                adjacency[n_rooms + a].push((n_rooms + b, None));
+                adjacency[n_rooms + b].push((n_rooms + a, None));
+            }
+        }
+
+        let door_ends = doors
+            .iter()
+            .map(|d| {
+                let room = room_at(rooms, (d.x, d.y))?;
+                let step = Vector2::<isize>::from(d.facing);
+                let (x, y) = (d.x + step.x, d.y + step.y);
+                let hallway = lines.iter().position(|l| {
+                    let b = l.bounds();
+                    x >= b.x && x < b.x_end() && y >= b.y && y < b.y_end()
+                })?;
+                Some((room, n_rooms + hallway))
+            })
+            .collect::<Vec<_>>();
+        for (i, ends) in door_ends.iter().enumerate() {
+            if let Some((room, hallway)) = *ends {
+                adjacency[room].push((hallway, Some(i)));
+                adjacency[hallway].push((room, Some(i)));
+            }
+        }
+
+        Self {
+            rooms,
+            adjacency,
+            door_ends,
+        }
+    }
+
+    fn index(&self, node: Node) -> usize {
+        match node {
+            Node::Room(i) => i,
+            Node::Hallway(i) => self.rooms.len() + i,
+        }
+    }
+
+    /// The id of every key that lies in a room, and the room it lies in.
+    fn key_rooms(&self, keys: &[Key]) -> Vec<(u32, usize)> {
+        keys.iter()
+            .filter_map(|k| Some((k.id, room_at(self.rooms, (k.x, k.y))?)))
+            .collect()
+    }
+
+    /// Every node reachable from `start` without going through a door locked
+    /// with a key not in `have`.
+    fn reach(&self, doors: &[Door], have: &HashSet<u32>, start: Node) -> Vec<bool> {
+        let mut seen = vec![false; self.adjacency.len()];
+        let start = self.index(start);
+        let mut queue = VecDeque::from([start]);
+        seen[start] = true;
+
+        while let Some(n) = queue.pop_front() {
+            for &(m, door) in &self.adjacency[n] {
+                let open = door.is_none_or(|i| {
+                    doors[i].state != DoorState::Locked
+                        || doors[i].key.is_some_and(|k| have.contains(&k))
+                });
+                if open && !seen[m] {
+                    seen[m] = true;
+                    queue.push_back(m);
+                }
+            }
+        }
+
+        seen
+    }
+
+    /// Walk from `start`, picking up every key that can be reached given the
+    /// keys picked up so far, until no more can be. Returns the keys picked up
+    /// and the nodes reachable with them.
+    fn collect_keys(
+        &self,
+        doors: &[Door],
+        keys: &[(u32, usize)],
+        start: Node,
+    ) -> (HashSet<u32>, Vec<bool>) {
+        let mut have = HashSet::new();
+        loop {
+            let reach = self.reach(doors, &have, start);
+            let before = have.len();
+            have.extend(keys.iter().filter(|(_, r)| reach[*r]).map(|(id, _)| *id));
+            if have.len() == before {
+                return (have, reach);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::{
+        util::Rectangle,
+        worldgen::{
+            doors::place_doors,
+            hallways::{rbsp, RbspParams},
+            raster::rasterize,
+        },
+    };
+
+    use super::*;
+
+    fn level(seed: u64) -> (SmallRng, Array2<Tile>, Vec<Region>, Vec<Line>, Vec<Door>) {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let (rooms, lines) = rbsp(
+            &mut rng,
+            Rectangle {
+                x: 0,
+                y: 0,
+                w: 128,
+                h: 128,
+            },
+            RbspParams {
+                max_room_len: 40,
+                ..Default::default()
+            },
+        )
+        .into_flat();
+        let rooms = rooms.into_iter().map(Region::from).collect::<Vec<_>>();
+        let doors = place_doors(&mut rng, &rooms, &lines);
+        let map = rasterize((128, 128), &rooms, &lines, &doors);
+        (rng, map, rooms, lines, doors)
+    }
+
+    #[test]
+    fn locked_levels_are_solvable() {
+        for seed in 0..50 {
+            let (mut rng, map, rooms, lines, mut doors) = level(seed);
+
+            let graph = DoorGraph::new(&rooms, &lines, &doors);
+
+            let keys = place_locks(
+                &mut rng,
+                &map,
+                &graph,
+                &mut doors,
+                &[],
+                Node::Room(0),
+                &LockParams { n_locks: 4 },
+            );
+
+            let locked = doors
+                .iter()
+                .filter(|d| d.state == DoorState::Locked)
+                .count();
+            assert_eq!(locked, keys.len());
+            assert_eq!(keys.len(), 4);
+            assert!(is_solvable(&graph, &doors, &keys, Node::Room(0)));
+            for k in &keys {
+                assert!(!map[(k.x as usize, k.y as usize)].is_solid());
+            }
+        }
+    }
+
+    #[test]
+    fn keys_are_needed() {
+        let (mut rng, map, rooms, lines, mut doors) = level(0);
+        let graph = DoorGraph::new(&rooms, &lines, &doors);
+
+        let keys = place_locks(
+            &mut rng,
+            &map,
+            &graph,
+            &mut doors,
+            &[],
+            Node::Room(0),
+            &LockParams { n_locks: 2 },
+        );
+
+        assert!(!is_solvable(&graph, &doors, &[], Node::Room(0)));
+        assert!(!is_solvable(&graph, &doors, &keys[1..], Node::Room(0)));
+        assert!(is_solvable(&graph, &doors, &keys, Node::Room(0)));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+
+use ndarray::Array2;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::tile::Tile;
+
+use super::connectivity::{label_regions, neighbors};
+
+/// A run of wall cells that could be knocked through.
+pub(crate) type Cells = Vec<(usize, usize)>;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoopParams {
+    /// A probability in [0, 1] that each pair of neighboring regions not
+    /// already joined by a door gets a new doorway knocked between them.
+    pub loop_factor: f32,
+
+    /// Walls thicker than this are never knocked through.
+    pub max_wall_thickness: usize,
+}
+
+/// Knock doorways through thin walls to add cycles to the map, indexed by `(x, y)`.
+///
+/// Rooms and hallways are found by treating doors as walls, so any pair of
+/// regions that share a thin wall but no door is a candidate. Since every room
+/// is already reachable, each new doorway creates a loop. Returns the cells
+/// carved for each new doorway.
+pub fn insert_loops(rng: &mut impl Rng, map: &mut Array2<Tile>, params: &LoopParams) -> Vec<Cells> {
+    let mut carved = vec![];
+    for (_, options) in thin_walls(map, params.max_wall_thickness) {
+        if rng.gen::<f32>() >= params.loop_factor {
+            continue;
+        }
+        let cells = options.choose(rng).unwrap();
+        for c in cells {
+            map[*c] = Tile::Door;
+        }
+        carved.push(cells.clone());
+    }
+    carved
+}
+
+/// Find every pair of regions that share a wall no thicker than
+/// `max_wall_thickness` but no door, with every way through the wall between
+/// them. Pairs are sorted by region label.
+pub(crate) fn thin_walls(
+    map: &Array2<Tile>,
+    max_wall_thickness: usize,
+) -> Vec<((usize, usize), Vec<Cells>)> {
+    let closed = map.mapv(|t| if t == Tile::Door { Tile::Wall } else { t });
+    let (labels, _) = label_regions(&closed);
+    let (w, h) = map.dim();
+
+    let mut joined = HashSet::new();
+    for (pos, t) in map.indexed_iter() {
+        if *t != Tile::Door {
+            continue;
+        }
+        let regions = neighbors((w, h), pos)
+            .filter_map(|n| labels[n])
+            .collect::<Vec<_>>();
+        for a in &regions {
+            for b in &regions {
+                joined.insert((*a.min(b), *a.max(b)));
+            }
+        }
+    }
+
+    let mut candidates: HashMap<(usize, usize), Vec<Cells>> = HashMap::new();
+    for ((x, y), t) in map.indexed_iter() {
+        if *t != Tile::Wall {
+            continue;
+        }
+        for (dx, dy) in [(1, 0), (0, 1)] {
+            let Some(before) = labels.get((x.wrapping_sub(dx), y.wrapping_sub(dy))) else {
+                continue;
+            };
+            let Some(a) = *before else {
+                continue;
+            };
+
+            let mut cells = vec![];
+            let (mut cx, mut cy) = (x, y);
+            while cells.len() < max_wall_thickness && map.get((cx, cy)) == Some(&Tile::Wall) {
+                cells.push((cx, cy));
+                (cx, cy) = (cx + dx, cy + dy);
+            }
+            if let Some(&Some(b)) = labels.get((cx, cy)) {
+                if a != b && !joined.contains(&(a.min(b), a.max(b))) {
+                    candidates
+                        .entry((a.min(b), a.max(b)))
+                        .or_default()
+                        .push(cells);
+                }
+            }
+        }
+    }
+
+    let mut pairs = candidates.into_iter().collect::<Vec<_>>();
+    pairs.sort_by_key(|(k, _)| *k);
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    fn example_map() -> Array2<Tile> {
+        array![
+            [1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 0, 0, 1],
+            [1, 2, 1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 0, 0, 1],
+            [1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 0, 0, 1],
+            [1, 1, 1, 1, 1, 1, 1],
+        ]
+        .map(|x| match x {
+            0 => Tile::Floor,
+            1 => Tile::Wall,
+            _ => Tile::Door,
+        })
+    }
+
+    #[test]
+    fn knocks_through_thin_walls_between_new_pairs() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut map = example_map();
+
+        let carved = insert_loops(
+            &mut rng,
+            &mut map,
+            &LoopParams {
+                loop_factor: 1.0,
+                max_wall_thickness: 1,
+            },
+        );
+
+        // The top two corridors are already joined, so only the bottom pair gets a door.
+        assert_eq!(carved.len(), 1);
+        assert_eq!(carved[0].len(), 1);
+        assert_eq!(carved[0][0].0, 4);
+        assert_eq!(map[carved[0][0]], Tile::Door);
+    }
+
+    #[test]
+    fn zero_loop_factor_changes_nothing() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut map = example_map();
+
+        let carved = insert_loops(
+            &mut rng,
+            &mut map,
+            &LoopParams {
+                loop_factor: 0.0,
+                max_wall_thickness: 2,
+            },
+        );
+
+        assert!(carved.is_empty());
+        assert_eq!(map, example_map());
+    }
+}
@@ -0,0 +1,617 @@
+//! A compact, versioned binary format for generated worlds.
+//!
+//! All integers are little-endian. A file consists of:
+//!
+//! - the magic bytes `BKRM` and a `u16` format version,
+//! - the world bounds as `i64 x, i64 y, u64 w, u64 h`,
+//! - the tile grid in `(x, y)` iteration order, run-length encoded as a `u64`
+//!   run count followed by `(u8 tile, u32 length)` runs, where each tile is an
+//!   index into [`Tile::ALL`],
+//! - the room table, hallways, doors, biomes, ceiling heights and keys, each a
+//!   `u32` count followed by that many records,
+//! - the material grid, run-length encoded like the tile grid but with each
+//!   material stored as its `u8` id,
+//! - the decals, as a `u32` count followed by that many records,
+//! - the indices of the teaser rooms, as a `u32` count followed by that many
+//!   `u32`s,
+//! - the named scalar fields, as a `u32` count followed by that many fields.
+//!
+//! Doors are stored as `i64 x, i64 y, u8 facing, u8 state` followed by a `u8`
+//! flag and a `u32` key id, which is only meaningful if the flag is set.
+//!
+//! Keys are stored as `i64 x, i64 y, u32 id`, and decals as
+//! `i64 x, i64 y, u8 kind, u8 facing`. Each scalar field is its name, as a
+//! `u32` byte length followed by that much UTF-8, then an `f32` for every tile
+//! in `(x, y)` iteration order.
+//!
+//! Version 1 files have no ceiling heights, and load with none. Version 1 and 2
+//! files have no door states or keys, and load with every door closed. Files
+//! before version 4 have no key table, and load with no keys. Files before
+//! version 5 have no material grid, and load with the default material
+//! everywhere. Files before version 6 have no decals, and load with none. Files
+//! before version 7 have no teaser rooms, and load with none. Files before
+//! version 8 have no scalar fields, and load with none.
+//!
+//! The partition tree is not stored, so loaded worlds have no `tree`.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use ndarray::Array2;
+
+use crate::{
+    tile::{MaterialId, Tile},
+    util::{Axis, Direction, Line, Rectangle, Region},
+    world::{Decal, DecalKind},
+};
+
+use super::{
+    biomes::Biome,
+    doors::{Door, DoorState},
+    locks::Key,
+    pipeline::GeneratedWorld,
+};
+
+const MAGIC: &[u8; 4] = b"BKRM";
+pub const VERSION: u16 = 8;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::East,
+    Direction::North,
+    Direction::West,
+    Direction::South,
+];
+
+#[derive(Debug)]
+pub enum MapFileError {
+    Io(io::Error),
+
+    /// The file doesn't start with the expected magic bytes.
+    BadMagic,
+
+    /// The file was written by a newer or unknown version of the format.
+    UnsupportedVersion(u16),
+
+    /// The file is structurally invalid.
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for MapFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapFileError::Io(e) => write!(f, "i/o error: {e}"),
+            MapFileError::BadMagic => write!(f, "not a map file"),
+            MapFileError::UnsupportedVersion(v) => write!(f, "unsupported map file version {v}"),
+            MapFileError::Corrupt(why) => write!(f, "corrupt map file: {why}"),
+        }
+    }
+}
+
+impl std::error::Error for MapFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapFileError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MapFileError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => MapFileError::Corrupt("unexpected end of file"),
+            _ => MapFileError::Io(e),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, MapFileError>;
+
+impl GeneratedWorld {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        write_rect(w, &self.bounds)?;
+
+        let runs = rle(self.tiles.iter().copied());
+        write_u64(w, runs.len() as u64)?;
+        for (tile, len) in runs {
+            w.write_all(&[encode(&Tile::ALL, tile)])?;
+            w.write_all(&len.to_le_bytes())?;
+        }
+
+        write_u32(w, self.rooms.len() as u32)?;
+        for room in &self.rooms {
+            write_u32(w, room.rects.len() as u32)?;
+            for r in &room.rects {
+                write_rect(w, r)?;
+            }
+        }
+
+        write_u32(w, self.lines.len() as u32)?;
+        for l in &self.lines {
+            write_i64(w, l.x as i64)?;
+            write_i64(w, l.y as i64)?;
+            write_u64(w, l.length as u64)?;
+            write_u64(w, l.width as u64)?;
+            w.write_all(&[l.axis as u8])?;
+        }
+
+        write_u32(w, self.doors.len() as u32)?;
+        for d in &self.doors {
+            write_i64(w, d.x as i64)?;
+            write_i64(w, d.y as i64)?;
+            w.write_all(&[d.facing as u8])?;
+            w.write_all(&[encode(&DoorState::ALL, d.state), d.key.is_some() as u8])?;
+            write_u32(w, d.key.unwrap_or(0))?;
+        }
+
+        write_u32(w, self.biomes.len() as u32)?;
+        for b in &self.biomes {
+            w.write_all(&[*b as u8])?;
+        }
+
+        write_u32(w, self.heights.len() as u32)?;
+        for h in &self.heights {
+            w.write_all(&h.to_le_bytes())?;
+        }
+
+        write_u32(w, self.keys.len() as u32)?;
+        for k in &self.keys {
+            write_i64(w, k.x as i64)?;
+            write_i64(w, k.y as i64)?;
+            write_u32(w, k.id)?;
+        }
+
+        let runs = rle(self.materials.iter().copied());
+        write_u64(w, runs.len() as u64)?;
+        for (material, len) in runs {
+            w.write_all(&[material.0])?;
+            w.write_all(&len.to_le_bytes())?;
+        }
+
+        write_u32(w, self.decals.len() as u32)?;
+        for d in &self.decals {
+            write_i64(w, d.x as i64)?;
+            write_i64(w, d.y as i64)?;
+            w.write_all(&[encode(&DecalKind::ALL, d.kind), d.facing as u8])?;
+        }
+
+        write_u32(w, self.teasers.len() as u32)?;
+        for t in &self.teasers {
+            write_u32(w, *t as u32)?;
+        }
+
+        write_u32(w, self.fields.len() as u32)?;
+        for (name, field) in &self.fields {
+            write_u32(w, name.len() as u32)?;
+            w.write_all(name.as_bytes())?;
+            for v in field {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(r: &mut impl Read) -> Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(MapFileError::BadMagic);
+        }
+        let mut version = [0; 2];
+        r.read_exact(&mut version)?;
+        let version = match u16::from_le_bytes(version) {
+            v @ (1..=VERSION) => v,
+            v => return Err(MapFileError::UnsupportedVersion(v)),
+        };
+
+        let bounds = read_rect(r)?;
+        let n_cells = bounds
+            .w
+            .checked_mul(bounds.h)
+            .ok_or(MapFileError::Corrupt("bounds too large"))?;
+        let n_runs = read_u64(r)?;
+        let mut tiles = vec![];
+        for _ in 0..n_runs {
+            let tile = decode(&Tile::ALL, read_u8(r)?, "unknown tile")?;
+            let len = read_u32(r)? as usize;
+            if tiles.len() + len > n_cells {
+                return Err(MapFileError::Corrupt("tile grid larger than bounds"));
+            }
+            tiles.extend(std::iter::repeat_n(tile, len));
+        }
+        let tiles = Array2::from_shape_vec((bounds.w, bounds.h), tiles)
+            .map_err(|_| MapFileError::Corrupt("tile grid smaller than bounds"))?;
+
+        let rooms = read_vec(r, |r| {
+            let rects = read_vec(r, read_rect)?;
+            Ok(Region { rects })
+        })?;
+        let lines = read_vec(r, |r| {
+            Ok(Line {
+                x: read_i64(r)? as isize,
+                y: read_i64(r)? as isize,
+                length: read_u64(r)? as usize,
+                width: read_u64(r)? as usize,
+                axis: decode(
+                    &[Axis::Horizontal, Axis::Vertical],
+                    read_u8(r)?,
+                    "unknown axis",
+                )?,
+            })
+        })?;
+        let doors = read_vec(r, |r| {
+            let mut door = Door::new(
+                read_i64(r)? as isize,
+                read_i64(r)? as isize,
+                decode(&DIRECTIONS, read_u8(r)?, "unknown direction")?,
+            );
+            if version >= 3 {
+                door.state = decode(&DoorState::ALL, read_u8(r)?, "unknown door state")?;
+                let has_key = read_u8(r)? != 0;
+                let key = read_u32(r)?;
+                door.key = has_key.then_some(key);
+            }
+            Ok(door)
+        })?;
+        let biomes = read_vec(r, |r| decode(&Biome::ALL, read_u8(r)?, "unknown biome"))?;
+        let heights = match version {
+            1 => vec![],
+            _ => read_vec(r, read_f32)?,
+        };
+        let keys = match version {
+            1..=3 => vec![],
+            _ => read_vec(r, |r| {
+                Ok(Key {
+                    x: read_i64(r)? as isize,
+                    y: read_i64(r)? as isize,
+                    id: read_u32(r)?,
+                })
+            })?,
+        };
+
+        let materials = match version {
+            1..=4 => Array2::default(tiles.dim()),
+            _ => {
+                let n_runs = read_u64(r)?;
+                let mut materials = vec![];
+                for _ in 0..n_runs {
+                    let material = MaterialId(read_u8(r)?);
+                    let len = read_u32(r)? as usize;
+                    if materials.len() + len > n_cells {
+                        return Err(MapFileError::Corrupt("material grid larger than bounds"));
+                    }
+                    materials.extend(std::iter::repeat_n(material, len));
+                }
+                Array2::from_shape_vec(tiles.dim(), materials)
+                    .map_err(|_| MapFileError::Corrupt("material grid smaller than bounds"))?
+            }
+        };
+
+        let decals = match version {
+            1..=5 => vec![],
+            _ => read_vec(r, |r| {
+                Ok(Decal {
+                    x: read_i64(r)? as isize,
+                    y: read_i64(r)? as isize,
+                    kind: decode(&DecalKind::ALL, read_u8(r)?, "unknown decal")?,
+                    facing: decode(&DIRECTIONS, read_u8(r)?, "unknown direction")?,
+                })
+            })?,
+        };
+
+        let teasers = match version {
+            1..=6 => vec![],
+            _ => read_vec(r, |r| Ok(read_u32(r)? as usize))?,
+        };
+
+        let fields = match version {
+            1..=7 => BTreeMap::new(),
+            _ => read_vec(r, |r| {
+                let len = read_u32(r)? as u64;
+                let mut name = vec![];
+                r.by_ref().take(len).read_to_end(&mut name)?;
+                if name.len() as u64 != len {
+                    return Err(MapFileError::Corrupt("truncated field name"));
+                }
+                let name = String::from_utf8(name)
+                    .map_err(|_| MapFileError::Corrupt("field name is not UTF-8"))?;
+                let values = (0..n_cells)
+                    .map(|_| read_f32(r))
+                    .collect::<Result<Vec<_>>>()?;
+                let field = Array2::from_shape_vec(tiles.dim(), values)
+                    .expect("field has one value per tile");
+                Ok((name, field))
+            })?
+            .into_iter()
+            .collect(),
+        };
+
+        Ok(GeneratedWorld {
+            bounds,
+            tiles,
+            materials,
+            rooms,
+            lines,
+            tree: None,
+            doors,
+            biomes,
+            heights,
+            keys,
+            decals,
+            teasers,
+            fields,
+        })
+    }
+}
+
+/// Run-length encode a sequence of tiles or materials. Runs are split so their
+/// lengths fit in a `u32`.
+fn rle<T: PartialEq>(cells: impl Iterator<Item = T>) -> Vec<(T, u32)> {
+    let mut runs: Vec<(T, u32)> = vec![];
+    for t in cells {
+        match runs.last_mut() {
+            Some((last, len)) if *last == t && *len < u32::MAX => *len += 1,
+            _ => runs.push((t, 1)),
+        }
+    }
+    runs
+}
+
+fn encode<T: PartialEq>(table: &[T], v: T) -> u8 {
+    table.iter().position(|t| *t == v).unwrap() as u8
+}
+
+fn decode<T: Copy>(table: &[T], i: u8, what: &'static str) -> Result<T> {
+    table
+        .get(i as usize)
+        .copied()
+        .ok_or(MapFileError::Corrupt(what))
+}
+
+fn read_vec<R: Read, T>(r: &mut R, mut f: impl FnMut(&mut R) -> Result<T>) -> Result<Vec<T>> {
+    let n = read_u32(r)?;
+    (0..n).map(|_| f(r)).collect()
+}
+
+fn write_rect(w: &mut impl Write, r: &Rectangle<isize, usize>) -> Result<()> {
+    write_i64(w, r.x as i64)?;
+    write_i64(w, r.y as i64)?;
+    write_u64(w, r.w as u64)?;
+    write_u64(w, r.h as u64)
+}
+
+fn read_rect(r: &mut impl Read) -> Result<Rectangle<isize, usize>> {
+    Ok(Rectangle {
+        x: read_i64(r)? as isize,
+        y: read_i64(r)? as isize,
+        w: read_u64(r)? as usize,
+        h: read_u64(r)? as usize,
+    })
+}
+
+fn write_u32(w: &mut impl Write, v: u32) -> Result<()> {
+    Ok(w.write_all(&v.to_le_bytes())?)
+}
+
+fn write_u64(w: &mut impl Write, v: u64) -> Result<()> {
+    Ok(w.write_all(&v.to_le_bytes())?)
+}
+
+fn write_i64(w: &mut impl Write, v: i64) -> Result<()> {
+    Ok(w.write_all(&v.to_le_bytes())?)
+}
+
+fn read_u8(r: &mut impl Read) -> Result<u8> {
+    let mut b = [0; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn read_f32(r: &mut impl Read) -> Result<f32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(f32::from_le_bytes(b))
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(r: &mut impl Read) -> Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_i64(r: &mut impl Read) -> Result<i64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(i64::from_le_bytes(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::{
+        ceilings::CeilingParams,
+        hallways::RbspParams,
+        locks::LockParams,
+        noise::{NoiseOverlay, NoiseParams},
+        pipeline::{AssignBiomes, AssignMaterials, Pipeline, PlaceDoors, WorldGenerator},
+    };
+
+    use super::*;
+
+    fn world() -> GeneratedWorld {
+        let mut rng = SmallRng::seed_from_u64(0);
+        Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(PlaceDoors)
+        .then(AssignBiomes { depth: 2 })
+        .then(CeilingParams {
+            base_height: 1.0,
+            hall_min_area: 200,
+            hall_height: 2.0,
+            jitter: 0.25,
+        })
+        .then(LockParams { n_locks: 2 })
+        .then(AssignMaterials)
+        .then(NoiseOverlay {
+            name: "grime".into(),
+            params: NoiseParams {
+                scale: 8.0,
+                octaves: 2,
+                persistence: 0.5,
+                lacunarity: 2.0,
+            },
+        })
+        .generate(
+            &mut rng,
+            Rectangle {
+                x: -20,
+                y: 30,
+                w: 80,
+                h: 60,
+            },
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut world = world();
+        world.decals.push(Decal {
+            x: 3,
+            y: 4,
+            kind: DecalKind::Arrow,
+            facing: Direction::South,
+        });
+        world.teasers.push(2);
+        let mut buf = vec![];
+
+        world.write_to(&mut buf).unwrap();
+        let loaded = GeneratedWorld::read_from(&mut buf.as_slice()).unwrap();
+
+        assert_eq!(loaded.bounds, world.bounds);
+        assert_eq!(loaded.tiles, world.tiles);
+        assert_eq!(loaded.rooms, world.rooms);
+        assert_eq!(loaded.lines, world.lines);
+        assert_eq!(loaded.doors, world.doors);
+        assert_eq!(loaded.biomes, world.biomes);
+        assert_eq!(loaded.heights, world.heights);
+        assert_eq!(loaded.keys, world.keys);
+        assert_eq!(loaded.materials, world.materials);
+        assert_eq!(loaded.decals, world.decals);
+        assert_eq!(loaded.teasers, world.teasers);
+        assert_eq!(loaded.fields, world.fields);
+    }
+
+    #[test]
+    fn reads_version_1() {
+        let world = world();
+        let world = GeneratedWorld {
+            doors: vec![],
+            heights: vec![],
+            keys: vec![],
+            materials: Array2::default(world.tiles.dim()),
+            fields: BTreeMap::new(),
+            ..world
+        };
+        let mut buf = vec![];
+        world.write_to(&mut buf).unwrap();
+
+        // With no doors, version 1 is the same, minus the empty height and key
+        // tables, the single-run material grid and the empty decal, teaser and
+        // field tables at the end.
+        buf[4..6].copy_from_slice(&1u16.to_le_bytes());
+        buf.truncate(buf.len() - 8 - 13 - 4 - 4 - 4);
+        let loaded = GeneratedWorld::read_from(&mut buf.as_slice()).unwrap();
+
+        assert_eq!(loaded.tiles, world.tiles);
+        assert_eq!(loaded.biomes, world.biomes);
+        assert!(loaded.heights.is_empty());
+    }
+
+    #[test]
+    fn round_trips_door_locks() {
+        let mut world = world();
+        for d in &mut world.doors {
+            *d = Door::new(d.x, d.y, d.facing);
+        }
+        world.doors[0].lock(7);
+        world.doors[1].open();
+        let mut buf = vec![];
+
+        world.write_to(&mut buf).unwrap();
+        let loaded = GeneratedWorld::read_from(&mut buf.as_slice()).unwrap();
+
+        assert_eq!(loaded.doors[0].state, DoorState::Locked);
+        assert_eq!(loaded.doors[0].key, Some(7));
+        assert!(loaded.doors[1].is_open());
+        assert_eq!(loaded.doors[2].key, None);
+    }
+
+    #[test]
+    fn uniform_grids_are_tiny() {
+        let bounds = Rectangle {
+            x: 0,
+            y: 0,
+            w: 1000,
+            h: 1000,
+        };
+        let world = GeneratedWorld::from_tiles(bounds, Array2::from_elem((1000, 1000), Tile::Wall));
+        let mut buf = vec![];
+
+        world.write_to(&mut buf).unwrap();
+
+        assert!(buf.len() < 128);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let mut buf = vec![];
+        world().write_to(&mut buf).unwrap();
+
+        let mut bad_magic = buf.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            GeneratedWorld::read_from(&mut bad_magic.as_slice()),
+            Err(MapFileError::BadMagic)
+        ));
+
+        let mut bad_version = buf.clone();
+        bad_version[4] = 99;
+        assert!(matches!(
+            GeneratedWorld::read_from(&mut bad_version.as_slice()),
+            Err(MapFileError::UnsupportedVersion(99))
+        ));
+
+        assert!(matches!(
+            GeneratedWorld::read_from(&mut &buf[..buf.len() - 1]),
+            Err(MapFileError::Corrupt(_))
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+use ndarray::{s, Array2};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::tile::Tile;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MazeParams {
+    /// Width of each corridor, in tiles.
+    pub cell_size: usize,
+
+    /// Thickness of the walls between corridors, in tiles.
+    pub wall_thickness: usize,
+
+    /// A probability in [0, 1] that each wall left standing by the perfect maze
+    /// is knocked out afterwards, creating loops.
+    pub loop_fraction: f32,
+}
+
+/// Generate a maze of twisting corridors using a recursive backtracker, indexed
+/// by `(x, y)`.
+///
+/// With a `loop_fraction` of 0 this is a perfect maze, with exactly one path
+/// between any two cells. The map is surrounded by a wall, and any space left
+/// over on the right and bottom edges is filled with wall.
+pub fn maze(rng: &mut impl Rng, (w, h): (usize, usize), params: &MazeParams) -> Array2<Tile> {
+    let mut map = Array2::from_elem((w, h), Tile::Wall);
+    let (cell, wall) = (params.cell_size.max(1), params.wall_thickness);
+    let pitch = cell + wall;
+    let (cw, ch) = (
+        (w.saturating_sub(wall)) / pitch,
+        (h.saturating_sub(wall)) / pitch,
+    );
+    if cw == 0 || ch == 0 {
+        return map;
+    }
+
+    let origin = |i: usize| wall + i * pitch;
+    let carve = |map: &mut Array2<Tile>, (i, j): (usize, usize), (di, dj): (usize, usize)| {
+        // Carve the cell, extended over the wall towards its east/south neighbor.
+        let (x, y) = (origin(i), origin(j));
+        map.slice_mut(s![x..x + cell + di * wall, y..y + cell + dj * wall])
+            .fill(Tile::Floor);
+    };
+
+    let mut visited = Array2::from_elem((cw, ch), false);
+    let mut stack = vec![(0, 0)];
+    visited[(0, 0)] = true;
+    carve(&mut map, (0, 0), (0, 0));
+
+    while let Some(&(i, j)) = stack.last() {
+        let mut options = vec![];
+        if i + 1 < cw && !visited[(i + 1, j)] {
+            options.push((i + 1, j));
+        }
+        if j + 1 < ch && !visited[(i, j + 1)] {
+            options.push((i, j + 1));
+        }
+        if i > 0 && !visited[(i - 1, j)] {
+            options.push((i - 1, j));
+        }
+        if j > 0 && !visited[(i, j - 1)] {
+            options.push((i, j - 1));
+        }
+
+        let Some(&next) = options.choose(rng) else {
+            stack.pop();
+            continue;
+        };
+        visited[next] = true;
+        carve(&mut map, next, (0, 0));
+        // Always carve the passage from the upper-left cell of the pair.
+        let (a, b) = (next.min((i, j)), next.max((i, j)));
+        carve(&mut map, a, (b.0 - a.0, b.1 - a.1));
+        stack.push(next);
+    }
+
+    if wall > 0 {
+        for i in 0..cw {
+            for j in 0..ch {
+                let (x, y) = (origin(i), origin(j));
+                if i + 1 < cw
+                    && map[(x + cell, y)].is_solid()
+                    && rng.gen::<f32>() < params.loop_fraction
+                {
+                    carve(&mut map, (i, j), (1, 0));
+                }
+                if j + 1 < ch
+                    && map[(x, y + cell)].is_solid()
+                    && rng.gen::<f32>() < params.loop_fraction
+                {
+                    carve(&mut map, (i, j), (0, 1));
+                }
+            }
+        }
+    }
+
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::connectivity::label_regions;
+
+    use super::*;
+
+    fn count_floor(map: &Array2<Tile>) -> usize {
+        map.iter().filter(|t| !t.is_solid()).count()
+    }
+
+    #[test]
+    fn perfect_maze_is_a_spanning_tree() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let map = maze(
+            &mut rng,
+            (41, 31),
+            &MazeParams {
+                cell_size: 1,
+                wall_thickness: 1,
+                loop_fraction: 0.0,
+            },
+        );
+
+        let (_, sizes) = label_regions(&map);
+        assert_eq!(sizes.len(), 1);
+        // 20x15 cells, joined by one fewer passage than there are cells.
+        assert_eq!(count_floor(&map), 300 + 299);
+    }
+
+    #[test]
+    fn full_loop_fraction_removes_every_wall() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let map = maze(
+            &mut rng,
+            (41, 31),
+            &MazeParams {
+                cell_size: 1,
+                wall_thickness: 1,
+                loop_fraction: 1.0,
+            },
+        );
+
+        // Every cell and every passage between neighbors is open.
+        assert_eq!(count_floor(&map), 300 + 19 * 15 + 20 * 14);
+    }
+}
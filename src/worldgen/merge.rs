@@ -0,0 +1,101 @@
+use rand::Rng;
+
+use super::hallways::BspNode;
+
+/// Merge sibling rooms of a BSP tree into larger rooms, removing the hallway
+/// between them.
+///
+/// Each partition whose children are both rooms is merged with probability
+/// `p_merge`. This runs bottom-up, so merged rooms may merge again with their
+/// own siblings, creating the occasional huge open hall.
+pub fn merge_rooms(rng: &mut impl Rng, tree: BspNode, p_merge: f32) -> BspNode {
+    match tree {
+        BspNode::Room(_) => tree,
+        BspNode::Ring {
+            bounds,
+            lines,
+            child,
+        } => BspNode::Ring {
+            bounds,
+            lines,
+            child: Box::new(merge_rooms(rng, *child, p_merge)),
+        },
+        BspNode::Partition {
+            bounds,
+            line,
+            children,
+        } => {
+            let [a, b] = *children;
+            let children = [merge_rooms(rng, a, p_merge), merge_rooms(rng, b, p_merge)];
+            let both_rooms = children.iter().all(|c| matches!(c, BspNode::Room(_)));
+
+            if both_rooms && rng.gen::<f32>() < p_merge {
+                BspNode::Room(bounds)
+            } else {
+                BspNode::Partition {
+                    bounds,
+                    line,
+                    children: Box::new(children),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::{
+        util::Rectangle,
+        worldgen::hallways::{rbsp, RbspParams},
+    };
+
+    use super::*;
+
+    fn tree(seed: u64) -> BspNode {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        rbsp(
+            &mut rng,
+            Rectangle {
+                x: 0,
+                y: 0,
+                w: 256,
+                h: 256,
+            },
+            RbspParams::default(),
+        )
+    }
+
+    #[test]
+    fn merge_everything() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let merged = merge_rooms(&mut rng, tree(0), 1.0);
+
+        assert_eq!(merged, BspNode::Room(tree(0).bounds().clone()));
+    }
+
+    #[test]
+    fn merge_nothing() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        assert_eq!(merge_rooms(&mut rng, tree(1), 0.0), tree(1));
+    }
+
+    #[test]
+    fn merging_keeps_total_area() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let original = tree(2);
+        let area = |t: &BspNode| {
+            let rooms = t.rooms().map(|r| r.w * r.h).sum::<usize>();
+            let lines = t.lines().map(|l| l.bounds()).map(|r| r.w * r.h);
+            rooms + lines.sum::<usize>()
+        };
+
+        let merged = merge_rooms(&mut rng, original.clone(), 0.5);
+
+        assert!(merged.rooms().count() < original.rooms().count());
+        assert_eq!(area(&merged), area(&original));
+    }
+}
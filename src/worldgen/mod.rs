@@ -1,22 +1,276 @@
+#[cfg(feature = "render")]
+pub mod animation;
+#[cfg(feature = "std")]
+pub mod biomes;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod ceilings;
+#[cfg(feature = "std")]
+pub mod cellular;
+#[cfg(feature = "std")]
+pub mod chunks;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod connectivity;
+#[cfg(feature = "std")]
+pub mod corridors;
+#[cfg(feature = "std")]
+pub mod deadends;
+#[cfg(feature = "std")]
+pub mod doors;
+#[cfg(feature = "std")]
+pub mod drunkard;
+#[cfg(feature = "std")]
+pub mod erosion;
+#[cfg(feature = "std")]
+pub mod furnish;
+#[cfg(feature = "std")]
+pub mod garage;
+#[cfg(feature = "std")]
+pub mod graph;
 pub mod hallways;
+#[cfg(feature = "std")]
+pub mod labels;
+#[cfg(feature = "std")]
+pub mod levels;
+#[cfg(feature = "std")]
+pub mod lights;
+#[cfg(feature = "std")]
+pub mod locks;
+#[cfg(feature = "std")]
+pub mod loops;
+#[cfg(feature = "std")]
+pub mod mapfile;
+#[cfg(feature = "std")]
+pub mod maze;
+#[cfg(feature = "std")]
+pub mod merge;
+#[cfg(feature = "std")]
+pub mod noise;
+pub mod observer;
+#[cfg(feature = "std")]
+pub mod pillars;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
+pub mod placement;
+#[cfg(feature = "std")]
+pub mod pools;
+#[cfg(feature = "std")]
+pub mod presets;
+#[cfg(feature = "std")]
+pub mod raster;
+#[cfg(feature = "render")]
+mod render;
+#[cfg(feature = "std")]
+pub mod scatter;
+#[cfg(feature = "std")]
+pub mod secrets;
+#[cfg(feature = "std")]
+pub mod shapes;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod sweep;
+#[cfg(feature = "std")]
+pub mod teasers;
+#[cfg(feature = "std")]
+pub mod tiled;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod voronoi;
+#[cfg(feature = "std")]
+pub mod wfc;
 
-use image::{ImageBuffer, Rgb, RgbImage};
+#[cfg(feature = "std")]
+use std::fmt::Write;
+
+#[cfg(feature = "std")]
 use ndarray::Array2;
 
-pub fn render_to_img(a: &Array2<bool>) -> RgbImage {
-    let (w, h) = a.dim();
-    let mut img = ImageBuffer::new(w as u32, h as u32);
-    for ((x, y), v) in a.indexed_iter() {
-        img.put_pixel(
-            x as u32,
-            y as u32,
-            if *v {
-                Rgb([0u8, 0, 0])
-            } else {
-                Rgb([255, 255, 255])
-            },
+#[cfg(feature = "std")]
+use crate::{
+    tile::Tile,
+    util::{Line, Region},
+};
+
+#[cfg(feature = "std")]
+use self::{
+    pipeline::GeneratedWorld,
+    raster::{check_bounds, RasterError},
+};
+
+#[cfg(feature = "render")]
+pub use render::{node_color, render_debug_img, render_to_img};
+
+/// Redraw a world's rooms, hallways and doors onto a fresh map the size of its
+/// bounds, in the same way the room-based generators draw them.
+///
+/// Fails if any of them reaches off the map, rather than clipping it.
+#[cfg(feature = "std")]
+pub fn rasterize(world: &GeneratedWorld) -> Result<Array2<Tile>, RasterError> {
+    let dim = (world.bounds.w, world.bounds.h);
+    check_bounds(dim, &world.rooms, &world.lines, &world.doors)?;
+    Ok(raster::rasterize(
+        dim,
+        &world.rooms,
+        &world.lines,
+        &world.doors,
+    ))
+}
+
+/// Draw the room and hallway structure as an SVG, with each room outlined and
+/// labeled with its index, and hallways drawn as filled strips.
+///
+/// Coordinates are in tiles, so the SVG can be scaled freely without losing
+/// detail.
+#[cfg(feature = "std")]
+pub fn render_to_svg(rooms: &[Region], lines: &[Line]) -> String {
+    let rects = rooms
+        .iter()
+        .flat_map(|r| r.rects.iter().cloned())
+        .chain(lines.iter().map(Line::bounds));
+    let (mut x0, mut y0, mut x1, mut y1) = (0, 0, 0, 0);
+    for r in rects {
+        x0 = x0.min(r.x);
+        y0 = y0.min(r.y);
+        x1 = x1.max(r.x_end());
+        y1 = y1.max(r.y_end());
+    }
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{x0} {y0} {} {}">"#,
+        x1 - x0,
+        y1 - y0
+    );
+    svg.push('\n');
+    for r in lines.iter().map(Line::bounds) {
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#c8b040"/>"##,
+            r.x, r.y, r.w, r.h
+        );
+    }
+    for (i, room) in rooms.iter().enumerate() {
+        for r in &room.rects {
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="black" stroke-width="0.5"/>"#,
+                r.x, r.y, r.w, r.h
+            );
+        }
+        let b = room.bounds();
+        let font_size = (b.w.min(b.h) as f32 / 3.0).clamp(1.0, 12.0);
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="{font_size}" text-anchor="middle" dominant-baseline="middle">{i}</text>"#,
+            b.x as f32 + b.w as f32 / 2.0,
+            b.y as f32 + b.h as f32 / 2.0,
         );
     }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::util::{Axis, Direction, Rectangle};
+
+    use super::{
+        doors::Door,
+        hallways::RbspParams,
+        pipeline::{Pipeline, PlaceDoors, WorldGenerator},
+        *,
+    };
+
+    #[test]
+    fn svg_has_every_room_and_line() {
+        let rooms = [
+            Region::from(Rectangle {
+                x: 0,
+                y: 0,
+                w: 10,
+                h: 5,
+            }),
+            Region::from(Rectangle {
+                x: 0,
+                y: 6,
+                w: 10,
+                h: 5,
+            }),
+        ];
+        let lines = [Line {
+            x: 0,
+            y: 5,
+            length: 9,
+            width: 1,
+            axis: Axis::Horizontal,
+        }];
+
+        let svg = render_to_svg(&rooms, &lines);
+
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 11">"#));
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert_eq!(svg.matches("<text").count(), 2);
+        assert!(svg.contains(">1</text>"));
+    }
+
+    #[test]
+    fn rasterize_redraws_generated_worlds() {
+        let params = RbspParams {
+            max_room_len: 40,
+            hallway_width: 2,
+            ..Default::default()
+        };
+        let bounds = Rectangle {
+            x: 0,
+            y: 0,
+            w: 96,
+            h: 64,
+        };
+        let world = Pipeline::new(params)
+            .then(PlaceDoors)
+            .generate(&mut SmallRng::seed_from_u64(0), bounds);
 
-    img
+        assert_eq!(rasterize(&world), Ok(world.tiles));
+    }
+
+    #[test]
+    fn rasterize_rejects_geometry_off_the_map() {
+        let bounds = Rectangle {
+            x: 0,
+            y: 0,
+            w: 10,
+            h: 10,
+        };
+        let mut world = GeneratedWorld::from_tiles(bounds, Array2::default((10, 10)));
+        world.lines.push(Line {
+            x: 0,
+            y: 8,
+            length: 9,
+            width: 2,
+            axis: Axis::Horizontal,
+        });
+        assert!(rasterize(&world).is_ok());
+
+        world.lines[0].width = 3;
+        assert_eq!(
+            rasterize(&world),
+            Err(RasterError::HallwayOutOfBounds { line: 0 })
+        );
+
+        world.lines.clear();
+        world.doors.push(Door::new(-1, 4, Direction::East));
+        assert_eq!(
+            rasterize(&world),
+            Err(RasterError::DoorOutOfBounds { door: 0 })
+        );
+    }
 }
@@ -0,0 +1,148 @@
+use ndarray::Array2;
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+
+/// 2D Perlin gradient noise.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut p: Vec<u8> = (0..=255).collect();
+        p.shuffle(&mut rng);
+
+        let mut perm = [0; 512];
+        for (i, v) in perm.iter_mut().enumerate() {
+            *v = p[i % 256];
+        }
+        Self { perm }
+    }
+
+    /// Sample the noise at the given point. The result is roughly in [-1, 1],
+    /// and is exactly 0 on integer coordinates.
+    pub fn get(&self, x: f32, y: f32) -> f32 {
+        let (xf, yf) = (x.floor(), y.floor());
+        let (xi, yi) = ((xf as i64 & 255) as usize, (yf as i64 & 255) as usize);
+        let (x, y) = (x - xf, y - yf);
+
+        let hash = |i: usize, j: usize| self.perm[self.perm[i] as usize + j];
+        let (u, v) = (fade(x), fade(y));
+
+        let n00 = grad(hash(xi, yi), x, y);
+        let n10 = grad(hash(xi + 1, yi), x - 1.0, y);
+        let n01 = grad(hash(xi, yi + 1), x, y - 1.0);
+        let n11 = grad(hash(xi + 1, yi + 1), x - 1.0, y - 1.0);
+
+        lerp(v, lerp(u, n00, n10), lerp(u, n01, n11))
+    }
+
+    /// Fractal Brownian motion: several octaves of noise summed together.
+    /// Normalized to roughly [-1, 1].
+    pub fn fbm(&self, x: f32, y: f32, params: &NoiseParams) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0 / params.scale;
+        let mut max = 0.0;
+        for _ in 0..params.octaves.max(1) {
+            total += self.get(x * frequency, y * frequency) * amplitude;
+            max += amplitude;
+            amplitude *= params.persistence;
+            frequency *= params.lacunarity;
+        }
+        total / max
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn grad(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => x - y,
+        2 => -x + y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseParams {
+    /// Size in tiles of the largest features.
+    pub scale: f32,
+
+    /// How many layers of increasingly fine noise to add together.
+    pub octaves: usize,
+
+    /// How much each octave's amplitude is multiplied by, usually around 0.5.
+    pub persistence: f32,
+
+    /// How much each octave's frequency is multiplied by, usually around 2.
+    pub lacunarity: f32,
+}
+
+/// A pass that stores a [`noise_field`] on the world under `name`, for
+/// renderers and gameplay to look up later. Running it again with the same
+/// name replaces the field.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseOverlay {
+    /// What the field means, such as `"mold"` or `"wetness"`.
+    pub name: String,
+
+    pub params: NoiseParams,
+}
+
+/// Produce a per-tile scalar field in [0, 1], indexed by `(x, y)`, for things
+/// like mold coverage, carpet wetness or light flicker intensity.
+pub fn noise_field(seed: u64, dim: (usize, usize), params: &NoiseParams) -> Array2<f32> {
+    let perlin = Perlin::new(seed);
+    Array2::from_shape_fn(dim, |(x, y)| {
+        let n = perlin.fbm(x as f32 + 0.5, y as f32 + 0.5, params);
+        (n * 0.5 + 0.5).clamp(0.0, 1.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> NoiseParams {
+        NoiseParams {
+            scale: 16.0,
+            octaves: 4,
+            persistence: 0.5,
+            lacunarity: 2.0,
+        }
+    }
+
+    #[test]
+    fn field_is_deterministic_and_in_range() {
+        let a = noise_field(7, (64, 64), &params());
+        let b = noise_field(7, (64, 64), &params());
+
+        assert_eq!(a, b);
+        assert!(a.iter().all(|v| (0.0..=1.0).contains(v)));
+        assert_ne!(a, noise_field(8, (64, 64), &params()));
+    }
+
+    #[test]
+    fn noise_is_smooth() {
+        let perlin = Perlin::new(0);
+        for i in 0..1000 {
+            let (x, y) = (i as f32 * 0.137, i as f32 * 0.071);
+            assert!((perlin.get(x, y) - perlin.get(x + 0.01, y)).abs() < 0.05);
+        }
+    }
+}
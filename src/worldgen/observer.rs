@@ -0,0 +1,56 @@
+use crate::util::{Line, Rectangle};
+
+#[cfg(feature = "std")]
+use super::pipeline::GeneratedWorld;
+
+/// Receives events as a world is generated, to drive progress bars or live
+/// visualizations.
+///
+/// Every method does nothing by default, so observers only need to implement
+/// the events they care about. `()` is an observer that ignores everything.
+pub trait GenerationObserver {
+    /// A rectangle was cut in two by a hallway.
+    fn on_partition(&mut self, _bounds: &Rectangle<isize, usize>, _line: &Line) {}
+
+    /// A corridor was carved around the edges of a rectangle.
+    fn on_ring(&mut self, _bounds: &Rectangle<isize, usize>, _lines: &[Line]) {}
+
+    /// A rectangle was kept as a room. Without perimeter rings, the areas of
+    /// all kept rooms add up to the area being generated, so they can be used
+    /// to measure progress.
+    fn on_room_kept(&mut self, _room: &Rectangle<isize, usize>) {}
+
+    /// The generator at the start of a pipeline finished.
+    #[cfg(feature = "std")]
+    fn on_generated(&mut self, _world: &GeneratedWorld) {}
+
+    /// A pass finished running. `index` counts passes from 0.
+    #[cfg(feature = "std")]
+    fn on_pass_complete(&mut self, _index: usize, _name: &str, _world: &GeneratedWorld) {}
+}
+
+impl GenerationObserver for () {}
+
+impl<O: GenerationObserver + ?Sized> GenerationObserver for &mut O {
+    fn on_partition(&mut self, bounds: &Rectangle<isize, usize>, line: &Line) {
+        (**self).on_partition(bounds, line)
+    }
+
+    fn on_ring(&mut self, bounds: &Rectangle<isize, usize>, lines: &[Line]) {
+        (**self).on_ring(bounds, lines)
+    }
+
+    fn on_room_kept(&mut self, room: &Rectangle<isize, usize>) {
+        (**self).on_room_kept(room)
+    }
+
+    #[cfg(feature = "std")]
+    fn on_generated(&mut self, world: &GeneratedWorld) {
+        (**self).on_generated(world)
+    }
+
+    #[cfg(feature = "std")]
+    fn on_pass_complete(&mut self, index: usize, name: &str, world: &GeneratedWorld) {
+        (**self).on_pass_complete(index, name, world)
+    }
+}
@@ -0,0 +1,124 @@
+use ndarray::{s, Array2};
+use rand::Rng;
+
+use crate::tile::Tile;
+
+/// Parameters for the "infinite pillar room" of Level 0.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PillarParams {
+    /// Distance between the origins of neighboring pillars on the grid.
+    pub spacing: usize,
+
+    /// Width and height of each square pillar.
+    pub pillar_size: usize,
+
+    /// Maximum distance a pillar may be moved off its grid position, on each axis.
+    pub jitter: usize,
+
+    /// A probability in [0, 1] that a partial wall extends from a pillar towards
+    /// each of its east and south neighbors.
+    pub wall_density: f32,
+}
+
+/// Generate a large open area with a semi-regular grid of square pillars and
+/// occasional partial walls running between them.
+///
+/// The returned map is indexed by `(x, y)`.
+pub fn pillar_grid(
+    rng: &mut impl Rng,
+    (w, h): (usize, usize),
+    params: &PillarParams,
+) -> Array2<Tile> {
+    let mut map = Array2::from_elem((w, h), Tile::Floor);
+    let spacing = params.spacing.max(1);
+    let size = params.pillar_size;
+
+    let (nx, ny) = (w / spacing + 1, h / spacing + 1);
+    let pillars = Array2::from_shape_fn((nx, ny), |(i, j)| {
+        let jitter = params.jitter as isize;
+        let x = (i * spacing) as isize + rng.gen_range(-jitter..=jitter);
+        let y = (j * spacing) as isize + rng.gen_range(-jitter..=jitter);
+        (x, y)
+    });
+
+    for ((i, j), &(x, y)) in pillars.indexed_iter() {
+        fill(&mut map, x, y, size, size, Tile::Pillar);
+
+        if i + 1 < nx && rng.gen::<f32>() < params.wall_density {
+            let gap = pillars[(i + 1, j)].0 - x - size as isize;
+            let len = rng.gen_range(0..=gap.max(0)) as usize;
+            fill(&mut map, x + size as isize, y, len, size, Tile::Wall);
+        }
+        if j + 1 < ny && rng.gen::<f32>() < params.wall_density {
+            let gap = pillars[(i, j + 1)].1 - y - size as isize;
+            let len = rng.gen_range(0..=gap.max(0)) as usize;
+            fill(&mut map, x, y + size as isize, size, len, Tile::Wall);
+        }
+    }
+
+    map
+}
+
+/// Fill a rectangle of the map, clipped to its bounds.
+fn fill(map: &mut Array2<Tile>, x: isize, y: isize, w: usize, h: usize, tile: Tile) {
+    let (mw, mh) = map.dim();
+    let clip = |v: isize, max: usize| v.clamp(0, max as isize) as usize;
+    let (x0, x1) = (clip(x, mw), clip(x + w as isize, mw));
+    let (y0, y1) = (clip(y, mh), clip(y + h as isize, mh));
+    map.slice_mut(s![x0..x1, y0..y1]).fill(tile);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn regular_grid_without_jitter() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let map = pillar_grid(
+            &mut rng,
+            (32, 32),
+            &PillarParams {
+                spacing: 8,
+                pillar_size: 2,
+                jitter: 0,
+                wall_density: 0.0,
+            },
+        );
+
+        for ((x, y), t) in map.indexed_iter() {
+            let expected = if x % 8 < 2 && y % 8 < 2 {
+                Tile::Pillar
+            } else {
+                Tile::Floor
+            };
+            assert_eq!(*t, expected, "at {x}, {y}");
+        }
+    }
+
+    #[test]
+    fn walls_only_between_pillars() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let map = pillar_grid(
+            &mut rng,
+            (64, 64),
+            &PillarParams {
+                spacing: 8,
+                pillar_size: 2,
+                jitter: 0,
+                wall_density: 1.0,
+            },
+        );
+
+        for ((x, y), t) in map.indexed_iter() {
+            if *t == Tile::Wall {
+                assert!(x % 8 < 2 || y % 8 < 2, "wall off the grid at {x}, {y}");
+            }
+        }
+    }
+}
@@ -0,0 +1,834 @@
+use std::collections::BTreeMap;
+
+use ndarray::Array2;
+use rand::{Rng, RngCore};
+
+use crate::{
+    tile::{MaterialId, Tile},
+    util::{Line, Rectangle, Region},
+    world::{ArrayWorld, Decal},
+};
+
+use super::{
+    biomes::{assign_biomes, material_map, Biome},
+    ceilings::{assign_heights, height_map, CeilingParams},
+    cellular::{cellular, CellularParams},
+    connectivity::connect_regions,
+    corridors::{route_corridors, RoutedRbspParams},
+    deadends::{prune_dead_ends, DeadEndParams},
+    doors::{place_doors, Door},
+    drunkard::{drunkards, DrunkardParams},
+    erosion::{erode_walls, ErosionParams},
+    furnish::{furnish, FurnishParams},
+    garage::{garage, GarageParams},
+    graph::Node,
+    hallways::{rbsp, rbsp_observed, BspNode, RbspParams},
+    locks::{place_locks, DoorGraph, Key, LockParams},
+    loops::{insert_loops, LoopParams},
+    maze::{maze, MazeParams},
+    noise::{noise_field, NoiseOverlay},
+    observer::GenerationObserver,
+    pillars::{pillar_grid, PillarParams},
+    placement::pick_spawn,
+    pools::{pool_rooms, PoolParams},
+    raster::{rasterize, rasterize_walled},
+    secrets::{place_secrets, SecretParams},
+    teasers::{place_teasers, TeaserParams},
+    voronoi::{voronoi_rooms, VoronoiParams},
+};
+
+/// The output of a [`WorldGenerator`].
+///
+/// Everything is in local coordinates, relative to the origin of `bounds`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeneratedWorld {
+    /// The area of the world that was generated.
+    pub bounds: Rectangle<isize, usize>,
+
+    /// The rasterized map, indexed by `(x, y)`.
+    pub tiles: Array2<Tile>,
+
+    /// The material of every tile, indexed like `tiles`, as assigned by
+    /// [`AssignMaterials`].
+    pub materials: Array2<MaterialId>,
+
+    /// The rooms the map was built from, if the generator has a notion of rooms.
+    pub rooms: Vec<Region>,
+
+    /// The hallways the map was built from, if the generator has a notion of
+    /// hallways.
+    pub lines: Vec<Line>,
+
+    /// The partition tree the rooms and hallways came from, for generators
+    /// based on binary space partitioning.
+    pub tree: Option<BspNode>,
+
+    /// Doorways, as placed by the generator or by [`PlaceDoors`].
+    pub doors: Vec<Door>,
+
+    /// The biome of each room, as assigned by [`AssignBiomes`].
+    pub biomes: Vec<Biome>,
+
+    /// The ceiling height of each room, as assigned by [`CeilingParams`].
+    pub heights: Vec<f32>,
+
+    /// Keys to the doors locked by [`LockParams`].
+    pub keys: Vec<Key>,
+
+    /// Markings painted on the floor, such as parking garage lanes.
+    pub decals: Vec<Decal>,
+
+    /// Rooms sealed off by [`TeaserParams`], which can be seen into but not
+    /// entered.
+    pub teasers: Vec<usize>,
+
+    /// Per-tile scalar fields stored by [`NoiseOverlay`], by name, each
+    /// indexed like `tiles`.
+    pub fields: BTreeMap<String, Array2<f32>>,
+}
+
+impl GeneratedWorld {
+    /// A world made of nothing but a tile map.
+    pub fn from_tiles(bounds: Rectangle<isize, usize>, tiles: Array2<Tile>) -> Self {
+        Self {
+            bounds,
+            materials: Array2::default(tiles.dim()),
+            tiles,
+            rooms: vec![],
+            lines: vec![],
+            tree: None,
+            doors: vec![],
+            biomes: vec![],
+            heights: vec![],
+            keys: vec![],
+            decals: vec![],
+            teasers: vec![],
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<GeneratedWorld> for ArrayWorld {
+    fn from(world: GeneratedWorld) -> Self {
+        let heights = (!world.heights.is_empty())
+            .then(|| height_map(world.tiles.dim(), &world.rooms, &world.heights, 1.0));
+        let array = ArrayWorld::from(world.tiles)
+            .with_materials(world.materials)
+            .with_doors(world.doors)
+            .with_decals(world.decals);
+        let array = world
+            .fields
+            .into_iter()
+            .fold(array, |array, (name, field)| array.with_field(name, field));
+        match heights {
+            Some(heights) => array.with_heights(heights),
+            None => array,
+        }
+    }
+}
+
+/// Anything that can fill an area with a map.
+pub trait WorldGenerator {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld;
+
+    /// Like [`WorldGenerator::generate`], but reporting progress to
+    /// `observer` along the way. Generators with nothing to report just
+    /// generate.
+    fn generate_observed(
+        &self,
+        rng: &mut impl Rng,
+        bounds: Rectangle<isize, usize>,
+        _observer: &mut impl GenerationObserver,
+    ) -> GeneratedWorld {
+        self.generate(rng, bounds)
+    }
+}
+
+/// A post-processing step run over a generated world, such as placing doors,
+/// pruning dead ends or furnishing rooms.
+///
+/// Passes take a `dyn` random number generator so that pass lists can hold
+/// passes of different types.
+pub trait WorldPass {
+    fn apply(&self, rng: &mut dyn RngCore, world: &mut GeneratedWorld);
+
+    /// A name for the pass in diagnostics.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<F: Fn(&mut dyn RngCore, &mut GeneratedWorld)> WorldPass for F {
+    fn apply(&self, rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        self(rng, world)
+    }
+}
+
+/// An ordered list of passes sharing one random number generator.
+#[derive(Default)]
+pub struct Passes {
+    passes: Vec<Box<dyn WorldPass>>,
+}
+
+impl Passes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pass to the end of the list.
+    pub fn then(mut self, pass: impl WorldPass + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Run every pass over the world, in the order they were added.
+    pub fn run(&self, mut rng: &mut impl Rng, world: &mut GeneratedWorld) {
+        for pass in &self.passes {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("pass", name = pass.name()).entered();
+            pass.apply(&mut rng, world);
+        }
+    }
+}
+
+/// A generator followed by any number of passes, run in order.
+///
+/// A pipeline is itself a generator, so pipelines can be nested.
+pub struct Pipeline<G> {
+    generator: G,
+    passes: Passes,
+}
+
+impl<G: WorldGenerator> Pipeline<G> {
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator,
+            passes: Passes::new(),
+        }
+    }
+
+    /// Add a pass to the end of the pipeline.
+    pub fn then(mut self, pass: impl WorldPass + 'static) -> Self {
+        self.passes = self.passes.then(pass);
+        self
+    }
+}
+
+impl<G: WorldGenerator> WorldGenerator for Pipeline<G> {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        self.generate_observed(rng, bounds, &mut ())
+    }
+
+    /// Run the pipeline, passing `observer` on to the generator, and reporting
+    /// to it after the generator and after every pass.
+    fn generate_observed(
+        &self,
+        mut rng: &mut impl Rng,
+        bounds: Rectangle<isize, usize>,
+        observer: &mut impl GenerationObserver,
+    ) -> GeneratedWorld {
+        let mut world = self.generator.generate_observed(rng, bounds, observer);
+        observer.on_generated(&world);
+        for (i, pass) in self.passes.passes.iter().enumerate() {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("pass", name = pass.name()).entered();
+            pass.apply(&mut rng, &mut world);
+            observer.on_pass_complete(i, pass.name(), &world);
+        }
+        world
+    }
+}
+
+fn dim(bounds: &Rectangle<isize, usize>) -> (usize, usize) {
+    (bounds.w, bounds.h)
+}
+
+/// Rooms and hallways, with a door into every room that touches a hallway.
+impl WorldGenerator for RbspParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        self.generate_observed(rng, bounds, &mut ())
+    }
+
+    /// Generate, reporting every partition and kept room to `observer` as the
+    /// tree is built.
+    fn generate_observed(
+        &self,
+        rng: &mut impl Rng,
+        bounds: Rectangle<isize, usize>,
+        observer: &mut impl GenerationObserver,
+    ) -> GeneratedWorld {
+        let local = Rectangle {
+            x: 0,
+            y: 0,
+            ..bounds.clone()
+        };
+        let tree = rbsp_observed(rng, local, self.clone(), observer);
+        let rooms = tree.rooms().cloned().map(Region::from).collect::<Vec<_>>();
+        let lines = tree.lines().cloned().collect::<Vec<_>>();
+        let doors = place_doors(rng, &rooms, &lines);
+        GeneratedWorld {
+            tiles: rasterize(dim(&bounds), &rooms, &lines, &doors),
+            materials: Array2::default(dim(&bounds)),
+            bounds,
+            rooms,
+            lines,
+            tree: Some(tree),
+            doors,
+            biomes: vec![],
+            heights: vec![],
+            keys: vec![],
+            decals: vec![],
+            teasers: vec![],
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+impl WorldGenerator for RoutedRbspParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let local = Rectangle {
+            x: 0,
+            y: 0,
+            ..bounds.clone()
+        };
+        let tree = rbsp(rng, local, self.rbsp.clone());
+        let routed = route_corridors(rng, &tree, dim(&bounds), &self.corridors);
+        GeneratedWorld {
+            tiles: rasterize(dim(&bounds), &routed.rooms, &routed.lines, &routed.doors),
+            materials: Array2::default(dim(&bounds)),
+            bounds,
+            rooms: routed.rooms,
+            lines: routed.lines,
+            tree: Some(tree),
+            doors: routed.doors,
+            biomes: vec![],
+            heights: vec![],
+            keys: vec![],
+            decals: vec![],
+            teasers: vec![],
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// Tiled pool rooms. Every room is given the [`Biome::Pool`] biome, and every
+/// tile the tile material.
+impl WorldGenerator for PoolParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let level = pool_rooms(rng, dim(&bounds), self);
+        GeneratedWorld {
+            materials: Array2::from_elem(dim(&bounds), MaterialId::TILE),
+            tiles: level.tiles,
+            bounds,
+            biomes: vec![Biome::Pool; level.rooms.len()],
+            rooms: level.rooms,
+            lines: level.lines,
+            tree: Some(level.tree),
+            doors: level.doors,
+            heights: vec![],
+            keys: vec![],
+            decals: vec![],
+            teasers: vec![],
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+/// One floor of a parking garage, in bare concrete.
+impl WorldGenerator for GarageParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let garage = garage(rng, dim(&bounds), self);
+        GeneratedWorld {
+            materials: Array2::from_elem(dim(&bounds), MaterialId::CONCRETE),
+            decals: garage.decals,
+            ..GeneratedWorld::from_tiles(bounds, garage.tiles)
+        }
+    }
+}
+
+impl WorldGenerator for MazeParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let tiles = maze(rng, dim(&bounds), self);
+        GeneratedWorld::from_tiles(bounds, tiles)
+    }
+}
+
+impl WorldGenerator for CellularParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let cave = cellular(rng, dim(&bounds), self);
+        GeneratedWorld {
+            rooms: cave.rooms,
+            ..GeneratedWorld::from_tiles(bounds, cave.tiles)
+        }
+    }
+}
+
+impl WorldGenerator for DrunkardParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let tiles = drunkards(rng, dim(&bounds), self);
+        GeneratedWorld::from_tiles(bounds, tiles)
+    }
+}
+
+impl WorldGenerator for PillarParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let tiles = pillar_grid(rng, dim(&bounds), self);
+        GeneratedWorld::from_tiles(bounds, tiles)
+    }
+}
+
+impl WorldGenerator for VoronoiParams {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        let tiles = voronoi_rooms(rng, dim(&bounds), self).tiles;
+        GeneratedWorld::from_tiles(bounds, tiles)
+    }
+}
+
+/// Punch a doorway into every room that touches a hallway, in place of any
+/// doors the world already has.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlaceDoors;
+
+impl WorldPass for PlaceDoors {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        // Doors are punched through the walls of their rooms.
+        for d in world.doors.drain(..) {
+            if let Some(c @ Tile::Door) = world.tiles.get_mut((d.x as usize, d.y as usize)) {
+                *c = Tile::Wall
+            }
+        }
+        let doors = place_doors(&mut rng, &world.rooms, &world.lines);
+        for d in &doors {
+            if let Some(c) = world.tiles.get_mut((d.x as usize, d.y as usize)) {
+                *c = Tile::Door
+            }
+        }
+        world.doors = doors;
+    }
+}
+
+/// Give every room a biome. Rooms keep the biome of their subtree down to
+/// `depth` partitions, if the world has a partition tree, and otherwise get a
+/// random biome each.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssignBiomes {
+    pub depth: usize,
+}
+
+impl WorldPass for AssignBiomes {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        world.biomes = match &world.tree {
+            Some(tree) => assign_biomes(&mut rng, tree, self.depth),
+            None => world.rooms.iter().map(|_| rng.gen()).collect(),
+        };
+    }
+}
+
+/// Give every tile the material of its room's biome, as assigned by
+/// [`AssignBiomes`]. Hallways, and rooms without a biome, get the default
+/// material.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssignMaterials;
+
+impl WorldPass for AssignMaterials {
+    fn apply(&self, _rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        world.materials = material_map(world.tiles.dim(), &world.rooms, &world.biomes);
+    }
+}
+
+/// Carve tunnels until every open cell is reachable from the first open cell
+/// of the map.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectRegions;
+
+impl WorldPass for ConnectRegions {
+    fn apply(&self, _rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        let start = world
+            .tiles
+            .indexed_iter()
+            .find(|(_, t)| !t.is_solid())
+            .map(|(pos, _)| pos);
+        if let Some(start) = start {
+            connect_regions(&mut world.tiles, start);
+        }
+    }
+}
+
+/// Thicken the walls of every room to `wall_thickness` tiles, so that rooms
+/// sit inset from their outlines and neighboring rooms are kept apart by solid
+/// wall. Doors are carved through to the floor of their room.
+///
+/// Only room floor is ever walled up, so this can run after passes that change
+/// other tiles, but should run after [`PlaceDoors`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InsetRooms {
+    pub wall_thickness: usize,
+}
+
+impl WorldPass for InsetRooms {
+    fn apply(&self, _rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        let inset = rasterize_walled(
+            world.tiles.dim(),
+            &world.rooms,
+            &world.lines,
+            &world.doors,
+            self.wall_thickness,
+        );
+        for (t, new) in world.tiles.iter_mut().zip(inset) {
+            if *t == Tile::Floor && new == Tile::Wall {
+                *t = Tile::Wall;
+            }
+        }
+    }
+}
+
+impl WorldPass for CeilingParams {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        world.heights = assign_heights(&mut rng, &world.rooms, self);
+    }
+}
+
+/// Lock doors so that the level can be finished starting from the spawn picked
+/// by [`pick_spawn`].
+impl WorldPass for LockParams {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        let Some((x, y)) = pick_spawn(&world.tiles, &world.rooms) else {
+            return;
+        };
+        let Some(spawn) = world
+            .rooms
+            .iter()
+            .position(|r| r.contains((x as isize, y as isize)))
+        else {
+            return;
+        };
+        let graph = DoorGraph::new(&world.rooms, &world.lines, &world.doors);
+        let keys = place_locks(
+            &mut rng,
+            &world.tiles,
+            &graph,
+            &mut world.doors,
+            &world.keys,
+            Node::Room(spawn),
+            self,
+        );
+        world.keys.extend(keys);
+    }
+}
+
+impl WorldPass for LoopParams {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        insert_loops(&mut rng, &mut world.tiles, self);
+    }
+}
+
+impl WorldPass for SecretParams {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        place_secrets(&mut rng, &mut world.tiles, self);
+    }
+}
+
+impl WorldPass for ErosionParams {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        erode_walls(&mut rng, &mut world.tiles, &mut world.materials, self);
+    }
+}
+
+/// Seal off rooms behind windows. Should run before [`LockParams`], so no keys
+/// end up sealed away.
+impl WorldPass for TeaserParams {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        let teasers = place_teasers(
+            &mut rng,
+            &mut world.tiles,
+            &world.rooms,
+            &mut world.doors,
+            self,
+        );
+        world.teasers.extend(teasers);
+    }
+}
+
+impl WorldPass for NoiseOverlay {
+    fn apply(&self, rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        let field = noise_field(rng.gen(), world.tiles.dim(), &self.params);
+        world.fields.insert(self.name.clone(), field);
+    }
+}
+
+impl WorldPass for DeadEndParams {
+    fn apply(&self, _rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        prune_dead_ends(&mut world.tiles, self);
+    }
+}
+
+impl WorldPass for FurnishParams {
+    fn apply(&self, mut rng: &mut dyn RngCore, world: &mut GeneratedWorld) {
+        furnish(&mut rng, &mut world.tiles, &world.rooms, self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::{connectivity::unreachable_cells, noise::NoiseParams};
+
+    use super::*;
+
+    fn bounds() -> Rectangle<isize, usize> {
+        Rectangle {
+            x: 100,
+            y: -50,
+            w: 64,
+            h: 48,
+        }
+    }
+
+    #[test]
+    fn generators_fill_bounds() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let worlds = [
+            RbspParams {
+                max_room_len: 20,
+                ..Default::default()
+            }
+            .generate(&mut rng, bounds()),
+            MazeParams {
+                cell_size: 2,
+                wall_thickness: 1,
+                loop_fraction: 0.1,
+            }
+            .generate(&mut rng, bounds()),
+            CellularParams {
+                fill_probability: 0.45,
+                iterations: 4,
+                wall_threshold: 5,
+            }
+            .generate(&mut rng, bounds()),
+        ];
+
+        for w in &worlds {
+            assert_eq!(w.bounds, bounds());
+            assert_eq!(w.tiles.dim(), (64, 48));
+        }
+        assert!(!worlds[0].rooms.is_empty());
+    }
+
+    #[test]
+    fn rbsp_with_door_and_biome_passes() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let pipeline = Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(PlaceDoors)
+        .then(AssignBiomes { depth: 2 });
+
+        let world = pipeline.generate(&mut rng, bounds());
+
+        assert!(!world.doors.is_empty());
+        assert_eq!(world.biomes.len(), world.rooms.len());
+        for d in &world.doors {
+            assert_eq!(world.tiles[(d.x as usize, d.y as usize)], Tile::Door);
+        }
+    }
+
+    #[test]
+    fn pipelines_forward_partitions_to_observers() {
+        #[derive(Default)]
+        struct Counter {
+            partitions: usize,
+            generated: bool,
+        }
+
+        impl GenerationObserver for Counter {
+            fn on_partition(&mut self, _: &Rectangle<isize, usize>, _: &Line) {
+                assert!(!self.generated);
+                self.partitions += 1;
+            }
+
+            fn on_generated(&mut self, _: &GeneratedWorld) {
+                self.generated = true;
+            }
+        }
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut counter = Counter::default();
+        let pipeline = Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(PlaceDoors);
+
+        let world = pipeline.generate_observed(&mut rng, bounds(), &mut counter);
+
+        let tree = world.tree.unwrap();
+        let partitions = tree
+            .iter()
+            .filter(|n| matches!(n, BspNode::Partition { .. }))
+            .count();
+        assert!(counter.generated);
+        assert!(partitions > 0);
+        assert_eq!(counter.partitions, partitions);
+    }
+
+    #[test]
+    fn placing_doors_again_replaces_them() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut world = RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        }
+        .generate(&mut rng, bounds());
+        let n = world.doors.len();
+
+        PlaceDoors.apply(&mut rng, &mut world);
+
+        assert!(n > 0);
+        assert_eq!(world.doors.len(), n);
+        let door_tiles = world.tiles.iter().filter(|&&t| t == Tile::Door).count();
+        assert_eq!(door_tiles, n);
+    }
+
+    #[test]
+    fn ceiling_heights_reach_array_world() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let pipeline = Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(CeilingParams {
+            base_height: 1.5,
+            hall_min_area: usize::MAX,
+            hall_height: 3.0,
+            jitter: 0.0,
+        });
+
+        let world = pipeline.generate(&mut rng, bounds());
+        let room = world.rooms[0].rects[0].clone();
+        let array = ArrayWorld::from(world);
+
+        assert_eq!(array.height((room.y + 1, room.x + 1)), 1.5);
+        assert_eq!(array.height((-1, 0)), 1.0);
+    }
+
+    #[test]
+    fn materials_follow_biomes() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let pipeline = Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(AssignBiomes { depth: 0 })
+        .then(AssignMaterials);
+
+        let world = pipeline.generate(&mut rng, bounds());
+        let room = world.rooms[0].rects[0].clone();
+        let material = world.biomes[0].material();
+        let line = world.lines[0].clone();
+        let array = ArrayWorld::from(world);
+
+        assert_eq!(array.material((room.y, room.x)), material);
+        assert_eq!(array.material((line.y, line.x)), MaterialId::default());
+        assert_eq!(array.material((-1, 0)), MaterialId::default());
+    }
+
+    #[test]
+    fn noise_fields_reach_array_world() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let pipeline = Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(NoiseOverlay {
+            name: "mold".into(),
+            params: NoiseParams {
+                scale: 16.0,
+                octaves: 3,
+                persistence: 0.5,
+                lacunarity: 2.0,
+            },
+        });
+
+        let world = pipeline.generate(&mut rng, bounds());
+        let mold = world.fields["mold"].clone();
+        let dim = world.tiles.dim();
+        let array = ArrayWorld::from(world);
+
+        assert_eq!(mold.dim(), dim);
+        assert!(mold.iter().all(|v| (0.0..=1.0).contains(v)));
+        assert_eq!(array.field("mold"), Some(&mold));
+        assert_eq!(array.field("wetness"), None);
+    }
+
+    #[test]
+    fn inset_rooms_stay_reachable() {
+        let pipeline = Pipeline::new(RbspParams {
+            min_room_len: 8,
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(PlaceDoors);
+        let mut world = pipeline.generate(&mut SmallRng::seed_from_u64(3), bounds());
+        let floors = |w: &GeneratedWorld| w.tiles.iter().filter(|t| **t == Tile::Floor).count();
+        let before = floors(&world);
+
+        InsetRooms { wall_thickness: 3 }.apply(&mut SmallRng::seed_from_u64(0), &mut world);
+
+        assert!(floors(&world) < before);
+        let line = &world.lines[0];
+        let start = (line.x as usize, line.y as usize);
+        assert!(unreachable_cells(&world.tiles, start).is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let pipeline = Pipeline::new(RbspParams {
+            max_room_len: 20,
+            ..Default::default()
+        })
+        .then(PlaceDoors)
+        .then(AssignBiomes { depth: 2 });
+        let world = pipeline.generate(&mut rng, bounds());
+
+        let json = serde_json::to_string(&world).unwrap();
+        let loaded: GeneratedWorld = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded.tiles, world.tiles);
+        assert_eq!(loaded.rooms, world.rooms);
+        assert_eq!(loaded.lines, world.lines);
+        assert_eq!(loaded.tree, world.tree);
+        assert_eq!(loaded.doors, world.doors);
+        assert_eq!(loaded.biomes, world.biomes);
+        assert_eq!(ArrayWorld::from(loaded).map(), &world.tiles);
+    }
+
+    #[test]
+    fn passes_run_in_order() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let pipeline = Pipeline::new(CellularParams {
+            fill_probability: 0.45,
+            iterations: 4,
+            wall_threshold: 5,
+        })
+        .then(|_: &mut dyn RngCore, w: &mut GeneratedWorld| {
+            w.tiles[(0, 0)] = Tile::Stairs;
+        })
+        .then(ConnectRegions);
+
+        let world = pipeline.generate(&mut rng, bounds());
+
+        assert_eq!(world.tiles[(0, 0)], Tile::Stairs);
+        assert!(unreachable_cells(&world.tiles, (0, 0)).is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+use ndarray::Array2;
+
+use crate::{tile::Tile, util::Region};
+
+use super::connectivity::distances;
+
+/// A cell picked for the player to reach, and how far it is from spawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exit {
+    pub pos: (usize, usize),
+
+    /// Length of the shortest walk from spawn to this exit, in tiles.
+    pub distance: usize,
+}
+
+/// Pick a spawn cell at the center of a medium-sized room, preferring rooms
+/// far from the edges of the map.
+///
+/// Rooms whose area falls between the 25th and 75th percentile are considered
+/// medium. Returns `None` if no candidate room has a walkable center.
+pub fn pick_spawn(map: &Array2<Tile>, rooms: &[Region]) -> Option<(usize, usize)> {
+    let mut areas = rooms.iter().map(Region::area).collect::<Vec<_>>();
+    areas.sort_unstable();
+    let (lo, hi) = (
+        *areas.get(areas.len() / 4)?,
+        *areas.get(areas.len() * 3 / 4)?,
+    );
+
+    let (w, h) = map.dim();
+    rooms
+        .iter()
+        .filter(|r| (lo..=hi).contains(&r.area()))
+        .filter_map(|r| {
+            let b = r.bounds();
+            let (x, y) = (b.x + b.w as isize / 2, b.y + b.h as isize / 2);
+            let pos = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+            let walkable = r.contains((x, y)) && !map.get(pos)?.is_solid();
+            walkable.then_some(pos)
+        })
+        .max_by_key(|&(x, y)| x.min(y).min(w - 1 - x).min(h - 1 - y))
+}
+
+/// Pick up to `n` exits reachable from `spawn`, each as far as possible from
+/// spawn and from the exits picked before it.
+///
+/// The first exit is the reachable cell farthest from spawn. Fewer than `n`
+/// exits are returned if there aren't enough distinct reachable cells.
+pub fn pick_exits(map: &Array2<Tile>, spawn: (usize, usize), n: usize) -> Vec<Exit> {
+    let from_spawn = distances(map, spawn);
+    let mut nearest = from_spawn.clone();
+    let mut exits = vec![];
+
+    for _ in 0..n {
+        let Some((pos, _)) = nearest
+            .indexed_iter()
+            .filter_map(|(pos, d)| Some((pos, (*d)?)))
+            .filter(|(_, d)| *d > 0)
+            .max_by_key(|(_, d)| *d)
+        else {
+            break;
+        };
+        exits.push(Exit {
+            pos,
+            distance: from_spawn[pos].unwrap(),
+        });
+
+        let from_exit = distances(map, pos);
+        nearest.zip_mut_with(&from_exit, |a, b| {
+            if let (Some(a), Some(b)) = (a.as_mut(), b) {
+                *a = (*a).min(*b);
+            }
+        });
+    }
+
+    exits
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use crate::util::Rectangle;
+
+    use super::*;
+
+    fn corridor() -> Array2<Tile> {
+        array![
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 0, 0, 0, 0, 1],
+            [1, 1, 1, 1, 0, 1, 1, 1, 1],
+            [1, 1, 1, 1, 0, 1, 1, 1, 1],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1],
+        ]
+        .map(|x| Tile::from(*x != 0))
+    }
+
+    #[test]
+    fn exits_are_spread_out() {
+        let map = corridor();
+
+        let exits = pick_exits(&map, (1, 4), 3);
+
+        assert_eq!(
+            exits,
+            vec![
+                Exit {
+                    pos: (1, 7),
+                    distance: 3
+                },
+                Exit {
+                    pos: (1, 1),
+                    distance: 3
+                },
+                Exit {
+                    pos: (3, 4),
+                    distance: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn exits_stop_when_out_of_cells() {
+        let map = corridor();
+
+        assert_eq!(pick_exits(&map, (1, 4), 100).len(), 8);
+    }
+
+    #[test]
+    fn spawn_in_medium_room_away_from_edges() {
+        let map = Array2::from_elem((100, 100), Tile::Floor);
+        let room = |x, y, w, h| Region::from(Rectangle { x, y, w, h });
+        let rooms = [
+            room(0, 0, 2, 2),
+            room(0, 10, 10, 10),
+            room(40, 40, 10, 10),
+            room(60, 60, 40, 40),
+        ];
+
+        assert_eq!(pick_spawn(&map, &rooms), Some((45, 45)));
+    }
+}
@@ -0,0 +1,277 @@
+use ndarray::{s, Array2};
+use rand::Rng;
+
+use crate::{
+    tile::{Tile, WaterDepth},
+    util::{Direction, Line, Rectangle, Region},
+};
+
+use super::{
+    doors::{place_doors, Door},
+    hallways::{rbsp, BspNode, RbspParams},
+    raster::rasterize,
+};
+
+/// Parameters for the "pool rooms": tiled rooms and corridors with sunken
+/// pools, looking through windows into each other.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolParams {
+    /// How the area is cut into rooms and the corridors between them.
+    pub rooms: RbspParams,
+
+    /// A probability in [0, 1] that a room gets a pool.
+    pub p_pool: f32,
+
+    /// Tiles of dry floor between a pool and the walls of its room.
+    pub margin: usize,
+
+    /// How far shallow water reaches in from the edge of a pool before it gets
+    /// deep. Pools too small to have a deep middle are shallow all over.
+    pub shallow_width: usize,
+
+    /// A probability in [0, 1] that each stretch of wall with open floor on
+    /// both sides becomes a window.
+    pub p_window: f32,
+}
+
+/// Output of [pool_rooms].
+#[derive(Debug, Clone)]
+pub struct PoolRooms {
+    /// The rasterized map, indexed by `(x, y)`.
+    pub tiles: Array2<Tile>,
+
+    pub tree: BspNode,
+    pub rooms: Vec<Region>,
+    pub lines: Vec<Line>,
+    pub doors: Vec<Door>,
+
+    /// The water of every pool, including its ramp.
+    pub pools: Vec<Rectangle<isize, usize>>,
+}
+
+/// Generate rooms and corridors as [rbsp] does, sink a pool with a ramp into
+/// some of the rooms, and glaze some of the walls between open areas.
+///
+/// Water and ramps can be walked through, so pools never cut anything off.
+pub fn pool_rooms(rng: &mut impl Rng, (w, h): (usize, usize), params: &PoolParams) -> PoolRooms {
+    let bounds = Rectangle { x: 0, y: 0, w, h };
+    let tree = rbsp(rng, bounds, params.rooms.clone());
+    let rooms = tree.rooms().cloned().map(Region::from).collect::<Vec<_>>();
+    let lines = tree.lines().cloned().collect::<Vec<_>>();
+    let doors = place_doors(rng, &rooms, &lines);
+    let mut tiles = rasterize((w, h), &rooms, &lines, &doors);
+
+    let mut pools = vec![];
+    for room in tree.rooms() {
+        // Leave the room's wall and the margin dry.
+        let Some(pool) = inset(room, 1 + params.margin) else {
+            continue;
+        };
+        if pool.w < 3 || pool.h < 3 || rng.gen::<f32>() >= params.p_pool {
+            continue;
+        }
+
+        fill(&mut tiles, &pool, Tile::Water(WaterDepth::Shallow));
+        if let Some(deep) = inset(&pool, params.shallow_width) {
+            fill(&mut tiles, &deep, Tile::Water(WaterDepth::Deep));
+        }
+        fill(&mut tiles, &ramp(&pool, rng.gen()), Tile::Ramp);
+        pools.push(pool);
+    }
+
+    place_windows(rng, &mut tiles, params.p_window);
+
+    PoolRooms {
+        tiles,
+        tree,
+        rooms,
+        lines,
+        doors,
+        pools,
+    }
+}
+
+/// Shrink a rectangle by `by` tiles on every side, if anything is left of it.
+fn inset(r: &Rectangle<isize, usize>, by: usize) -> Option<Rectangle<isize, usize>> {
+    (r.w > 2 * by && r.h > 2 * by).then(|| Rectangle {
+        x: r.x + by as isize,
+        y: r.y + by as isize,
+        w: r.w - 2 * by,
+        h: r.h - 2 * by,
+    })
+}
+
+/// The middle third of the edge of a pool on the given side, where a ramp
+/// leads down into it.
+fn ramp(pool: &Rectangle<isize, usize>, side: Direction) -> Rectangle<isize, usize> {
+    let (third_w, third_h) = (pool.w / 3, pool.h / 3);
+    let across_x = Rectangle {
+        x: pool.x + third_w as isize,
+        w: pool.w - 2 * third_w,
+        h: 1,
+        ..pool.clone()
+    };
+    let across_y = Rectangle {
+        y: pool.y + third_h as isize,
+        w: 1,
+        h: pool.h - 2 * third_h,
+        ..pool.clone()
+    };
+    match side {
+        Direction::West => across_y,
+        Direction::East => Rectangle {
+            x: pool.x_end() - 1,
+            ..across_y
+        },
+        Direction::South => across_x,
+        Direction::North => Rectangle {
+            y: pool.y_end() - 1,
+            ..across_x
+        },
+    }
+}
+
+/// Fill a rectangle of the map, clipped to its bounds.
+fn fill(map: &mut Array2<Tile>, r: &Rectangle<isize, usize>, tile: Tile) {
+    let (mw, mh) = map.dim();
+    let clip = |v: isize, max: usize| v.clamp(0, max as isize) as usize;
+    let (x0, x1) = (clip(r.x, mw), clip(r.x_end(), mw));
+    let (y0, y1) = (clip(r.y, mh), clip(r.y_end(), mh));
+    map.slice_mut(s![x0..x1, y0..y1]).fill(tile);
+}
+
+/// Turn walls one tile thick, with open floor on both sides, into windows.
+fn place_windows(rng: &mut impl Rng, map: &mut Array2<Tile>, p_window: f32) {
+    let original = map.clone();
+    let (w, h) = map.dim();
+    let tile = |x: usize, dx: isize, y: usize, dy: isize| {
+        let (x, y) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        original.get((x, y)).copied()
+    };
+    // Doorways aren't counted as open, so windows don't end up beside doors.
+    let open = |t: Option<Tile>| t.is_some_and(|t| !t.is_solid() && t != Tile::Door);
+    let solid = |t: Option<Tile>| t.is_none_or(Tile::is_solid);
+
+    for x in 0..w {
+        for y in 0..h {
+            if original[(x, y)] != Tile::Wall {
+                continue;
+            }
+            let across_x = open(tile(x, -1, y, 0))
+                && open(tile(x, 1, y, 0))
+                && solid(tile(x, 0, y, -1))
+                && solid(tile(x, 0, y, 1));
+            let across_y = open(tile(x, 0, y, -1))
+                && open(tile(x, 0, y, 1))
+                && solid(tile(x, -1, y, 0))
+                && solid(tile(x, 1, y, 0));
+            if (across_x || across_y) && rng.gen::<f32>() < p_window {
+                map[(x, y)] = Tile::Window;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::{connectivity::unreachable_cells, hallways::OffsetDistribution};
+
+    use super::*;
+
+    fn params() -> PoolParams {
+        PoolParams {
+            rooms: RbspParams {
+                min_room_len: 8,
+                max_room_len: 30,
+                hallway_width: 2,
+                offset_distribution: OffsetDistribution::Triangular,
+                max_aspect_ratio: 3.0,
+                ..Default::default()
+            },
+            p_pool: 0.7,
+            margin: 1,
+            shallow_width: 1,
+            p_window: 0.3,
+        }
+    }
+
+    #[test]
+    fn pools_have_a_ramp_and_a_deep_middle() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let level = pool_rooms(&mut rng, (128, 96), &params());
+
+        assert!(!level.pools.is_empty());
+        for pool in &level.pools {
+            let tiles = level
+                .tiles
+                .slice(s![
+                    pool.x as usize..pool.x_end() as usize,
+                    pool.y as usize..pool.y_end() as usize
+                ])
+                .to_owned();
+            assert!(tiles
+                .iter()
+                .all(|t| t.water().is_some() || *t == Tile::Ramp));
+            assert!(tiles.iter().any(|t| *t == Tile::Ramp));
+            assert_eq!(
+                tiles[(pool.w / 2, pool.h / 2)],
+                Tile::Water(WaterDepth::Deep)
+            );
+
+            // The pool sits in dry floor.
+            let (x, y) = ((pool.x - 1) as usize, (pool.y - 1) as usize);
+            assert_eq!(level.tiles[(x, y)], Tile::Floor);
+        }
+    }
+
+    #[test]
+    fn windows_look_between_open_tiles() {
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        let level = pool_rooms(&mut rng, (128, 96), &params());
+
+        let windows = level
+            .tiles
+            .indexed_iter()
+            .filter(|(_, t)| **t == Tile::Window)
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>();
+        assert!(!windows.is_empty());
+        let open = |x: usize, dx: isize, y: usize, dy: isize| {
+            let pos = (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy));
+            level.tiles.get(pos).is_some_and(|t| !t.is_solid())
+        };
+        for (x, y) in windows {
+            assert!(
+                (open(x, -1, y, 0) && open(x, 1, y, 0)) || (open(x, 0, y, -1) && open(x, 0, y, 1))
+            );
+        }
+    }
+
+    #[test]
+    fn pools_keep_levels_connected() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let level = pool_rooms(&mut rng, (128, 96), &params());
+        let without_pools = level.tiles.mapv(|t| match t {
+            Tile::Water(_) | Tile::Ramp => Tile::Floor,
+            Tile::Window => Tile::Wall,
+            t => t,
+        });
+
+        let start = level
+            .tiles
+            .indexed_iter()
+            .find(|(_, t)| **t == Tile::Floor)
+            .unwrap()
+            .0;
+
+        assert_eq!(
+            unreachable_cells(&level.tiles, start).len(),
+            unreachable_cells(&without_pools, start).len()
+        );
+    }
+}
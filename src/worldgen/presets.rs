@@ -0,0 +1,216 @@
+use rand::Rng;
+
+use crate::util::Rectangle;
+
+use super::{
+    ceilings::CeilingParams,
+    deadends::{DeadEndAction, DeadEndParams},
+    furnish::FurnishParams,
+    hallways::{OffsetDistribution, PerimeterRing, RbspParams},
+    locks::LockParams,
+    loops::LoopParams,
+    maze::MazeParams,
+    pillars::PillarParams,
+    pipeline::{
+        AssignBiomes, ConnectRegions, GeneratedWorld, Pipeline, PlaceDoors, WorldGenerator,
+    },
+    pools::PoolParams,
+    secrets::SecretParams,
+};
+
+/// A named generator with passes and parameters tuned to look like one of the
+/// well-known levels, as a starting point that needs no tuning.
+///
+/// Every preset is a [`WorldGenerator`], and can be used anywhere one is
+/// expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Preset {
+    /// Level 0: an endless open room of pillars and stray wall segments.
+    Level0,
+
+    /// Level 1: big, tall warehouse halls full of shelving, joined by wide
+    /// corridors.
+    Level1Warehouse,
+
+    /// Level 2: narrow, winding maintenance corridors with the occasional
+    /// cramped utility room and hidden shortcut.
+    Level2Maintenance,
+
+    /// Partitioned office floors of rooms, hallways and cubicles, with some
+    /// doors locked.
+    Office,
+
+    /// Tiled rooms and corridors with sunken pools, glimpsed through windows.
+    PoolRooms,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::Level0,
+        Preset::Level1Warehouse,
+        Preset::Level2Maintenance,
+        Preset::Office,
+        Preset::PoolRooms,
+    ];
+
+    /// A short, stable name for the preset, for command lines and config files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Level0 => "level0",
+            Preset::Level1Warehouse => "level1-warehouse",
+            Preset::Level2Maintenance => "level2-maintenance",
+            Preset::Office => "office",
+            Preset::PoolRooms => "pool-rooms",
+        }
+    }
+
+    /// Look up a preset by its [`name`](Preset::name).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+impl WorldGenerator for Preset {
+    fn generate(&self, rng: &mut impl Rng, bounds: Rectangle<isize, usize>) -> GeneratedWorld {
+        match self {
+            Preset::Level0 => Pipeline::new(PillarParams {
+                spacing: 8,
+                pillar_size: 2,
+                jitter: 1,
+                wall_density: 0.25,
+            })
+            .then(ConnectRegions)
+            .generate(rng, bounds),
+
+            Preset::Level1Warehouse => Pipeline::new(RbspParams {
+                min_room_len: 16,
+                max_room_len: 64,
+                p_keep_rooms: 0.5,
+                hallway_width: 4,
+                offset_distribution: OffsetDistribution::Triangular,
+                max_aspect_ratio: 3.0,
+                perimeter_ring: PerimeterRing::Outer,
+                ..Default::default()
+            })
+            .then(PlaceDoors)
+            .then(ConnectRegions)
+            .then(FurnishParams {
+                min_area: 300,
+                p_cubicles: 0.0,
+                cubicle_size: 5,
+                prop_density: 8.0,
+                margin: 2,
+            })
+            .then(CeilingParams {
+                base_height: 2.0,
+                hall_min_area: 1500,
+                hall_height: 3.0,
+                jitter: 0.0,
+            })
+            .generate(rng, bounds),
+
+            Preset::Level2Maintenance => Pipeline::new(MazeParams {
+                cell_size: 1,
+                wall_thickness: 1,
+                loop_fraction: 0.05,
+            })
+            .then(DeadEndParams {
+                max_length: 8,
+                action: DeadEndAction::Cap { room_size: 3 },
+            })
+            .then(SecretParams {
+                p_secret: 0.02,
+                max_wall_thickness: 1,
+            })
+            .generate(rng, bounds),
+
+            Preset::Office => Pipeline::new(RbspParams {
+                max_room_len: 40,
+                hallway_width: 2,
+                offset_distribution: OffsetDistribution::Triangular,
+                max_aspect_ratio: 4.0,
+                ..Default::default()
+            })
+            .then(PlaceDoors)
+            .then(ConnectRegions)
+            .then(LoopParams {
+                loop_factor: 0.3,
+                max_wall_thickness: 1,
+            })
+            .then(FurnishParams {
+                min_area: 400,
+                p_cubicles: 0.5,
+                cubicle_size: 5,
+                prop_density: 5.0,
+                margin: 2,
+            })
+            .then(AssignBiomes { depth: 2 })
+            .then(CeilingParams {
+                base_height: 1.0,
+                hall_min_area: 1200,
+                hall_height: 1.5,
+                jitter: 0.1,
+            })
+            .then(LockParams { n_locks: 2 })
+            .generate(rng, bounds),
+
+            Preset::PoolRooms => Pipeline::new(PoolParams {
+                rooms: RbspParams {
+                    min_room_len: 8,
+                    max_room_len: 40,
+                    p_keep_rooms: 0.4,
+                    k_deoblongification: 3.0,
+                    hallway_width: 3,
+                    max_aspect_ratio: 3.0,
+                    ..Default::default()
+                },
+                p_pool: 0.6,
+                margin: 2,
+                shallow_width: 2,
+                p_window: 0.15,
+            })
+            .then(ConnectRegions)
+            .then(LoopParams {
+                loop_factor: 0.6,
+                max_wall_thickness: 2,
+            })
+            .generate(rng, bounds),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn presets_fill_bounds_with_open_space() {
+        for preset in Preset::ALL {
+            let mut rng = SmallRng::seed_from_u64(0);
+            let bounds = Rectangle {
+                x: 0,
+                y: 0,
+                w: 96,
+                h: 80,
+            };
+
+            let world = preset.generate(&mut rng, bounds.clone());
+
+            assert_eq!(world.bounds, bounds);
+            assert_eq!(world.tiles.dim(), (96, 80));
+            let open = world.tiles.iter().filter(|t| !t.is_solid()).count();
+            assert!(open > 96 * 80 / 10, "{} is too closed in", preset.name());
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+        }
+        assert_eq!(Preset::from_name("level-fun"), None);
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt;
+
+use cgmath::Vector2;
+use ndarray::Array2;
+
+use crate::{
+    tile::Tile,
+    util::{Corner, Line, Rectangle, Region},
+};
+
+use super::doors::Door;
+
+/// Rasterize rooms, hallways and doors into a map indexed by `(x, y)`.
+///
+/// Everything that isn't a room interior, hallway or door is left as wall.
+pub fn rasterize(
+    dim: (usize, usize),
+    rooms: &[Region],
+    lines: &[Line],
+    doors: &[Door],
+) -> Array2<Tile> {
+    rasterize_walled(dim, rooms, lines, doors, 1)
+}
+
+/// Like [rasterize], but with the walls of every room `wall_thickness` tiles
+/// thick, so that rooms are inset from their outlines and from the hallways
+/// around them. Doors are carved through to the floor of their room.
+pub fn rasterize_walled(
+    dim: (usize, usize),
+    rooms: &[Region],
+    lines: &[Line],
+    doors: &[Door],
+    wall_thickness: usize,
+) -> Array2<Tile> {
+    let mut a = Array2::from_elem(dim, Tile::Wall);
+    let hallways = hallway_mask(dim, lines);
+    for r in rooms {
+        draw_room_walled(&mut a, r, &hallways, wall_thickness);
+    }
+    for h in lines {
+        draw_hallway(&mut a, h)
+    }
+    for d in doors {
+        if let Some(c) = a.get_mut((d.x as usize, d.y as usize)) {
+            *c = Tile::Door
+        }
+        if let Some(room) = rooms.iter().find(|r| r.contains((d.x, d.y))) {
+            carve_doorway(&mut a, d, room, wall_thickness);
+        }
+    }
+    a
+}
+
+/// Geometry that doesn't fit on the map it's being rasterized onto. Rooms,
+/// hallways and doors are referred to by their index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RasterError {
+    RoomOutOfBounds { room: usize },
+    HallwayOutOfBounds { line: usize },
+    DoorOutOfBounds { door: usize },
+}
+
+impl fmt::Display for RasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RasterError::RoomOutOfBounds { room } => write!(f, "room {room} is off the map"),
+            RasterError::HallwayOutOfBounds { line } => {
+                write!(f, "hallway {line} is off the map")
+            }
+            RasterError::DoorOutOfBounds { door } => write!(f, "door {door} is off the map"),
+        }
+    }
+}
+
+impl std::error::Error for RasterError {}
+
+/// Check that every room, hallway (including its width) and door lies entirely
+/// on a map of size `dim`, which [rasterize] would otherwise silently clip.
+pub fn check_bounds(
+    (w, h): (usize, usize),
+    rooms: &[Region],
+    lines: &[Line],
+    doors: &[Door],
+) -> Result<(), RasterError> {
+    let on_map = |r: &Rectangle<isize, usize>| {
+        r.x >= 0 && r.y >= 0 && r.x_end() <= w as isize && r.y_end() <= h as isize
+    };
+    if let Some(room) = rooms.iter().position(|r| !r.rects.iter().all(on_map)) {
+        return Err(RasterError::RoomOutOfBounds { room });
+    }
+    if let Some(line) = lines.iter().position(|l| !on_map(&l.bounds())) {
+        return Err(RasterError::HallwayOutOfBounds { line });
+    }
+    let door_on_map = |d: &Door| d.x >= 0 && d.y >= 0 && d.x < w as isize && d.y < h as isize;
+    if let Some(door) = doors.iter().position(|d| !door_on_map(d)) {
+        return Err(RasterError::DoorOutOfBounds { door });
+    }
+    Ok(())
+}
+
+pub fn draw_hallway(a: &mut Array2<Tile>, l: &Line) {
+    for pos in l.points() {
+        if let Some(c) = a.get_mut((pos.0 as usize, pos.1 as usize)) {
+            *c = Tile::Floor
+        }
+    }
+}
+
+/// Mark every tile covered by a hallway.
+pub fn hallway_mask(dim: (usize, usize), lines: &[Line]) -> Array2<bool> {
+    let mut mask = Array2::from_elem(dim, false);
+    for pos in lines.iter().flat_map(Line::points) {
+        if let Some(c) = mask.get_mut((pos.0 as usize, pos.1 as usize)) {
+            *c = true
+        }
+    }
+    mask
+}
+
+/// Carve out the inside of a room, leaving a wall around its edges.
+///
+/// A tile is carved only if it and all eight of its neighbors belong to the
+/// room and are not covered by a hallway, which leaves a wall both along the
+/// room's outline and behind the hallway that cut it from its parent.
+pub fn draw_room(a: &mut Array2<Tile>, r: &Region, hallways: &Array2<bool>) {
+    draw_room_walled(a, r, hallways, 1)
+}
+
+/// Like [draw_room], but leaving a wall `thickness` tiles thick.
+pub fn draw_room_walled(
+    a: &mut Array2<Tile>,
+    r: &Region,
+    hallways: &Array2<bool>,
+    thickness: usize,
+) {
+    let inside = |(x, y): (isize, isize)| {
+        r.contains((x, y)) && hallways.get((x as usize, y as usize)) == Some(&false)
+    };
+    let t = thickness as isize;
+    for (x, y) in r.cells() {
+        let interior = (-t..=t).all(|dx| (-t..=t).all(|dy| inside((x + dx, y + dy))));
+        if interior {
+            if let Some(c) = a.get_mut((x as usize, y as usize)) {
+                *c = Tile::Floor
+            }
+        }
+    }
+}
+
+/// Carve a passage from a door through a wall `thickness` tiles thick to the
+/// floor of its room: straight in, then along the wall towards the middle of
+/// the room if the door is too close to a corner to reach the floor directly.
+fn carve_doorway(a: &mut Array2<Tile>, d: &Door, room: &Region, thickness: usize) {
+    let inward = -Vector2::<isize>::from(d.facing);
+    let floor = |a: &Array2<Tile>, p: Vector2<isize>| {
+        a.get((p.x as usize, p.y as usize)) == Some(&Tile::Floor)
+    };
+    let carve = |a: &mut Array2<Tile>, p: Vector2<isize>| {
+        if let Some(c) = a.get_mut((p.x as usize, p.y as usize)) {
+            if *c == Tile::Wall {
+                *c = Tile::Floor
+            }
+        }
+    };
+
+    let mut pos = Vector2::new(d.x, d.y);
+    for _ in 1..thickness {
+        if floor(a, pos + inward) || !room.contains((pos + inward).into()) {
+            return;
+        }
+        pos += inward;
+        carve(a, pos);
+    }
+
+    let b = room.bounds();
+    let middle = Vector2::new(b.x + b.w as isize / 2, b.y + b.h as isize / 2);
+    let across = Vector2::new(inward.y.abs(), inward.x.abs());
+    let to_middle = middle - pos;
+    let along = if to_middle.x * across.x + to_middle.y * across.y < 0 {
+        -across
+    } else {
+        across
+    };
+    while !floor(a, pos + inward) && room.contains((pos + along).into()) {
+        pos += along;
+        carve(a, pos);
+    }
+}
+
+/// Bevel the corners of open areas with diagonal walls.
+///
+/// A floor tile with walls on two adjacent sides becomes a diagonal wall
+/// filling that corner, as long as the open tiles on its other sides stay
+/// connected around it, so no part of the map is cut off.
+pub fn chamfer_corners(a: &mut Array2<Tile>) {
+    // Walls are looked up in the original map, so bevels don't cascade along
+    // the edges of a room.
+    let original = a.clone();
+    let (w, h) = a.dim();
+    for x in 0..w {
+        for y in 0..h {
+            if a[(x, y)] != Tile::Floor {
+                continue;
+            }
+            let solid = |a: &Array2<Tile>, offset: Vector2<isize>| {
+                let pos = (
+                    x.checked_add_signed(offset.x),
+                    y.checked_add_signed(offset.y),
+                );
+                match pos {
+                    (Some(nx), Some(ny)) => a.get((nx, ny)).is_none_or(|t| t.is_solid()),
+                    _ => true,
+                }
+            };
+            let corner = Corner::ALL.into_iter().find(|c| {
+                let (ew, ns) = c.sides();
+                let (ew, ns) = (Vector2::from(ew), Vector2::from(ns));
+                solid(&original, ew)
+                    && solid(&original, ns)
+                    && !solid(a, -ew)
+                    && !solid(a, -ns)
+                    && !solid(a, -ew - ns)
+            });
+            if let Some(c) = corner {
+                a[(x, y)] = Tile::Diagonal(c);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{Axis, Direction, Rectangle};
+
+    use super::*;
+
+    #[test]
+    fn rooms_are_walled_off_from_hallways() {
+        let room = Region::from(Rectangle {
+            x: 0,
+            y: 0,
+            w: 8,
+            h: 6,
+        });
+        let line = Line {
+            x: 0,
+            y: 0,
+            length: 7,
+            width: 1,
+            axis: Axis::Horizontal,
+        };
+
+        let a = rasterize((8, 6), &[room], &[line], &[]);
+
+        for ((x, y), t) in a.indexed_iter() {
+            let expected = if y == 0 || (x > 0 && x < 7 && y > 1 && y < 5) {
+                Tile::Floor
+            } else {
+                Tile::Wall
+            };
+            assert_eq!(*t, expected, "at {x}, {y}");
+        }
+    }
+
+    #[test]
+    fn chamfers_room_corners() {
+        let room = Region::from(Rectangle {
+            x: 0,
+            y: 0,
+            w: 6,
+            h: 5,
+        });
+        let corridor = Line {
+            x: 0,
+            y: 6,
+            length: 5,
+            width: 1,
+            axis: Axis::Horizontal,
+        };
+        let mut a = rasterize((6, 7), &[room], &[corridor], &[]);
+
+        chamfer_corners(&mut a);
+
+        assert_eq!(a[(1, 1)], Tile::Diagonal(Corner::SouthWest));
+        assert_eq!(a[(4, 1)], Tile::Diagonal(Corner::SouthEast));
+        assert_eq!(a[(1, 3)], Tile::Diagonal(Corner::NorthWest));
+        assert_eq!(a[(4, 3)], Tile::Diagonal(Corner::NorthEast));
+        assert_eq!(a[(2, 2)], Tile::Floor);
+        assert!(a
+            .slice(ndarray::s![.., 6])
+            .iter()
+            .all(|t| *t == Tile::Floor));
+    }
+
+    #[test]
+    fn thick_walls_keep_rooms_apart() {
+        let rooms = [
+            Region::from(Rectangle {
+                x: 0,
+                y: 0,
+                w: 8,
+                h: 10,
+            }),
+            Region::from(Rectangle {
+                x: 8,
+                y: 0,
+                w: 8,
+                h: 10,
+            }),
+        ];
+        // A door right next to the corner, where the wall is thickest.
+        let door = Door::new(8, 1, Direction::West);
+
+        let a = rasterize_walled((16, 10), &rooms, &[], &[door], 2);
+
+        assert_eq!(a[(2, 5)], Tile::Floor);
+        assert_eq!(a[(5, 5)], Tile::Floor);
+        assert!((6..10).all(|x| a[(x, 5)] == Tile::Wall));
+        assert_eq!(a[(8, 1)], Tile::Door);
+        assert_eq!(a[(9, 1)], Tile::Floor);
+        assert_eq!(a[(9, 2)], Tile::Floor);
+        assert_eq!(a[(10, 2)], Tile::Floor);
+    }
+}
@@ -0,0 +1,168 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use ndarray::Array2;
+
+use crate::{
+    tile::Tile,
+    util::{Line, Region},
+};
+
+use super::graph::Node;
+
+pub fn render_to_img(a: &Array2<Tile>) -> RgbImage {
+    let (w, h) = a.dim();
+    let mut img = ImageBuffer::new(w as u32, h as u32);
+    for ((x, y), v) in a.indexed_iter() {
+        img.put_pixel(x as u32, y as u32, v.color());
+    }
+
+    img
+}
+
+/// 3×5 pixel glyphs for the digits 0-9, one row per `u8`, most significant of
+/// the low three bits on the left.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+const HALLWAY_COLOR: Rgb<u8> = Rgb([230, 60, 40]);
+const LABEL_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Render a map with every room's open tiles in its own color and hallways
+/// highlighted, to make the room structure easy to see. Walls and furniture
+/// keep their usual colors.
+///
+/// If `labels` is set, each room's index is drawn in the middle of its bounds.
+pub fn render_debug_img(
+    a: &Array2<Tile>,
+    rooms: &[Region],
+    lines: &[Line],
+    labels: bool,
+) -> RgbImage {
+    let mut img = render_to_img(a);
+    let mut paint = |(x, y): (isize, isize), color: Rgb<u8>| {
+        let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) else {
+            return;
+        };
+        if x < img.width() && y < img.height() && !a[(x as usize, y as usize)].is_solid() {
+            img.put_pixel(x, y, color);
+        }
+    };
+
+    for (i, r) in rooms.iter().enumerate() {
+        let color = room_color(i);
+        for pos in r.cells() {
+            paint(pos, color);
+        }
+    }
+    for pos in lines.iter().flat_map(Line::points) {
+        paint(pos, HALLWAY_COLOR);
+    }
+
+    if labels {
+        for (i, r) in rooms.iter().enumerate() {
+            let b = r.bounds();
+            draw_number(&mut img, i, b.x + b.w as isize / 2, b.y + b.h as isize / 2);
+        }
+    }
+
+    img
+}
+
+/// The color [`render_debug_img`] paints the open tiles of a room or hallway.
+pub fn node_color(node: Node) -> Rgb<u8> {
+    match node {
+        Node::Room(i) => room_color(i),
+        Node::Hallway(_) => HALLWAY_COLOR,
+    }
+}
+
+/// A distinct, fairly light color for each room, stepping the hue by the golden
+/// ratio so neighboring indices look different.
+fn room_color(i: usize) -> Rgb<u8> {
+    let hue = (i as f32 * 0.618_034).fract() * 6.0;
+    let (s, v) = (0.45, 0.95);
+    let f = hue.fract();
+    let (p, q, t) = (v * (1.0 - s), v * (1.0 - s * f), v * (1.0 - s * (1.0 - f)));
+    let (r, g, b) = match hue as u32 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    Rgb([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8])
+}
+
+/// Draw a number centered on the given pixel, clipped to the image.
+fn draw_number(img: &mut RgbImage, n: usize, cx: isize, cy: isize) {
+    let digits = n.to_string();
+    let width = digits.len() as isize * 4 - 1;
+    for (i, d) in digits.bytes().enumerate() {
+        let glyph = DIGITS[(d - b'0') as usize];
+        let x0 = cx - width / 2 + i as isize * 4;
+        for (dy, row) in glyph.iter().enumerate() {
+            for dx in 0..3 {
+                if row & (0b100 >> dx) == 0 {
+                    continue;
+                }
+                let (x, y) = (x0 + dx, cy - 2 + dy as isize);
+                if x >= 0 && y >= 0 && (x as u32) < img.width() && (y as u32) < img.height() {
+                    img.put_pixel(x as u32, y as u32, LABEL_COLOR);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::{Axis, Rectangle};
+
+    use super::*;
+
+    #[test]
+    fn debug_img_colors_rooms_apart() {
+        let rooms = [
+            Region::from(Rectangle {
+                x: 0,
+                y: 0,
+                w: 10,
+                h: 5,
+            }),
+            Region::from(Rectangle {
+                x: 0,
+                y: 6,
+                w: 10,
+                h: 5,
+            }),
+        ];
+        let lines = [Line {
+            x: 0,
+            y: 5,
+            length: 9,
+            width: 1,
+            axis: Axis::Horizontal,
+        }];
+        let map = Array2::from_elem((10, 11), Tile::Floor);
+
+        let img = render_debug_img(&map, &rooms, &lines, false);
+
+        assert_eq!(*img.get_pixel(3, 2), room_color(0));
+        assert_eq!(*img.get_pixel(3, 8), room_color(1));
+        assert_eq!(*img.get_pixel(3, 5), HALLWAY_COLOR);
+        assert_ne!(room_color(0), room_color(1));
+
+        let labeled = render_debug_img(&map, &rooms, &lines, true);
+        assert_ne!(labeled, img);
+    }
+}
@@ -0,0 +1,126 @@
+use ndarray::Array2;
+use rand::Rng;
+
+use crate::util::Region;
+
+/// Scatter points over a `(w, h)` grid, keeping only those where `allowed`
+/// holds and no two closer than `min_distance` to each other.
+///
+/// This is dart throwing, with a bucket grid to look up nearby points. The
+/// number of darts is proportional to the most points that could fit in the
+/// grid, so the result is close to maximal without being exact.
+pub fn poisson_disk(
+    rng: &mut impl Rng,
+    (w, h): (usize, usize),
+    min_distance: f32,
+    allowed: impl Fn((usize, usize)) -> bool,
+) -> Vec<(usize, usize)> {
+    if w == 0 || h == 0 {
+        return vec![];
+    }
+    let cell = min_distance.max(1.0).ceil() as usize;
+    let mut buckets: Array2<Vec<(usize, usize)>> =
+        Array2::from_elem((w.div_ceil(cell), h.div_ceil(cell)), vec![]);
+    let mut points = vec![];
+
+    let darts = 30 * (w * h) / (cell * cell);
+    for _ in 0..darts {
+        let p = (rng.gen_range(0..w), rng.gen_range(0..h));
+        if !allowed(p) {
+            continue;
+        }
+        let (bx, by) = (p.0 / cell, p.1 / cell);
+        let too_close = (bx.saturating_sub(1)..=bx + 1)
+            .flat_map(|i| (by.saturating_sub(1)..=by + 1).map(move |j| (i, j)))
+            .filter_map(|b| buckets.get(b))
+            .flatten()
+            .any(|q| distance(p, *q) < min_distance);
+        if !too_close {
+            buckets[(bx, by)].push(p);
+            points.push(p);
+        }
+    }
+
+    points
+}
+
+/// Scatter points over the cells where `mask` is `true`.
+pub fn poisson_disk_in_mask(
+    rng: &mut impl Rng,
+    mask: &Array2<bool>,
+    min_distance: f32,
+) -> Vec<(usize, usize)> {
+    poisson_disk(rng, mask.dim(), min_distance, |p| mask[p])
+}
+
+/// Scatter points over the cells of a region, in world coordinates.
+pub fn poisson_disk_in_region(
+    rng: &mut impl Rng,
+    region: &Region,
+    min_distance: f32,
+) -> Vec<(isize, isize)> {
+    let b = region.bounds();
+    poisson_disk(rng, (b.w, b.h), min_distance, |(x, y)| {
+        region.contains((b.x + x as isize, b.y + y as isize))
+    })
+    .into_iter()
+    .map(|(x, y)| (b.x + x as isize, b.y + y as isize))
+    .collect()
+}
+
+pub(crate) fn distance(a: (usize, usize), b: (usize, usize)) -> f32 {
+    let dx = a.0 as f32 - b.0 as f32;
+    let dy = a.1 as f32 - b.1 as f32;
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::util::Rectangle;
+
+    use super::*;
+
+    #[test]
+    fn mask_points_keep_their_distance() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mask = Array2::from_shape_fn((40, 40), |(x, y)| (x + y) % 7 != 0);
+
+        let points = poisson_disk_in_mask(&mut rng, &mask, 4.0);
+
+        assert!(points.len() > 20);
+        for (i, a) in points.iter().enumerate() {
+            assert!(mask[*a]);
+            for b in &points[i + 1..] {
+                assert!(distance(*a, *b) >= 4.0);
+            }
+        }
+    }
+
+    #[test]
+    fn region_points_stay_inside() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let region = Region {
+            rects: vec![
+                Rectangle {
+                    x: -10,
+                    y: 5,
+                    w: 20,
+                    h: 4,
+                },
+                Rectangle {
+                    x: -10,
+                    y: 9,
+                    w: 4,
+                    h: 20,
+                },
+            ],
+        };
+
+        let points = poisson_disk_in_region(&mut rng, &region, 3.0);
+
+        assert!(points.len() > 5);
+        assert!(points.iter().all(|p| region.contains(*p)));
+    }
+}
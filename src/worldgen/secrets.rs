@@ -0,0 +1,82 @@
+use ndarray::Array2;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::tile::Tile;
+
+use super::loops::{thin_walls, Cells};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecretParams {
+    /// A probability in [0, 1] that each pair of neighboring regions not
+    /// already joined by a door gets a secret passage between them.
+    pub p_secret: f32,
+
+    /// Walls thicker than this never hide a passage.
+    pub max_wall_thickness: usize,
+}
+
+/// Turn some thin walls between neighboring rooms into secret walls, which
+/// look like walls but can be walked through, on a map indexed by `(x, y)`.
+///
+/// Candidates are found the same way as for [`insert_loops`], so a secret
+/// passage never joins two regions that already share a door. Returns the cells
+/// turned into secret walls for each passage.
+///
+/// [`insert_loops`]: super::loops::insert_loops
+pub fn place_secrets(
+    rng: &mut impl Rng,
+    map: &mut Array2<Tile>,
+    params: &SecretParams,
+) -> Vec<Cells> {
+    let mut hidden = vec![];
+    for (_, options) in thin_walls(map, params.max_wall_thickness) {
+        if rng.gen::<f32>() >= params.p_secret {
+            continue;
+        }
+        let cells = options.choose(rng).unwrap();
+        for c in cells {
+            map[*c] = Tile::SecretWall;
+        }
+        hidden.push(cells.clone());
+    }
+    hidden
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::connectivity::label_regions;
+
+    use super::*;
+
+    #[test]
+    fn secret_walls_join_regions_only_for_the_player() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut map = array![
+            [1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 1],
+            [1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 1],
+            [1, 1, 1, 1, 1],
+        ]
+        .map(|x| Tile::from(*x == 1));
+
+        let hidden = place_secrets(
+            &mut rng,
+            &mut map,
+            &SecretParams {
+                p_secret: 1.0,
+                max_wall_thickness: 1,
+            },
+        );
+
+        assert_eq!(hidden.len(), 1);
+        let cell = hidden[0][0];
+        assert_eq!((cell.0, map[cell]), (2, Tile::SecretWall));
+        assert!(map[cell].is_solid() && map[cell].is_passable());
+        assert_eq!(label_regions(&map).1.len(), 2);
+    }
+}
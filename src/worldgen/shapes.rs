@@ -0,0 +1,292 @@
+use rand::Rng;
+
+use crate::util::{Axis, Line, Rectangle, Region};
+
+use super::hallways::BspNode;
+
+/// Parameters for joining pairs of sibling rooms into L and T shapes.
+#[derive(Debug, Clone)]
+pub struct ShapeParams {
+    /// Probability in [0, 1] that a pair of sibling rooms becomes an L, with
+    /// one room as the bar and one end of the other as the arm.
+    pub p_l: f32,
+
+    /// Probability in [0, 1] that a pair of sibling rooms becomes a T, with one
+    /// room as the bar and the middle of the other as the stem. Checked after
+    /// `p_l`.
+    pub p_t: f32,
+
+    /// Minimum width of any arm of a shaped room. Pairs too small to keep
+    /// their arms at least this wide stay as two rectangular rooms.
+    pub min_arm: usize,
+}
+
+/// Flatten a BSP tree into its rooms and hallways as
+/// [`BspNode::into_flat`] does, but joining some pairs of sibling rooms into L
+/// or T shaped regions.
+///
+/// A joined pair loses the hallway between them, which becomes part of the
+/// bar. The part of the other room cut away beside the arm is left out of
+/// every region, so it stays solid when the map is rasterized.
+pub fn shape_rooms(
+    rng: &mut impl Rng,
+    tree: &BspNode,
+    params: &ShapeParams,
+) -> (Vec<Region>, Vec<Line>) {
+    let (mut rooms, mut lines) = (vec![], vec![]);
+    shape_subtree(rng, tree, params, &mut rooms, &mut lines);
+    (rooms, lines)
+}
+
+fn shape_subtree(
+    rng: &mut impl Rng,
+    node: &BspNode,
+    params: &ShapeParams,
+    rooms: &mut Vec<Region>,
+    lines: &mut Vec<Line>,
+) {
+    match node {
+        BspNode::Room(r) => rooms.push(r.clone().into()),
+        BspNode::Ring {
+            lines: ring, child, ..
+        } => {
+            lines.extend(ring.iter().cloned());
+            shape_subtree(rng, child, params, rooms, lines);
+        }
+        BspNode::Partition { line, children, .. } => {
+            if let [BspNode::Room(a), BspNode::Room(b)] = &**children {
+                let roll = rng.gen::<f32>();
+                let shape = if roll < params.p_l {
+                    join(rng, [a, b], line, false, params.min_arm)
+                } else if roll < params.p_l + params.p_t {
+                    join(rng, [a, b], line, true, params.min_arm)
+                } else {
+                    None
+                };
+                if let Some(shape) = shape {
+                    rooms.push(shape);
+                    return;
+                }
+            }
+            lines.push(line.clone());
+            for child in children.iter() {
+                shape_subtree(rng, child, params, rooms, lines);
+            }
+        }
+    }
+}
+
+/// Join two rooms on either side of a hallway into an L, or a T if `t`. One
+/// room and the hallway make the bar, and part of the other room the arm:
+/// one end of it for an L, or the middle for a T.
+fn join(
+    rng: &mut impl Rng,
+    rooms: [&Rectangle<isize, usize>; 2],
+    line: &Line,
+    t: bool,
+    min_arm: usize,
+) -> Option<Region> {
+    // Built with the hallway running along x, then transposed back.
+    let transposed = line.axis == Axis::Vertical;
+    let orient = |r: Rectangle<isize, usize>| if transposed { transpose(r) } else { r };
+    let hall = orient(line.bounds());
+    let [bar, arm] = match rng.gen::<bool>() {
+        true => rooms.map(|r| orient(r.clone())),
+        false => [rooms[1], rooms[0]].map(|r| orient(r.clone())),
+    };
+
+    let bar = Rectangle {
+        x: bar.x.min(hall.x),
+        y: bar.y.min(hall.y),
+        w: (bar.x_end().max(hall.x_end()) - bar.x.min(hall.x)) as usize,
+        h: (bar.y_end().max(hall.y_end()) - bar.y.min(hall.y)) as usize,
+    };
+    let arm = if t {
+        let cw = cut(rng, arm.w, 4, 3, min_arm)?;
+        if arm.w - 2 * cw < min_arm {
+            return None;
+        }
+        Rectangle {
+            x: arm.x + cw as isize,
+            w: arm.w - 2 * cw,
+            ..arm
+        }
+    } else {
+        let w = arm.w - cut(rng, arm.w, 3, 2, min_arm)?;
+        let x = match rng.gen::<bool>() {
+            true => arm.x,
+            false => arm.x_end() - w as isize,
+        };
+        Rectangle { x, w, ..arm }
+    };
+
+    Some(Region {
+        rects: vec![orient(bar), orient(arm)],
+    })
+}
+
+/// Pick how much to cut off a side of length `len`, between `len / max_div`
+/// and `len / min_div`, keeping both the cut and the rest at least `min_arm`.
+fn cut(
+    rng: &mut impl Rng,
+    len: usize,
+    max_div: usize,
+    min_div: usize,
+    min_arm: usize,
+) -> Option<usize> {
+    let (lo, hi) = ((len / max_div).max(min_arm), len / min_div);
+    (lo <= hi && len - hi >= min_arm).then(|| rng.gen_range(lo..=hi))
+}
+
+fn transpose(r: Rectangle<isize, usize>) -> Rectangle<isize, usize> {
+    Rectangle {
+        x: r.y,
+        y: r.x,
+        w: r.h,
+        h: r.w,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    /// Two rooms split by a hallway, along y if `axis` is horizontal and
+    /// along x if it's vertical.
+    fn pair(axis: Axis) -> BspNode {
+        let rect = |x, y, w, h| Rectangle { x, y, w, h };
+        let node = BspNode::Partition {
+            bounds: rect(3, -4, 30, 21),
+            line: Line {
+                x: 3,
+                y: 6,
+                length: 29,
+                width: 1,
+                axis: Axis::Horizontal,
+            },
+            children: Box::new([
+                BspNode::Room(rect(3, -4, 30, 10)),
+                BspNode::Room(rect(3, 7, 30, 10)),
+            ]),
+        };
+        match axis {
+            Axis::Horizontal => node,
+            Axis::Vertical => {
+                let BspNode::Partition {
+                    bounds,
+                    line,
+                    children,
+                } = node
+                else {
+                    unreachable!()
+                };
+                BspNode::Partition {
+                    bounds: transpose(bounds),
+                    line: Line {
+                        x: line.y,
+                        y: line.x,
+                        axis: Axis::Vertical,
+                        ..line
+                    },
+                    children: Box::new(
+                        children.map(|c| BspNode::Room(transpose(c.bounds().clone()))),
+                    ),
+                }
+            }
+        }
+    }
+
+    fn params(p_l: f32, p_t: f32) -> ShapeParams {
+        ShapeParams {
+            p_l,
+            p_t,
+            min_arm: 3,
+        }
+    }
+
+    /// Which corners of `r` are missing from `region`.
+    fn missing_corners(region: &Region, r: &Rectangle<isize, usize>) -> usize {
+        let corners = [
+            (r.x, r.y),
+            (r.x_end() - 1, r.y),
+            (r.x, r.y_end() - 1),
+            (r.x_end() - 1, r.y_end() - 1),
+        ];
+        corners.iter().filter(|c| !region.contains(**c)).count()
+    }
+
+    fn assert_disjoint_within(region: &Region, r: &Rectangle<isize, usize>) {
+        for (i, a) in region.rects.iter().enumerate() {
+            assert!(a.x >= r.x && a.x_end() <= r.x_end());
+            assert!(a.y >= r.y && a.y_end() <= r.y_end());
+            for b in &region.rects[i + 1..] {
+                assert!(!a.intersects(b), "{a:?} overlaps {b:?}");
+            }
+        }
+        assert_eq!(&region.bounds(), r);
+    }
+
+    #[test]
+    fn sibling_rooms_join_into_l_shapes() {
+        for i in 0..50 {
+            let mut rng = SmallRng::seed_from_u64(i);
+            let axis = if i % 2 == 0 {
+                Axis::Horizontal
+            } else {
+                Axis::Vertical
+            };
+            let tree = pair(axis);
+
+            let (rooms, lines) = shape_rooms(&mut rng, &tree, &params(1.0, 0.0));
+
+            assert_eq!(rooms.len(), 1);
+            assert!(lines.is_empty());
+            assert_disjoint_within(&rooms[0], tree.bounds());
+            assert_eq!(missing_corners(&rooms[0], tree.bounds()), 1);
+        }
+    }
+
+    #[test]
+    fn sibling_rooms_join_into_t_shapes() {
+        for i in 0..50 {
+            let mut rng = SmallRng::seed_from_u64(i);
+            let tree = pair(Axis::Horizontal);
+
+            let (rooms, _) = shape_rooms(&mut rng, &tree, &params(0.0, 1.0));
+
+            assert_eq!(rooms.len(), 1);
+            assert_disjoint_within(&rooms[0], tree.bounds());
+            assert_eq!(missing_corners(&rooms[0], tree.bounds()), 2);
+            // Both missing corners are on the same side, either side of the
+            // stem.
+            let b = tree.bounds();
+            let top = [(b.x, b.y), (b.x_end() - 1, b.y)];
+            let bottom = [(b.x, b.y_end() - 1), (b.x_end() - 1, b.y_end() - 1)];
+            assert!(
+                top.iter().all(|c| !rooms[0].contains(*c))
+                    || bottom.iter().all(|c| !rooms[0].contains(*c))
+            );
+        }
+    }
+
+    #[test]
+    fn unshaped_pairs_flatten_as_they_are() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let tree = pair(Axis::Vertical);
+        let small_arms = ShapeParams {
+            min_arm: 20,
+            ..params(0.5, 0.5)
+        };
+
+        let (rooms, lines) = shape_rooms(&mut rng, &tree, &small_arms);
+
+        let (rects, flat_lines) = tree.into_flat();
+        assert_eq!(
+            rooms,
+            rects.into_iter().map(Region::from).collect::<Vec<_>>()
+        );
+        assert_eq!(lines, flat_lines);
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use ndarray::Array2;
+
+use crate::{
+    tile::Tile,
+    util::{Line, Region},
+};
+
+use super::connectivity::{label_regions, neighbors};
+
+/// Summary statistics of a generated world, for tuning generator parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldStats {
+    pub room_count: usize,
+
+    /// Number of rooms by area, in power-of-two buckets: bucket `i` counts
+    /// rooms with an area in `[2^i, 2^(i+1))`.
+    pub area_histogram: Vec<usize>,
+
+    /// The long-to-short side ratio of each room's bounds, sorted ascending.
+    pub aspect_ratios: Vec<f32>,
+
+    /// Total length of every hallway, in tiles.
+    pub corridor_length: usize,
+
+    /// Number of open cells with exactly one open neighbor.
+    pub dead_ends: usize,
+
+    /// Fraction of open cells in the largest connected region, in [0, 1].
+    pub largest_component_fraction: f32,
+}
+
+impl WorldStats {
+    /// Compute statistics for a rasterized map indexed by `(x, y)` and the
+    /// rooms and hallways it was built from.
+    pub fn new(map: &Array2<Tile>, rooms: &[Region], lines: &[Line]) -> Self {
+        let mut area_histogram = vec![];
+        for r in rooms {
+            let bucket = r.area().max(1).ilog2() as usize;
+            if area_histogram.len() <= bucket {
+                area_histogram.resize(bucket + 1, 0);
+            }
+            area_histogram[bucket] += 1;
+        }
+
+        let mut aspect_ratios = rooms
+            .iter()
+            .map(|r| {
+                let b = r.bounds();
+                b.w.max(b.h) as f32 / b.w.min(b.h).max(1) as f32
+            })
+            .collect::<Vec<_>>();
+        aspect_ratios.sort_by(f32::total_cmp);
+
+        let dead_ends = map
+            .indexed_iter()
+            .filter(|(pos, t)| {
+                !t.is_solid()
+                    && neighbors(map.dim(), *pos)
+                        .filter(|n| !map[*n].is_solid())
+                        .count()
+                        == 1
+            })
+            .count();
+
+        let (_, sizes) = label_regions(map);
+        let open = sizes.iter().sum::<usize>();
+        let largest_component_fraction = match open {
+            0 => 0.0,
+            _ => *sizes.iter().max().unwrap() as f32 / open as f32,
+        };
+
+        Self {
+            room_count: rooms.len(),
+            area_histogram,
+            aspect_ratios,
+            corridor_length: lines.iter().map(|l| l.length + 1).sum(),
+            dead_ends,
+            largest_component_fraction,
+        }
+    }
+
+    pub fn median_aspect_ratio(&self) -> Option<f32> {
+        self.aspect_ratios
+            .get(self.aspect_ratios.len() / 2)
+            .copied()
+    }
+}
+
+impl fmt::Display for WorldStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "rooms: {}", self.room_count)?;
+        writeln!(f, "room areas:")?;
+        for (i, n) in self.area_histogram.iter().enumerate() {
+            writeln!(f, "  {:>7}..{:<7} {n}", 1usize << i, 1usize << (i + 1))?;
+        }
+        if let (Some(min), Some(max)) = (self.aspect_ratios.first(), self.aspect_ratios.last()) {
+            writeln!(
+                f,
+                "aspect ratios: min {min:.2}, median {:.2}, max {max:.2}",
+                self.median_aspect_ratio().unwrap()
+            )?;
+        }
+        writeln!(f, "corridor length: {}", self.corridor_length)?;
+        writeln!(f, "dead ends: {}", self.dead_ends)?;
+        write!(
+            f,
+            "largest component: {:.1}%",
+            self.largest_component_fraction * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use crate::util::{Axis, Rectangle};
+
+    use super::*;
+
+    #[test]
+    fn computes_stats() {
+        let map = array![
+            [0, 0, 0, 1, 0],
+            [1, 1, 0, 1, 0],
+            [1, 1, 0, 1, 1],
+            [0, 0, 0, 1, 1],
+        ]
+        .map(|x| Tile::from(*x != 0));
+        let rooms = [
+            Region::from(Rectangle {
+                x: 0,
+                y: 0,
+                w: 3,
+                h: 3,
+            }),
+            Region::from(Rectangle {
+                x: 0,
+                y: 0,
+                w: 8,
+                h: 2,
+            }),
+        ];
+        let lines = [Line {
+            x: 0,
+            y: 0,
+            length: 4,
+            width: 2,
+            axis: Axis::Vertical,
+        }];
+
+        let stats = WorldStats::new(&map, &rooms, &lines);
+
+        assert_eq!(stats.room_count, 2);
+        assert_eq!(stats.area_histogram, vec![0, 0, 0, 1, 1]);
+        assert_eq!(stats.aspect_ratios, vec![1.0, 4.0]);
+        assert_eq!(stats.corridor_length, 5);
+        assert_eq!(stats.dead_ends, 4);
+        assert_eq!(stats.largest_component_fraction, 0.8);
+    }
+}
@@ -0,0 +1,194 @@
+//! Rasterizing and rendering maps too large to hold in memory at once.
+//!
+//! A [`TileSource`] can rasterize any window of a map on demand, so a map can
+//! be produced one band of rows at a time and written out as it goes. Memory
+//! use then depends on the band size rather than the size of the map.
+
+#[cfg(feature = "render")]
+use std::io::Write;
+
+use ndarray::{s, Array2};
+
+use crate::{
+    tile::Tile,
+    util::{Line, Rectangle, Region},
+};
+
+use super::{chunks::ChunkGenerator, raster::rasterize};
+
+/// A map whose tiles can be produced a window at a time.
+pub trait TileSource {
+    /// Width and height of the whole map.
+    fn dim(&self) -> (usize, usize);
+
+    /// Rasterize the tiles within `window`, indexed by `(x, y)` relative to the
+    /// window's origin. The window always lies within the map.
+    fn window(&self, window: &Rectangle<isize, usize>) -> Array2<Tile>;
+}
+
+impl TileSource for Array2<Tile> {
+    fn dim(&self) -> (usize, usize) {
+        Array2::dim(self)
+    }
+
+    fn window(&self, window: &Rectangle<isize, usize>) -> Array2<Tile> {
+        let (x, y) = (window.x as usize, window.y as usize);
+        self.slice(s![x..x + window.w, y..y + window.h]).to_owned()
+    }
+}
+
+/// The chunked world within `(0, 0)` and `(width, height)`, generated only
+/// where needed.
+#[derive(Debug, Clone)]
+pub struct ChunkSource {
+    pub generator: ChunkGenerator,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TileSource for ChunkSource {
+    fn dim(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn window(&self, window: &Rectangle<isize, usize>) -> Array2<Tile> {
+        // Rooms are carved based on their neighbors, so rasterize one extra
+        // tile on every side to get the window's edges right.
+        let (x0, y0) = (window.x - 1, window.y - 1);
+        let (x1, y1) = (window.x_end(), window.y_end());
+        let (cx0, cy0) = self.generator.chunk_coords((x0, y0));
+        let (cx1, cy1) = self.generator.chunk_coords((x1, y1));
+
+        let (mut rooms, mut lines) = (vec![], vec![]);
+        for cx in cx0..=cx1 {
+            for cy in cy0..=cy1 {
+                let chunk = self.generator.generate(cx, cy);
+                rooms.extend(chunk.rooms().map(|r| {
+                    Region::from(Rectangle {
+                        x: r.x - x0,
+                        y: r.y - y0,
+                        ..r.clone()
+                    })
+                }));
+                lines.extend(chunk.lines().map(|l| Line {
+                    x: l.x - x0,
+                    y: l.y - y0,
+                    ..l.clone()
+                }));
+            }
+        }
+
+        let padded = rasterize((window.w + 2, window.h + 2), &rooms, &lines, &[]);
+        padded
+            .slice(s![1..window.w + 1, 1..window.h + 1])
+            .to_owned()
+    }
+}
+
+/// Split a map into bands of up to `rows` full-width rows, from `y = 0` up.
+pub fn bands((w, h): (usize, usize), rows: usize) -> impl Iterator<Item = Rectangle<isize, usize>> {
+    (0..h).step_by(rows.max(1)).map(move |y| Rectangle {
+        x: 0,
+        y: y as isize,
+        w,
+        h: rows.max(1).min(h - y),
+    })
+}
+
+/// Render a map to a PNG, rasterizing and encoding `rows` rows at a time. Like
+/// [`render_to_img`], the first row of the image is `y = 0`.
+///
+/// [`render_to_img`]: super::render_to_img
+#[cfg(feature = "render")]
+pub fn write_png(
+    source: &impl TileSource,
+    rows: usize,
+    out: impl Write,
+) -> Result<(), png::EncodingError> {
+    let (w, h) = source.dim();
+    let mut encoder = png::Encoder::new(out, w as u32, h as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+
+    let mut row = Vec::with_capacity(w * 3);
+    for band in bands((w, h), rows) {
+        let tiles = source.window(&band);
+        for y in 0..band.h {
+            row.clear();
+            row.extend((0..w).flat_map(|x| tiles[(x, y)].color().0));
+            stream.write_all(&row)?;
+        }
+    }
+    stream.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::worldgen::hallways::RbspParams;
+
+    use super::*;
+
+    fn source() -> ChunkSource {
+        ChunkSource {
+            generator: ChunkGenerator {
+                world_seed: 7,
+                chunk_size: 32,
+                params: RbspParams {
+                    min_room_len: 4,
+                    max_room_len: 16,
+                    ..Default::default()
+                },
+            },
+            width: 100,
+            height: 70,
+        }
+    }
+
+    #[test]
+    fn bands_cover_the_map() {
+        let bands = bands((10, 25), 10).collect::<Vec<_>>();
+
+        assert_eq!(
+            bands.iter().map(|b| (b.y, b.h)).collect::<Vec<_>>(),
+            vec![(0, 10), (10, 10), (20, 5)]
+        );
+        assert!(bands.iter().all(|b| b.x == 0 && b.w == 10));
+    }
+
+    #[test]
+    fn windows_match_the_whole_map() {
+        let source = source();
+        let whole = source.window(&Rectangle {
+            x: 0,
+            y: 0,
+            w: 100,
+            h: 70,
+        });
+
+        for band in bands(source.dim(), 9) {
+            let (y, h) = (band.y as usize, band.h);
+            assert_eq!(source.window(&band), whole.slice(s![.., y..y + h]));
+        }
+        assert!(whole.iter().any(|t| !t.is_solid()));
+    }
+
+    #[test]
+    #[cfg(feature = "render")]
+    fn streamed_png_matches_render_to_img() {
+        let source = source();
+        let whole = source.window(&Rectangle {
+            x: 0,
+            y: 0,
+            w: 100,
+            h: 70,
+        });
+
+        let mut png = vec![];
+        write_png(&source, 16, &mut png).unwrap();
+
+        let decoded = image::load_from_memory(&png).unwrap().into_rgb8();
+        assert_eq!(decoded, crate::worldgen::render_to_img(&whole));
+    }
+}
@@ -0,0 +1,166 @@
+use ndarray::Array2;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{tile::Tile, util::Region};
+
+use super::{connectivity::label_regions, doors::Door};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TeaserParams {
+    /// How many rooms to seal off. Fewer are sealed if there aren't enough
+    /// rooms that can be seen into and sealed without cutting anything else off.
+    pub n_teasers: usize,
+
+    /// The most windows to put into each sealed room.
+    pub max_windows: usize,
+}
+
+/// Seal off some rooms, on a map indexed by `(x, y)`, so they can only be
+/// looked into through windows and never entered. Returns the indices of the
+/// sealed rooms.
+///
+/// A room is only sealed if it has a stretch of wall one tile thick with open
+/// floor on both sides to put a window in, and walling up its doors doesn't
+/// cut off any other part of the map. Its doors are removed from `doors`.
+pub fn place_teasers(
+    rng: &mut impl Rng,
+    map: &mut Array2<Tile>,
+    rooms: &[Region],
+    doors: &mut Vec<Door>,
+    params: &TeaserParams,
+) -> Vec<usize> {
+    let mut order = (0..rooms.len()).collect::<Vec<_>>();
+    order.shuffle(rng);
+
+    let mut teasers = vec![];
+    for i in order {
+        if teasers.len() >= params.n_teasers {
+            break;
+        }
+        let room = &rooms[i];
+        let mut windows = window_candidates(map, room);
+        if windows.is_empty() {
+            continue;
+        }
+
+        let mut sealed = map.clone();
+        let room_doors = doors
+            .iter()
+            .filter(|d| room.contains((d.x, d.y)))
+            .collect::<Vec<_>>();
+        for d in &room_doors {
+            if let Some(c) = sealed.get_mut((d.x as usize, d.y as usize)) {
+                *c = Tile::Wall;
+            }
+        }
+        // Sealing splits the room off into a region of its own, and must not
+        // split anything else.
+        let (_, before) = label_regions(map);
+        let (_, after) = label_regions(&sealed);
+        if after.len() > before.len() + 1 {
+            continue;
+        }
+
+        windows.shuffle(rng);
+        for &pos in windows.iter().take(params.max_windows) {
+            sealed[pos] = Tile::Window;
+        }
+        *map = sealed;
+        doors.retain(|d| !room.contains((d.x, d.y)));
+        teasers.push(i);
+    }
+    teasers
+}
+
+/// Walls on the edge of a room with the room's open floor on one side and open
+/// floor outside the room on the other, in a straight stretch of wall.
+fn window_candidates(map: &Array2<Tile>, room: &Region) -> Vec<(usize, usize)> {
+    let tile = |(x, y): (isize, isize)| {
+        let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+        map.get((x, y)).copied()
+    };
+    let open = |pos| tile(pos).is_some_and(|t| !t.is_solid());
+    let solid = |pos| tile(pos).is_none_or(Tile::is_solid);
+
+    room.cells()
+        .filter(|&pos| tile(pos) == Some(Tile::Wall))
+        .filter(|&(x, y)| {
+            [(1, 0), (0, 1)].into_iter().any(|(dx, dy)| {
+                let (a, b) = ((x - dx, y - dy), (x + dx, y + dy));
+                (open(a) && open(b))
+                    && (room.contains(a) != room.contains(b))
+                    && solid((x - dy, y - dx))
+                    && solid((x + dy, y + dx))
+            })
+        })
+        .map(|(x, y)| (x as usize, y as usize))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::{
+        util::Rectangle,
+        worldgen::{
+            connectivity::unreachable_cells,
+            doors::place_doors,
+            hallways::{rbsp, RbspParams},
+            raster::rasterize,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn teasers_can_be_seen_but_not_entered() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (rooms, lines) = rbsp(
+            &mut rng,
+            Rectangle {
+                x: 0,
+                y: 0,
+                w: 96,
+                h: 96,
+            },
+            RbspParams {
+                min_room_len: 6,
+                max_room_len: 24,
+                ..Default::default()
+            },
+        )
+        .into_flat();
+        let rooms = rooms.into_iter().map(Region::from).collect::<Vec<_>>();
+        let mut doors = place_doors(&mut rng, &rooms, &lines);
+        let mut map = rasterize((96, 96), &rooms, &lines, &doors);
+        let n_doors = doors.len();
+
+        let params = TeaserParams {
+            n_teasers: 3,
+            max_windows: 2,
+        };
+        let teasers = place_teasers(&mut rng, &mut map, &rooms, &mut doors, &params);
+
+        assert_eq!(teasers.len(), 3);
+        assert_eq!(doors.len(), n_doors - 3);
+        let start = (lines[0].x as usize, lines[0].y as usize);
+        let unreachable = unreachable_cells(&map, start);
+        for (x, y) in &unreachable {
+            let pos = (*x as isize, *y as isize);
+            assert!(teasers.iter().any(|&i| rooms[i].contains(pos)));
+        }
+        for &i in &teasers {
+            let cells = rooms[i].cells().collect::<Vec<_>>();
+            let windows = cells
+                .iter()
+                .filter(|(x, y)| map[(*x as usize, *y as usize)] == Tile::Window)
+                .count();
+            assert!((1..=2).contains(&windows));
+            assert!(cells
+                .iter()
+                .any(|(x, y)| unreachable.contains(&(*x as usize, *y as usize))));
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[cfg(feature = "render")]
+use image::RgbImage;
+
+use crate::tile::Tile;
+
+use super::pipeline::GeneratedWorld;
+
+/// Options for exporting a world as a Tiled map.
+#[derive(Debug, Clone)]
+pub struct TiledParams {
+    /// Width and height of each tile, in pixels.
+    pub tile_size: usize,
+
+    /// Spawn points to add to the `spawns` object layer, in tile coordinates.
+    pub spawns: Vec<(usize, usize)>,
+
+    /// Path of the tileset image, relative to the map. [`save_tmj`] draws it
+    /// there with [`tileset_image`] if the `render` feature is enabled.
+    pub tileset_image: String,
+}
+
+/// Write a world as a Tiled JSON map (TMJ), to be opened in Tiled or imported
+/// into engines that read Tiled maps.
+///
+/// The map has a `tiles` layer with one tile per [`Tile`] variant, in the order
+/// of [`Tile::ALL`], and `rooms`, `doors` and `spawns` object layers. Rooms
+/// made of several rectangles get one object per rectangle, all with the same
+/// name.
+///
+/// Tile `(x, y)` of the world's tiles is drawn in column `x` and row `y`, and
+/// objects are placed on the same grid, so a door object sits on its door
+/// tile. Doors and spawns are points in the middle of their tile.
+pub fn write_tmj(
+    w: &mut impl Write,
+    world: &GeneratedWorld,
+    params: &TiledParams,
+) -> io::Result<()> {
+    let (width, height) = world.tiles.dim();
+    let size = params.tile_size;
+    let mut next_id = 1;
+    let mut object = |name: &str, x: isize, y: isize, w: usize, h: usize, point: bool| {
+        let id = next_id;
+        next_id += 1;
+        let middle = if point { size as isize / 2 } else { 0 };
+        let px = |v: isize| v * size as isize + middle;
+        format!(
+            r#"{{"id":{id},"name":"{name}","type":"","x":{},"y":{},"width":{},"height":{},"rotation":0,"visible":true{}}}"#,
+            px(x),
+            px(y),
+            w * size,
+            h * size,
+            if point { r#","point":true"# } else { "" },
+        )
+    };
+
+    let rooms = world
+        .rooms
+        .iter()
+        .enumerate()
+        .flat_map(|(i, r)| r.rects.iter().map(move |rect| (i, rect)))
+        .map(|(i, r)| object(&format!("room {i}"), r.x, r.y, r.w, r.h, false))
+        .collect::<Vec<_>>();
+    let doors = world
+        .doors
+        .iter()
+        .map(|d| object(&format!("door {:?}", d.facing), d.x, d.y, 0, 0, true))
+        .collect::<Vec<_>>();
+    let spawns = params
+        .spawns
+        .iter()
+        .map(|&(x, y)| object("spawn", x as isize, y as isize, 0, 0, true))
+        .collect::<Vec<_>>();
+
+    let data = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|pos| gid(world.tiles[pos]).to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let tiles = Tile::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, t)| format!(r#"{{"id":{i},"type":"{t:?}"}}"#))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let object_layer = |id: usize, name: &str, objects: &[String]| {
+        format!(
+            r#"{{"id":{id},"name":"{name}","type":"objectgroup","draworder":"topdown","objects":[{}],"opacity":1,"visible":true,"x":0,"y":0}}"#,
+            objects.join(","),
+        )
+    };
+
+    write!(
+        w,
+        r#"{{"type":"map","version":"1.10","tiledversion":"1.10.2","orientation":"orthogonal","renderorder":"right-down","infinite":false,"width":{width},"height":{height},"tilewidth":{size},"tileheight":{size},"nextlayerid":5,"nextobjectid":{next_id},"#
+    )?;
+    // One row of tiles, in the layout of `tileset_image`.
+    let n = Tile::ALL.len();
+    write!(
+        w,
+        r#""tilesets":[{{"firstgid":1,"name":"backrooms","tilecount":{n},"columns":{n},"tilewidth":{size},"tileheight":{size},"image":"{}","imagewidth":{},"imageheight":{size},"margin":0,"spacing":0,"tiles":[{tiles}]}}],"#,
+        escape(&params.tileset_image),
+        n * size,
+    )?;
+    write!(
+        w,
+        r#""layers":[{{"id":1,"name":"tiles","type":"tilelayer","width":{width},"height":{height},"data":[{data}],"opacity":1,"visible":true,"x":0,"y":0}},{},{},{}]}}"#,
+        object_layer(2, "rooms", &rooms),
+        object_layer(3, "doors", &doors),
+        object_layer(4, "spawns", &spawns),
+    )
+}
+
+/// Save a world as a Tiled JSON map, and its tileset image next to it if the
+/// `render` feature is enabled. See [`write_tmj`].
+pub fn save_tmj(
+    path: impl AsRef<Path>,
+    world: &GeneratedWorld,
+    params: &TiledParams,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut w = BufWriter::new(File::create(path)?);
+    write_tmj(&mut w, world, params)?;
+    w.flush()?;
+
+    #[cfg(feature = "render")]
+    {
+        let image = path.with_file_name(&params.tileset_image);
+        tileset_image(params.tile_size)
+            .save(image)
+            .map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+/// The tileset of an exported map: one square of each [`Tile`]'s color,
+/// `tile_size` pixels across, in a row in the order of [`Tile::ALL`].
+#[cfg(feature = "render")]
+pub fn tileset_image(tile_size: usize) -> RgbImage {
+    let n = Tile::ALL.len();
+    RgbImage::from_fn((n * tile_size) as u32, tile_size as u32, |x, _| {
+        Tile::ALL[x as usize / tile_size].color()
+    })
+}
+
+/// The global tile ID of a tile in the exported tileset.
+fn gid(tile: Tile) -> usize {
+    Tile::ALL.iter().position(|t| *t == tile).unwrap() + 1
+}
+
+/// Escape a string for use inside a JSON string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+    use serde_json::Value;
+
+    use crate::{
+        util::{Direction, Rectangle, Region},
+        worldgen::doors::Door,
+    };
+
+    use super::*;
+
+    #[test]
+    fn exports_layers() {
+        let mut tiles = Array2::from_elem((4, 3), Tile::Wall);
+        tiles[(2, 1)] = Tile::Floor;
+        tiles[(1, 2)] = Tile::Door;
+        let mut world = GeneratedWorld::from_tiles(
+            Rectangle {
+                x: 0,
+                y: 0,
+                w: 4,
+                h: 3,
+            },
+            tiles,
+        );
+        world.rooms = vec![Region::from(Rectangle {
+            x: 1,
+            y: 1,
+            w: 2,
+            h: 2,
+        })];
+        world.doors = vec![Door::new(1, 2, Direction::South)];
+        let mut buf = vec![];
+
+        write_tmj(
+            &mut buf,
+            &world,
+            &TiledParams {
+                tile_size: 16,
+                spawns: vec![(2, 1)],
+                tileset_image: "tiles.png".to_string(),
+            },
+        )
+        .unwrap();
+        let map: Value = serde_json::from_slice(&buf).unwrap();
+
+        assert_eq!(map["width"], 4);
+        assert_eq!(map["height"], 3);
+        let layers = map["layers"].as_array().unwrap();
+        let data = layers[0]["data"].as_array().unwrap();
+        assert_eq!(data.len(), 12);
+        assert_eq!(data[4 + 2], gid(Tile::Floor));
+        assert_eq!(data[0], gid(Tile::Wall));
+        assert_eq!(layers[1]["objects"][0]["x"], 16);
+        assert_eq!(layers[1]["objects"][0]["width"], 32);
+        // Points land on the tile they're for.
+        let tile_at = |o: &Value| {
+            let (x, y) = (o["x"].as_u64().unwrap(), o["y"].as_u64().unwrap());
+            data[(y / 16 * 4 + x / 16) as usize].clone()
+        };
+        let door = &layers[2]["objects"][0];
+        assert_eq!(door["point"], true);
+        assert_eq!(tile_at(door), gid(Tile::Door));
+        assert_eq!(tile_at(&layers[3]["objects"][0]), gid(Tile::Floor));
+        assert_eq!(map["nextobjectid"], 4);
+
+        let tileset = &map["tilesets"][0];
+        assert_eq!(tileset["image"], "tiles.png");
+        assert_eq!(tileset["columns"], Tile::ALL.len());
+        assert_eq!(tileset["imagewidth"], 16 * Tile::ALL.len());
+    }
+}
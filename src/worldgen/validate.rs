@@ -0,0 +1,289 @@
+use std::fmt;
+
+use cgmath::Vector2;
+
+use crate::{tile::Tile, util::Rectangle};
+
+use super::{doors::DoorState, pipeline::GeneratedWorld};
+
+/// A broken invariant in a generated world, as found by [`validate`]. Rooms,
+/// hallways, doors and keys are referred to by their index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The tile map isn't the size of the world's bounds.
+    TileSizeMismatch {
+        bounds: (usize, usize),
+        tiles: (usize, usize),
+    },
+
+    /// A room with no area.
+    EmptyRoom { room: usize },
+
+    /// A room that extends past the edge of the map.
+    RoomOutOfBounds { room: usize },
+
+    /// Two rooms that share at least one tile.
+    RoomsOverlap { a: usize, b: usize },
+
+    /// A hallway with zero width, which covers no tiles.
+    EmptyHallway { line: usize },
+
+    /// A hallway that extends past the edge of the map.
+    HallwayOutOfBounds { line: usize },
+
+    /// A door that isn't on the map.
+    DoorOutOfBounds { door: usize },
+
+    /// A door that's solid, or doesn't have walkable tiles both in front of and
+    /// behind it.
+    DoorLeadsNowhere { door: usize },
+
+    /// A locked door whose key isn't anywhere in the world.
+    MissingKey { door: usize },
+
+    /// A key that's off the map or inside something solid.
+    UnreachableKey { key: usize },
+
+    /// Per-room data with a different length than the room list.
+    RoomDataMismatch {
+        field: &'static str,
+        len: usize,
+        rooms: usize,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::TileSizeMismatch { bounds, tiles } => write!(
+                f,
+                "tile map is {}x{}, but bounds are {}x{}",
+                tiles.0, tiles.1, bounds.0, bounds.1
+            ),
+            ValidationError::EmptyRoom { room } => write!(f, "room {room} is empty"),
+            ValidationError::RoomOutOfBounds { room } => {
+                write!(f, "room {room} is out of bounds")
+            }
+            ValidationError::RoomsOverlap { a, b } => write!(f, "rooms {a} and {b} overlap"),
+            ValidationError::EmptyHallway { line } => write!(f, "hallway {line} has no width"),
+            ValidationError::HallwayOutOfBounds { line } => {
+                write!(f, "hallway {line} is out of bounds")
+            }
+            ValidationError::DoorOutOfBounds { door } => write!(f, "door {door} is out of bounds"),
+            ValidationError::DoorLeadsNowhere { door } => {
+                write!(f, "door {door} doesn't join two walkable tiles")
+            }
+            ValidationError::MissingKey { door } => {
+                write!(f, "door {door} is locked and has no key")
+            }
+            ValidationError::UnreachableKey { key } => {
+                write!(f, "key {key} is out of bounds or in a wall")
+            }
+            ValidationError::RoomDataMismatch { field, len, rooms } => {
+                write!(f, "{len} {field} for {rooms} rooms")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Check a generated world for broken invariants, such as overlapping rooms or
+/// doors that lead into walls, returning every problem found.
+///
+/// Meant for diagnosing generators and parameters that produce strange output.
+pub fn validate(world: &GeneratedWorld) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let (w, h) = (world.bounds.w, world.bounds.h);
+    if world.tiles.dim() != (w, h) {
+        errors.push(ValidationError::TileSizeMismatch {
+            bounds: (w, h),
+            tiles: world.tiles.dim(),
+        });
+    }
+
+    let (w, h) = world.tiles.dim();
+    let on_map = |r: &Rectangle<isize, usize>| {
+        r.x >= 0 && r.y >= 0 && r.x_end() <= w as isize && r.y_end() <= h as isize
+    };
+    let tile = |(x, y): (isize, isize)| {
+        let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+        world.tiles.get((x, y)).copied()
+    };
+    let walkable = |pos| tile(pos).is_some_and(|t: Tile| !t.is_solid());
+
+    for (i, room) in world.rooms.iter().enumerate() {
+        if room.area() == 0 {
+            errors.push(ValidationError::EmptyRoom { room: i });
+        }
+        if !room.rects.iter().all(on_map) {
+            errors.push(ValidationError::RoomOutOfBounds { room: i });
+        }
+        for (j, other) in world.rooms.iter().enumerate().skip(i + 1) {
+            let overlap = room
+                .rects
+                .iter()
+                .any(|a| other.rects.iter().any(|b| a.intersects(b)));
+            if overlap {
+                errors.push(ValidationError::RoomsOverlap { a: i, b: j });
+            }
+        }
+    }
+
+    for (i, line) in world.lines.iter().enumerate() {
+        if line.width == 0 {
+            errors.push(ValidationError::EmptyHallway { line: i });
+        } else if !on_map(&line.bounds()) {
+            errors.push(ValidationError::HallwayOutOfBounds { line: i });
+        }
+    }
+
+    for (i, door) in world.doors.iter().enumerate() {
+        let pos = (door.x, door.y);
+        if tile(pos).is_none() {
+            errors.push(ValidationError::DoorOutOfBounds { door: i });
+            continue;
+        }
+        let step = Vector2::<isize>::from(door.facing);
+        let front = (door.x + step.x, door.y + step.y);
+        let back = (door.x - step.x, door.y - step.y);
+        if !(walkable(pos) && walkable(front) && walkable(back)) {
+            errors.push(ValidationError::DoorLeadsNowhere { door: i });
+        }
+        let has_key = door
+            .key
+            .is_some_and(|id| world.keys.iter().any(|k| k.id == id));
+        if door.state == DoorState::Locked && !has_key {
+            errors.push(ValidationError::MissingKey { door: i });
+        }
+    }
+
+    for (i, key) in world.keys.iter().enumerate() {
+        if !walkable((key.x, key.y)) {
+            errors.push(ValidationError::UnreachableKey { key: i });
+        }
+    }
+
+    let per_room = [
+        ("biomes", world.biomes.len()),
+        ("heights", world.heights.len()),
+    ];
+    for (field, len) in per_room {
+        if len != 0 && len != world.rooms.len() {
+            errors.push(ValidationError::RoomDataMismatch {
+                field,
+                len,
+                rooms: world.rooms.len(),
+            });
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array2;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::{
+        util::{Axis, Direction, Line, Region},
+        worldgen::{
+            doors::Door,
+            locks::Key,
+            pipeline::{PlaceDoors, WorldPass},
+            raster::rasterize,
+        },
+    };
+
+    use super::*;
+
+    /// Two rooms split by a hallway, laid out the way rbsp would.
+    fn two_rooms() -> GeneratedWorld {
+        let bounds = Rectangle {
+            x: 0,
+            y: 0,
+            w: 11,
+            h: 7,
+        };
+        let mut world = GeneratedWorld::from_tiles(bounds, Array2::from_elem((11, 7), Tile::Wall));
+        world.rooms = vec![
+            Region::from(Rectangle {
+                x: 0,
+                y: 0,
+                w: 5,
+                h: 7,
+            }),
+            Region::from(Rectangle {
+                x: 6,
+                y: 0,
+                w: 5,
+                h: 7,
+            }),
+        ];
+        world.lines = vec![Line {
+            x: 5,
+            y: 0,
+            length: 6,
+            width: 1,
+            axis: Axis::Vertical,
+        }];
+        world.tiles = rasterize((11, 7), &world.rooms, &world.lines, &[]);
+        PlaceDoors.apply(&mut SmallRng::seed_from_u64(0), &mut world);
+        world
+    }
+
+    #[test]
+    fn clean_worlds_pass() {
+        let world = two_rooms();
+
+        assert_eq!(world.doors.len(), 2);
+        assert_eq!(validate(&world), vec![]);
+    }
+
+    #[test]
+    fn finds_overlaps_and_stray_geometry() {
+        let mut world = two_rooms();
+        world.rooms[1].rects[0].x = 4;
+        world.rooms[1].rects[0].w = 6;
+        world.rooms.push(Region { rects: vec![] });
+        world.lines[0].length = 7;
+        world.lines.push(Line {
+            width: 0,
+            ..world.lines[0].clone()
+        });
+
+        assert_eq!(
+            validate(&world),
+            vec![
+                ValidationError::RoomsOverlap { a: 0, b: 1 },
+                ValidationError::EmptyRoom { room: 2 },
+                ValidationError::HallwayOutOfBounds { line: 0 },
+                ValidationError::EmptyHallway { line: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_broken_doors_and_keys() {
+        let mut world = two_rooms();
+        world.doors.push(Door::new(2, 0, Direction::South));
+        world.doors[0].lock(7);
+        world.keys.push(Key { id: 3, x: 0, y: 0 });
+        world.heights = vec![1.0];
+
+        assert_eq!(
+            validate(&world),
+            vec![
+                ValidationError::MissingKey { door: 0 },
+                ValidationError::DoorLeadsNowhere { door: 2 },
+                ValidationError::UnreachableKey { key: 0 },
+                ValidationError::RoomDataMismatch {
+                    field: "heights",
+                    len: 1,
+                    rooms: 2
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use ndarray::Array2;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::tile::Tile;
+
+use super::connectivity::{connect_regions, neighbors};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoronoiParams {
+    /// How many rooms to generate.
+    pub n_sites: usize,
+
+    /// How many rounds of Lloyd relaxation to run. More rounds make rooms more
+    /// evenly sized.
+    pub relax_iterations: usize,
+}
+
+/// Output of [voronoi_rooms].
+#[derive(Debug, Clone)]
+pub struct VoronoiMap {
+    /// The rasterized map, indexed by `(x, y)`.
+    pub tiles: Array2<Tile>,
+
+    /// Which room each tile belongs to, indexed by `(x, y)`.
+    pub cells: Array2<usize>,
+
+    /// The relaxed position of each room's site.
+    pub sites: Vec<(f32, f32)>,
+}
+
+/// Generate irregular rooms from the cells of a Lloyd-relaxed Voronoi diagram.
+///
+/// Hallways run along the edges between cells, and every room is walled off
+/// from them except for a single door. Any part of the map left unreachable
+/// afterwards is joined up with [connect_regions].
+pub fn voronoi_rooms(
+    rng: &mut impl Rng,
+    (w, h): (usize, usize),
+    params: &VoronoiParams,
+) -> VoronoiMap {
+    let mut sites = (0..params.n_sites.max(1))
+        .map(|_| (rng.gen_range(0.0..w as f32), rng.gen_range(0.0..h as f32)))
+        .collect::<Vec<_>>();
+    let mut cells = nearest_sites((w, h), &sites);
+
+    for _ in 0..params.relax_iterations {
+        let mut sums = vec![(0.0, 0.0, 0usize); sites.len()];
+        for ((x, y), &c) in cells.indexed_iter() {
+            sums[c].0 += x as f32 + 0.5;
+            sums[c].1 += y as f32 + 0.5;
+            sums[c].2 += 1;
+        }
+        for (s, (sx, sy, n)) in sites.iter_mut().zip(sums) {
+            if n > 0 {
+                *s = (sx / n as f32, sy / n as f32);
+            }
+        }
+        cells = nearest_sites((w, h), &sites);
+    }
+
+    let differs = |(x, y): (usize, usize), (nx, ny): (usize, usize)| {
+        nx < w && ny < h && cells[(x, y)] != cells[(nx, ny)]
+    };
+    let hallway = Array2::from_shape_fn((w, h), |(x, y)| {
+        differs((x, y), (x + 1, y)) || differs((x, y), (x, y + 1))
+    });
+
+    let mut tiles = Array2::from_shape_fn((w, h), |(x, y)| {
+        let on_edge = x == 0 || y == 0 || x == w - 1 || y == h - 1;
+        let by_hallway = neighbors((w, h), (x, y)).any(|n| hallway[n]);
+        if on_edge {
+            Tile::Wall
+        } else if hallway[(x, y)] {
+            Tile::Floor
+        } else if by_hallway {
+            Tile::Wall
+        } else {
+            Tile::Floor
+        }
+    });
+
+    let mut door_candidates = vec![vec![]; sites.len()];
+    for ((x, y), t) in tiles.indexed_iter() {
+        if *t != Tile::Wall || hallway[(x, y)] {
+            continue;
+        }
+        let (mut to_hallway, mut to_room) = (false, false);
+        for n in neighbors((w, h), (x, y)) {
+            to_hallway |= hallway[n] && tiles[n] == Tile::Floor;
+            to_room |= !hallway[n] && tiles[n] == Tile::Floor && cells[n] == cells[(x, y)];
+        }
+        if to_hallway && to_room {
+            door_candidates[cells[(x, y)]].push((x, y));
+        }
+    }
+    for candidates in door_candidates {
+        if let Some(&d) = candidates.choose(rng) {
+            tiles[d] = Tile::Door;
+        }
+    }
+
+    if let Some((start, _)) = tiles.indexed_iter().find(|(_, t)| !t.is_solid()) {
+        connect_regions(&mut tiles, start);
+    }
+
+    VoronoiMap {
+        tiles,
+        cells,
+        sites,
+    }
+}
+
+fn nearest_sites((w, h): (usize, usize), sites: &[(f32, f32)]) -> Array2<usize> {
+    Array2::from_shape_fn((w, h), |(x, y)| {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        let dist = |(sx, sy): (f32, f32)| (sx - px).powi(2) + (sy - py).powi(2);
+        (0..sites.len())
+            .min_by(|a, b| dist(sites[*a]).total_cmp(&dist(sites[*b])))
+            .unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::worldgen::connectivity::label_regions;
+
+    use super::*;
+
+    #[test]
+    fn rooms_are_reachable() {
+        for seed in 0..10 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+
+            let map = voronoi_rooms(
+                &mut rng,
+                (96, 64),
+                &VoronoiParams {
+                    n_sites: 12,
+                    relax_iterations: 3,
+                },
+            );
+
+            assert_eq!(label_regions(&map.tiles).1.len(), 1);
+            assert_eq!(map.sites.len(), 12);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use cgmath::Vector2;
+use ndarray::Array2;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{tile::Tile, util::Direction};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::East,
+    Direction::North,
+    Direction::West,
+    Direction::South,
+];
+
+/// Which tiles may appear next to each other, and how often each tile should appear.
+///
+/// Sets of candidate tiles are stored as bitmasks, so at most 64 distinct tiles
+/// are supported.
+#[derive(Debug, Clone, Default)]
+pub struct WfcRules {
+    tiles: Vec<Tile>,
+    weights: Vec<f32>,
+
+    /// `allowed[d][a]` is the set of tiles that may be placed in direction `d` of tile `a`.
+    allowed: [Vec<u64>; 4],
+}
+
+impl WfcRules {
+    /// Learn the rules from an example map, indexed by `(x, y)`. Every pair of
+    /// neighboring tiles in the example becomes an allowed adjacency, and tiles
+    /// are weighted by how often they appear.
+    pub fn from_example(example: &Array2<Tile>) -> Self {
+        let mut rules = Self::default();
+        for (_, &t) in example.indexed_iter() {
+            let i = rules.add_tile(t, 0.0);
+            rules.weights[i] += 1.0;
+        }
+        for (pos, &a) in example.indexed_iter() {
+            for d in DIRECTIONS {
+                if let Some(n) = step(example.dim(), pos, d) {
+                    rules.allow(a, d, example[n]);
+                }
+            }
+        }
+        rules
+    }
+
+    /// Register a tile with the given weight, returning its index. If the tile
+    /// is already registered, only its index is returned.
+    pub fn add_tile(&mut self, tile: Tile, weight: f32) -> usize {
+        if let Some(i) = self.index(tile) {
+            return i;
+        }
+        assert!(self.tiles.len() < 64, "WFC supports at most 64 tiles");
+        self.tiles.push(tile);
+        self.weights.push(weight);
+        for a in &mut self.allowed {
+            a.push(0);
+        }
+        self.tiles.len() - 1
+    }
+
+    /// Allow `b` to be placed in direction `dir` of `a`, and therefore `a` in
+    /// the opposite direction of `b`. Both tiles must already be registered.
+    pub fn allow(&mut self, a: Tile, dir: Direction, b: Tile) {
+        let ia = self.index(a).expect("tile was not registered");
+        let ib = self.index(b).expect("tile was not registered");
+        self.allowed[dir as usize][ia] |= 1 << ib;
+        self.allowed[(-dir) as usize][ib] |= 1 << ia;
+    }
+
+    fn index(&self, tile: Tile) -> Option<usize> {
+        self.tiles.iter().position(|t| *t == tile)
+    }
+
+    fn all(&self) -> u64 {
+        match self.tiles.len() {
+            64 => u64::MAX,
+            n => (1 << n) - 1,
+        }
+    }
+}
+
+/// Generate a map of the given size, indexed by `(x, y)`, using Wave Function
+/// Collapse.
+///
+/// The algorithm may run into a contradiction, in which case it restarts from
+/// scratch, up to `max_attempts` times before giving up. Every step scans the
+/// whole map, so this is meant for small maps.
+pub fn wfc(
+    rng: &mut impl Rng,
+    rules: &WfcRules,
+    dim: (usize, usize),
+    max_attempts: usize,
+) -> Option<Array2<Tile>> {
+    (0..max_attempts).find_map(|_| collapse(rng, rules, dim))
+}
+
+fn collapse(rng: &mut impl Rng, rules: &WfcRules, dim: (usize, usize)) -> Option<Array2<Tile>> {
+    let mut wave = Array2::from_elem(dim, rules.all());
+
+    loop {
+        let mut best = None;
+        let mut best_entropy = f32::INFINITY;
+        for (pos, &m) in wave.indexed_iter() {
+            let entropy = match m.count_ones() {
+                0 => return None,
+                1 => continue,
+                // Break ties randomly so the map doesn't grow in scan order.
+                n => n as f32 + rng.gen::<f32>() * 0.5,
+            };
+            if entropy < best_entropy {
+                best = Some(pos);
+                best_entropy = entropy;
+            }
+        }
+        let Some(pos) = best else {
+            break;
+        };
+
+        let options = bits(wave[pos]).collect::<Vec<_>>();
+        let choice = *options
+            .choose_weighted(rng, |t| rules.weights[*t])
+            .or_else(|_| options.choose(rng).ok_or(()))
+            .ok()?;
+        wave[pos] = 1 << choice;
+        propagate(&mut wave, rules, pos)?;
+    }
+
+    Some(wave.map(|m| rules.tiles[m.trailing_zeros() as usize]))
+}
+
+/// Remove options from neighboring cells that are no longer compatible, until
+/// nothing changes. Returns `None` if some cell runs out of options.
+fn propagate(wave: &mut Array2<u64>, rules: &WfcRules, start: (usize, usize)) -> Option<()> {
+    let mut stack = vec![start];
+    while let Some(pos) = stack.pop() {
+        for d in DIRECTIONS {
+            let Some(n) = step(wave.dim(), pos, d) else {
+                continue;
+            };
+            let allowed = bits(wave[pos]).fold(0, |acc, t| acc | rules.allowed[d as usize][t]);
+            let new = wave[n] & allowed;
+            if new == 0 {
+                return None;
+            }
+            if new != wave[n] {
+                wave[n] = new;
+                stack.push(n);
+            }
+        }
+    }
+    Some(())
+}
+
+fn bits(mut m: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if m == 0 {
+            return None;
+        }
+        let i = m.trailing_zeros() as usize;
+        m &= m - 1;
+        Some(i)
+    })
+}
+
+fn step((w, h): (usize, usize), (x, y): (usize, usize), d: Direction) -> Option<(usize, usize)> {
+    let v = Vector2::<isize>::from(d);
+    let nx = x.checked_add_signed(v.x)?;
+    let ny = y.checked_add_signed(v.y)?;
+    (nx < w && ny < h).then_some((nx, ny))
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn output_only_uses_example_adjacencies() {
+        let example = array![
+            [1, 1, 1, 1, 1],
+            [1, 0, 0, 0, 1],
+            [1, 0, 2, 0, 1],
+            [1, 0, 0, 0, 1],
+            [1, 1, 1, 1, 1],
+        ]
+        .map(|x| match x {
+            0 => Tile::Floor,
+            1 => Tile::Wall,
+            _ => Tile::Pillar,
+        });
+        let rules = WfcRules::from_example(&example);
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let map = wfc(&mut rng, &rules, (24, 24), 10).unwrap();
+
+        for (pos, &a) in map.indexed_iter() {
+            for d in DIRECTIONS {
+                if let Some(n) = step(map.dim(), pos, d) {
+                    let (ia, ib) = (rules.index(a).unwrap(), rules.index(map[n]).unwrap());
+                    assert!(rules.allowed[d as usize][ia] & (1 << ib) != 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn handcrafted_rules() {
+        let mut rules = WfcRules::default();
+        rules.add_tile(Tile::Floor, 3.0);
+        rules.add_tile(Tile::Carpet, 1.0);
+        rules.add_tile(Tile::Wall, 1.0);
+        for d in DIRECTIONS {
+            rules.allow(Tile::Floor, d, Tile::Floor);
+            rules.allow(Tile::Floor, d, Tile::Carpet);
+            rules.allow(Tile::Floor, d, Tile::Wall);
+            rules.allow(Tile::Carpet, d, Tile::Carpet);
+            rules.allow(Tile::Wall, d, Tile::Wall);
+        }
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        let map = wfc(&mut rng, &rules, (16, 16), 10).unwrap();
+
+        for (pos, &a) in map.indexed_iter() {
+            for d in DIRECTIONS {
+                if let Some(n) = step(map.dim(), pos, d) {
+                    let pair = (a, map[n]);
+                    assert!(
+                        pair != (Tile::Carpet, Tile::Wall) && pair != (Tile::Wall, Tile::Carpet)
+                    );
+                }
+            }
+        }
+    }
+}